@@ -0,0 +1,219 @@
+// alerts.rs
+//
+// Saved queries evaluated against every inscription the scanner finds.
+// Each query from `[[alerts]]` in the config combines optional MIME, size
+// and regex criteria; matches are appended to the query's output file as
+// JSON lines.
+
+use crate::config::AlertConfig;
+use crate::parser::{mime_matches, Inscription};
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AlertError {
+    #[error("Invalid regex in alert '{0}': {1}")]
+    InvalidRegex(String, regex::Error),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, AlertError>;
+
+/// Line appended to a query's output file for each match
+#[derive(Debug, Serialize)]
+struct AlertMatch<'a> {
+    query: &'a str,
+    txid: String,
+    content_type: &'a str,
+    size: usize,
+}
+
+/// A compiled alert query
+#[derive(Debug)]
+pub struct AlertQuery {
+    pub name: String,
+    mime: Option<String>,
+    min_size: Option<usize>,
+    max_size: Option<usize>,
+    regex: Option<Regex>,
+    output: PathBuf,
+}
+
+impl AlertQuery {
+    pub fn new(config: &AlertConfig) -> Result<Self> {
+        let regex = config
+            .regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| AlertError::InvalidRegex(config.name.clone(), e))?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            mime: config.mime.as_ref().map(|mime| mime.to_ascii_lowercase()),
+            min_size: config.min_size,
+            max_size: config.max_size,
+            regex,
+            output: config.output.clone(),
+        })
+    }
+
+    /// Whether every criterion set on the query matches the inscription
+    pub fn matches(&self, inscription: &Inscription) -> bool {
+        if let Some(pattern) = &self.mime {
+            if !mime_matches(pattern, inscription.mime_type()) {
+                return false;
+            }
+        }
+
+        let body = inscription.content.bytes();
+        if self.min_size.is_some_and(|min| body.len() < min)
+            || self.max_size.is_some_and(|max| body.len() > max)
+        {
+            return false;
+        }
+
+        match &self.regex {
+            Some(regex) => regex.is_match(&body),
+            None => true,
+        }
+    }
+}
+
+/// All configured alert queries
+#[derive(Debug, Default)]
+pub struct Alerts {
+    queries: Vec<AlertQuery>,
+}
+
+impl Alerts {
+    pub fn new(configs: &[AlertConfig]) -> Result<Self> {
+        let queries = configs.iter().map(AlertQuery::new).collect::<Result<Vec<_>>>()?;
+        Ok(Self { queries })
+    }
+
+    /// Names of the queries matching the inscription
+    pub fn evaluate(&self, inscription: &Inscription) -> Vec<&str> {
+        self.queries
+            .iter()
+            .filter(|query| query.matches(inscription))
+            .map(|query| query.name.as_str())
+            .collect()
+    }
+
+    /// Appends the inscription to the output of every matching query
+    ///
+    /// Returns the number of queries that matched.
+    pub fn emit(&self, inscription: &Inscription) -> Result<usize> {
+        let mut matched = 0;
+        for query in self.queries.iter().filter(|query| query.matches(inscription)) {
+            let line = serde_json::to_string(&AlertMatch {
+                query: &query.name,
+                txid: inscription.txid.to_string(),
+                content_type: inscription.mime_type(),
+                size: inscription.content.bytes().len(),
+            })?;
+
+            if let Some(parent) = query.output.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&query.output)?;
+            writeln!(file, "{}", line)?;
+            matched += 1;
+        }
+        Ok(matched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::InscriptionType;
+    use bitcoin::Txid;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    fn txid(n: u8) -> Txid {
+        Txid::from_str(&format!("{:064x}", n)).unwrap()
+    }
+
+    fn svg(size: usize) -> InscriptionType {
+        let mut data = b"<svg xmlns=\"http://www.w3.org/2000/svg\">".to_vec();
+        data.resize(size, b' ');
+        InscriptionType::Image { mime_type: "image/svg+xml".to_string(), data }
+    }
+
+    #[test]
+    fn test_queries_match_the_right_inscriptions() {
+        let temp_dir = TempDir::new().unwrap();
+        let large_svg = temp_dir.path().join("alerts/large-svg.jsonl");
+        let bailout = temp_dir.path().join("alerts/bailout.jsonl");
+
+        let alerts = Alerts::new(&[
+            AlertConfig {
+                name: "large-svg".to_string(),
+                mime: Some("image/svg+xml".to_string()),
+                min_size: Some(50 * 1024),
+                max_size: None,
+                regex: None,
+                output: large_svg.clone(),
+            },
+            AlertConfig {
+                name: "bailout".to_string(),
+                mime: Some("text/*".to_string()),
+                min_size: None,
+                max_size: None,
+                regex: Some("(?i)bailout".to_string()),
+                output: bailout.clone(),
+            },
+        ])
+        .unwrap();
+
+        let big = Inscription::new(txid(1), svg(60 * 1024));
+        let small = Inscription::new(txid(2), svg(1024));
+        let headline = Inscription::new(txid(3), InscriptionType::Text("second Bailout for banks".to_string()));
+        let other = Inscription::new(txid(4), InscriptionType::Text("gm".to_string()));
+
+        assert_eq!(alerts.evaluate(&big), vec!["large-svg"]);
+        assert!(alerts.evaluate(&small).is_empty());
+        assert_eq!(alerts.evaluate(&headline), vec!["bailout"]);
+        assert!(alerts.evaluate(&other).is_empty());
+
+        for inscription in [&big, &small, &headline, &other] {
+            alerts.emit(inscription).unwrap();
+        }
+
+        let svg_lines = fs::read_to_string(&large_svg).unwrap();
+        assert_eq!(svg_lines.lines().count(), 1);
+        assert!(svg_lines.contains(&txid(1).to_string()));
+
+        let text_lines = fs::read_to_string(&bailout).unwrap();
+        assert_eq!(text_lines.lines().count(), 1);
+        assert!(text_lines.contains(&txid(3).to_string()));
+    }
+
+    #[test]
+    fn test_invalid_regex_is_rejected() {
+        let result = Alerts::new(&[AlertConfig {
+            name: "broken".to_string(),
+            mime: None,
+            min_size: None,
+            max_size: None,
+            regex: Some("(".to_string()),
+            output: PathBuf::from("unused.jsonl"),
+        }]);
+        assert!(matches!(result, Err(AlertError::InvalidRegex(..))));
+    }
+}
@@ -0,0 +1,212 @@
+use super::db::CacheBatch;
+use super::{CacheDb, CacheError, Result};
+use crate::utils::run_blocking;
+use bitcoin::consensus::encode;
+use bitcoin::hashes::Hash;
+use bitcoin::{Block, BlockHash};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Prefix for raw block entries kept in the cache DB
+const BLOCK_PREFIX: &[u8] = b"block:";
+/// Prefix for the insertion-order log used for eviction
+const ORDER_PREFIX: &[u8] = b"blockseq:";
+/// Key of the persisted eviction bookkeeping
+const STATE_KEY: &[u8] = b"blockcache:state";
+
+/// Eviction bookkeeping, persisted so the size limit holds across restarts
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct CacheState {
+    oldest: u64,
+    next: u64,
+    bytes: u64,
+}
+
+/// One insertion-order entry: which block was cached and how large it was
+#[derive(Debug, Serialize, Deserialize)]
+struct OrderEntry {
+    hash: [u8; 32],
+    size: u64,
+}
+
+/// Consensus-serialized blocks per block hash, so re-scans skip the RPC
+///
+/// Keyed by hash rather than height so a reorg can't serve a stale block.
+/// Once the cached bytes exceed `max_bytes` the oldest entries are evicted.
+pub struct BlockCache {
+    db: Arc<CacheDb>,
+    max_bytes: u64,
+    state: Mutex<CacheState>,
+    #[cfg(test)]
+    hits: AtomicU64,
+}
+
+impl BlockCache {
+    pub fn new(db: Arc<CacheDb>, max_bytes: u64) -> Result<Self> {
+        let state = db.get::<CacheState>(STATE_KEY)?.unwrap_or_default();
+        Ok(Self {
+            db,
+            max_bytes,
+            state: Mutex::new(state),
+            #[cfg(test)]
+            hits: AtomicU64::new(0),
+        })
+    }
+
+    /// The cached block for `hash`, if any
+    pub fn get(&self, hash: &BlockHash) -> Result<Option<Block>> {
+        let key = Self::key(hash);
+        let bytes = match self.db.get::<Vec<u8>>(&key)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        match encode::deserialize(&bytes) {
+            Ok(block) => {
+                #[cfg(test)]
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(block))
+            }
+            Err(_) => {
+                // The eviction log still counts it; the entry just ages out unread
+                self.db.delete(&key)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Caches a block, evicting the oldest entries past the size limit
+    ///
+    /// The block, its order entry, the evictions and the bookkeeping go in
+    /// one atomic write, so a crash can't leave them disagreeing.
+    pub fn put(&self, hash: &BlockHash, block: &Block) -> Result<()> {
+        let key = Self::key(hash);
+        let bytes = encode::serialize(block);
+        let size = bytes.len() as u64;
+        if size > self.max_bytes {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().map_err(|e| CacheError::LockError(e.to_string()))?;
+        if self.db.contains(&key)? {
+            return Ok(());
+        }
+        let mut next = state.clone();
+        let mut batch = CacheBatch::default();
+        batch.put(&key, &bytes)?;
+        batch.put(&Self::order_key(next.next), &OrderEntry { hash: hash.to_byte_array(), size })?;
+        next.next += 1;
+        next.bytes += size;
+
+        // The new block fits the limit on its own, so eviction stops before its entry
+        while next.bytes > self.max_bytes && next.oldest < next.next {
+            let order_key = Self::order_key(next.oldest);
+            if let Some(entry) = self.db.get::<OrderEntry>(&order_key)? {
+                batch.delete(&Self::key(&BlockHash::from_byte_array(entry.hash)));
+                next.bytes = next.bytes.saturating_sub(entry.size);
+            }
+            batch.delete(&order_key);
+            next.oldest += 1;
+        }
+        batch.put(STATE_KEY, &next)?;
+        self.db.write(batch)?;
+        *state = next;
+        Ok(())
+    }
+
+    /// Returns the cached block for `hash`, or fetches and caches it
+    ///
+    /// Cache failures are logged and fall through to `fetch`; they never fail the lookup.
+    /// RocksDB calls go through `run_blocking`, which blocks the current worker in
+    /// place (its queued tasks move to other workers) or runs them inline.
+    pub async fn get_or_fetch<F, Fut, E>(&self, hash: &BlockHash, fetch: F) -> std::result::Result<Block, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = std::result::Result<Block, E>>,
+    {
+        match run_blocking(|| self.get(hash)) {
+            Ok(Some(block)) => return Ok(block),
+            Ok(None) => {}
+            Err(e) => warn!("Block cache lookup for {} failed: {}", hash, e),
+        }
+
+        let block = fetch().await?;
+        if let Err(e) = run_blocking(|| self.put(hash, &block)) {
+            warn!("Failed to cache block {}: {}", hash, e);
+        }
+        Ok(block)
+    }
+
+    /// Number of lookups answered from the cache
+    #[cfg(test)]
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    fn key(hash: &BlockHash) -> Vec<u8> {
+        [BLOCK_PREFIX, &hash.to_byte_array()[..]].concat()
+    }
+
+    fn order_key(seq: u64) -> Vec<u8> {
+        [ORDER_PREFIX, &seq.to_be_bytes()[..]].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::Network;
+    use std::sync::atomic::AtomicUsize;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_second_fetch_is_served_from_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let cache = BlockCache::new(db, 64 * 1024 * 1024).unwrap();
+        let block = genesis_block(Network::Bitcoin);
+        let hash = block.block_hash();
+        let rpc_calls = AtomicUsize::new(0);
+
+        for _ in 0..2 {
+            let fetched = cache
+                .get_or_fetch(&hash, || async {
+                    rpc_calls.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, CacheError>(block.clone())
+                })
+                .await
+                .unwrap();
+            assert_eq!(fetched.block_hash(), hash);
+        }
+
+        assert_eq!(rpc_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.hits(), 1);
+    }
+
+    #[test]
+    fn test_oldest_blocks_are_evicted_past_the_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let block = genesis_block(Network::Bitcoin);
+        let size = encode::serialize(&block).len() as u64;
+        let cache = BlockCache::new(db.clone(), size * 2).unwrap();
+
+        let hashes: Vec<BlockHash> = (1..=3).map(|i| BlockHash::from_byte_array([i; 32])).collect();
+        for hash in &hashes {
+            cache.put(hash, &block).unwrap();
+        }
+
+        assert!(cache.get(&hashes[0]).unwrap().is_none());
+        assert!(cache.get(&hashes[1]).unwrap().is_some());
+        assert!(cache.get(&hashes[2]).unwrap().is_some());
+
+        // The bookkeeping survives a reopen
+        let reopened = BlockCache::new(db, size * 2).unwrap();
+        reopened.put(&BlockHash::from_byte_array([4; 32]), &block).unwrap();
+        assert!(reopened.get(&hashes[1]).unwrap().is_none());
+    }
+}
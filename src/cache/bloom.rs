@@ -1,9 +1,62 @@
 use super::Result;
-use bloom::BloomFilter;
+use bitcoin::hashes::{sha256, Hash};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
 use std::sync::RwLock;
 
+/// Bit vector and sizing of a bloom filter, as saved to disk
+///
+/// Positions are derived from a SHA-256 of the key rather than a randomly
+/// seeded hasher, so a filter saved by one run is valid in the next.
+#[derive(Debug, Serialize, Deserialize)]
+struct Filter {
+    size: usize,
+    fp_rate: f64,
+    num_hashes: u32,
+    bits: Vec<u64>,
+}
+
+impl Filter {
+    fn new(size: usize, fp_rate: f64) -> Self {
+        let items = size.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(items * fp_rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let num_hashes = ((num_bits as f64 / items) * ln2).round().max(1.0) as u32;
+
+        Self {
+            size,
+            fp_rate,
+            num_hashes,
+            bits: vec![0; num_bits.div_ceil(64)],
+        }
+    }
+
+    /// Bit positions for `key`, using double hashing over one digest
+    fn positions(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let digest = sha256::Hash::hash(key).to_byte_array();
+        let h1 = u64::from_le_bytes(digest[..8].try_into().expect("8 bytes"));
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().expect("8 bytes"));
+        let num_bits = self.bits.len() as u64 * 64;
+
+        (0..self.num_hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    fn insert(&mut self, key: &[u8]) {
+        for bit in self.positions(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        self.positions(key).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
 pub struct BloomCache {
-    filter: RwLock<BloomFilter>,
+    filter: RwLock<Filter>,
     size: usize,
     fp_rate: f64,
 }
@@ -11,17 +64,71 @@ pub struct BloomCache {
 impl BloomCache {
     pub fn new(size: usize, fp_rate: f64) -> Self {
         Self {
-            filter: RwLock::new(BloomFilter::new(size, fp_rate)),
+            filter: RwLock::new(Filter::new(size, fp_rate)),
             size,
             fp_rate,
         }
     }
 
+    /// Restores a filter saved with `save`, or starts empty if there is none
+    ///
+    /// A saved filter built for a different size or rate is discarded, since
+    /// its bit positions no longer line up with the configured filter, and
+    /// so is one that is unreadable or whose bits don't match its sizing.
+    pub fn load(path: &Path, size: usize, fp_rate: f64) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(size, fp_rate));
+        }
+
+        let filter: Filter = match bincode::deserialize_from(BufReader::new(File::open(path)?)) {
+            Ok(filter) => filter,
+            Err(e) => {
+                warn!("Ignoring unreadable bloom filter {}: {}", path.display(), e);
+                return Ok(Self::new(size, fp_rate));
+            }
+        };
+        if filter.size != size || filter.fp_rate != fp_rate {
+            warn!(
+                "Ignoring saved bloom filter {} (built for size {} at rate {})",
+                path.display(),
+                filter.size,
+                filter.fp_rate
+            );
+            return Ok(Self::new(size, fp_rate));
+        }
+        let expected = Filter::new(size, fp_rate);
+        if filter.bits.len() != expected.bits.len() || filter.num_hashes != expected.num_hashes {
+            warn!("Ignoring corrupt bloom filter {} ({} words of bits)", path.display(), filter.bits.len());
+            return Ok(Self::new(size, fp_rate));
+        }
+
+        Ok(Self {
+            filter: RwLock::new(filter),
+            size,
+            fp_rate,
+        })
+    }
+
+    /// Writes the filter to `path`, replacing any previous save atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let filter = self.filter.read().map_err(|_| {
+            super::CacheError::LockError("Failed to acquire read lock for bloom filter".to_string())
+        })?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        bincode::serialize_into(&mut writer, &*filter)?;
+        writer.flush()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
     pub fn insert(&self, key: &[u8]) -> Result<()> {
         let mut filter = self.filter.write().map_err(|_| {
-            super::CacheError::DbError(rocksdb::Error::new(
-                "Failed to acquire write lock for bloom filter"
-            ))
+            super::CacheError::LockError("Failed to acquire write lock for bloom filter".to_string())
         })?;
         filter.insert(key);
         Ok(())
@@ -29,20 +136,17 @@ impl BloomCache {
 
     pub fn contains(&self, key: &[u8]) -> Result<bool> {
         let filter = self.filter.read().map_err(|_| {
-            super::CacheError::DbError(rocksdb::Error::new(
-                "Failed to acquire read lock for bloom filter"
-            ))
+            super::CacheError::LockError("Failed to acquire read lock for bloom filter".to_string())
         })?;
         Ok(filter.contains(key))
     }
 
+    #[allow(dead_code)]
     pub fn clear(&self) -> Result<()> {
         let mut filter = self.filter.write().map_err(|_| {
-            super::CacheError::DbError(rocksdb::Error::new(
-                "Failed to acquire write lock for bloom filter"
-            ))
+            super::CacheError::LockError("Failed to acquire write lock for bloom filter".to_string())
         })?;
-        *filter = BloomFilter::new(self.size, self.fp_rate);
+        *filter = Filter::new(self.size, self.fp_rate);
         Ok(())
     }
 }
@@ -50,22 +154,66 @@ impl BloomCache {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn test_bloom_cache() {
         let cache = BloomCache::new(1000, 0.01);
-        
+
         // Test insert and contains
         cache.insert(b"test1").unwrap();
         cache.insert(b"test2").unwrap();
-        
+
         assert!(cache.contains(b"test1").unwrap());
         assert!(cache.contains(b"test2").unwrap());
         assert!(!cache.contains(b"test3").unwrap());
-        
+
         // Test clear
         cache.clear().unwrap();
         assert!(!cache.contains(b"test1").unwrap());
         assert!(!cache.contains(b"test2").unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bloom.bin");
+
+        // Nothing saved yet, so loading starts empty
+        let cache = BloomCache::load(&path, 1000, 0.01).unwrap();
+        assert!(!cache.contains(b"test1").unwrap());
+
+        let keys: Vec<_> = (0..100).map(|i| format!("key-{}", i)).collect();
+        for key in &keys {
+            cache.insert(key.as_bytes()).unwrap();
+        }
+        cache.save(&path).unwrap();
+
+        let loaded = BloomCache::load(&path, 1000, 0.01).unwrap();
+        for key in &keys {
+            assert!(loaded.contains(key.as_bytes()).unwrap());
+        }
+        assert!(!loaded.contains(b"never-inserted").unwrap());
+
+        // A filter saved with different sizing is not reused
+        let resized = BloomCache::load(&path, 2000, 0.01).unwrap();
+        assert!(!resized.contains(b"key-0").unwrap());
+    }
+
+    #[test]
+    fn test_corrupt_save_is_rebuilt() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bloom.bin");
+
+        // Matching sizing but too few bits would index out of bounds
+        let mut short = Filter::new(1000, 0.01);
+        short.bits.truncate(1);
+        fs::write(&path, bincode::serialize(&short).unwrap()).unwrap();
+        let loaded = BloomCache::load(&path, 1000, 0.01).unwrap();
+        loaded.insert(b"key").unwrap();
+        assert!(loaded.contains(b"key").unwrap());
+
+        fs::write(&path, b"not a filter").unwrap();
+        assert!(!BloomCache::load(&path, 1000, 0.01).unwrap().contains(b"key").unwrap());
+    }
+}
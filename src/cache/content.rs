@@ -0,0 +1,91 @@
+use super::{CacheDb, Result};
+use blake3::Hash;
+use std::sync::Arc;
+
+/// Prefix for `contenthash:<hash>:<id>`, one entry per inscription
+const CONTENT_PREFIX: &[u8] = b"contenthash:";
+
+/// Prefix for `contenthash-of:<id>`, the hash each inscription was recorded under
+const REVERSE_PREFIX: &[u8] = b"contenthash-of:";
+
+/// Ids of every inscription per blake3 hash of its decoded body
+///
+/// Groups copies of the same content across inscriptions, e.g. to spot
+/// spam or re-inscriptions, regardless of the declared content type.
+/// Each id has its own key, so recording never rewrites a growing list.
+pub struct ContentHashIndex {
+    db: Arc<CacheDb>,
+}
+
+impl ContentHashIndex {
+    pub fn new(db: Arc<CacheDb>) -> Self {
+        Self { db }
+    }
+
+    /// Adds `inscription_id` under `hash`; recording it again is a no-op
+    pub fn record(&self, hash: &Hash, inscription_id: &str) -> Result<()> {
+        self.db.batch_put(&[
+            (Self::key(hash, inscription_id), Vec::new()),
+            (Self::reverse_key(inscription_id), hash.as_bytes().to_vec()),
+        ])
+    }
+
+    /// Inscription ids recorded under `hash`, in id order
+    pub fn ids(&self, hash: &Hash) -> Result<Vec<String>> {
+        let prefix = Self::key(hash, "");
+        Ok(self
+            .db
+            .keys_with_prefix(&prefix)?
+            .into_iter()
+            .map(|key| String::from_utf8_lossy(&key[prefix.len()..]).into_owned())
+            .collect())
+    }
+
+    /// Forgets every inscription of `txid`, returning how many were recorded
+    pub fn forget_txid(&self, txid: &str) -> Result<usize> {
+        let reverse = self.db.keys_with_prefix(&Self::reverse_key(&format!("{}i", txid)))?;
+        let mut keys = Vec::with_capacity(reverse.len() * 2);
+        for key in reverse {
+            if let Some(hash) = self.db.get::<Vec<u8>>(&key)? {
+                let id = String::from_utf8_lossy(&key[REVERSE_PREFIX.len()..]).into_owned();
+                keys.push([CONTENT_PREFIX, &hash, b":", id.as_bytes()].concat());
+            }
+            keys.push(key);
+        }
+        self.db.delete_keys(&keys)?;
+        Ok(keys.len() / 2)
+    }
+
+    fn key(hash: &Hash, inscription_id: &str) -> Vec<u8> {
+        [CONTENT_PREFIX, hash.as_bytes(), b":", inscription_id.as_bytes()].concat()
+    }
+
+    fn reverse_key(inscription_id: &str) -> Vec<u8> {
+        [REVERSE_PREFIX, inscription_id.as_bytes()].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ids_are_recorded_once_per_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = ContentHashIndex::new(Arc::new(CacheDb::new(temp_dir.path()).unwrap()));
+        let hash = blake3::hash(b"gm");
+
+        index.record(&hash, "ai0").unwrap();
+        index.record(&hash, "bi0").unwrap();
+        index.record(&hash, "ai0").unwrap();
+        assert_eq!(index.ids(&hash).unwrap(), vec!["ai0", "bi0"]);
+        assert!(index.ids(&blake3::hash(b"gn")).unwrap().is_empty());
+
+        // Forgetting a txid drops its ids and leaves the rest
+        index.record(&blake3::hash(b"gn"), "ai1").unwrap();
+        assert_eq!(index.forget_txid("a").unwrap(), 2);
+        assert_eq!(index.ids(&hash).unwrap(), vec!["bi0"]);
+        assert!(index.ids(&blake3::hash(b"gn")).unwrap().is_empty());
+    }
+}
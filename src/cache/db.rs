@@ -1,21 +1,58 @@
 use super::Result;
-use rocksdb::{DB, Options};
+use rocksdb::{DB, Options, WriteOptions};
 use std::path::Path;
 use serde::{Serialize, de::DeserializeOwned};
 
 pub struct CacheDb {
     db: DB,
+    write_opts: WriteOptions,
+}
+
+/// Puts and deletes applied in one atomic write by `CacheDb::write`
+#[derive(Default)]
+pub struct CacheBatch {
+    batch: rocksdb::WriteBatch,
+}
+
+impl CacheBatch {
+    pub fn put<T: Serialize>(&mut self, key: &[u8], value: &T) -> Result<()> {
+        self.batch.put(key, bincode::serialize(value)?);
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.batch.delete(key);
+    }
 }
 
 impl CacheDb {
+    #[allow(dead_code)]
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_sync(path, false)
+    }
+
+    /// Opens the cache, optionally fsyncing the WAL on every write
+    ///
+    /// Without `sync_writes`, durability relies on explicit `flush` calls
+    /// at checkpoints, trading a small loss window for throughput.
+    pub fn with_sync<P: AsRef<Path>>(path: P, sync_writes: bool) -> Result<Self> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.set_compression_type(rocksdb::DBCompressionType::Snappy);
         opts.set_write_buffer_size(64 * 1024 * 1024); // 64MB write buffer
         
         let db = DB::open(&opts, path)?;
-        Ok(Self { db })
+
+        let mut write_opts = WriteOptions::default();
+        write_opts.set_sync(sync_writes);
+        Ok(Self { db, write_opts })
+    }
+
+    /// Syncs the WAL and flushes memtables so everything written so far survives a crash
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush_wal(true)?;
+        self.db.flush()?;
+        Ok(())
     }
 
     pub fn get<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>> {
@@ -25,17 +62,99 @@ impl CacheDb {
         }
     }
 
+    /// Whether `key` is stored, without copying its value out
+    pub fn contains(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.db.get_pinned(key)?.is_some())
+    }
+
     pub fn put<T: Serialize>(&self, key: &[u8], value: &T) -> Result<()> {
         let data = bincode::serialize(value)?;
-        self.db.put(key, data)?;
+        self.db.put_opt(key, data, &self.write_opts)?;
         Ok(())
     }
 
     pub fn delete(&self, key: &[u8]) -> Result<()> {
-        self.db.delete(key)?;
+        self.db.delete_opt(key, &self.write_opts)?;
         Ok(())
     }
 
+    /// Deletes every key starting with `prefix`, returning how many were removed
+    pub fn delete_prefix(&self, prefix: &[u8]) -> Result<usize> {
+        let mut batch = rocksdb::WriteBatch::default();
+        for item in self.db.prefix_iterator(prefix) {
+            let (key, _) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            batch.delete(key);
+        }
+
+        let count = batch.len();
+        self.db.write_opt(batch, &self.write_opts)?;
+        Ok(count)
+    }
+
+    /// Every key starting with `prefix`, in key order
+    pub fn keys_with_prefix(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let mut keys = Vec::new();
+        for item in self.db.prefix_iterator(prefix) {
+            let (key, _) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            keys.push(key.to_vec());
+        }
+        Ok(keys)
+    }
+
+    /// Whether any key starts with `prefix`
+    pub fn has_prefix(&self, prefix: &[u8]) -> Result<bool> {
+        match self.db.prefix_iterator(prefix).next() {
+            Some(item) => Ok(item?.0.starts_with(prefix)),
+            None => Ok(false),
+        }
+    }
+
+    /// Deletes `keys` in one atomic write
+    pub fn delete_keys(&self, keys: &[Vec<u8>]) -> Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+        for key in keys {
+            batch.delete(key);
+        }
+        self.db.write_opt(batch, &self.write_opts)?;
+        Ok(())
+    }
+
+    /// Deletes the keys starting with `prefix` whose value is `stale` or
+    /// can no longer be decoded, returning how many were removed
+    pub fn delete_prefix_where<T: DeserializeOwned>(&self, prefix: &[u8], stale: impl Fn(&T) -> bool) -> Result<usize> {
+        let mut batch = rocksdb::WriteBatch::default();
+        for item in self.db.prefix_iterator(prefix) {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if bincode::deserialize(&value).map_or(true, |value| stale(&value)) {
+                batch.delete(key);
+            }
+        }
+
+        let count = batch.len();
+        self.db.write_opt(batch, &self.write_opts)?;
+        Ok(count)
+    }
+
+    /// Applies every operation of `batch` atomically
+    pub fn write(&self, batch: CacheBatch) -> Result<()> {
+        self.db.write_opt(batch.batch, &self.write_opts)?;
+        Ok(())
+    }
+
+    /// Compacts the whole key range, reclaiming the space of deleted and overwritten keys
+    pub fn compact(&self) {
+        self.db.compact_range::<&[u8], &[u8]>(None, None);
+    }
+
     pub fn batch_put<T: Serialize>(&self, items: &[(Vec<u8>, T)]) -> Result<()> {
         let mut batch = rocksdb::WriteBatch::default();
         
@@ -44,7 +163,7 @@ impl CacheDb {
             batch.put(key, data);
         }
 
-        self.db.write(batch)?;
+        self.db.write_opt(batch, &self.write_opts)?;
         Ok(())
     }
 }
@@ -92,4 +211,27 @@ mod tests {
         assert_eq!(retrieved2.id, 2);
         assert_eq!(retrieved3.id, 3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_flushed_state_survives_crash() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_dir = temp_dir.path().join("db");
+        let cache = CacheDb::new(&db_dir).unwrap();
+
+        cache.put(b"checkpoint", &780_000u64).unwrap();
+        cache.flush().unwrap();
+
+        // Copy the files while the DB is still open, as a crash would leave them
+        let crashed_dir = temp_dir.path().join("crashed");
+        std::fs::create_dir_all(&crashed_dir).unwrap();
+        for entry in std::fs::read_dir(&db_dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_name() != "LOCK" {
+                std::fs::copy(entry.path(), crashed_dir.join(entry.file_name())).unwrap();
+            }
+        }
+
+        let recovered = CacheDb::new(&crashed_dir).unwrap();
+        assert_eq!(recovered.get::<u64>(b"checkpoint").unwrap(), Some(780_000));
+    }
+}
@@ -0,0 +1,137 @@
+use super::{BloomCache, CacheDb, Result};
+use std::sync::Arc;
+
+/// Prefix for the exact "already stored" markers kept in the cache DB
+const STORED_PREFIX: &[u8] = b"stored:";
+
+/// Prefix for `stored-at:<height>:<key>`, the markers of each block height
+const HEIGHT_PREFIX: &[u8] = b"stored-at:";
+
+/// Skips inscriptions that were already stored, e.g. during rescans
+///
+/// The bloom filter answers most lookups from memory; because it can
+/// report false positives, every hit is confirmed against the cache DB
+/// before anything is skipped.
+pub struct Deduplicator {
+    bloom: Arc<BloomCache>,
+    db: Arc<CacheDb>,
+}
+
+impl Deduplicator {
+    pub fn new(bloom: Arc<BloomCache>, db: Arc<CacheDb>) -> Self {
+        Self { bloom, db }
+    }
+
+    /// Whether `key` has definitely been stored before
+    pub fn is_duplicate(&self, key: &[u8]) -> Result<bool> {
+        if !self.bloom.contains(key)? {
+            return Ok(false);
+        }
+        self.is_marked(key)
+    }
+
+    /// Whether the cache DB holds a stored marker for `key`
+    ///
+    /// Skips the bloom filter, which may not have been saved before a crash.
+    pub fn is_marked(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.db.get::<bool>(&Self::db_key(key))?.is_some())
+    }
+
+    /// Records `key`, found at `height`, once it has been handled successfully
+    pub fn mark_stored(&self, key: &[u8], height: u64) -> Result<()> {
+        self.bloom.insert(key)?;
+        self.db.batch_put(&[(Self::db_key(key), true), (Self::height_key(height, key), true)])
+    }
+
+    /// Forgets every key marked at one of `heights`, stored or not, so a
+    /// rescan of them handles everything again. Returns how many were forgotten.
+    pub fn forget_heights(&self, heights: impl IntoIterator<Item = u64>) -> Result<usize> {
+        let mut forgotten = 0;
+        for height in heights {
+            let prefix = Self::height_key(height, b"");
+            let mut keys = self.db.keys_with_prefix(&prefix)?;
+            forgotten += keys.len();
+            let markers: Vec<Vec<u8>> = keys.iter().map(|key| Self::db_key(&key[prefix.len()..])).collect();
+            keys.extend(markers);
+            self.db.delete_keys(&keys)?;
+        }
+        Ok(forgotten)
+    }
+
+    /// Whether anything was marked at one of `heights`
+    pub fn any_at_heights(&self, heights: impl IntoIterator<Item = u64>) -> Result<bool> {
+        for height in heights {
+            if self.db.has_prefix(&Self::height_key(height, b""))? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Forgets every inscription of `txid`, so a rescan stores them again
+    pub fn forget_txid(&self, txid: &str) -> Result<usize> {
+        self.db.delete_prefix(&Self::db_key(format!("{}i", txid).as_bytes()))
+    }
+
+    fn db_key(key: &[u8]) -> Vec<u8> {
+        [STORED_PREFIX, key].concat()
+    }
+
+    /// Big-endian, so a height's keys share one prefix
+    fn height_key(height: u64, key: &[u8]) -> Vec<u8> {
+        [HEIGHT_PREFIX, &height.to_be_bytes(), b":", key].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bloom_hits_are_confirmed() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let dedup = Deduplicator::new(Arc::new(BloomCache::new(1000, 0.01)), db);
+
+        assert!(!dedup.is_duplicate(b"txid-a").unwrap());
+        dedup.mark_stored(b"txid-a", 1).unwrap();
+        assert!(dedup.is_duplicate(b"txid-a").unwrap());
+
+        // A bloom-only hit (a false positive) is not trusted without the DB marker
+        dedup.bloom.insert(b"txid-b").unwrap();
+        assert!(!dedup.is_duplicate(b"txid-b").unwrap());
+    }
+
+    #[test]
+    fn test_forgotten_txids_are_stored_again() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let dedup = Deduplicator::new(Arc::new(BloomCache::new(1000, 0.01)), db);
+
+        for key in ["aaaai0", "aaaai1", "aaaabi0"] {
+            dedup.mark_stored(key.as_bytes(), 1).unwrap();
+        }
+        assert_eq!(dedup.forget_txid("aaaa").unwrap(), 2);
+        assert!(!dedup.is_duplicate(b"aaaai0").unwrap());
+        assert!(!dedup.is_duplicate(b"aaaai1").unwrap());
+        assert!(dedup.is_duplicate(b"aaaabi0").unwrap());
+    }
+
+    #[test]
+    fn test_forgotten_heights_are_handled_again() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let dedup = Deduplicator::new(Arc::new(BloomCache::new(1000, 0.01)), db);
+
+        dedup.mark_stored(b"aaaai0", 100).unwrap();
+        dedup.mark_stored(b"bbbbi0", 100).unwrap();
+        dedup.mark_stored(b"cccci0", 101).unwrap();
+        assert!(dedup.any_at_heights([99, 100]).unwrap());
+        assert_eq!(dedup.forget_heights([100]).unwrap(), 2);
+        assert!(!dedup.any_at_heights([99, 100]).unwrap());
+        assert!(!dedup.is_duplicate(b"aaaai0").unwrap());
+        assert!(!dedup.is_duplicate(b"bbbbi0").unwrap());
+        assert!(dedup.is_duplicate(b"cccci0").unwrap());
+    }
+}
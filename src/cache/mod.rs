@@ -1,8 +1,16 @@
 mod db;
 mod bloom;
+mod dedup;
+mod parsed;
+mod blocks;
+mod content;
 
 pub use db::CacheDb;
 pub use bloom::BloomCache;
+pub use dedup::Deduplicator;
+pub use parsed::ParsedBlockCache;
+pub use blocks::BlockCache;
+pub use content::ContentHashIndex;
 
 use thiserror::Error;
 
@@ -13,6 +21,15 @@ pub enum CacheError {
     
     #[error("Serialization error: {0}")]
     SerializationError(#[from] bincode::Error),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Lock error: {0}")]
+    LockError(String),
 }
 
 pub type Result<T> = std::result::Result<T, CacheError>;
\ No newline at end of file
@@ -0,0 +1,153 @@
+use super::{CacheDb, Result};
+use crate::parser::Inscription;
+use bitcoin::hashes::Hash;
+use bitcoin::BlockHash;
+use serde::{Deserialize, Serialize};
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Prefix for parsed-block entries kept in the cache DB
+const PARSED_PREFIX: &[u8] = b"parsed:";
+
+/// Parse results for one block, as stored in the cache DB
+///
+/// Inscriptions are kept as JSON because their serde representation
+/// (JSON bodies, CBOR metadata) doesn't round-trip through bincode.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedBlock {
+    parser_version: String,
+    stored_at: u64,
+    inscriptions: String,
+}
+
+/// Parsed inscriptions per block hash, so re-scanned blocks skip parsing
+///
+/// Entries expire after `ttl` and are ignored when they were produced by a
+/// different parser version or configuration.
+pub struct ParsedBlockCache {
+    db: Arc<CacheDb>,
+    ttl: Duration,
+    #[cfg(test)]
+    hits: AtomicU64,
+}
+
+impl ParsedBlockCache {
+    pub fn new(db: Arc<CacheDb>, ttl: Duration) -> Self {
+        Self {
+            db,
+            ttl,
+            #[cfg(test)]
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    /// Cached inscriptions for a block, if fresh and from the same parser version
+    pub fn get(&self, hash: &BlockHash, parser_version: &str) -> Result<Option<Vec<Inscription>>> {
+        let key = Self::key(hash);
+        let cached = match self.db.get::<CachedBlock>(&key)? {
+            Some(cached) => cached,
+            None => return Ok(None),
+        };
+
+        let expired = now().saturating_sub(cached.stored_at) > self.ttl.as_secs();
+        if expired || cached.parser_version != parser_version {
+            self.db.delete(&key)?;
+            return Ok(None);
+        }
+
+        #[cfg(test)]
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Ok(Some(serde_json::from_str(&cached.inscriptions)?))
+    }
+
+    pub fn put(&self, hash: &BlockHash, parser_version: &str, inscriptions: &[Inscription]) -> Result<()> {
+        self.db.put(&Self::key(hash), &CachedBlock {
+            parser_version: parser_version.to_string(),
+            stored_at: now(),
+            inscriptions: serde_json::to_string(inscriptions)?,
+        })
+    }
+
+    /// Deletes every entry past its TTL, returning how many were removed
+    ///
+    /// Expired entries are otherwise only dropped when their block is looked up again.
+    pub fn purge_expired(&self) -> Result<usize> {
+        let now = now();
+        let ttl = self.ttl.as_secs();
+        self.db.delete_prefix_where(PARSED_PREFIX, |cached: &CachedBlock| now.saturating_sub(cached.stored_at) > ttl)
+    }
+
+    /// Number of lookups answered from the cache
+    #[cfg(test)]
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    fn key(hash: &BlockHash) -> Vec<u8> {
+        [PARSED_PREFIX, &hash.to_byte_array()[..]].concat()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::InscriptionType;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_entries_are_versioned_and_expire() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let hash = BlockHash::all_zeros();
+        let inscriptions = vec![Inscription::new(
+            bitcoin::Txid::all_zeros(),
+            InscriptionType::Json(serde_json::json!({"p": "brc-20"})),
+        )];
+
+        let cache = ParsedBlockCache::new(db.clone(), Duration::from_secs(3600));
+        cache.put(&hash, "v1", &inscriptions).unwrap();
+        let cached = cache.get(&hash, "v1").unwrap().unwrap();
+        assert_eq!(cached[0].content_id(), inscriptions[0].content_id());
+        assert_eq!(cache.hits(), 1);
+
+        // A different parser version invalidates the entry
+        assert!(cache.get(&hash, "v2").unwrap().is_none());
+        assert!(cache.get(&hash, "v1").unwrap().is_none());
+
+        // So does age
+        let expiring = ParsedBlockCache::new(db, Duration::ZERO);
+        expiring.put(&hash, "v1", &inscriptions).unwrap();
+        std::thread::sleep(Duration::from_millis(1100));
+        assert!(expiring.get(&hash, "v1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_purge_removes_only_expired_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let cache = ParsedBlockCache::new(db.clone(), Duration::from_secs(3600));
+
+        let fresh = BlockHash::all_zeros();
+        let stale = BlockHash::from_byte_array([1; 32]);
+        cache.put(&fresh, "v1", &[]).unwrap();
+        db.put(&ParsedBlockCache::key(&stale), &CachedBlock {
+            parser_version: "v1".to_string(),
+            stored_at: now() - 7200,
+            inscriptions: "[]".to_string(),
+        })
+        .unwrap();
+
+        assert_eq!(cache.purge_expired().unwrap(), 1);
+        assert!(db.get::<CachedBlock>(&ParsedBlockCache::key(&stale)).unwrap().is_none());
+        assert!(cache.get(&fresh, "v1").unwrap().is_some());
+    }
+}
@@ -1,9 +1,13 @@
+mod schema;
 mod settings;
+mod validate;
 
-pub use settings::Config;
+pub use schema::schema;
+pub use settings::{AlertConfig, Config, NodeConfig, StorageBackend};
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io::{self, Write};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,10 +16,124 @@ pub enum ConfigError {
     IoError(#[from] std::io::Error),
     #[error("Failed to parse config: {0}")]
     ParseError(#[from] toml::de::Error),
+    /// The config parsed but a value can't work
+    #[error("Invalid config: {0}")]
+    ValidationError(String),
+    #[error("{} already exists; pass --force to overwrite it", .0.display())]
+    AlreadyExists(PathBuf),
 }
 
+/// Loads and validates the config at `path`
+///
+/// SCANNER_RPC_URL, SCANNER_RPC_USER and SCANNER_RPC_PASSWORD take
+/// precedence over the file, which takes precedence over the defaults, so
+/// credentials don't have to be kept in it.
 pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+    load_config_with(path.as_ref(), |name| std::env::var(name).ok())
+}
+
+/// `load_config` with the environment read through `lookup`
+fn load_config_with(path: &Path, lookup: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
     let content = fs::read_to_string(path)?;
-    let config: Config = toml::from_str(&content)?;
+    let mut config: Config = toml::from_str(&content)?;
+    apply_env_overrides(&mut config, lookup);
+    config.validate()?;
     Ok(config)
+}
+
+/// Writes the commented default config to `path`
+///
+/// An existing file is only replaced with `force`.
+pub fn init_config(path: &Path, force: bool) -> Result<(), ConfigError> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .create_new(!force)
+        .truncate(true)
+        .open(path)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => ConfigError::AlreadyExists(path.to_path_buf()),
+            _ => e.into(),
+        })?;
+    file.write_all(schema().as_bytes())?;
+    Ok(())
+}
+
+/// Replaces the node settings `lookup` has a value for
+fn apply_env_overrides(config: &mut Config, lookup: impl Fn(&str) -> Option<String>) {
+    if let Some(url) = lookup("SCANNER_RPC_URL") {
+        config.node.rpc_url = url;
+    }
+    if let Some(user) = lookup("SCANNER_RPC_USER") {
+        config.node.rpc_user = user;
+    }
+    if let Some(password) = lookup("SCANNER_RPC_PASSWORD") {
+        config.node.rpc_password = password;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn config_file(node: &str) -> NamedTempFile {
+        let dir = std::env::temp_dir();
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "[node]\n{}\nmax_concurrent_requests = 4\n\
+             [storage]\nimage_dir = {:?}\ntext_log = {:?}\n\
+             [processing]\nbatch_size = 10\n",
+            node,
+            dir.join("images"),
+            dir.join("inscriptions.log")
+        )
+        .unwrap();
+        file
+    }
+
+    #[test]
+    fn test_env_overrides_the_file() {
+        let file = config_file("rpc_url = \"http://127.0.0.1:8332\"\nrpc_password = \"from-file\"");
+        let env = |name: &str| (name == "SCANNER_RPC_PASSWORD").then(|| "from-env".to_string());
+        let config = load_config_with(file.path(), env).unwrap();
+        assert_eq!(config.node.rpc_password, "from-env");
+        assert_eq!(config.node.rpc_url, "http://127.0.0.1:8332");
+    }
+
+    #[test]
+    fn test_init_config_writes_a_loadable_default() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        init_config(&path, false).unwrap();
+
+        let config = load_config(&path).unwrap();
+        assert_eq!(config.processing.batch_size, Config::default().processing.batch_size);
+
+        // Only replaced with force
+        fs::write(&path, "# edited").unwrap();
+        assert!(matches!(init_config(&path, false), Err(ConfigError::AlreadyExists(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# edited");
+        init_config(&path, true).unwrap();
+        load_config(&path).unwrap();
+    }
+
+    #[test]
+    fn test_env_can_supply_fields_missing_from_the_file() {
+        let file = config_file("");
+        let mut config: Config = toml::from_str(&fs::read_to_string(file.path()).unwrap()).unwrap();
+        assert!(config.node.rpc_url.is_empty());
+
+        let env = |name: &str| match name {
+            "SCANNER_RPC_URL" => Some("http://10.0.0.2:8332".to_string()),
+            "SCANNER_RPC_USER" => Some("scanner".to_string()),
+            _ => None,
+        };
+        apply_env_overrides(&mut config, env);
+        assert_eq!(config.node.rpc_url, "http://10.0.0.2:8332");
+        assert_eq!(config.node.rpc_user, "scanner");
+        assert!(config.node.rpc_password.is_empty());
+        config.validate().unwrap();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,199 @@
+use super::Config;
+use toml::Value;
+
+/// Every config field as (section, key, type, description), in the order printed
+///
+/// Values and defaults come from `Config::default()`; the tests fail when a
+/// field is added to the config without being documented here.
+const FIELDS: &[(&str, &str, &str, &str)] = &[
+    ("node", "rpc_url", "string, required", "Bitcoin Core RPC endpoint; SCANNER_RPC_URL overrides it"),
+    ("node", "rpc_user", "string", "RPC username; SCANNER_RPC_USER overrides it"),
+    ("node", "rpc_password", "string", "RPC password; SCANNER_RPC_PASSWORD overrides it"),
+    ("node", "cookie_file", "path, optional", "Bitcoin Core .cookie file; replaces rpc_user/rpc_password when set"),
+    ("node", "max_concurrent_requests", "integer, required", "RPC requests in flight at once"),
+    ("node", "verify_merkle", "bool", "Recompute each block's merkle root before trusting its transactions"),
+    ("node", "max_retries", "integer", "Retries for connection-level RPC failures before giving up"),
+    ("node", "retry_base_ms", "integer", "Initial retry delay in milliseconds, doubled after each attempt"),
+    ("node", "network", "\"bitcoin\" | \"testnet\" | \"signet\" | \"regtest\"", "Chain the node must be on; checked at startup"),
+    ("node", "proxy", "string, optional", "socks5://[user:pass@]host:port or http://[user:pass@]host:port proxy for RPC; required for .onion nodes (e.g. Tor's socks5://127.0.0.1:9050)"),
+    ("storage", "image_dir", "path, required", "Where image inscriptions are written"),
+    ("storage", "text_log", "path, required", "JSON lines log of text and JSON inscriptions"),
+    ("storage", "max_log_bytes", "integer", "Rename text_log to text_log.1, .2, ... once it reaches this size and start a new one; 0 never rotates"),
+    ("storage", "binary_dir", "path", "Bodies with a declared type other than text or images (audio, video, HTML, ...), each with a <id>.json sidecar holding its MIME type"),
+    ("storage", "archive_dir", "path, optional", "Keep raw envelope transactions per height so ranges can be reprocessed"),
+    ("storage", "index_thumbnails", "bool", "Embed a 32x32 base64 preview of each image in the image index"),
+    ("storage", "generate_thumbnails", "bool", "Write a <txid>-<hash>.thumb.png (max 256px) next to each image; skipped for SVG"),
+    ("storage", "compress_images", "bool", "Gzip stored images; JPEG/WebP/GIF/AVIF are kept as-is"),
+    ("storage", "strict_images", "bool", "Skip images whose bytes don't match their MIME type instead of relabeling them"),
+    ("storage", "shard_depth", "integer", "Nest images under this many ab/cd/ directory levels (at most 4) named by leading txid characters; 0 keeps image_dir flat"),
+    ("storage", "link_content", "bool", "Store each distinct body once under content/ and link inscriptions to it"),
+    ("storage", "store_tx_metadata", "bool", "Record the reveal transaction's version, lock time, input/output counts and weight"),
+    ("storage", "backend", "\"jsonl\" | \"sqlite\"", "Keep text and JSON inscriptions in text_log or in the sqlite_path database"),
+    ("storage", "sqlite_path", "path", "Database file used by the sqlite backend"),
+    ("storage", "stream", "bool", "Also publish every stored inscription as JSON to stream_subject on stream_url; needs the nats feature"),
+    ("storage", "stream_url", "string", "NATS server stream messages go to; scanning continues without it if unreachable"),
+    ("storage", "stream_subject", "string", "Subject each inscription is published to as JSON"),
+    ("storage", "recent_cache_size", "integer", "Recently stored text, JSON and SVG inscriptions kept in memory so lookups by txid and delegates skip the disk; 0 disables"),
+    ("cache", "enabled", "bool", "RocksDB cache; also deduplicates inscriptions across rescans"),
+    ("cache", "path", "path", "Cache directory; the dedup bloom filter is saved next to it as <path>.bloom"),
+    ("cache", "sync_writes", "bool", "Fsync every write instead of only at checkpoints"),
+    ("cache", "bloom_filter_size", "integer", "Expected number of stored inscriptions the dedup filter is sized for"),
+    ("cache", "bloom_filter_fp_rate", "float", "Target false-positive rate of the dedup filter, strictly between 0 and 1"),
+    ("cache", "parsed_block_ttl_secs", "integer", "How long parse results are reused for re-scanned blocks, in seconds; 0 disables"),
+    ("cache", "block_cache", "bool", "Keep fetched blocks in the cache DB so re-scans skip the RPC"),
+    ("cache", "block_cache_max_mb", "integer", "Block cache size limit in MiB; the oldest blocks are evicted past it"),
+    ("processing", "batch_size", "integer, required", "Blocks fetched from the node per RPC batch"),
+    ("processing", "lenient", "bool", "Try to recover inscriptions that don't follow the spec exactly, including envelopes in non-taproot witnesses"),
+    ("processing", "max_inscriptions_per_tx", "integer", "Envelopes parsed per transaction; later ones are skipped and the tx is flagged"),
+    ("processing", "max_inscription_size", "integer", "Body bytes kept per inscription; larger bodies are recorded as oversized"),
+    ("processing", "genesis_address", "bool", "Record the address each inscription was revealed to"),
+    ("processing", "content_types", "array of strings", "Only read bodies declaring these types (MIME or \"type/*\"); empty keeps all"),
+    ("processing", "sanitize_svg", "bool", "Strip scripts, event handlers, javascript: URLs and <foreignObject> from SVGs before storing them"),
+    ("processing", "tokio_worker_threads", "integer, optional", "Async runtime workers, at most cores - 1; parsing uses the remaining cores (default: cores / 4)"),
+    ("processing", "thread_count", "integer, optional", "Parser threads (default: the cores left after the tokio workers)"),
+    ("processing", "chunk_size", "integer", "Blocks per parallel parsing chunk, independent of batch_size"),
+    ("processing", "max_in_flight", "integer", "Fetched blocks held in memory waiting to be parsed; fetching pauses at this many"),
+    ("maintenance", "interval_secs", "integer", "Seconds between upkeep runs (rotating the text log, purging expired parse results, compaction); 0 disables"),
+    ("alerts", "name", "string, required", "Name of the saved query"),
+    ("alerts", "mime", "string, optional", "Exact MIME type, or a \"type/*\" wildcard"),
+    ("alerts", "min_size", "integer, optional", "Minimum body size in bytes"),
+    ("alerts", "max_size", "integer, optional", "Maximum body size in bytes"),
+    ("alerts", "regex", "string, optional", "Regular expression matched against the body"),
+    ("alerts", "output", "path, required", "File that matches are appended to as JSON lines"),
+];
+
+/// Values shown for fields that are unset by default
+const EXAMPLES: &[(&str, &str, &str)] = &[
+    ("node", "cookie_file", "\"/home/bitcoin/.bitcoin/.cookie\""),
+    ("node", "proxy", "\"socks5://127.0.0.1:9050\""),
+    ("storage", "archive_dir", "\"./data/raw\""),
+    ("processing", "tokio_worker_threads", "2"),
+    ("processing", "thread_count", "6"),
+    ("alerts", "name", "\"large-svg\""),
+    ("alerts", "mime", "\"image/svg+xml\""),
+    ("alerts", "min_size", "51200"),
+    ("alerts", "max_size", "1048576"),
+    ("alerts", "regex", "\"(?i)<script\""),
+    ("alerts", "output", "\"./data/alerts/large-svg.jsonl\""),
+];
+
+/// Renders a commented example config documenting every field
+///
+/// Fields set by default are written out with their default value, so the
+/// output loads as is; unset ones are commented out with an example.
+pub fn schema() -> String {
+    let defaults = Value::try_from(Config::default()).unwrap_or_else(|_| Value::Table(Default::default()));
+    let mut out = String::new();
+    let mut current = "";
+
+    for &(section, key, ty, description) in FIELDS {
+        if section != current {
+            if !current.is_empty() {
+                out.push('\n');
+            }
+            if section == "alerts" {
+                out.push_str("# Saved queries, any number of them; every criterion that is set must match\n");
+                out.push_str("# [[alerts]]\n");
+            } else {
+                out.push_str(&format!("[{}]\n", section));
+            }
+            current = section;
+        }
+
+        let value = defaults.get(section).and_then(|table| table.get(key));
+        let shown = match value {
+            Some(value) => format!("default: {}", value),
+            None => "unset by default".to_string(),
+        };
+        out.push_str(&format!("# {} ({}; {})\n", description, ty, shown));
+
+        let example = EXAMPLES
+            .iter()
+            .find(|(s, k, _)| *s == section && *k == key)
+            .map(|(_, _, example)| *example);
+        match (value, example) {
+            (Some(value), _) if section != "alerts" => out.push_str(&format!("{} = {}\n", key, value)),
+            (_, Some(example)) => out.push_str(&format!("# {} = {}\n", key, example)),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AlertConfig;
+
+    #[test]
+    fn test_schema_parses_back_into_the_default_config() {
+        let config: Config = toml::from_str(&schema()).unwrap();
+        let defaults = Config::default();
+        assert_eq!(config.node.rpc_url, defaults.node.rpc_url);
+        assert_eq!(config.storage.sqlite_path, defaults.storage.sqlite_path);
+        assert_eq!(config.cache.bloom_filter_fp_rate, defaults.cache.bloom_filter_fp_rate);
+        assert_eq!(config.processing.max_inscription_size, defaults.processing.max_inscription_size);
+        assert!(config.storage.archive_dir.is_none());
+        assert!(config.alerts.is_empty());
+    }
+
+    /// The default config with every optional field set and one alert,
+    /// so serializing it yields every key the config has
+    fn fully_populated() -> Config {
+        let mut config = Config::default();
+        config.node.cookie_file = Some("/tmp/.cookie".into());
+        config.node.proxy = Some("socks5://127.0.0.1:9050".to_string());
+        config.storage.archive_dir = Some("./data/raw".into());
+        config.processing.tokio_worker_threads = Some(4);
+        config.processing.thread_count = Some(4);
+        config.processing.content_types = vec!["text/plain".to_string()];
+        config.alerts.push(AlertConfig {
+            name: "large-svg".to_string(),
+            mime: Some("image/svg+xml".to_string()),
+            min_size: Some(1),
+            max_size: Some(2),
+            regex: Some("<script".to_string()),
+            output: "./data/alerts.jsonl".into(),
+        });
+        config
+    }
+
+    #[test]
+    fn test_every_config_field_is_documented() {
+        let documented = |section: &str, key: &str| FIELDS.iter().any(|(s, k, _, _)| *s == section && *k == key);
+
+        let populated = Value::try_from(fully_populated()).unwrap();
+        let mut keys = Vec::new();
+        for (section, value) in populated.as_table().unwrap() {
+            // Array sections like [[alerts]] are checked through their first entry
+            let table = match value {
+                Value::Array(entries) => entries.first(),
+                value => Some(value),
+            };
+            for key in table.and_then(Value::as_table).map(|table| table.keys()).into_iter().flatten() {
+                assert!(documented(section, key), "{}.{} is missing from the schema", section, key);
+                keys.push((section.as_str(), key.as_str()));
+            }
+        }
+        for &(section, key, _, _) in FIELDS {
+            assert!(keys.contains(&(section, key)), "{}.{} is documented but not a config field", section, key);
+        }
+
+        // Unset fields don't serialize, so their examples must stand in
+        let defaults = Value::try_from(Config::default()).unwrap();
+        for &(section, key, _, _) in FIELDS {
+            let set = defaults.get(section).and_then(|table| table.get(key)).is_some();
+            let example = EXAMPLES.iter().any(|(s, k, _)| *s == section && *k == key);
+            assert!(set || example, "{}.{} has neither a default nor an example", section, key);
+        }
+
+        // With its examples uncommented, the alerts section loads too
+        let alert: String = EXAMPLES
+            .iter()
+            .filter(|(section, _, _)| *section == "alerts")
+            .map(|(_, key, example)| format!("{} = {}\n", key, example))
+            .collect();
+        let config: Config = toml::from_str(&format!("{}\n[[alerts]]\n{}", schema(), alert)).unwrap();
+        assert_eq!(config.alerts[0].min_size, Some(51200));
+    }
+}
@@ -1,30 +1,282 @@
-use serde::Deserialize;
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub node: NodeConfig,
     pub storage: StorageConfig,
     pub processing: ProcessingConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    /// Saved queries evaluated against every inscription
+    #[serde(default)]
+    pub alerts: Vec<AlertConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct NodeConfig {
+    /// May be left out when SCANNER_RPC_URL supplies it
+    #[serde(default)]
     pub rpc_url: String,
+    #[serde(default)]
     pub rpc_user: String,
+    #[serde(default)]
     pub rpc_password: String,
+    /// Bitcoin Core `.cookie` file; used instead of user/password when set
+    #[serde(default)]
+    pub cookie_file: Option<PathBuf>,
     pub max_concurrent_requests: usize,
+    /// Recompute each block's merkle root before trusting its txdata
+    #[serde(default)]
+    pub verify_merkle: bool,
+    /// Retries for connection-level RPC failures before giving up
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Initial retry delay in milliseconds, doubled after each attempt
+    #[serde(default = "default_retry_base_ms")]
+    pub retry_base_ms: u64,
+    /// Chain the node is expected to follow: "bitcoin", "testnet", "signet" or "regtest"
+    #[serde(default = "default_network")]
+    pub network: Network,
+    /// socks5:// or http:// proxy for the RPC connection; required for .onion nodes
+    #[serde(default)]
+    pub proxy: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_base_ms() -> u64 {
+    200
+}
+
+fn default_network() -> Network {
+    Network::Bitcoin
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StorageConfig {
     pub image_dir: PathBuf,
     pub text_log: PathBuf,
+    /// Rotate text_log to text_log.N once it reaches this many bytes; 0 never rotates
+    #[serde(default)]
+    pub max_log_bytes: u64,
+    /// Bodies with a declared type that isn't text or an image, with a JSON sidecar each
+    #[serde(default = "default_binary_dir")]
+    pub binary_dir: PathBuf,
+    /// Keep raw envelope transactions per height so ranges can be reprocessed
+    #[serde(default)]
+    pub archive_dir: Option<PathBuf>,
+    /// Embed a 32x32 base64 preview of each image in the image index
+    #[serde(default)]
+    pub index_thumbnails: bool,
+    /// Write a 256px `<txid>-<hash>.thumb.png` next to each decodable image
+    #[serde(default)]
+    pub generate_thumbnails: bool,
+    /// Gzip stored images (`.bin.gz`), except already-compressed formats
+    #[serde(default)]
+    pub compress_images: bool,
+    /// Skip images whose bytes don't match their MIME type instead of relabeling them
+    #[serde(default)]
+    pub strict_images: bool,
+    /// Nest images under this many `ab/cd/` directory levels named by leading txid characters; 0 is flat
+    #[serde(default)]
+    pub shard_depth: usize,
+    /// Store each distinct body once under `content/` and link inscriptions to it
+    #[serde(default)]
+    pub link_content: bool,
+    /// Record the reveal transaction's version, lock time, input/output counts and weight
+    #[serde(default)]
+    pub store_tx_metadata: bool,
+    /// Where text and JSON inscriptions are kept
+    #[serde(default)]
+    pub backend: StorageBackend,
+    /// Database file used by the sqlite backend
+    #[serde(default = "default_sqlite_path")]
+    pub sqlite_path: PathBuf,
+    /// Also publish every stored inscription to `stream_subject`, whatever the backend
+    #[serde(default)]
+    pub stream: bool,
+    /// NATS server stream messages are published to
+    #[serde(default = "default_stream_url")]
+    pub stream_url: String,
+    /// Subject every inscription is published on
+    #[serde(default = "default_stream_subject")]
+    pub stream_subject: String,
+    /// Recently stored text, JSON and SVG inscriptions kept in memory for lookups; 0 disables
+    #[serde(default = "default_recent_cache_size")]
+    pub recent_cache_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Append JSON lines to `text_log`
+    #[default]
+    Jsonl,
+    /// Rows in the `sqlite_path` database, indexed by txid
+    Sqlite,
+}
+
+fn default_binary_dir() -> PathBuf {
+    PathBuf::from("./data/binary")
+}
+
+fn default_recent_cache_size() -> usize {
+    1024
+}
+
+fn default_sqlite_path() -> PathBuf {
+    PathBuf::from("./data/inscriptions.db")
 }
 
-#[derive(Debug, Deserialize)]
+fn default_stream_url() -> String {
+    "nats://127.0.0.1:4222".to_string()
+}
+
+fn default_stream_subject() -> String {
+    "inscriptions".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ProcessingConfig {
     pub batch_size: usize,
+    /// Try to recover inscriptions that don't follow the spec exactly
+    #[serde(default)]
+    pub lenient: bool,
+    /// Envelopes parsed per transaction before the rest are skipped
+    #[serde(default = "default_max_inscriptions_per_tx")]
+    pub max_inscriptions_per_tx: usize,
+    /// Body bytes kept per inscription; larger ones are recorded as oversized
+    #[serde(default = "default_max_inscription_size")]
+    pub max_inscription_size: usize,
+    /// Record the address of the output each inscription was revealed to
+    #[serde(default)]
+    pub genesis_address: bool,
+    /// Declared content types (MIME essences or `type/*`) whose bodies are read;
+    /// other envelopes are skipped before their body is reassembled. Empty keeps all.
+    #[serde(default)]
+    pub content_types: Vec<String>,
+    /// Remove scripts, event handlers, `javascript:` URLs and `<foreignObject>` from SVG bodies before they're stored
+    #[serde(default)]
+    pub sanitize_svg: bool,
+    /// Tokio worker threads, capped at one fewer than the cores; rayon parsing gets the rest.
+    /// Defaults to a quarter of the available cores.
+    #[serde(default)]
+    pub tokio_worker_threads: Option<usize>,
+    /// Parser threads; defaults to the cores left after the tokio workers
+    #[serde(default)]
+    pub thread_count: Option<usize>,
+    /// Blocks per parallel parsing chunk, independent of `batch_size`
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// Fetched blocks allowed to wait for parsing before fetching pauses
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+fn default_chunk_size() -> usize {
+    10
+}
+
+fn default_max_in_flight() -> usize {
+    32
+}
+
+fn default_max_inscriptions_per_tx() -> usize {
+    crate::parser::DEFAULT_MAX_INSCRIPTIONS_PER_TX
+}
+
+fn default_max_inscription_size() -> usize {
+    crate::parser::DEFAULT_MAX_INSCRIPTION_SIZE
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cache_path")]
+    pub path: PathBuf,
+    /// Fsync every write instead of only at checkpoints (slower, no loss window)
+    #[serde(default)]
+    pub sync_writes: bool,
+    /// Expected number of stored inscriptions the dedup filter is sized for
+    #[serde(default = "default_bloom_filter_size")]
+    pub bloom_filter_size: usize,
+    /// Target false-positive rate of the dedup filter
+    #[serde(default = "default_bloom_filter_fp_rate")]
+    pub bloom_filter_fp_rate: f64,
+    /// How long parsed blocks are reused on re-scans, in seconds; 0, the default, disables it
+    #[serde(default)]
+    pub parsed_block_ttl_secs: u64,
+    /// Keep fetched blocks in the cache DB so re-scans skip the RPC
+    #[serde(default)]
+    pub block_cache: bool,
+    /// Size limit of the block cache in MiB; the oldest blocks are evicted past it
+    #[serde(default = "default_block_cache_max_mb")]
+    pub block_cache_max_mb: u64,
+}
+
+fn default_cache_path() -> PathBuf {
+    PathBuf::from("./data/cache")
+}
+
+fn default_bloom_filter_size() -> usize {
+    1_000_000
+}
+
+fn default_bloom_filter_fp_rate() -> f64 {
+    0.01
+}
+
+fn default_block_cache_max_mb() -> u64 {
+    2048
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_cache_path(),
+            sync_writes: false,
+            bloom_filter_size: default_bloom_filter_size(),
+            bloom_filter_fp_rate: default_bloom_filter_fp_rate(),
+            parsed_block_ttl_secs: 0,
+            block_cache: false,
+            block_cache_max_mb: default_block_cache_max_mb(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Seconds between upkeep runs (text log rotation, expired parse results, compaction); 0 disables it
+    #[serde(default)]
+    pub interval_secs: u64,
+}
+
+/// A named query; every criterion that is set must match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertConfig {
+    pub name: String,
+    /// Exact MIME type, or a `type/*` wildcard
+    #[serde(default)]
+    pub mime: Option<String>,
+    /// Minimum body size in bytes
+    #[serde(default)]
+    pub min_size: Option<usize>,
+    /// Maximum body size in bytes
+    #[serde(default)]
+    pub max_size: Option<usize>,
+    /// Regular expression matched against the body
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// File that matches are appended to as JSON lines
+    pub output: PathBuf,
 }
 
 impl Default for Config {
@@ -34,15 +286,79 @@ impl Default for Config {
                 rpc_url: "http://127.0.0.1:8332".to_string(),
                 rpc_user: "user".to_string(),
                 rpc_password: "password".to_string(),
+                cookie_file: None,
                 max_concurrent_requests: 16,
+                verify_merkle: false,
+                max_retries: default_max_retries(),
+                retry_base_ms: default_retry_base_ms(),
+                network: default_network(),
+                proxy: None,
             },
             storage: StorageConfig {
                 image_dir: PathBuf::from("./data/images"),
                 text_log: PathBuf::from("./data/inscriptions.log"),
+                max_log_bytes: 0,
+                binary_dir: default_binary_dir(),
+                archive_dir: None,
+                index_thumbnails: false,
+                generate_thumbnails: false,
+                compress_images: false,
+                strict_images: false,
+                shard_depth: 0,
+                link_content: false,
+                store_tx_metadata: false,
+                backend: StorageBackend::default(),
+                sqlite_path: default_sqlite_path(),
+                stream: false,
+                stream_url: default_stream_url(),
+                stream_subject: default_stream_subject(),
+                recent_cache_size: default_recent_cache_size(),
             },
             processing: ProcessingConfig {
                 batch_size: 1000,
+                lenient: false,
+                max_inscriptions_per_tx: default_max_inscriptions_per_tx(),
+                max_inscription_size: default_max_inscription_size(),
+                genesis_address: false,
+                content_types: Vec::new(),
+                sanitize_svg: false,
+                tokio_worker_threads: None,
+                thread_count: None,
+                chunk_size: default_chunk_size(),
+                max_in_flight: default_max_in_flight(),
             },
+            cache: CacheConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            alerts: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_with_node(extra: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(&format!(
+            "[node]\nrpc_url = \"http://127.0.0.1:8332\"\nmax_concurrent_requests = 4\n{}\n\
+             [storage]\nimage_dir = \"images\"\ntext_log = \"inscriptions.log\"\n\
+             [processing]\nbatch_size = 10\n",
+            extra
+        ))
+    }
+
+    #[test]
+    fn test_network_parses_from_toml() {
+        assert_eq!(parse_with_node("").unwrap().node.network, Network::Bitcoin);
+        for (name, network) in [
+            ("bitcoin", Network::Bitcoin),
+            ("testnet", Network::Testnet),
+            ("signet", Network::Signet),
+            ("regtest", Network::Regtest),
+        ] {
+            let config = parse_with_node(&format!("network = \"{}\"", name)).unwrap();
+            assert_eq!(config.node.network, network);
         }
+        assert!(parse_with_node("network = \"mainnet\"").is_err());
     }
 }
@@ -0,0 +1,229 @@
+use super::{Config, ConfigError};
+use crate::node::Proxy;
+use crate::storage::MAX_SHARD_DEPTH;
+use std::path::Path;
+use url::Url;
+
+impl Config {
+    /// Checks the values a scan can't work with, before anything uses them
+    ///
+    /// Storage directories don't have to exist yet, but the closest one that
+    /// does must be writable so they can be created.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::ValidationError(message));
+
+        if self.node.rpc_url.trim().is_empty() {
+            return invalid("node.rpc_url is empty; set it to the node's RPC endpoint, e.g. http://127.0.0.1:8332".to_string());
+        }
+        let rpc_url = match Url::parse(&self.node.rpc_url) {
+            Ok(url) => url,
+            Err(e) => {
+                return invalid(format!(
+                    "node.rpc_url {:?} is not a valid URL ({}); include the scheme, e.g. http://127.0.0.1:8332",
+                    self.node.rpc_url, e
+                ))
+            }
+        };
+        match &self.node.proxy {
+            Some(proxy) => {
+                if let Err(e) = Proxy::parse(proxy) {
+                    return invalid(format!("node.proxy: {}", e));
+                }
+            }
+            None if rpc_url.host_str().is_some_and(|host| host.ends_with(".onion")) => {
+                return invalid(format!(
+                    "node.rpc_url {} is a Tor address; set node.proxy to Tor's SOCKS port, e.g. socks5://127.0.0.1:9050",
+                    self.node.rpc_url
+                ));
+            }
+            None => {}
+        }
+        if self.node.max_concurrent_requests == 0 {
+            return invalid("node.max_concurrent_requests must be at least 1".to_string());
+        }
+        if self.processing.batch_size == 0 {
+            return invalid("processing.batch_size must be at least 1".to_string());
+        }
+        // Also rejects NaN, which fails both comparisons
+        if !(self.cache.bloom_filter_fp_rate > 0.0 && self.cache.bloom_filter_fp_rate < 1.0) {
+            return invalid(format!(
+                "cache.bloom_filter_fp_rate must be between 0 and 1 (exclusive), got {}",
+                self.cache.bloom_filter_fp_rate
+            ));
+        }
+        if self.storage.shard_depth > MAX_SHARD_DEPTH {
+            return invalid(format!("storage.shard_depth must be at most {}", MAX_SHARD_DEPTH));
+        }
+        if self.storage.stream && !cfg!(feature = "nats") {
+            return invalid("storage.stream needs a build with the nats feature (cargo build --features nats)".to_string());
+        }
+
+        let mut dirs = vec![
+            ("storage.image_dir", self.storage.image_dir.as_path()),
+            ("storage.binary_dir", self.storage.binary_dir.as_path()),
+        ];
+        if let Some(parent) = self.storage.text_log.parent() {
+            dirs.push(("storage.text_log", parent));
+        }
+        if let Some(archive_dir) = &self.storage.archive_dir {
+            dirs.push(("storage.archive_dir", archive_dir.as_path()));
+        }
+        for (field, dir) in dirs {
+            check_writable(field, dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fails unless `dir`, or the closest ancestor that exists, is a writable directory
+fn check_writable(field: &str, dir: &Path) -> Result<(), ConfigError> {
+    let existing = dir.ancestors().find(|path| path.as_os_str().is_empty() || path.exists());
+    let existing = match existing {
+        // A relative path with nothing existing yet is created in the working directory
+        Some(path) if path.as_os_str().is_empty() => Path::new("."),
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let metadata = std::fs::metadata(existing)?;
+    if !metadata.is_dir() {
+        return Err(ConfigError::ValidationError(format!(
+            "{} {} can't be created: {} is not a directory",
+            field,
+            dir.display(),
+            existing.display()
+        )));
+    }
+    // Mode bits miss other owners, read-only mounts and ACLs, so try a write
+    let probe = existing.join(format!(".write-test-{}", std::process::id()));
+    if let Err(e) = std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        return Err(ConfigError::ValidationError(format!(
+            "{} {} is not writable: can't create a file in {}: {}",
+            field,
+            dir.display(),
+            existing.display(),
+            e
+        )));
+    }
+    std::fs::remove_file(&probe)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config_in(dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.storage.image_dir = dir.join("images");
+        config.storage.text_log = dir.join("inscriptions.log");
+        config.storage.binary_dir = dir.join("binary");
+        config
+    }
+
+    fn error(config: &Config) -> String {
+        match config.validate() {
+            Err(ConfigError::ValidationError(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let temp_dir = TempDir::new().unwrap();
+        config_in(temp_dir.path()).validate().unwrap();
+    }
+
+    #[test]
+    fn test_rpc_url_must_be_set_and_parse() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = config_in(temp_dir.path());
+
+        config.node.rpc_url = " ".to_string();
+        assert!(error(&config).contains("node.rpc_url is empty"));
+
+        config.node.rpc_url = "127.0.0.1 8332".to_string();
+        assert!(error(&config).contains("not a valid URL"));
+    }
+
+    #[test]
+    fn test_onion_node_needs_a_valid_proxy() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = config_in(temp_dir.path());
+        config.node.rpc_url = "http://abcdefghijklmnop.onion:8332".to_string();
+        assert!(error(&config).contains("set node.proxy"));
+
+        config.node.proxy = Some("socks5://127.0.0.1".to_string());
+        assert!(error(&config).contains("node.proxy"));
+
+        config.node.proxy = Some("socks5://127.0.0.1:9050".to_string());
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_counts_must_be_positive() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = config_in(temp_dir.path());
+        config.processing.batch_size = 0;
+        assert!(error(&config).contains("processing.batch_size"));
+
+        let mut config = config_in(temp_dir.path());
+        config.node.max_concurrent_requests = 0;
+        assert!(error(&config).contains("node.max_concurrent_requests"));
+
+        let mut config = config_in(temp_dir.path());
+        config.storage.shard_depth = MAX_SHARD_DEPTH + 1;
+        assert!(error(&config).contains("storage.shard_depth"));
+
+        for fp_rate in [0.0, 1.0, -0.5, f64::NAN] {
+            let mut config = config_in(temp_dir.path());
+            config.cache.bloom_filter_fp_rate = fp_rate;
+            assert!(error(&config).contains("cache.bloom_filter_fp_rate"));
+        }
+    }
+
+    #[test]
+    fn test_stream_works_with_either_backend_but_needs_the_nats_feature() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = config_in(temp_dir.path());
+        config.storage.backend = crate::config::StorageBackend::Sqlite;
+        config.storage.stream = true;
+        if cfg!(feature = "nats") {
+            assert!(config.validate().is_ok());
+        } else {
+            assert!(error(&config).contains("nats feature"));
+        }
+    }
+
+    #[test]
+    fn test_storage_dirs_must_be_writable() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // A file where a directory would have to be created
+        let file = temp_dir.path().join("file");
+        fs::write(&file, b"").unwrap();
+        let mut config = config_in(temp_dir.path());
+        config.storage.image_dir = file.join("images");
+        assert!(error(&config).contains("not a directory"));
+
+        let readonly = temp_dir.path().join("readonly");
+        fs::create_dir(&readonly).unwrap();
+        let mut permissions = fs::metadata(&readonly).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&readonly, permissions).unwrap();
+        let mut config = config_in(temp_dir.path());
+        config.storage.archive_dir = Some(readonly.join("raw"));
+        // Root writes regardless of mode bits, and then so may the scan
+        if fs::File::create(readonly.join("probe")).is_err() {
+            assert!(error(&config).contains("storage.archive_dir"));
+            assert!(error(&config).contains("not writable"));
+        } else {
+            config.validate().unwrap();
+        }
+        // The probe file is cleaned up either way
+        let names: Vec<_> = fs::read_dir(&readonly).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert!(names.iter().all(|name| !name.to_string_lossy().starts_with(".write-test")));
+    }
+}
@@ -0,0 +1,180 @@
+// counting.rs
+//
+// Statistics-only pass over a block range.
+//
+// Blocks are fetched and parsed as in a normal scan, but the inscriptions
+// are only tallied, by content type and by block height, and then dropped.
+// Nothing is stored, so it's a quick way to size a range before scanning it.
+
+use crate::node::NodeError;
+use crate::parser::{Inscription, ParallelParser};
+use crate::scanner::{fetch_blocks, BlockSource};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Rows in the height histogram of the report
+const HISTOGRAM_ROWS: u64 = 20;
+
+/// Widest bar in the height histogram
+const HISTOGRAM_WIDTH: u64 = 40;
+
+/// What a range holds, tallied without storing any of it
+#[derive(Debug, Default)]
+pub struct ScanCounts {
+    pub range: Range<u64>,
+    pub blocks: u64,
+    pub inscriptions: u64,
+    /// Inscriptions per MIME essence
+    pub by_type: BTreeMap<String, u64>,
+    /// Inscriptions per block height, for heights that have any
+    pub by_height: BTreeMap<u64, u64>,
+    /// Heights that couldn't be fetched
+    pub skipped: Vec<u64>,
+}
+
+impl ScanCounts {
+    pub fn new(range: Range<u64>) -> Self {
+        Self { range, ..Self::default() }
+    }
+
+    /// Tallies the inscriptions parsed from `blocks` blocks
+    pub fn record(&mut self, blocks: u64, inscriptions: &[Inscription]) {
+        self.blocks += blocks;
+        self.inscriptions += inscriptions.len() as u64;
+        for inscription in inscriptions {
+            let essence = inscription.mime_type().split(';').next().unwrap_or("").trim().to_string();
+            *self.by_type.entry(essence).or_insert(0) += 1;
+            *self.by_height.entry(inscription.block_height).or_insert(0) += 1;
+        }
+    }
+
+    /// Inscriptions per bucket of equal height spans, at most `rows` of them
+    pub fn histogram(&self, rows: u64) -> Vec<(Range<u64>, u64)> {
+        let span = self.range.end.saturating_sub(self.range.start);
+        if span == 0 {
+            return Vec::new();
+        }
+        let width = span.div_ceil(rows.max(1));
+        let mut buckets: Vec<(Range<u64>, u64)> = (self.range.start..self.range.end)
+            .step_by(width as usize)
+            .map(|start| (start..(start + width).min(self.range.end), 0))
+            .collect();
+        for (height, count) in self.by_height.range(self.range.clone()) {
+            buckets[((height - self.range.start) / width) as usize].1 += count;
+        }
+        buckets
+    }
+}
+
+impl fmt::Display for ScanCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Inscription counts for blocks {} to {}:", self.range.start, self.range.end.saturating_sub(1))?;
+        writeln!(f, "  Blocks Parsed: {}", self.blocks)?;
+        if !self.skipped.is_empty() {
+            writeln!(f, "  Blocks Skipped: {}", self.skipped.len())?;
+        }
+        writeln!(f, "  Inscriptions Found: {}", self.inscriptions)?;
+
+        let mut types: Vec<_> = self.by_type.iter().collect();
+        types.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (content_type, count) in types {
+            let share = *count as f64 * 100.0 / self.inscriptions as f64;
+            writeln!(f, "  {}: {} ({:.1}%)", content_type, count, share)?;
+        }
+
+        let histogram = self.histogram(HISTOGRAM_ROWS);
+        let most = histogram.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        if most > 0 {
+            writeln!(f, "By height:")?;
+            for (heights, count) in histogram {
+                let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(most) as usize);
+                writeln!(f, "  {:>8}-{:<8} {:>8} {}", heights.start, heights.end - 1, count, bar)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fetches and parses `range`, counting the inscriptions instead of storing them
+///
+/// Blocks are parsed in chunks of `chunk_size` as they arrive. Blocks that
+/// can't be fetched are skipped and listed in `skipped`, unless `fail_fast`.
+pub async fn count_range(
+    source: Arc<dyn BlockSource>,
+    parser: &ParallelParser,
+    range: Range<u64>,
+    chunk_size: usize,
+    max_in_flight: usize,
+    fail_fast: bool,
+) -> Result<ScanCounts, NodeError> {
+    let mut counts = ScanCounts::new(range.clone());
+    let mut blocks = fetch_blocks(source, range, max_in_flight);
+    let mut pending = Vec::with_capacity(chunk_size);
+
+    while let Some(fetched) = blocks.recv().await {
+        match fetched {
+            Ok(block) => pending.push(block),
+            Err((height, e)) if fail_fast => {
+                warn!("Failed to fetch block {}: {}", height, e);
+                return Err(e);
+            }
+            Err((height, e)) => {
+                warn!("Skipping block {}: {}", height, e);
+                counts.skipped.push(height);
+            }
+        }
+        if pending.len() >= chunk_size.max(1) {
+            let chunk = std::mem::take(&mut pending);
+            let count = chunk.len() as u64;
+            counts.record(count, &parser.process_blocks(chunk));
+        }
+    }
+    if !pending.is_empty() {
+        let count = pending.len() as u64;
+        counts.record(count, &parser.process_blocks(pending));
+    }
+    info!("Counted {} inscriptions in {} blocks", counts.inscriptions, counts.blocks);
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::InscriptionParser;
+    use crate::scanner::MockSource;
+
+    #[tokio::test]
+    async fn test_counts_over_mock_range() {
+        let parser = ParallelParser::new(4, Some(2)).unwrap();
+        let counts = count_range(Arc::new(MockSource), &parser, 100..150, 4, 8, false).await.unwrap();
+
+        // Mock blocks carry one inscription each, cycling through four types
+        assert_eq!(counts.blocks, 50);
+        assert_eq!(counts.inscriptions, 50);
+        assert_eq!(counts.by_type.get("text/plain"), Some(&13));
+        assert_eq!(counts.by_type.get("image/png"), Some(&13));
+        assert_eq!(counts.by_type.get("application/json"), Some(&12));
+        assert_eq!(counts.by_type.get("application/octet-stream"), Some(&12));
+        assert_eq!(counts.by_height.len(), 50);
+        assert!(counts.by_height.iter().all(|(height, count)| (100..150).contains(height) && *count == 1));
+        assert!(counts.skipped.is_empty());
+
+        let histogram = counts.histogram(5);
+        assert_eq!(histogram.len(), 5);
+        assert_eq!(histogram[0], (100..110, 10));
+        assert_eq!(histogram[4], (140..150, 10));
+
+        let report = counts.to_string();
+        assert!(report.contains("Inscriptions Found: 50"));
+        assert!(report.contains("text/plain: 13 (26.0%)"));
+
+        // Skipping body decoding doesn't change what's counted
+        let parser = parser.with_inscription_parser(InscriptionParser::new().with_count_only(true));
+        let quick = count_range(Arc::new(MockSource), &parser, 100..150, 4, 8, false).await.unwrap();
+        assert_eq!(quick.by_type, counts.by_type);
+        assert_eq!(quick.by_height, counts.by_height);
+    }
+}
@@ -0,0 +1,135 @@
+// diff_chain.rs
+//
+// Finds stored inscriptions whose block was orphaned by a reorg that
+// happened while the scanner wasn't running, by comparing the block hash
+// recorded for each stored height with the node's active chain. With
+// `--purge` their records are deleted along with every index entry that
+// points at them (content ids, parent links, content hashes), so a later
+// rescan stores the canonical blocks cleanly.
+
+use crate::error::AppError;
+use crate::node::NodeClient;
+use crate::storage::{Result, Storage};
+use bitcoin::BlockHash;
+use log::{info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A block inscriptions were stored from that is no longer on the active chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanedBlock {
+    pub height: u64,
+    /// Hash the inscriptions were scanned from
+    pub stored: BlockHash,
+    /// Hash at that height on the active chain; `None` above the node's tip
+    pub active: Option<BlockHash>,
+    /// Txids of the inscriptions stored from the block
+    pub txids: Vec<String>,
+}
+
+/// Compares the recorded hash of every stored height with `active`
+pub fn find_orphaned(storage: &Storage, active: &BTreeMap<u64, BlockHash>) -> Result<Vec<OrphanedBlock>> {
+    let orphaned: BTreeMap<u64, BlockHash> = storage
+        .recorded_blocks()?
+        .into_iter()
+        .filter(|(height, hash)| active.get(height) != Some(hash))
+        .collect();
+
+    let heights = orphaned.keys().copied().collect();
+    let mut txids = storage.txids_at_heights(&heights)?;
+    Ok(orphaned
+        .into_iter()
+        .map(|(height, stored)| OrphanedBlock {
+            height,
+            stored,
+            active: active.get(&height).copied(),
+            txids: txids.remove(&height).unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Re-fetches the active chain's hash for every stored height and reports
+/// the orphaned blocks, deleting their inscriptions when `purge` is set
+pub async fn diff_chain(
+    storage: &Storage,
+    client: &NodeClient,
+    purge: bool,
+) -> std::result::Result<Vec<OrphanedBlock>, AppError> {
+    let recorded = storage.recorded_blocks()?;
+    let tip = client.get_block_count().await?;
+
+    let mut active = BTreeMap::new();
+    for &height in recorded.keys().filter(|&&height| height <= tip) {
+        active.insert(height, client.get_block_hash(height).await?);
+    }
+    info!("Checked {} stored blocks against the active chain (tip {})", recorded.len(), tip);
+
+    let orphaned = find_orphaned(storage, &active)?;
+    for block in &orphaned {
+        match block.active {
+            Some(active) => warn!("Block {} {} was replaced by {} ({} inscriptions)",
+                block.height, block.stored, active, block.txids.len()),
+            None => warn!("Block {} {} is above the node's tip ({} inscriptions)",
+                block.height, block.stored, block.txids.len()),
+        }
+    }
+
+    if purge && !orphaned.is_empty() {
+        let heights: BTreeSet<u64> = orphaned.iter().map(|block| block.height).collect();
+        storage.purge_heights(&heights)?;
+    }
+    Ok(orphaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Inscription, InscriptionType};
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_inscriptions_on_orphaned_blocks_are_flagged() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        let orphaned_txid = Txid::from_str("1111111111111111111111111111111111111111111111111111111111111111").unwrap();
+        let canonical_txid = Txid::from_str("2222222222222222222222222222222222222222222222222222222222222222").unwrap();
+        let mut orphaned = Inscription::new(orphaned_txid, InscriptionType::Text("stale".to_string()));
+        orphaned.block_height = 100;
+        orphaned.parents = vec![format!("{}i0", canonical_txid)];
+        let mut canonical = Inscription::new(canonical_txid, InscriptionType::Text("kept".to_string()));
+        canonical.block_height = 101;
+        storage.store_inscription(&orphaned).await.unwrap();
+        storage.store_inscription(&canonical).await.unwrap();
+
+        let stale_hash = BlockHash::from_byte_array([1; 32]);
+        let replacement = BlockHash::from_byte_array([2; 32]);
+        let kept_hash = BlockHash::from_byte_array([3; 32]);
+        storage.record_blocks(&[(100, stale_hash), (101, kept_hash)]).unwrap();
+
+        let active = BTreeMap::from([(100, replacement), (101, kept_hash)]);
+        let flagged = find_orphaned(&storage, &active).unwrap();
+        assert_eq!(flagged, vec![OrphanedBlock {
+            height: 100,
+            stored: stale_hash,
+            active: Some(replacement),
+            txids: vec![orphaned_txid.to_string()],
+        }]);
+
+        // Purging removes the orphaned inscription and leaves the canonical one
+        storage.purge_heights(&BTreeSet::from([100])).unwrap();
+        let remaining: Vec<_> = storage.entries().unwrap().map(|entry| entry.unwrap().txid).collect();
+        assert_eq!(remaining, vec![canonical_txid.to_string()]);
+        assert!(find_orphaned(&storage, &active).unwrap().is_empty());
+        // ...and the indexes no longer point at it
+        assert!(storage.children_of(&canonical.inscription_id()).unwrap().is_empty());
+        assert_eq!(storage.content_refcount(&orphaned.content_id()).unwrap(), 0);
+        assert_eq!(storage.content_refcount(&canonical.content_id()).unwrap(), 1);
+    }
+}
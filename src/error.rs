@@ -0,0 +1,84 @@
+// error.rs
+//
+// Top-level error for the scanner binary.
+//
+// Every module error is wrapped here so `main` can tell what failed and
+// exit with a code scripts can react to, e.g. retrying on a node outage
+// but not on a broken config.
+
+use crate::alerts::AlertError;
+use crate::cache::CacheError;
+use crate::config::ConfigError;
+use crate::node::NodeError;
+use crate::parser::ParserError;
+use crate::storage::StorageError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    /// Invalid combination of command line arguments
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+
+    #[error("Node error: {0}")]
+    Node(#[from] NodeError),
+
+    #[error("Storage error: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+
+    #[error("Alert error: {0}")]
+    Alert(#[from] AlertError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A reorg reached deeper than the recorded block hashes
+    #[error("Reorg error: {0}")]
+    Reorg(String),
+
+    #[error("Parser error: {0}")]
+    Parser(#[from] ParserError),
+}
+
+impl AppError {
+    /// Process exit code for the error; 1 stays unused, and a panic exits with 101
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AppError::Usage(_) => 2,
+            AppError::Config(_) => 3,
+            AppError::Node(_) => 4,
+            AppError::Storage(_) => 5,
+            AppError::Cache(_) => 6,
+            AppError::Alert(_) => 7,
+            AppError::Io(_) => 8,
+            AppError::Reorg(_) => 9,
+            AppError::Parser(_) => 10,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_config_errors_use_the_config_exit_code() {
+        let missing = crate::config::load_config("/nonexistent/config.toml").unwrap_err();
+        assert_eq!(AppError::from(missing).exit_code(), 3);
+
+        let mut invalid = NamedTempFile::new().unwrap();
+        writeln!(invalid, "[node").unwrap();
+        let invalid = crate::config::load_config(invalid.path()).unwrap_err();
+        assert_eq!(AppError::from(invalid).exit_code(), 3);
+
+        assert_ne!(AppError::Usage(String::new()).exit_code(), 3);
+    }
+}
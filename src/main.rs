@@ -27,24 +27,49 @@
 // - Parallel block processing using rayon
 // - Connection pooling for RPC calls
 
+mod alerts;
+mod cache;
 mod config;
+mod counting;
+mod diff_chain;
+mod error;
+mod maintenance;
 mod node;
 mod parser;
+mod progress;
+mod prometheus;
+mod reorg;
+mod reprocess;
+mod runtime;
+mod sampling;
+mod scanner;
+mod server;
+mod shutdown;
+mod since;
 mod storage;
+mod targets;
+#[cfg(test)]
+mod test_utils;
+mod tui;
 mod utils;
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use error::AppError;
+use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio;
 use log::{info, error, warn};
-use bitcoin::{Block, Transaction, TxOut, blockdata::script::Builder};
-use bitcoin::block::{Header, Version};
-use bitcoin::hash_types::TxMerkleNode;
-use bitcoin::hashes::Hash;
-use bitcoin::pow::CompactTarget;
-use bitcoin::blockdata::opcodes::all::{OP_IF, OP_ENDIF};
-use bitcoin::opcodes::{OP_0, OP_FALSE};
-use bitcoin::script::PushBytesBuf;
+use bitcoin::Block;
+
+/// Skipped blocks listed in the end-of-scan summary; --failures-file has them all
+const MAX_FAILURES_SHOWN: usize = 10;
+
+/// Blocks generated in mock mode without --mock-blocks
+const DEFAULT_MOCK_BLOCKS: u64 = 10;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
@@ -58,6 +83,16 @@ struct Args {
     #[clap(long)]
     start_block: Option<u64>,
 
+    /// Start scanning from the first block mined at or after DATE
+    /// An RFC 3339 timestamp or YYYY-MM-DD (midnight UTC); found from block headers
+    #[clap(long, value_name = "DATE", value_parser = since::parse_timestamp, conflicts_with_all = ["start_block", "resume", "mock"])]
+    since: Option<u32>,
+
+    /// Stop scanning after this block height (inclusive)
+    /// Clamped to the node's tip; defaults to scanning up to the tip
+    #[clap(long)]
+    stop_block: Option<u64>,
+
     /// Resume scanning from last processed block
     /// Requires previous scan data in storage
     #[clap(long)]
@@ -68,99 +103,311 @@ struct Args {
     #[clap(short, long)]
     verbose: bool,
 
+    /// Don't show the progress bar
+    /// It's also hidden when stdout is not a terminal or with --verbose
+    #[clap(long)]
+    no_progress: bool,
+
     /// Run in mock mode without Bitcoin node
     /// Generates test inscriptions for development
     #[clap(long)]
     mock: bool,
+
+    /// Number of blocks mock mode generates (default: 10)
+    /// Their inscriptions cycle through text, PNG, JSON and binary content
+    #[clap(long, value_name = "N", requires = "mock")]
+    mock_blocks: Option<u64>,
+
+    /// Show a live dashboard instead of log output
+    /// Falls back to plain logging when stdout is not a terminal
+    #[clap(long)]
+    tui: bool,
+
+    /// Re-parse archived raw envelopes for START..=END and exit
+    /// Requires storage.archive_dir; never contacts the node
+    #[clap(long, num_args = 2, value_names = ["START", "END"])]
+    reprocess_range: Option<Vec<u64>>,
+
+    /// Fetch and parse START..=END again, replacing what was stored from those blocks
+    /// Leaves the resume cursor alone; running it twice gives the same result
+    #[clap(
+        long,
+        num_args = 2,
+        value_names = ["START", "END"],
+        conflicts_with_all = ["resume", "start_block", "stop_block", "reprocess_range", "dry_run", "since"]
+    )]
+    rescan: Option<Vec<u64>>,
+
+    /// Parse the transactions listed in FILE (one txid per line), store their inscriptions and exit
+    /// Transactions outside the mempool need -txindex on the node
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["resume", "start_block", "stop_block", "reprocess_range", "rescan", "mock", "since"]
+    )]
+    txids: Option<PathBuf>,
+
+    /// Parse the blocks listed in FILE (one block hash per line), store their inscriptions and exit
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["resume", "start_block", "stop_block", "reprocess_range", "rescan", "mock", "txids", "since"]
+    )]
+    block_hashes: Option<PathBuf>,
+
+    /// Read hex-encoded blocks from stdin, one per line, store their inscriptions and exit
+    /// Needs no node, e.g. for air-gapped analysis; undecodable lines are skipped. Blocks
+    /// without a BIP34 height need it as a prefix: "<height> <hex>"
+    #[clap(
+        long,
+        conflicts_with_all = ["resume", "start_block", "stop_block", "reprocess_range", "rescan", "mock", "txids",
+            "block_hashes", "since", "count_only", "content_type_stats"]
+    )]
+    stdin_blocks: bool,
+
+    /// Take over the storage lock even if another instance appears to hold it
+    /// Only use this after confirming no other scanner is running; with
+    /// --init-config, overwrite an existing file
+    #[clap(long)]
+    force: bool,
+
+    /// Export stored inscriptions to this directory in ord's layout and exit
+    /// Writes `<number>` bodies with `<number>.meta.json` sidecars
+    #[clap(long)]
+    export_ord: Option<PathBuf>,
+
+    /// Export stored inscriptions to this file and exit
+    /// CSV or JSON depending on the extension; images are referenced by path
+    #[clap(long, conflicts_with = "export_ord")]
+    export: Option<PathBuf>,
+
+    /// Parse and report inscriptions without writing anything
+    /// Skips storage, alert outputs, the archive, the cache and the resume cursor
+    #[clap(long, conflicts_with_all = ["export_ord", "export", "reprocess_range"])]
+    dry_run: bool,
+
+    /// Parse N random blocks from the range, report the content-type mix and exit
+    /// Stores nothing; useful for picking filters and sizing storage
+    #[clap(long, value_name = "N")]
+    content_type_stats: Option<usize>,
+
+    /// Parse the range and print inscription counts by type and height, storing nothing
+    /// Skips storage, alerts, the archive, the cache and the resume cursor
+    #[clap(
+        long,
+        conflicts_with_all = ["resume", "rescan", "reprocess_range", "txids", "block_hashes", "export_ord", "export",
+            "dry_run", "content_type_stats", "serve", "tui"]
+    )]
+    count_only: bool,
+
+    /// Serve an HTTP API on ADDR (e.g. 127.0.0.1:3000) while scanning
+    /// Keeps serving after the scan finishes, until Ctrl-C
+    #[clap(long, value_name = "ADDR")]
+    serve: Option<std::net::SocketAddr>,
+
+    /// Serve Prometheus metrics at http://ADDR/metrics while scanning
+    #[clap(long, value_name = "ADDR")]
+    metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Print a commented example config documenting every option and exit
+    /// Generated from the config definitions, with each field's type and default
+    #[clap(long)]
+    dump_config_schema: bool,
+
+    /// Write a commented default config to PATH (default: config.toml) and exit
+    /// Won't overwrite an existing file without --force
+    #[clap(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "config.toml")]
+    init_config: Option<PathBuf>,
+
+    /// Stop on the first block that can't be fetched instead of skipping it
+    /// Without it, skipped blocks are summarized when the scan ends
+    #[clap(long)]
+    fail_fast: bool,
+
+    /// Allow at most N RPC requests in flight, overriding node.max_concurrent_requests
+    /// Tune it to the node's rpcthreads; parsing threads are unaffected
+    #[clap(long, value_name = "N", value_parser = parse_workers)]
+    workers: Option<usize>,
+
+    /// Refuse to scan while the node is still syncing instead of just warning
+    /// An unsynced node reports a tip behind the real chain, so the scan would end early
+    #[clap(long)]
+    require_synced: bool,
+
+    /// Write the blocks skipped after fetch errors to this file as JSON
+    #[clap(long, value_name = "PATH", conflicts_with = "fail_fast")]
+    failures_file: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-/// Creates a mock block containing a test inscription
-/// 
-/// This function generates a valid Bitcoin block structure with a single
-/// transaction containing an inscription. Used for testing the scanner
-/// without requiring a Bitcoin node connection.
-///
-/// Parameters:
-/// - height: Block height, used to generate unique content
-///
-/// Returns:
-/// - Block: A complete Bitcoin block with test inscription
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+    /// Find stored inscriptions whose block is no longer on the active chain
+    /// Compares the recorded block hash of every stored height with the node's
+    DiffChain {
+        /// Delete the inscriptions of orphaned blocks so a rescan can replace them
+        #[clap(long)]
+        purge: bool,
+    },
+}
+
+impl Args {
+    /// Whether this run must leave storage, the cache and the archive untouched
+    fn read_only(&self) -> bool {
+        self.dry_run || self.count_only || self.content_type_stats.is_some()
+    }
+}
+
+/// Parses `--workers`, which needs at least one request slot
+fn parse_workers(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(workers) => Ok(workers),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Applies flags that override config values for this run only
+fn apply_cli_overrides(config: &mut config::Config, args: &Args) {
+    if let Some(workers) = args.workers {
+        config.node.max_concurrent_requests = workers;
+    }
+}
+
+/// The inscription parser configured by the `[processing]` section
+fn inscription_parser(config: &config::Config) -> parser::InscriptionParser {
+    parser::InscriptionParser::new()
+        .with_lenient(config.processing.lenient)
+        .with_max_inscriptions_per_tx(config.processing.max_inscriptions_per_tx)
+        .with_max_inscription_size(config.processing.max_inscription_size)
+        .with_genesis_address(config.processing.genesis_address)
+        .with_content_types(config.processing.content_types.clone())
+        .with_sanitize_svg(config.processing.sanitize_svg)
+        .with_classifier(classifier(config))
+        .with_network(config.node.network)
+        .with_tx_metadata(config.storage.store_tx_metadata)
+}
+
+/// Maps content types to inscription variants for every parser the scanner builds
 ///
-/// Technical Details:
-/// - Creates valid script following ordinal inscription format
-/// - Uses standard OP_FALSE OP_IF pattern
-/// - Includes proper MIME type and content
-/// - Sets valid block header fields
-fn create_mock_inscription_block(height: u64) -> Block {
-    // Create inscription script following ordinal protocol
-    // Format: OP_FALSE OP_IF <content-type> OP_0 <content> OP_ENDIF
-    let mut content_type = PushBytesBuf::new();
-    content_type.extend_from_slice(b"text/plain;charset=utf-8").unwrap();
-
-    let mut content = PushBytesBuf::new();
-    content.extend_from_slice(format!("Hello from block {}!", height).as_bytes()).unwrap();
-
-    // Build complete inscription script
-    let script = Builder::new()
-        .push_opcode(OP_FALSE)  // Standard inscription marker
-        .push_opcode(OP_IF)     // Start conditional
-        .push_slice(&content_type)
-        .push_opcode(OP_0)      // Content type separator
-        .push_slice(&content)
-        .push_opcode(OP_ENDIF)  // End conditional
-        .into_script();
-
-    // Create transaction with inscription output
-    let tx = Transaction {
-        version: 2,
-        lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
-        input: vec![],
-        output: vec![TxOut {
-            value: 0,            // Inscriptions typically use zero-value outputs
-            script_pubkey: script,
-        }],
-    };
+/// The one place to install a different `Classifier`.
+fn classifier(config: &config::Config) -> Box<dyn parser::Classifier> {
+    Box::new(parser::DefaultClassifier { sanitize_svg: config.processing.sanitize_svg })
+}
+
+/// Exclusive end of the scan range: the tip, lowered to just past `stop_block` if given
+fn effective_end_block(tip: u64, stop_block: Option<u64>) -> u64 {
+    match stop_block {
+        Some(stop) => tip.min(stop.saturating_add(1)),
+        None => tip,
+    }
+}
 
-    // Generate deterministic block header
-    let zeros = [0u8; 32];
-    let prev_blockhash = bitcoin::BlockHash::from_slice(&zeros).unwrap();
-    let merkle_root = TxMerkleNode::from_slice(&zeros).unwrap();
-
-    Block {
-        header: Header {
-            version: Version::ONE,
-            prev_blockhash,
-            merkle_root,
-            time: height as u32,  // Use height as timestamp for deterministic testing
-            bits: CompactTarget::from_consensus(0x1d00ffff),
-            nonce: 0,
-        },
-        txdata: vec![tx],
+/// Parses sampled blocks and tallies what they contain, storing nothing
+fn tally_content_types(parser: &parser::ParallelParser, blocks: Vec<(u64, Block)>) -> sampling::ContentTypeStats {
+    let mut stats = sampling::ContentTypeStats::default();
+    let count = blocks.len() as u64;
+    stats.record(count, &parser.process_blocks(blocks));
+    stats
+}
+
+/// Writes the completion script for `shell` to `out`
+fn write_completions(shell: Shell, out: &mut dyn std::io::Write) {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
+}
+
+fn main() -> ExitCode {
+    match start(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Logging may be silenced (--tui) or not set up yet, so print directly
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Parse command line arguments and initialize logging
-    let args = Args::parse();
+/// Sets up logging and configuration, then runs the scan on a runtime sized from the config
+fn start(args: Args) -> Result<(), AppError> {
+    if let Some(Command::Completions { shell }) = args.command {
+        write_completions(shell, &mut std::io::stdout());
+        return Ok(());
+    }
+    if args.dump_config_schema {
+        print!("{}", config::schema());
+        return Ok(());
+    }
+    if let Some(path) = &args.init_config {
+        config::init_config(path, args.force)?;
+        println!("Wrote a default config to {}; set node.rpc_url and the credentials before scanning", path.display());
+        return Ok(());
+    }
+
+    // Log lines would scribble over the dashboard, so silence them while it runs
+    let use_tui = args.tui && std::io::stdout().is_terminal();
+    let log_level = if use_tui {
+        log::LevelFilter::Off
+    } else if args.verbose {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
     env_logger::Builder::from_default_env()
-        .filter_level(if args.verbose { log::LevelFilter::Debug } else { log::LevelFilter::Info })
+        .filter_level(log_level)
         .init();
+    if args.tui && !use_tui {
+        warn!("--tui requires a terminal, falling back to plain logging");
+    }
 
     info!("Starting Bitcoin Inscription Scanner");
 
     // Load and validate configuration
     info!("Loading configuration from {}", args.config.display());
-    let config = config::load_config(args.config)?;
+    let mut config = config::load_config(args.config.clone())?;
+    apply_cli_overrides(&mut config, &args);
+    info!("Using up to {} concurrent RPC requests", config.node.max_concurrent_requests);
 
+    let threads = runtime::ThreadBudget::from_config(&config);
+    let runtime = runtime::build_runtime(&threads)?;
+    runtime.block_on(run(args, config, threads, use_tui))
+}
+
+async fn run(
+    args: Args,
+    config: config::Config,
+    threads: runtime::ThreadBudget,
+    use_tui: bool,
+) -> Result<(), AppError> {
     // Initialize system components
     let node_client = if args.mock {
         info!("Running in mock mode");
         None
+    } else if args.stdin_blocks {
+        info!("Reading blocks from stdin");
+        None
     } else {
         info!("Connecting to Bitcoin node at {}", config.node.rpc_url);
         match node::NodeClient::new(&config) {
-            Ok(client) => Some(client),
+            Ok(client) => {
+                client.check_network().await?;
+                if let Some(problem) = client.get_blockchain_info().await?.sync_problem() {
+                    if args.require_synced {
+                        return Err(node::NodeError::NotSynced(problem).into());
+                    }
+                    warn!("Scanning an unsynced node: {}; the scan will stop at its current tip", problem);
+                }
+                Some(client)
+            }
             Err(e) => {
                 error!("Failed to connect to Bitcoin node: {}", e);
                 error!("Please check your Bitcoin node is running and the credentials are correct");
@@ -171,19 +418,203 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    // Initialize parser with batch size from config
-    let parser = parser::ParallelParser::new(config.processing.batch_size);
+    // Created before the parser, which records per-type parse times into it
+    let metrics = Arc::new(utils::Metrics::new());
+
+    // Initialize parser with the chunking and thread count from config
+    let parser = parser::ParallelParser::new(config.processing.chunk_size, Some(threads.rayon_threads))?
+        .with_inscription_parser(inscription_parser(&config).with_metrics(metrics.clone()));
     
     info!("Initializing storage");
-    let storage = storage::Storage::new(
-        config.storage.image_dir.clone(),
-        config.storage.text_log.clone(),
-    )?;
+    let storage = if args.read_only() {
+        if args.dry_run {
+            info!("Dry run: nothing will be written");
+        }
+        storage::Storage::dry_run(
+            config.storage.image_dir.clone(),
+            config.storage.text_log.clone(),
+        )
+    } else {
+        let storage = storage::Storage::new(
+            config.storage.image_dir.clone(),
+            config.storage.text_log.clone(),
+        )?
+        .with_index_thumbnails(config.storage.index_thumbnails)
+        .with_thumbnails(config.storage.generate_thumbnails)
+        .with_compress_images(config.storage.compress_images)
+        .with_strict_images(config.storage.strict_images)
+        .with_image_shard_depth(config.storage.shard_depth)
+        .with_max_log_bytes(config.storage.max_log_bytes)
+        .with_recent_cache(config.storage.recent_cache_size)
+        .with_binary(storage::BinaryStorage::new(config.storage.binary_dir.clone())?);
+        let storage = match config.storage.backend {
+            config::StorageBackend::Jsonl => storage,
+            config::StorageBackend::Sqlite => {
+                storage.with_sqlite(storage::SqliteStorage::open(&config.storage.sqlite_path)?)
+            }
+        };
+        let storage = if config.storage.stream {
+            storage.with_stream(
+                storage::StreamSink::connect_nats(&config.storage.stream_url, &config.storage.stream_subject).await,
+            )
+        } else {
+            storage
+        };
+        if config.storage.link_content {
+            let linked = storage::LinkedStorage::open(storage.data_dir())?;
+            storage.with_linked(linked)
+        } else {
+            storage
+        }
+    };
+
+    // Refuse to share the storage directory with another running instance
+    let _lock = if args.read_only() {
+        None
+    } else {
+        Some(storage::ScanLock::acquire(storage.data_dir(), args.force)?)
+    };
+
+    if let Some(dir) = &args.export_ord {
+        let count = storage::export_ord(&storage, dir)?;
+        info!("Exported {} inscriptions to {}", count, dir.display());
+        return Ok(());
+    }
+
+    if let Some(path) = &args.export {
+        let count = storage::export(&storage, path)?;
+        info!("Exported {} inscriptions to {}", count, path.display());
+        return Ok(());
+    }
+
+    let archive = match &config.storage.archive_dir {
+        Some(dir) if !args.read_only() => Some(storage::RawArchive::new(dir.clone())?),
+        _ => None,
+    };
+
+    if let Some(range) = &args.reprocess_range {
+        let archive = archive
+            .as_ref()
+            .ok_or_else(|| AppError::Usage("--reprocess-range requires storage.archive_dir".to_string()))?;
+        reprocess::reprocess_range(archive, &inscription_parser(&config), &storage, range[0], range[1]).await?;
+        return Ok(());
+    }
+
+    if let (Some(path), Some(client)) = (&args.txids, &node_client) {
+        let txids: Vec<bitcoin::Txid> = targets::read_ids(path)?;
+        targets::scan_txids(client, &inscription_parser(&config), &storage, &txids).await?;
+        return Ok(());
+    }
+
+    if let (Some(path), Some(client)) = (&args.block_hashes, &node_client) {
+        let hashes: Vec<bitcoin::BlockHash> = targets::read_ids(path)?;
+        targets::scan_block_hashes(client, &parser, &storage, &hashes).await?;
+        return Ok(());
+    }
+
+    if args.stdin_blocks {
+        targets::scan_raw_blocks(std::io::stdin().lock(), &parser, &storage).await?;
+        return Ok(());
+    }
+
+    let alerts = alerts::Alerts::new(&config.alerts)?;
+
+    // Optional RocksDB cache, flushed at every checkpoint and on shutdown
+    let cache = if config.cache.enabled && !args.read_only() {
+        Some(Arc::new(cache::CacheDb::with_sync(&config.cache.path, config.cache.sync_writes)?))
+    } else {
+        None
+    };
+
+    // With block_cache set, blocks fetched over RPC are kept in the cache DB too
+    let node_client = match (node_client, &cache) {
+        (Some(client), Some(db)) if config.cache.block_cache => {
+            let max_bytes = config.cache.block_cache_max_mb * 1024 * 1024;
+            Some(Arc::new(client.with_block_cache(Arc::new(cache::BlockCache::new(db.clone(), max_bytes)?))))
+        }
+        (client, _) => client.map(Arc::new),
+    };
+
+    // With the cache enabled, skip inscriptions a previous run already stored.
+    // The bloom filter is saved next to the cache DB so it survives restarts.
+    let bloom_path = config.cache.path.with_extension("bloom");
+    let bloom = match &cache {
+        Some(_) => Some(Arc::new(cache::BloomCache::load(
+            &bloom_path,
+            config.cache.bloom_filter_size,
+            config.cache.bloom_filter_fp_rate,
+        )?)),
+        None => None,
+    };
+    // The cache also indexes every body by hash, for finding copies
+    let storage = Arc::new(match (&cache, &bloom) {
+        (Some(db), Some(bloom)) => storage
+            .with_dedup(cache::Deduplicator::new(bloom.clone(), db.clone()))
+            .with_content_hashes(cache::ContentHashIndex::new(db.clone())),
+        _ => storage,
+    });
+
+    // Re-scanned blocks (overlapping ranges, reorg recovery) reuse earlier parse results
+    let parsed_cache = match &cache {
+        Some(db) if config.cache.parsed_block_ttl_secs > 0 => Some(Arc::new(
+            cache::ParsedBlockCache::new(db.clone(), Duration::from_secs(config.cache.parsed_block_ttl_secs)),
+        )),
+        _ => None,
+    };
+    let parser = match &parsed_cache {
+        Some(parsed) => parser.with_parsed_cache(parsed.clone()),
+        None => parser,
+    };
+
+    let maintenance = (config.maintenance.interval_secs > 0).then(|| {
+        maintenance::Maintenance::new(Duration::from_secs(config.maintenance.interval_secs))
+            .with_storage(storage.clone())
+            .with_cache(cache.clone())
+            .with_parsed_cache(parsed_cache.clone())
+    });
+
+    if let Some(Command::DiffChain { purge }) = args.command {
+        let client = node_client
+            .as_ref()
+            .ok_or_else(|| AppError::Usage("diff-chain needs a Bitcoin node, not --mock".to_string()))?;
+        let orphaned = diff_chain::diff_chain(&storage, client, purge).await?;
+        if orphaned.is_empty() {
+            println!("All stored blocks are on the active chain");
+        } else {
+            let inscriptions: usize = orphaned.iter().map(|block| block.txids.len()).sum();
+            let lowest = orphaned[0].height;
+            if purge {
+                println!("Purged {} inscriptions from {} orphaned blocks; rescan from block {} to replace them",
+                    inscriptions, orphaned.len(), lowest);
+            } else {
+                println!("{} inscriptions in {} orphaned blocks (lowest {}); rerun with --purge to delete them",
+                    inscriptions, orphaned.len(), lowest);
+            }
+        }
+        if let Some(cache) = &cache {
+            cache.flush()?;
+        }
+        return Ok(());
+    }
 
     // Determine scanning start position
-    let start_block = if args.resume {
-        warn!("Resume functionality not yet implemented, starting from block 0");
-        0
+    let start_block = if let Some(range) = &args.rescan {
+        range[0]
+    } else if args.resume {
+        match storage.load_scan_state()? {
+            Some(state) => {
+                info!("Resuming after last completed block {}", state.last_block);
+                state.last_block + 1
+            }
+            None => {
+                warn!("No saved scan state found, starting from genesis");
+                0
+            }
+        }
+    } else if let (Some(timestamp), Some(client)) = (args.since, &node_client) {
+        let height = since::block_since(client, timestamp).await?;
+        info!("Block {} is the first at or after timestamp {}", height, timestamp);
+        height
     } else {
         args.start_block.unwrap_or(0)
     };
@@ -200,83 +631,266 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
     } else {
-        // In mock mode, process 10 blocks for testing
-        start_block + 10
+        start_block + args.mock_blocks.unwrap_or(DEFAULT_MOCK_BLOCKS)
     };
 
-    info!("Starting scan from block {} to {}", start_block, latest_block);
+    // Bound the range by --stop-block, which is inclusive while latest_block is not
+    if let Some(stop_block) = args.stop_block {
+        if stop_block < start_block {
+            return Err(AppError::Usage(format!(
+                "--stop-block {} is below the start block {}", stop_block, start_block
+            )));
+        }
+        if stop_block >= latest_block {
+            warn!("--stop-block {} is beyond the tip, scanning to {}", stop_block, latest_block);
+        }
+    }
+    let latest_block = match &args.rescan {
+        Some(range) if range[1] < range[0] => {
+            return Err(AppError::Usage(format!("--rescan end {} is below its start {}", range[1], range[0])));
+        }
+        Some(range) if range[1] >= latest_block => {
+            return Err(AppError::Usage(format!("--rescan end {} is beyond the tip {}", range[1], latest_block)));
+        }
+        Some(range) => range[1] + 1,
+        None => effective_end_block(latest_block, args.stop_block),
+    };
 
-    // Main scanning loop - processes blocks in batches
-    let mut current_block = start_block;
-    while current_block < latest_block {
-        // Calculate batch size for this iteration
-        let end_block = std::cmp::min(
-            current_block + config.processing.batch_size as u64,
-            latest_block,
-        );
+    info!("Scanning blocks [{}, {}]", start_block, latest_block.saturating_sub(1));
 
-        info!("Processing blocks {} to {}", current_block, end_block);
-
-        // Fetch blocks - either from node or generate mock blocks
-        let blocks = if let Some(client) = &node_client {
-            let mut blocks = Vec::new();
-            for height in current_block..end_block {
-                match client.get_block_hash(height).await {
-                    Ok(hash) => {
-                        match client.get_block(&hash).await {
-                            Ok(block) => {
-                                // Special handling for genesis block
-                                if height == 0 {
-                                    info!("Genesis block found!");
-                                    if let Some(coinbase) = block.txdata.first() {
-                                        info!("Genesis coinbase tx: {:?}", coinbase);
-                                        for input in &coinbase.input {
-                                            info!("Genesis input script: {:?}", input.script_sig);
-                                            info!("Genesis script bytes: {:?}", input.script_sig.as_bytes());
-                                            if let Ok(text) = String::from_utf8(input.script_sig.as_bytes().to_vec()) {
-                                                info!("Genesis script as text: {}", text);
-                                            }
-                                        }
-                                    }
-                                }
-                                blocks.push(block)
-                            },
-                            Err(e) => {
-                                error!("Failed to fetch block {}: {}", height, e);
-                                continue;  // Skip failed blocks but continue processing
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to get hash for block {}: {}", height, e);
-                        continue;
-                    }
-                }
-            }
-            blocks
-        } else {
-            // Generate mock blocks for testing
-            (current_block..end_block)
-                .map(create_mock_inscription_block)
-                .collect()
+    if let Some(samples) = args.content_type_stats {
+        let heights = sampling::sample_heights(start_block, latest_block, samples, rand::random());
+        info!("Sampling {} blocks for content-type statistics", heights.len());
+
+        let mut blocks = Vec::with_capacity(heights.len());
+        for height in heights {
+            blocks.push((height, match &node_client {
+                Some(client) => client.get_block(&client.get_block_hash(height).await?).await?,
+                None => scanner::create_mock_inscription_block(height),
+            }));
+        }
+        println!("{}", tally_content_types(&parser, blocks));
+        return Ok(());
+    }
+
+    if args.count_only {
+        let source: Arc<dyn scanner::BlockSource> = match &node_client {
+            Some(client) => client.clone(),
+            None => Arc::new(scanner::MockSource),
         };
+        // Only what classifies an inscription is worth computing when it's dropped anyway
+        let parser = parser.with_inscription_parser(
+            inscription_parser(&config)
+                .with_genesis_address(false)
+                .with_tx_metadata(false)
+                .with_sanitize_svg(false)
+                .with_count_only(true),
+        );
+        let counts = counting::count_range(
+            source,
+            &parser,
+            start_block..latest_block,
+            config.processing.chunk_size,
+            config.processing.max_in_flight,
+            args.fail_fast,
+        )
+        .await?;
+        println!("{}", counts);
+        return Ok(());
+    }
 
-        // Process blocks in parallel using rayon to find text inscriptions
-        let texts = parser.process_blocks(blocks);
-        info!("Found {} text inscriptions in blocks {} to {}", 
-            texts.len(), current_block, end_block);
+    let shutdown = shutdown::Shutdown::new();
+    shutdown.install();
 
-        // Store discovered text inscriptions
-        for text in texts {
-            if let Err(e) = storage.store_text(text).await {
-                error!("Failed to store text inscription: {}", e);
-            }
+    let dashboard = if use_tui {
+        Some(tui::Dashboard::start(metrics.clone(), shutdown.clone(), start_block, latest_block)?)
+    } else {
+        None
+    };
+
+    // The bar would fight the dashboard, and interleave with verbose logs
+    let progress = if args.no_progress || args.verbose || use_tui || !std::io::stdout().is_terminal() {
+        None
+    } else {
+        Some(progress::ScanProgress::new(start_block, latest_block))
+    };
+
+    let server = match args.serve {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            Some(tokio::spawn(server::serve(listener, storage.clone(), metrics.clone())))
+        }
+        None => None,
+    };
+    let metrics_server = match args.metrics_addr {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            Some(tokio::spawn(prometheus::serve(listener, metrics.clone())))
+        }
+        None => None,
+    };
+
+    let source: Arc<dyn scanner::BlockSource> = match &node_client {
+        Some(client) => client.clone(),
+        None => Arc::new(scanner::MockSource),
+    };
+    let scanner = scanner::Scanner::new(source, &parser, &storage, &metrics, &config)
+        .with_dry_run(args.dry_run)
+        .with_alerts(&alerts)
+        .with_archive(archive.as_ref())
+        .with_cache(cache.as_deref())
+        .with_dashboard(dashboard.as_ref())
+        .with_progress(progress.as_ref())
+        .with_maintenance(maintenance.as_ref())
+        .with_shutdown(&shutdown)
+        .with_fail_fast(args.fail_fast)
+        .with_headers(node_client.clone().map(|client| client as Arc<dyn reorg::HeaderSource>));
+    let scanned = if args.rescan.is_some() {
+        scanner.rescan(start_block..latest_block).await
+    } else if args.resume {
+        match scanner.retry_skipped().await {
+            Ok(()) => scanner.run(start_block..latest_block).await,
+            Err(e) => Err(e),
         }
+    } else {
+        scanner.run(start_block..latest_block).await
+    };
+    // Written even when the scan failed, so the blocks skipped before it aren't lost
+    let failures = scanner.failures();
+    if let Some(path) = &args.failures_file {
+        scanner::write_failures(path, &failures)?;
+        info!("Wrote {} skipped block(s) to {}", failures.len(), path.display());
+    }
+    let stopped_at = scanned?;
+
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+    if let Some(cache) = &cache {
+        cache.flush()?;
+    }
+    if let Some(bloom) = &bloom {
+        bloom.save(&bloom_path)?;
+    }
 
-        info!("Completed blocks {} to {}", current_block, end_block);
-        current_block = end_block;
+    if let Some(dashboard) = dashboard {
+        dashboard.finish()?;
     }
 
-    info!("Scanning completed");
+    // Printed rather than logged so the summary survives --tui silencing the logs
+    println!("{}", metrics.get_stats());
+    if !failures.is_empty() {
+        println!("{}", scanner::failure_summary(&failures, MAX_FAILURES_SHOWN));
+    }
+
+    if let Some(server) = server {
+        if !shutdown.is_requested() {
+            info!("Scan finished, still serving the HTTP API (Ctrl-C to stop)");
+        }
+        while !shutdown.is_requested() && !server.is_finished() {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        server.abort();
+    }
+    if let Some(metrics_server) = metrics_server {
+        metrics_server.abort();
+    }
+    if shutdown.is_requested() && args.rescan.is_some() {
+        info!("Rescan interrupted before block {}; rerun it to finish the range", stopped_at);
+    } else if shutdown.is_requested() {
+        info!("Scan interrupted after block {}; rerun with --resume to continue", stopped_at.saturating_sub(1));
+    } else if stopped_at < latest_block {
+        info!("Scanning completed at the chain's tip, block {}", stopped_at.saturating_sub(1));
+    } else {
+        info!("Scanning completed");
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions_for_every_shell() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut out = Vec::new();
+            write_completions(shell, &mut out);
+            let script = String::from_utf8(out).unwrap();
+            assert!(script.contains("stop-block"), "{:?} completions missing flags", shell);
+        }
+    }
+
+    #[test]
+    fn test_workers_flag_overrides_the_config() {
+        let mut config = config::Config::default();
+        config.node.max_concurrent_requests = 16;
+        apply_cli_overrides(&mut config, &Args::parse_from(["scanner"]));
+        assert_eq!(config.node.max_concurrent_requests, 16);
+
+        apply_cli_overrides(&mut config, &Args::parse_from(["scanner", "--workers", "4"]));
+        assert_eq!(config.node.max_concurrent_requests, 4);
+
+        assert!(Args::try_parse_from(["scanner", "--workers", "0"]).is_err());
+        assert!(Args::try_parse_from(["scanner", "--workers", "many"]).is_err());
+    }
+
+    #[test]
+    fn test_mock_blocks_needs_mock() {
+        assert_eq!(Args::parse_from(["scanner", "--mock", "--mock-blocks", "100"]).mock_blocks, Some(100));
+        assert!(Args::try_parse_from(["scanner", "--mock-blocks", "100"]).is_err());
+    }
+
+    #[test]
+    fn test_report_commands_are_read_only() {
+        assert!(!Args::parse_from(["scanner"]).read_only());
+        assert!(Args::parse_from(["scanner", "--count-only"]).read_only());
+        assert!(Args::parse_from(["scanner", "--content-type-stats", "50"]).read_only());
+    }
+
+    #[test]
+    fn test_content_type_stats_over_mock_range() {
+        let parser = parser::ParallelParser::new(10, None).unwrap();
+        let heights = sampling::sample_heights(0, 1000, 8, 42);
+        let blocks = heights
+            .iter()
+            .map(|&height| (height, scanner::create_mock_inscription_block(height)))
+            .collect();
+
+        let stats = tally_content_types(&parser, blocks);
+        assert_eq!(stats.blocks_sampled, 8);
+        assert_eq!(stats.inscriptions, 8);
+        // Mock blocks cycle through four types by height
+        let texts = heights.iter().filter(|&height| height % 4 == 0).count() as u64;
+        assert_eq!(stats.by_type.get("text/plain").copied().unwrap_or(0), texts);
+        let mock_types = ["text/plain", "image/png", "application/json", "application/octet-stream"];
+        assert!(stats.by_type.keys().all(|mime| mock_types.contains(&mime.as_str())));
+    }
+
+    #[tokio::test]
+    async fn test_csv_export_after_mock_scan() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = storage::Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        let parser = parser::ParallelParser::new(10, None).unwrap();
+        let blocks = (100..105)
+            .map(|height| (height, scanner::create_mock_inscription_block(height)))
+            .collect();
+        for inscription in parser.process_blocks(blocks) {
+            storage.store_inscription(&inscription).await.unwrap();
+        }
+
+        // Block 103's binary inscription isn't stored without binary storage
+        let path = temp_dir.path().join("export.csv");
+        assert_eq!(storage::export(&storage, &path).unwrap(), 4);
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("txid,type,content_type,block_height,size,timestamp,path"));
+        assert_eq!(lines.count(), 4);
+    }
+}
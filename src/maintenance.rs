@@ -0,0 +1,138 @@
+// maintenance.rs
+//
+// Periodic upkeep for long-running scans.
+//
+// Every `maintenance.interval_secs` the text log is rotated, the cache
+// drops expired parse results and is compacted so deleted and overwritten
+// keys stop taking up space. The scan loop runs it between batches, after
+// the cursor is saved, so it never overlaps a batch's writes.
+
+use crate::cache::{CacheDb, ParsedBlockCache};
+use crate::error::AppError;
+use crate::storage::Storage;
+use crate::utils::{run_blocking, Metrics};
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Runs log and cache upkeep once per interval
+pub struct Maintenance {
+    interval: Duration,
+    last_run: Mutex<Instant>,
+    storage: Option<Arc<Storage>>,
+    cache: Option<Arc<CacheDb>>,
+    parsed: Option<Arc<ParsedBlockCache>>,
+}
+
+impl Maintenance {
+    /// Schedules upkeep every `interval`, the first one an interval from now
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_run: Mutex::new(Instant::now()),
+            storage: None,
+            cache: None,
+            parsed: None,
+        }
+    }
+
+    /// Rotates the text log of `storage`
+    pub fn with_storage(mut self, storage: Arc<Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Compacts `cache`
+    pub fn with_cache(mut self, cache: Option<Arc<CacheDb>>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Also purges expired parse results from `parsed`
+    pub fn with_parsed_cache(mut self, parsed: Option<Arc<ParsedBlockCache>>) -> Self {
+        self.parsed = parsed;
+        self
+    }
+
+    /// Runs the upkeep if an interval has passed since the last run at `now`
+    ///
+    /// Returns whether it ran.
+    pub async fn run_if_due(&self, now: Instant, metrics: &Metrics) -> Result<bool, AppError> {
+        {
+            let mut last_run = self.last_run.lock().unwrap_or_else(|e| e.into_inner());
+            if now.saturating_duration_since(*last_run) < self.interval {
+                return Ok(false);
+            }
+            *last_run = now;
+        }
+
+        self.run(metrics).await?;
+        Ok(true)
+    }
+
+    async fn run(&self, metrics: &Metrics) -> Result<(), AppError> {
+        let started = Instant::now();
+        let mut actions = Vec::new();
+        if let Some(storage) = &self.storage {
+            if run_blocking(|| storage.rotate_text_log())? {
+                actions.push("rotated the text log".to_string());
+            }
+        }
+        if let Some(parsed) = &self.parsed {
+            let purged = run_blocking(|| parsed.purge_expired())?;
+            actions.push(format!("purged {} expired parsed blocks", purged));
+        }
+        if let Some(cache) = self.cache.clone() {
+            // Compaction rewrites the whole key range, so it gets a blocking thread of its own
+            match tokio::task::spawn_blocking(move || cache.compact()).await {
+                Ok(()) => actions.push("compacted the cache".to_string()),
+                Err(e) => warn!("Cache compaction failed: {}", e),
+            }
+        }
+        metrics.increment_maintenance_runs();
+        let actions = if actions.is_empty() { "nothing to do".to_string() } else { actions.join(", ") };
+        info!("Maintenance: {} in {:.2?}", actions, started.elapsed());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Inscription, InscriptionType};
+    use bitcoin::Txid;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_runs_once_per_interval() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = Arc::new(CacheDb::new(temp_dir.path().join("cache")).unwrap());
+        let parsed = Arc::new(ParsedBlockCache::new(cache.clone(), Duration::from_secs(3600)));
+        let log = temp_dir.path().join("inscriptions.log");
+        let storage = Arc::new(Storage::new(temp_dir.path().join("images"), log.clone()).unwrap());
+        let metrics = Metrics::new();
+        let maintenance = Maintenance::new(Duration::from_secs(60))
+            .with_storage(storage.clone())
+            .with_cache(Some(cache))
+            .with_parsed_cache(Some(parsed));
+        let start = Instant::now();
+
+        let txid = Txid::from_str(&"01".repeat(32)).unwrap();
+        storage.store_inscription(&Inscription::new(txid, InscriptionType::Text("kept".to_string()))).await.unwrap();
+
+        let mut due = Vec::new();
+        for secs in [30, 60, 90, 119, 120, 300] {
+            due.push(maintenance.run_if_due(start + Duration::from_secs(secs), &metrics).await.unwrap());
+        }
+        assert_eq!(due, vec![false, true, false, false, true, true]);
+        assert_eq!(metrics.get_stats().maintenance_runs, 3);
+
+        // Only the first run had entries to rotate out
+        let segment = temp_dir.path().join("inscriptions.log.1");
+        assert!(std::fs::read_to_string(segment).unwrap().contains("kept"));
+        assert!(!temp_dir.path().join("inscriptions.log.2").exists());
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "");
+        assert_eq!(storage.entries().unwrap().count(), 1);
+    }
+}
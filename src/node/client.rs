@@ -1,72 +1,395 @@
-use crate::config::Config;
+use crate::cache::BlockCache;
+use crate::config::{Config, NodeConfig};
 use super::error::{NodeError, Result};
-use bitcoin::{Block, BlockHash};
+use super::proxy::{Proxy, ProxyTransport};
+use super::range::fetch_ordered;
+use super::retry::retry;
+use super::verify::verify_merkle_root;
+use bitcoin::block::Header;
+use bitcoin::{Block, BlockHash, Network, Transaction, Txid};
 use bitcoincore_rpc::{Auth, Client, RpcApi};
+use log::{info, warn};
+use serde::Deserialize;
 use tokio::sync::Semaphore;
 use std::sync::Arc;
 use std::str::FromStr;
+use std::time::Duration;
+
+/// One entry of `getchaintips`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChainTip {
+    pub height: u64,
+    pub hash: BlockHash,
+    /// Blocks between the tip and the active chain; 0 for the active tip
+    #[serde(rename = "branchlen")]
+    pub branch_length: u64,
+    /// "active", "valid-fork", "valid-headers", "headers-only" or "invalid"
+    pub status: String,
+}
+
+/// Block a transaction was confirmed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Confirmation {
+    pub height: u64,
+    pub time: u32,
+}
+
+/// Blocks a synced node's headers may run ahead while it validates a new tip
+const SYNC_LAG: u64 = 2;
+
+/// Sync state from `getblockchaininfo`
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockchainInfo {
+    /// Height of the most-work fully validated block
+    pub blocks: u64,
+    /// Height of the most-work header, which runs ahead while syncing
+    pub headers: u64,
+    #[serde(rename = "initialblockdownload")]
+    pub initial_block_download: bool,
+    /// Estimated share of the chain verified, from 0 to 1
+    #[serde(rename = "verificationprogress")]
+    pub verification_progress: f64,
+}
+
+impl BlockchainInfo {
+    /// Why the node's tip can't be trusted yet, or `None` once it is synced
+    pub fn sync_problem(&self) -> Option<String> {
+        if self.initial_block_download {
+            Some(format!(
+                "the node is in initial block download at block {} of {} ({:.1}% verified)",
+                self.blocks,
+                self.headers,
+                self.verification_progress * 100.0
+            ))
+        } else if self.headers.saturating_sub(self.blocks) > SYNC_LAG {
+            Some(format!("the node has validated block {} of {} known headers", self.blocks, self.headers))
+        } else {
+            None
+        }
+    }
+}
 
 pub struct NodeClient {
-    client: Client,
+    client: Arc<Client>,
     semaphore: Arc<Semaphore>,
+    verify_merkle: bool,
+    max_retries: u32,
+    retry_base: Duration,
+    network: Network,
+    block_cache: Option<Arc<BlockCache>>,
 }
 
 impl NodeClient {
     pub fn new(config: &Config) -> Result<Self> {
-        let auth = Auth::UserPass(
-            config.node.rpc_user.clone(),
-            config.node.rpc_password.clone(),
-        );
+        let auth = auth_for(&config.node);
         
-        let client = Client::new(&config.node.rpc_url, auth)
-            .map_err(|e| NodeError::ConnectionError(e.to_string()))?;
+        let client = match &config.node.proxy {
+            Some(proxy) => {
+                let proxy = Proxy::parse(proxy).map_err(NodeError::ConnectionError)?;
+                info!("Connecting through proxy {}", proxy);
+                let transport = ProxyTransport::new(&config.node.rpc_url, proxy, auth)?;
+                Client::from_jsonrpc(bitcoincore_rpc::jsonrpc::Client::with_transport(transport))
+            }
+            None => Client::new(&config.node.rpc_url, auth)
+                .map_err(|e| NodeError::ConnectionError(e.to_string()))?,
+        };
         
         Ok(Self {
-            client,
+            client: Arc::new(client),
             semaphore: Arc::new(Semaphore::new(config.node.max_concurrent_requests)),
+            verify_merkle: config.node.verify_merkle,
+            max_retries: config.node.max_retries,
+            retry_base: Duration::from_millis(config.node.retry_base_ms),
+            network: config.node.network,
+            block_cache: None,
+        })
+    }
+
+    /// Serves blocks from `cache` when present and caches every block fetched over RPC
+    pub fn with_block_cache(mut self, cache: Arc<BlockCache>) -> Self {
+        self.block_cache = Some(cache);
+        self
+    }
+
+    /// Runs a blocking RPC call off the async runtime, retrying transient failures
+    ///
+    /// The RPC client is synchronous, so calls go through `spawn_blocking`
+    /// to let concurrent fetches actually overlap.
+    async fn call<T, F>(&self, rpc: F) -> Result<T>
+    where
+        F: Fn(&Client) -> bitcoincore_rpc::Result<T> + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        let rpc = Arc::new(rpc);
+        retry(self.max_retries, self.retry_base, || {
+            let client = self.client.clone();
+            let rpc = rpc.clone();
+            async move {
+                tokio::task::spawn_blocking(move || rpc(&client))
+                    .await
+                    .map_err(|e| NodeError::ConnectionError(format!("RPC task failed: {}", e)))?
+                    .map_err(NodeError::from)
+            }
         })
+        .await
     }
 
     pub async fn get_block(&self, hash: &BlockHash) -> Result<Block> {
         let _permit = self.semaphore.acquire().await.map_err(|e| {
             NodeError::ConnectionError(format!("Failed to acquire semaphore: {}", e))
         })?;
+        self.fetch_block(hash).await
+    }
+
+    /// Fetches the blocks for `start..end` concurrently, in height order
+    ///
+    /// Each height's hash and block lookups share one semaphore permit, so
+    /// at most `max_concurrent_requests` heights are in flight at once.
+    pub async fn get_blocks_range(&self, start: u64, end: u64) -> Result<Vec<Block>> {
+        fetch_ordered(&self.semaphore, start..end, |height| async move {
+            let hash = self.get_block_hash(height).await?;
+            self.fetch_block(&hash).await
+        })
+        .await
+    }
 
+    /// Fetches a block without taking a semaphore permit, from the block cache if enabled
+    async fn fetch_block(&self, hash: &BlockHash) -> Result<Block> {
+        match &self.block_cache {
+            Some(cache) => cache.get_or_fetch(hash, || self.download_block(hash)).await,
+            None => self.download_block(hash).await,
+        }
+    }
+
+    /// Fetches and decodes a block over RPC
+    async fn download_block(&self, hash: &BlockHash) -> Result<Block> {
         let rpc_hash = bitcoincore_rpc::bitcoin::BlockHash::from_str(&hash.to_string())
-            .map_err(|e| NodeError::ConnectionError(format!("Failed to convert hash: {}", e)))?;
+            .map_err(|e| NodeError::Deserialization(format!("Failed to convert hash: {}", e)))?;
 
         // Get block as hex string
-        let block_hex = hex::decode(
-            self.client
-                .get_block_hex(&rpc_hash)
-                .map_err(|e| NodeError::RpcError(e))?
-        ).map_err(|e| NodeError::ConnectionError(format!("Failed to decode hex: {}", e)))?;
-        bitcoin::consensus::encode::deserialize(&block_hex)
-            .map_err(|e| NodeError::ConnectionError(format!("Failed to deserialize block: {}", e)))
+        let block_hex = self.call(move |client| client.get_block_hex(&rpc_hash)).await?;
+        let block_hex = hex::decode(block_hex)
+            .map_err(|e| NodeError::Deserialization(format!("Failed to decode hex: {}", e)))?;
+        let block: Block = bitcoin::consensus::encode::deserialize(&block_hex)
+            .map_err(|e| NodeError::Deserialization(format!("Failed to deserialize block: {}", e)))?;
+
+        if self.verify_merkle {
+            verify_merkle_root(&block)?;
+        }
+        Ok(block)
+    }
+
+    /// Fetches a transaction and the block confirming it; outside the mempool this needs `-txindex` on the node
+    ///
+    /// The confirmation is `None` while the transaction is unconfirmed.
+    pub async fn get_raw_transaction(&self, txid: &Txid) -> Result<(Transaction, Option<Confirmation>)> {
+        let rpc_txid = bitcoincore_rpc::bitcoin::Txid::from_str(&txid.to_string())
+            .map_err(|e| NodeError::Deserialization(format!("Failed to convert txid: {}", e)))?;
+
+        let info = self.call(move |client| client.get_raw_transaction_info(&rpc_txid, None)).await?;
+        let tx = bitcoin::consensus::encode::deserialize(&info.hex)
+            .map_err(|e| NodeError::Deserialization(format!("Failed to deserialize transaction: {}", e)))?;
+        let confirmation = match info.blockhash {
+            Some(hash) => {
+                let header = self.call(move |client| client.get_block_header_info(&hash)).await?;
+                Some(Confirmation { height: header.height as u64, time: header.time as u32 })
+            }
+            None => None,
+        };
+        Ok((tx, confirmation))
+    }
+
+    /// Fetches only the 80-byte header of a block
+    pub async fn get_block_header(&self, hash: &BlockHash) -> Result<Header> {
+        let hash = hash.to_string();
+        let header_hex: String = self
+            .call(move |client| {
+                client.call("getblockheader", &[serde_json::json!(hash), serde_json::json!(false)])
+            })
+            .await?;
+        decode_header(&header_hex)
+    }
+
+    /// Every tip the node knows of, including those of stale branches
+    pub async fn get_chain_tips(&self) -> Result<Vec<ChainTip>> {
+        self.call(|client| client.call("getchaintips", &[])).await
     }
 
     pub async fn get_block_count(&self) -> Result<u64> {
-        self.client
-            .get_block_count()
-            .map_err(|e| NodeError::RpcError(e))
+        self.call(|client| client.get_block_count()).await
     }
 
     pub async fn get_block_hash(&self, height: u64) -> Result<BlockHash> {
-        let rpc_hash = self.client
-            .get_block_hash(height)
-            .map_err(|e| NodeError::RpcError(e))?;
+        let rpc_hash = self
+            .call(move |client| client.get_block_hash(height))
+            .await
+            .map_err(|e| e.at_height(height))?;
 
         BlockHash::from_str(&rpc_hash.to_string())
-            .map_err(|e| NodeError::ConnectionError(format!("Failed to convert hash: {}", e)))
+            .map_err(|e| NodeError::Deserialization(format!("Failed to convert hash: {}", e)))
+    }
+
+    /// Chain the node reports in `getblockchaininfo`, as its BIP70 name ("main", "test", ...)
+    pub async fn get_chain(&self) -> Result<String> {
+        let info: serde_json::Value = self.call(|client| client.call("getblockchaininfo", &[])).await?;
+        info["chain"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| NodeError::Deserialization("getblockchaininfo has no chain".to_string()))
+    }
+
+    /// Sync progress of the node
+    pub async fn get_blockchain_info(&self) -> Result<BlockchainInfo> {
+        self.call(|client| client.call("getblockchaininfo", &[])).await
+    }
+
+    /// Errors unless the node follows the configured network
+    pub async fn check_network(&self) -> Result<()> {
+        let chain = self.get_chain().await?;
+        if chain_network(&chain) != Some(self.network) {
+            return Err(NodeError::NetworkMismatch {
+                expected: self.network.to_string(),
+                actual: chain,
+            });
+        }
+        info!("Node is on {}", self.network);
+        Ok(())
     }
 
     #[allow(dead_code)]
     pub async fn get_best_block_hash(&self) -> Result<BlockHash> {
-        let rpc_hash = self.client
-            .get_best_block_hash()
-            .map_err(|e| NodeError::RpcError(e))?;
+        let rpc_hash = self.call(|client| client.get_best_block_hash()).await?;
 
         BlockHash::from_str(&rpc_hash.to_string())
-            .map_err(|e| NodeError::ConnectionError(format!("Failed to convert hash: {}", e)))
+            .map_err(|e| NodeError::Deserialization(format!("Failed to convert hash: {}", e)))
+    }
+}
+
+/// Maps a BIP70 chain name, as reported by bitcoind, to its network
+fn chain_network(chain: &str) -> Option<Network> {
+    match chain {
+        "main" => Some(Network::Bitcoin),
+        "test" => Some(Network::Testnet),
+        "signet" => Some(Network::Signet),
+        "regtest" => Some(Network::Regtest),
+        _ => None,
+    }
+}
+
+/// Decodes a header as returned by non-verbose `getblockheader`
+fn decode_header(header_hex: &str) -> Result<Header> {
+    let bytes = hex::decode(header_hex)
+        .map_err(|e| NodeError::Deserialization(format!("Failed to decode hex: {}", e)))?;
+    bitcoin::consensus::encode::deserialize(&bytes)
+        .map_err(|e| NodeError::Deserialization(format!("Failed to deserialize header: {}", e)))
+}
+
+/// Picks the RPC credentials, preferring a cookie file over user/password
+fn auth_for(config: &NodeConfig) -> Auth {
+    match &config.cookie_file {
+        Some(path) => {
+            if !config.rpc_user.is_empty() || !config.rpc_password.is_empty() {
+                warn!("Both cookie_file and rpc_user/rpc_password are set, using the cookie file");
+            }
+            Auth::CookieFile(path.clone())
+        }
+        None => Auth::UserPass(config.rpc_user.clone(), config.rpc_password.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::serve_rpc_once;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_auth_selection() {
+        let mut config = Config::default();
+        assert_eq!(
+            auth_for(&config.node),
+            Auth::UserPass("user".to_string(), "password".to_string())
+        );
+
+        // The cookie file wins even when user/password are also set
+        config.node.cookie_file = Some(PathBuf::from("/home/bitcoin/.bitcoin/.cookie"));
+        assert_eq!(
+            auth_for(&config.node),
+            Auth::CookieFile(PathBuf::from("/home/bitcoin/.bitcoin/.cookie"))
+        );
+    }
+
+    #[test]
+    fn test_decode_header() {
+        // Header of mainnet block 1, as getblockheader returns it
+        let header = decode_header(concat!(
+            "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000",
+            "982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e",
+            "61bc6649ffff001d01e36299",
+        ))
+        .unwrap();
+        assert_eq!(
+            header.prev_blockhash.to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(
+            header.block_hash().to_string(),
+            "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"
+        );
+
+        assert!(decode_header("00").is_err());
+        assert!(decode_header("not hex").is_err());
+    }
+
+    #[test]
+    fn test_chain_tips_deserialize() {
+        let tips: Vec<ChainTip> = serde_json::from_str(
+            r#"[{"height": 1, "hash": "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048",
+                 "branchlen": 0, "status": "active"}]"#,
+        )
+        .unwrap();
+        assert_eq!(tips[0].height, 1);
+        assert_eq!(tips[0].branch_length, 0);
+        assert_eq!(tips[0].status, "active");
+    }
+
+    #[tokio::test]
+    async fn test_node_in_initial_block_download() {
+        let mut config = Config::default();
+        config.node.rpc_url = serve_rpc_once(serde_json::json!({
+            "chain": "main",
+            "blocks": 350_000,
+            "headers": 860_000,
+            "initialblockdownload": true,
+            "verificationprogress": 0.0412,
+        }));
+        let client = NodeClient::new(&config).unwrap();
+
+        let info = client.get_blockchain_info().await.unwrap();
+        assert!(info.initial_block_download);
+        assert_eq!((info.blocks, info.headers), (350_000, 860_000));
+        let problem = info.sync_problem().unwrap();
+        assert!(problem.contains("initial block download at block 350000 of 860000"), "{}", problem);
+
+        let synced = BlockchainInfo {
+            blocks: 860_000,
+            headers: 860_000,
+            initial_block_download: false,
+            verification_progress: 0.9999,
+        };
+        assert_eq!(synced.sync_problem(), None);
+        let validating_tip = BlockchainInfo { blocks: 859_999, ..synced };
+        assert_eq!(validating_tip.sync_problem(), None);
+        let behind = BlockchainInfo { blocks: 859_990, ..synced };
+        assert!(behind.sync_problem().is_some());
+    }
+
+    #[test]
+    fn test_chain_names_map_to_networks() {
+        assert_eq!(chain_network("main"), Some(Network::Bitcoin));
+        assert_eq!(chain_network("test"), Some(Network::Testnet));
+        assert_eq!(chain_network("signet"), Some(Network::Signet));
+        assert_eq!(chain_network("regtest"), Some(Network::Regtest));
+        assert_eq!(chain_network("testnet4"), None);
     }
 }
@@ -1,12 +1,154 @@
+use bitcoincore_rpc::jsonrpc;
 use thiserror::Error;
 
+/// Bitcoin Core's RPC_MISC_ERROR, returned for blocks removed by pruning
+const RPC_MISC_ERROR: i32 = -1;
+/// Bitcoin Core's RPC_INVALID_ADDRESS_OR_KEY, returned for unknown block hashes
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+/// Bitcoin Core's RPC_INVALID_PARAMETER, returned for heights above the tip
+const RPC_INVALID_PARAMETER: i32 = -8;
+/// Bitcoin Core's RPC_IN_WARMUP, returned while the node is still starting
+const RPC_IN_WARMUP: i32 = -28;
+
 #[derive(Error, Debug)]
 pub enum NodeError {
     #[error("RPC error: {0}")]
-    RpcError(#[from] bitcoincore_rpc::Error),
+    RpcError(bitcoincore_rpc::Error),
     
     #[error("Connection error: {0}")]
     ConnectionError(String),
+
+    #[error("Merkle root mismatch: {0}")]
+    MerkleMismatch(String),
+
+    /// The chain has no block at this height, e.g. it's above the tip
+    #[error("No block at height {0}")]
+    BlockNotFound(u64),
+
+    /// The node doesn't know the requested block hash
+    #[error("Unknown block: {0}")]
+    UnknownBlock(String),
+
+    /// The node couldn't be reached, dropped the connection or is still starting
+    #[error("Not connected to the node: {0}")]
+    NotConnected(String),
+
+    /// The node answered but the response couldn't be decoded
+    #[error("Deserialization error: {0}")]
+    Deserialization(String),
+
+    /// The block existed but has been pruned from the node
+    #[error("Block pruned: {0}")]
+    Pruned(String),
+
+    /// The node follows a different chain than the configured network
+    #[error("Network mismatch: configured for {expected} but the node is on {actual}")]
+    NetworkMismatch { expected: String, actual: String },
+
+    /// The node is still syncing, so its tip is behind the real chain
+    #[error("Node not synced: {0}")]
+    NotSynced(String),
+}
+
+impl NodeError {
+    /// Whether the failure is connection-level and worth retrying
+    pub fn is_transient(&self) -> bool {
+        matches!(self, NodeError::NotConnected(_) | NodeError::ConnectionError(_))
+    }
+
+    /// Pins a missing block on the height that was asked for
+    ///
+    /// Bitcoin Core doesn't say which height was out of range, so lookups
+    /// by height call this to turn `UnknownBlock` into `BlockNotFound`.
+    pub fn at_height(self, height: u64) -> Self {
+        match self {
+            NodeError::UnknownBlock(_) => NodeError::BlockNotFound(height),
+            other => other,
+        }
+    }
+}
+
+impl From<bitcoincore_rpc::Error> for NodeError {
+    fn from(error: bitcoincore_rpc::Error) -> Self {
+        use bitcoincore_rpc::Error as RpcError;
+
+        match error {
+            RpcError::JsonRpc(jsonrpc::Error::Rpc(ref e)) => match e.code {
+                RPC_MISC_ERROR if e.message.contains("pruned") => NodeError::Pruned(e.message.clone()),
+                RPC_INVALID_ADDRESS_OR_KEY | RPC_INVALID_PARAMETER => {
+                    NodeError::UnknownBlock(e.message.clone())
+                }
+                RPC_IN_WARMUP => NodeError::NotConnected(e.message.clone()),
+                _ => NodeError::RpcError(error),
+            },
+            RpcError::JsonRpc(jsonrpc::Error::Transport(e)) => NodeError::NotConnected(e.to_string()),
+            RpcError::Io(e) => NodeError::NotConnected(e.to_string()),
+            RpcError::JsonRpc(jsonrpc::Error::Json(e)) | RpcError::Json(e) => {
+                NodeError::Deserialization(e.to_string())
+            }
+            RpcError::Hex(e) => NodeError::Deserialization(e.to_string()),
+            RpcError::BitcoinSerialization(e) => NodeError::Deserialization(e.to_string()),
+            RpcError::UnexpectedStructure => {
+                NodeError::Deserialization("unexpected response structure".to_string())
+            }
+            other => NodeError::RpcError(other),
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, NodeError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn rpc_error(code: i32, message: &str) -> bitcoincore_rpc::Error {
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(jsonrpc::error::RpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }))
+    }
+
+    #[test]
+    fn test_rpc_errors_map_to_variants() {
+        assert!(matches!(
+            NodeError::from(rpc_error(-5, "Block not found")),
+            NodeError::UnknownBlock(_)
+        ));
+        assert!(matches!(
+            NodeError::from(rpc_error(-8, "Block height out of range")).at_height(900_000),
+            NodeError::BlockNotFound(900_000)
+        ));
+        assert!(matches!(
+            NodeError::from(rpc_error(-1, "Block not available (pruned data)")),
+            NodeError::Pruned(_)
+        ));
+        assert!(matches!(
+            NodeError::from(rpc_error(-28, "Loading block index...")),
+            NodeError::NotConnected(_)
+        ));
+        assert!(matches!(
+            NodeError::from(rpc_error(-32601, "Method not found")),
+            NodeError::RpcError(_)
+        ));
+        // Only missing blocks are pinned on a height
+        assert!(matches!(
+            NodeError::from(rpc_error(-1, "Block not available (pruned data)")).at_height(5),
+            NodeError::Pruned(_)
+        ));
+
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused");
+        assert!(matches!(
+            NodeError::from(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(Box::new(refused)))),
+            NodeError::NotConnected(_)
+        ));
+        let reset = io::Error::new(io::ErrorKind::ConnectionReset, "connection reset by peer");
+        assert!(matches!(NodeError::from(bitcoincore_rpc::Error::Io(reset)), NodeError::NotConnected(_)));
+        assert!(matches!(
+            NodeError::from(bitcoincore_rpc::Error::UnexpectedStructure),
+            NodeError::Deserialization(_)
+        ));
+    }
+}
@@ -1,4 +1,10 @@
 mod client;
 mod error;
+mod proxy;
+mod range;
+mod retry;
+mod verify;
 
-pub use client::NodeClient;
\ No newline at end of file
+pub use client::{Confirmation, NodeClient};
+pub use error::NodeError;
+pub use proxy::Proxy;
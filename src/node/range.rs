@@ -0,0 +1,73 @@
+use super::error::{NodeError, Result};
+use futures::future::join_all;
+use std::future::Future;
+use std::ops::Range;
+use tokio::sync::Semaphore;
+
+/// Fetches every height concurrently, at most `semaphore`'s permits at a time
+///
+/// Results come back in height order regardless of completion order; the
+/// first failure (by height) fails the whole range.
+pub(crate) async fn fetch_ordered<T, F, Fut>(
+    semaphore: &Semaphore,
+    heights: Range<u64>,
+    fetch: F,
+) -> Result<Vec<T>>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let fetch = &fetch;
+    let fetches = heights.map(|height| async move {
+        let _permit = semaphore.acquire().await.map_err(|e| {
+            NodeError::ConnectionError(format!("Failed to acquire semaphore: {}", e))
+        })?;
+        fetch(height).await
+    });
+
+    join_all(fetches).await.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_preserves_order_and_bounds_concurrency() {
+        let semaphore = Semaphore::new(3);
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let (in_flight, peak) = (&in_flight, &peak);
+
+        // Later heights finish first, so ordering must come from the input
+        let heights = fetch_ordered(&semaphore, 100..110, move |height| async move {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(110 - height)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(height)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(heights, (100..110).collect::<Vec<_>>());
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failure_fails_the_range() {
+        let semaphore = Semaphore::new(4);
+        let result = fetch_ordered(&semaphore, 0..5, |height| async move {
+            if height == 3 {
+                Err(NodeError::BlockNotFound(height))
+            } else {
+                Ok(height)
+            }
+        })
+        .await;
+
+        assert!(matches!(result, Err(NodeError::BlockNotFound(_))));
+    }
+}
@@ -0,0 +1,81 @@
+use super::error::Result;
+use log::warn;
+use std::future::Future;
+use std::time::Duration;
+
+/// Runs `op`, retrying transient failures with exponential backoff
+///
+/// The delay starts at `base` and doubles after every failed attempt.
+/// Errors that aren't transient (e.g. a missing block) are returned
+/// immediately, as is the last error once `max_retries` is exhausted.
+pub(crate) async fn retry<T, F, Fut>(max_retries: u32, base: Duration, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if e.is_transient() && attempt < max_retries => {
+                let delay = base.saturating_mul(2u32.saturating_pow(attempt));
+                warn!("Transient node error (attempt {} of {}), retrying in {:?}: {}",
+                    attempt + 1, max_retries + 1, delay, e);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::error::NodeError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let counter = AtomicU32::new(0);
+        let calls = &counter;
+        let result = retry(3, Duration::from_millis(1), move || async move {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(NodeError::NotConnected("connection refused".to_string()))
+            } else {
+                Ok(42)
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_missing_block() {
+        let counter = AtomicU32::new(0);
+        let calls = &counter;
+        let result: Result<()> = retry(3, Duration::from_millis(1), move || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(NodeError::BlockNotFound(7))
+        })
+        .await;
+
+        assert!(matches!(result, Err(NodeError::BlockNotFound(_))));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let counter = AtomicU32::new(0);
+        let calls = &counter;
+        let result: Result<()> = retry(2, Duration::from_millis(1), move || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(NodeError::NotConnected("timed out".to_string()))
+        })
+        .await;
+
+        assert!(matches!(result, Err(NodeError::NotConnected(_))));
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,60 @@
+use super::error::{NodeError, Result};
+use bitcoin::Block;
+
+/// Recomputes the merkle root from a block's txdata and checks it
+/// against the header, catching nodes or proxies that return txdata
+/// inconsistent with the block they claim to serve.
+pub fn verify_merkle_root(block: &Block) -> Result<()> {
+    match block.compute_merkle_root() {
+        Some(root) if root == block.header.merkle_root => Ok(()),
+        computed => Err(NodeError::MerkleMismatch(format!(
+            "block {} header commits to {} but txdata hashes to {}",
+            block.block_hash(),
+            block.header.merkle_root,
+            computed.map_or_else(|| "nothing".to_string(), |root| root.to_string()),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::dummy_header;
+    use bitcoin::{ScriptBuf, Transaction, TxOut};
+
+    fn block_with_outputs(values: &[u64]) -> Block {
+        let txdata = values
+            .iter()
+            .map(|&value| Transaction {
+                version: 2,
+                lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![TxOut { value, script_pubkey: ScriptBuf::new() }],
+            })
+            .collect();
+
+        let mut block = Block {
+            header: dummy_header(),
+            txdata,
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        block
+    }
+
+    #[test]
+    fn test_merkle_root_matches() {
+        let block = block_with_outputs(&[1, 2, 3]);
+        assert!(verify_merkle_root(&block).is_ok());
+    }
+
+    #[test]
+    fn test_tampered_txdata_fails() {
+        let mut block = block_with_outputs(&[1, 2, 3]);
+        block.txdata[1].output[0].value = 42;
+
+        match verify_merkle_root(&block) {
+            Err(NodeError::MerkleMismatch(_)) => {}
+            other => panic!("Expected merkle mismatch, got {:?}", other),
+        }
+    }
+}
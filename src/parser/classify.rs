@@ -0,0 +1,87 @@
+// classify.rs
+//
+// Mapping from an inscription's content type and body to an
+// `InscriptionType`, behind a trait so particular content types can be
+// handled differently without touching envelope parsing.
+
+use super::inscription::{mime_essence, InscriptionType};
+use super::svg;
+use std::fmt;
+
+/// Turns a body into an `InscriptionType` according to its content type
+///
+/// `content_type` is the declared type, or the sniffed one when the body's
+/// magic bytes contradict the declaration. Returning `None` drops the
+/// inscription. Other classifiers can delegate the types they don't
+/// handle to `DefaultClassifier`.
+pub trait Classifier: fmt::Debug + Send + Sync {
+    fn classify(&self, content_type: &str, body: Vec<u8>) -> Option<InscriptionType>;
+
+    /// Identifies the classifier in the parser's version tag, so cached
+    /// parse results from a different classifier aren't reused
+    fn name(&self) -> &str;
+}
+
+/// The built-in mapping: UTF-8 text, JSON, SVG and images, anything else `Unknown`
+#[derive(Debug, Default, Clone)]
+pub struct DefaultClassifier {
+    /// Remove scripts, `<foreignObject>` elements, event handler attributes
+    /// and `javascript:` URLs from SVG bodies
+    pub sanitize_svg: bool,
+}
+
+impl Classifier for DefaultClassifier {
+    fn classify(&self, content_type: &str, body: Vec<u8>) -> Option<InscriptionType> {
+        match content_type {
+            mime if mime_essence(mime) == "text/plain" => String::from_utf8(body).ok().map(classify_text),
+            mime if mime_essence(mime) == "application/json" => match serde_json::from_slice(&body) {
+                Ok(value) => Some(InscriptionType::Json(value)),
+                Err(_) => match String::from_utf8(body) {
+                    Ok(text) => Some(InscriptionType::Text(text)),
+                    Err(e) => Some(InscriptionType::Unknown(e.into_bytes())),
+                },
+            },
+            mime if mime_essence(mime) == "image/svg+xml" => match String::from_utf8(body) {
+                Ok(text) => Some(self.classify_svg(text)),
+                Err(e) => Some(InscriptionType::Unknown(e.into_bytes())),
+            },
+            mime if mime.starts_with("image/") => Some(InscriptionType::Image {
+                mime_type: content_type.to_string(),
+                data: body,
+            }),
+            _ => Some(InscriptionType::Unknown(body)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "default"
+    }
+}
+
+impl DefaultClassifier {
+    /// Validates an SVG body as XML, removing scripts when sanitizing
+    ///
+    /// Malformed documents are kept as raw bytes rather than stored as images.
+    fn classify_svg(&self, text: String) -> InscriptionType {
+        if !self.sanitize_svg {
+            if svg::is_svg_document(&text) {
+                return InscriptionType::Svg(text);
+            }
+            return InscriptionType::Unknown(text.into_bytes());
+        }
+        match svg::strip_scripts(&text) {
+            Some(svg) => InscriptionType::Svg(svg),
+            None => InscriptionType::Unknown(text.into_bytes()),
+        }
+    }
+}
+
+/// Promotes text that is really a JSON object, falling back to plain text
+fn classify_text(text: String) -> InscriptionType {
+    if text.trim_start().starts_with('{') {
+        if let Ok(value) = serde_json::from_str(&text) {
+            return InscriptionType::Json(value);
+        }
+    }
+    InscriptionType::Text(text)
+}
@@ -0,0 +1,119 @@
+// encoding.rs
+//
+// Content-encoding support for inscription bodies: magic-byte detection
+// and decompression of compressed bodies.
+
+use flate2::read::GzDecoder;
+use std::io::{self, Read};
+
+/// gzip member header magic
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// zstd frame magic (0xFD2FB528 little-endian)
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Guesses a body's content encoding from its leading bytes
+///
+/// Only formats with a magic number can be detected; brotli streams
+/// have none and are only recognized through the encoding tag.
+pub fn detect_encoding(body: &[u8]) -> Option<&'static str> {
+    if body.starts_with(GZIP_MAGIC) {
+        Some("gzip")
+    } else if body.starts_with(ZSTD_MAGIC) {
+        Some("zstd")
+    } else {
+        None
+    }
+}
+
+/// Result of decompressing a body under a size cap
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decoded {
+    Body(Vec<u8>),
+    /// The body inflates past the cap; decoding stopped there
+    Oversized,
+}
+
+/// Decompresses a body according to a content-encoding name
+///
+/// Names are matched case-insensitively, as in HTTP's Content-Encoding.
+/// At most `max` bytes are inflated, so a small compressed body can't
+/// expand into gigabytes.
+pub fn decompress(encoding: &str, body: &[u8], max: usize) -> io::Result<Decoded> {
+    match encoding.trim().to_ascii_lowercase().as_str() {
+        "br" => read_capped(brotli::Decompressor::new(body, 4096), max),
+        "gzip" => read_capped(GzDecoder::new(body), max),
+        "zstd" => read_capped(zstd::stream::read::Decoder::new(body)?, max),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported content encoding: {}", other),
+        )),
+    }
+}
+
+/// Reads `reader` to the end, giving up one byte past `max`
+fn read_capped(reader: impl Read, max: usize) -> io::Result<Decoded> {
+    let mut decoded = Vec::new();
+    reader.take(max as u64 + 1).read_to_end(&mut decoded)?;
+    if decoded.len() > max {
+        return Ok(Decoded::Oversized);
+    }
+    Ok(Decoded::Body(decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn test_detect_and_decompress_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"Hello, Bitcoin!").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(detect_encoding(&compressed), Some("gzip"));
+        assert_eq!(decompress("gzip", &compressed, 1024).unwrap(), Decoded::Body(b"Hello, Bitcoin!".to_vec()));
+    }
+
+    #[test]
+    fn test_detect_zstd_and_plain() {
+        let compressed = zstd::stream::encode_all(&b"Hello, Bitcoin!"[..], 0).unwrap();
+        assert_eq!(detect_encoding(&compressed), Some("zstd"));
+        assert_eq!(decompress("zstd", &compressed, 1024).unwrap(), Decoded::Body(b"Hello, Bitcoin!".to_vec()));
+
+        assert_eq!(detect_encoding(b"Hello, Bitcoin!"), None);
+        assert!(decompress("lzma", b"anything", 1024).is_err());
+    }
+
+    #[test]
+    fn test_decompress_brotli() {
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+        encoder.write_all(b"Hello, Bitcoin!").unwrap();
+        let compressed = encoder.into_inner();
+
+        // Brotli has no magic number, so it can only be declared
+        assert_eq!(detect_encoding(&compressed), None);
+        assert_eq!(decompress("br", &compressed, 1024).unwrap(), Decoded::Body(b"Hello, Bitcoin!".to_vec()));
+    }
+
+    #[test]
+    fn test_decompression_stops_at_the_cap() {
+        // A megabyte of zeros compresses to about a kilobyte
+        let zeros = vec![0u8; 1024 * 1024];
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&zeros).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let zstd = zstd::stream::encode_all(&zeros[..], 19).unwrap();
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+        encoder.write_all(&zeros).unwrap();
+        let brotli = encoder.into_inner();
+
+        for (encoding, body) in [("gzip", &gzip), ("zstd", &zstd), ("br", &brotli)] {
+            assert_eq!(decompress(encoding, body, 4096).unwrap(), Decoded::Oversized, "{}", encoding);
+            assert_eq!(decompress(encoding, body, zeros.len()).unwrap(), Decoded::Body(zeros.clone()), "{}", encoding);
+        }
+    }
+}
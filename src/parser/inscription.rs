@@ -7,8 +7,9 @@
 // both text and image inscriptions.
 //
 // Protocol Details:
-// - Inscriptions use OP_FALSE OP_IF ... OP_ENDIF pattern
-// - Content type and content are separated by OP_0
+// - Inscriptions use OP_FALSE OP_IF "ord" ... OP_ENDIF pattern
+// - Fields are (tag, value) push pairs; tag 1 is the content type
+// - An empty push (tag 0) separates the fields from the body
 // - Supports standard MIME types for content identification
 //
 // Performance Considerations:
@@ -21,14 +22,23 @@
 // - Graceful handling of invalid UTF-8
 // - Detailed logging for debugging
 
-use bitcoin::{Script, Transaction};
+use bitcoin::{Address, Network, Script, Transaction, Witness};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
 use bitcoin::blockdata::script::Instruction;
 use bitcoin::blockdata::opcodes::all;
 use bitcoin::opcodes::{OP_0, OP_FALSE};
+use super::classify::{Classifier, DefaultClassifier};
+use super::encoding::{self, Decoded};
+use super::recursion;
+use super::sniff::sniff_mime;
+use crate::utils::Metrics;
 use serde::{Serialize, Deserialize};
+use std::borrow::Cow;
 use std::iter::Peekable;
 use std::str::FromStr;
-use log::debug;
+use std::sync::Arc;
+use std::time::Instant;
+use log::{debug, warn};
 
 /// Represents different types of inscription content
 /// 
@@ -46,8 +56,149 @@ pub enum InscriptionType {
         data: Vec<u8> 
     },
     
+    /// JSON documents such as BRC-20 operations
+    Json(serde_json::Value),
+
+    /// Well-formed SVG documents, kept as text since they're XML
+    Svg(String),
+
     /// Unknown content types preserved as raw bytes
     Unknown(Vec<u8>),
+
+    /// Envelope without a body, e.g. one that only assigns a content type to a sat
+    Empty,
+
+    /// Body larger than the parser's size limit; only its size is kept
+    Oversized { size: usize },
+}
+
+impl InscriptionType {
+    /// Whether the inscription carries no body bytes at all
+    pub fn is_empty(&self) -> bool {
+        match self {
+            InscriptionType::Text(text) => text.is_empty(),
+            InscriptionType::Image { data, .. } => data.is_empty(),
+            InscriptionType::Json(_) => false,
+            InscriptionType::Svg(svg) => svg.is_empty(),
+            InscriptionType::Unknown(data) => data.is_empty(),
+            InscriptionType::Empty => true,
+            InscriptionType::Oversized { .. } => false,
+        }
+    }
+
+    /// Body bytes as they would be stored; JSON is re-serialized compactly
+    pub fn bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            InscriptionType::Text(text) => Cow::Borrowed(text.as_bytes()),
+            InscriptionType::Image { data, .. } => Cow::Borrowed(data),
+            InscriptionType::Json(value) => Cow::Owned(value.to_string().into_bytes()),
+            InscriptionType::Svg(svg) => Cow::Borrowed(svg.as_bytes()),
+            InscriptionType::Unknown(data) => Cow::Borrowed(data),
+            InscriptionType::Empty | InscriptionType::Oversized { .. } => Cow::Borrowed(&[]),
+        }
+    }
+
+    /// Short category name used in logs and summaries
+    pub fn kind(&self) -> &'static str {
+        match self {
+            InscriptionType::Text(_) => "text",
+            InscriptionType::Image { .. } => "image",
+            InscriptionType::Json(_) => "json",
+            InscriptionType::Svg(_) => "svg",
+            InscriptionType::Unknown(_) => "unknown",
+            InscriptionType::Empty => "empty",
+            InscriptionType::Oversized { .. } => "oversized",
+        }
+    }
+}
+
+/// Protocol identifier pushed immediately after OP_FALSE OP_IF
+const PROTOCOL_ID: &[u8] = b"ord";
+
+/// Envelope tag holding the MIME content type
+pub const TAG_CONTENT_TYPE: u64 = 1;
+
+/// Envelope tag holding the little-endian sat offset the inscription binds to
+pub const TAG_POINTER: u64 = 2;
+
+/// Envelope tag holding the id of a parent inscription; repeated for each parent
+pub const TAG_PARENT: u64 = 3;
+
+/// Envelope tag holding CBOR metadata, possibly split across several pushes
+pub const TAG_METADATA: u64 = 5;
+
+/// Envelope tag naming the body's compression, e.g. "br" or "gzip"
+pub const TAG_CONTENT_ENCODING: u64 = 9;
+
+/// Envelope tag holding the id of an inscription whose content this one renders
+pub const TAG_DELEGATE: u64 = 11;
+
+/// Tags this parser understands; unknown even tags invalidate an envelope
+const KNOWN_TAGS: &[u64] = &[
+    TAG_CONTENT_TYPE,
+    TAG_POINTER,
+    TAG_PARENT,
+    TAG_METADATA,
+    TAG_CONTENT_ENCODING,
+    TAG_DELEGATE,
+];
+
+/// Metadata attached to an inscription under tag 5
+///
+/// Metadata that isn't valid CBOR is kept as raw bytes rather than
+/// discarding the whole inscription.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Metadata {
+    /// Successfully decoded CBOR value
+    Cbor(serde_cbor::Value),
+
+    /// Bytes that failed to decode as CBOR
+    Raw(Vec<u8>),
+}
+
+impl Metadata {
+    /// Decodes concatenated tag-5 bytes, falling back to `Raw`
+    fn decode(bytes: Vec<u8>) -> Self {
+        match serde_cbor::from_slice(&bytes) {
+            Ok(value) => Metadata::Cbor(value),
+            Err(e) => {
+                debug!("Metadata is not valid CBOR: {}", e);
+                Metadata::Raw(bytes)
+            }
+        }
+    }
+}
+
+/// Reason ord assigns an inscription a negative (cursed) number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Curse {
+    /// The envelope used an OP_1NEGATE or OP_1..OP_16 opcode where a data push was expected
+    Pushnum,
+}
+
+/// Basic shape of the reveal transaction, kept when the parser records it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxMetadata {
+    pub version: i32,
+    /// Consensus-encoded lock time
+    pub lock_time: u32,
+    pub input_count: usize,
+    pub output_count: usize,
+    /// Weight in weight units
+    pub weight: u64,
+}
+
+impl TxMetadata {
+    pub fn of(tx: &Transaction) -> Self {
+        Self {
+            version: tx.version,
+            lock_time: tx.lock_time.to_consensus_u32(),
+            input_count: tx.input.len(),
+            output_count: tx.output.len(),
+            weight: tx.weight().to_wu(),
+        }
+    }
 }
 
 /// Represents a complete inscription found in a transaction
@@ -59,11 +210,175 @@ pub enum InscriptionType {
 pub struct Inscription {
     /// Transaction ID where the inscription was found
     pub txid: bitcoin::Txid,
+
+    /// Position among the inscriptions found in the transaction
+    pub index: u32,
+
+    /// Height of the block the transaction was found in
+    pub block_height: u64,
+
+    /// Timestamp from that block's header
+    pub block_time: u32,
     
     /// Parsed inscription content
     pub content: InscriptionType,
+
+    /// Declared content type (tag 1), if present
+    pub content_type: Option<String>,
+
+    /// Every tag/value field of the envelope in script order, body excluded
+    pub tags: Vec<(u64, Vec<u8>)>,
+
+    /// Sat offset within the outputs the inscription is assigned to (tag 2)
+    pub pointer: Option<u64>,
+
+    /// Ids (`<txid>i<n>`) of the parent inscriptions, in envelope order (tag 3)
+    pub parents: Vec<String>,
+
+    /// Decoded metadata (tag 5)
+    pub metadata: Option<Metadata>,
+
+    /// Encoding the body was stored in before decompression
+    pub content_encoding: Option<String>,
+
+    /// Whether `content_encoding` was guessed from magic bytes rather than declared
+    pub encoding_detected: bool,
+
+    /// Body exactly as inscribed, kept when it had to be decompressed
+    pub compressed_body: Option<Vec<u8>>,
+
+    /// Inscription id (`<txid>i<n>`) whose content this one renders (tag 11)
+    pub delegate: Option<String>,
+
+    /// Whether the transaction held more envelopes than the parser's
+    /// per-transaction limit, so later ones were not parsed
+    pub tx_truncated: bool,
+
+    /// Address of the reveal output the inscribed sat lands on, when the
+    /// parser records it and the output script has an address form
+    pub genesis_address: Option<String>,
+
+    /// Version, lock time, input/output counts and weight of the reveal
+    /// transaction, when the parser records them
+    pub tx_metadata: Option<TxMetadata>,
+
+    /// Why ord considers the inscription cursed, if it does
+    pub curse: Option<Curse>,
+
+    /// Ids of the inscriptions the body loads through `/content/<id>`,
+    /// for recursive HTML, SVG, JSON and text inscriptions
+    pub references: Vec<String>,
+
+    /// `content_id` of the body as inscribed, recorded by the parser for
+    /// JSON, whose parsed value doesn't keep the original bytes
+    pub raw_content_id: Option<String>,
+}
+
+impl Inscription {
+    /// Creates an inscription with no envelope fields
+    pub fn new(txid: bitcoin::Txid, content: InscriptionType) -> Self {
+        Self {
+            txid,
+            index: 0,
+            block_height: 0,
+            block_time: 0,
+            content,
+            content_type: None,
+            tags: Vec::new(),
+            pointer: None,
+            parents: Vec::new(),
+            metadata: None,
+            content_encoding: None,
+            encoding_detected: false,
+            compressed_body: None,
+            delegate: None,
+            tx_truncated: false,
+            genesis_address: None,
+            tx_metadata: None,
+            curse: None,
+            references: Vec::new(),
+            raw_content_id: None,
+        }
+    }
+
+    /// Inscription id in ord's `<txid>i<index>` form
+    ///
+    /// Identifies where the inscription was made; see `content_id` for an
+    /// identifier of what was inscribed.
+    pub fn inscription_id(&self) -> String {
+        format!("{}i{}", self.txid, self.index)
+    }
+
+    /// Effective MIME type: the image's own type, else the declared one
+    pub fn mime_type(&self) -> &str {
+        match &self.content {
+            InscriptionType::Image { mime_type, .. } => mime_type,
+            InscriptionType::Svg(_) => "image/svg+xml",
+            InscriptionType::Json(_) if self.content_type.is_none() => "application/json",
+            InscriptionType::Text(_) if self.content_type.is_none() => "text/plain;charset=utf-8",
+            _ => self.content_type.as_deref().unwrap_or("application/octet-stream"),
+        }
+    }
+
+    /// Content identifier: SHA-256 over the MIME type and body as inscribed
+    ///
+    /// Unlike the inscription id (`<txid>i<n>`), this depends only on what
+    /// was inscribed, so identical content on different transactions or
+    /// chains shares the same value. JSON built without the parser has no
+    /// original bytes and is hashed as re-serialized.
+    ///
+    /// Returns:
+    /// - Lowercase hex digest
+    pub fn content_id(&self) -> String {
+        match &self.raw_content_id {
+            Some(id) => id.clone(),
+            None => content_digest(self.mime_type(), &self.content.bytes()),
+        }
+    }
+
+    /// blake3 hash of the decoded body alone
+    ///
+    /// Image files are named by it, and the content hash index groups
+    /// copies of a body under it whatever their declared type.
+    pub fn content_hash(&self) -> blake3::Hash {
+        blake3::hash(&self.content.bytes())
+    }
 }
 
+/// SHA-256 over a MIME type and a body, lowercase hex
+fn content_digest(mime_type: &str, body: &[u8]) -> String {
+    let mut engine = sha256::Hash::engine();
+    engine.input(mime_type.as_bytes());
+    // Separator so the type can't run into the body
+    engine.input(&[0]);
+    engine.input(body);
+    sha256::Hash::from_engine(engine).to_string()
+}
+
+// Field names accepted by the custom deserializer
+const FIELDS: &[&str] = &[
+    "txid",
+    "index",
+    "block_height",
+    "block_time",
+    "content",
+    "content_type",
+    "tags",
+    "pointer",
+    "parents",
+    "metadata",
+    "content_encoding",
+    "encoding_detected",
+    "compressed_body",
+    "delegate",
+    "tx_truncated",
+    "genesis_address",
+    "tx_metadata",
+    "curse",
+    "references",
+    "raw_content_id",
+];
+
 // Custom serialization implementation to handle Bitcoin types
 impl Serialize for Inscription {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -71,11 +386,29 @@ impl Serialize for Inscription {
         S: serde::Serializer,
     {
         use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("Inscription", 2)?;
+        let mut state = serializer.serialize_struct("Inscription", FIELDS.len())?;
         
         // Convert Txid to string for compatibility
         state.serialize_field("txid", &self.txid.to_string())?;
+        state.serialize_field("index", &self.index)?;
+        state.serialize_field("block_height", &self.block_height)?;
+        state.serialize_field("block_time", &self.block_time)?;
         state.serialize_field("content", &self.content)?;
+        state.serialize_field("content_type", &self.content_type)?;
+        state.serialize_field("tags", &self.tags)?;
+        state.serialize_field("pointer", &self.pointer)?;
+        state.serialize_field("parents", &self.parents)?;
+        state.serialize_field("metadata", &self.metadata)?;
+        state.serialize_field("content_encoding", &self.content_encoding)?;
+        state.serialize_field("encoding_detected", &self.encoding_detected)?;
+        state.serialize_field("compressed_body", &self.compressed_body)?;
+        state.serialize_field("delegate", &self.delegate)?;
+        state.serialize_field("tx_truncated", &self.tx_truncated)?;
+        state.serialize_field("genesis_address", &self.genesis_address)?;
+        state.serialize_field("tx_metadata", &self.tx_metadata)?;
+        state.serialize_field("curse", &self.curse)?;
+        state.serialize_field("references", &self.references)?;
+        state.serialize_field("raw_content_id", &self.raw_content_id)?;
         state.end()
     }
 }
@@ -103,7 +436,25 @@ impl<'de> Deserialize<'de> for Inscription {
                 V: MapAccess<'de>,
             {
                 let mut txid = None;
+                let mut index = None;
+                let mut block_height = None;
+                let mut block_time = None;
                 let mut content = None;
+                let mut content_type = None;
+                let mut tags = None;
+                let mut pointer = None;
+                let mut parents = None;
+                let mut metadata = None;
+                let mut content_encoding = None;
+                let mut encoding_detected = None;
+                let mut compressed_body = None;
+                let mut delegate = None;
+                let mut tx_truncated = None;
+                let mut genesis_address = None;
+                let mut tx_metadata = None;
+                let mut curse = None;
+                let mut references = None;
+                let mut raw_content_id = None;
 
                 // Parse fields from map
                 while let Some(key) = map.next_key::<String>()? {
@@ -113,20 +464,96 @@ impl<'de> Deserialize<'de> for Inscription {
                             txid = Some(bitcoin::Txid::from_str(&txid_str)
                                 .map_err(de::Error::custom)?);
                         }
+                        "index" => {
+                            index = Some(map.next_value()?);
+                        }
+                        "block_height" => {
+                            block_height = Some(map.next_value()?);
+                        }
+                        "block_time" => {
+                            block_time = Some(map.next_value()?);
+                        }
                         "content" => {
                             content = Some(map.next_value()?);
                         }
+                        "content_type" => {
+                            content_type = map.next_value()?;
+                        }
+                        "tags" => {
+                            tags = Some(map.next_value()?);
+                        }
+                        "pointer" => {
+                            pointer = map.next_value()?;
+                        }
+                        "parents" => {
+                            parents = Some(map.next_value()?);
+                        }
+                        "metadata" => {
+                            metadata = map.next_value()?;
+                        }
+                        "content_encoding" => {
+                            content_encoding = map.next_value()?;
+                        }
+                        "encoding_detected" => {
+                            encoding_detected = Some(map.next_value()?);
+                        }
+                        "compressed_body" => {
+                            compressed_body = map.next_value()?;
+                        }
+                        "delegate" => {
+                            delegate = map.next_value()?;
+                        }
+                        "tx_truncated" => {
+                            tx_truncated = Some(map.next_value()?);
+                        }
+                        "genesis_address" => {
+                            genesis_address = map.next_value()?;
+                        }
+                        "tx_metadata" => {
+                            tx_metadata = map.next_value()?;
+                        }
+                        "curse" => {
+                            curse = map.next_value()?;
+                        }
+                        "references" => {
+                            references = Some(map.next_value()?);
+                        }
+                        "raw_content_id" => {
+                            raw_content_id = map.next_value()?;
+                        }
                         _ => {
-                            return Err(de::Error::unknown_field(&key, &["txid", "content"]));
+                            return Err(de::Error::unknown_field(&key, FIELDS));
                         }
                     }
                 }
 
-                // Ensure all required fields are present
+                // Ensure all required fields are present; envelope fields
+                // are optional so records written before they existed load
                 let txid = txid.ok_or_else(|| de::Error::missing_field("txid"))?;
                 let content = content.ok_or_else(|| de::Error::missing_field("content"))?;
 
-                Ok(Inscription { txid, content })
+                Ok(Inscription {
+                    txid,
+                    index: index.unwrap_or_default(),
+                    block_height: block_height.unwrap_or_default(),
+                    block_time: block_time.unwrap_or_default(),
+                    content,
+                    content_type,
+                    tags: tags.unwrap_or_default(),
+                    pointer,
+                    parents: parents.unwrap_or_default(),
+                    metadata,
+                    content_encoding,
+                    encoding_detected: encoding_detected.unwrap_or_default(),
+                    compressed_body,
+                    delegate,
+                    tx_truncated: tx_truncated.unwrap_or_default(),
+                    genesis_address,
+                    tx_metadata,
+                    curse,
+                    references: references.unwrap_or_default(),
+                    raw_content_id,
+                })
             }
         }
 
@@ -134,201 +561,760 @@ impl<'de> Deserialize<'de> for Inscription {
     }
 }
 
+/// Raw fields of a single envelope, before classification
+#[derive(Debug, Default)]
+struct Envelope {
+    /// Tag/value pairs in script order
+    tags: Vec<(u64, Vec<u8>)>,
+
+    /// Concatenated body pushes, if the body tag was present
+    body: Option<Vec<u8>>,
+
+    /// Total size of the body pushes when they exceeded the size limit;
+    /// the body is then left empty
+    oversized: Option<usize>,
+
+    /// Whether a pushnum opcode stood in for a data push
+    pushnum: bool,
+
+    /// Whether the declared content type is outside the allowlist; the
+    /// body is then never read
+    filtered: bool,
+}
+
+impl Envelope {
+    /// Returns the first value recorded for a tag
+    fn field(&self, tag: u64) -> Option<&[u8]> {
+        self.fields(tag).next()
+    }
+
+    /// Returns every value recorded for a tag, in script order
+    fn fields(&self, tag: u64) -> impl Iterator<Item = &[u8]> {
+        self.tags
+            .iter()
+            .filter(move |(t, _)| *t == tag)
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+/// Bumped whenever a change alters what the parser produces for a block,
+/// so cached parse results from older versions are discarded
+pub const PARSER_VERSION: u32 = 12;
+
+/// Shortest coinbase push reported as text; shorter ones are mostly extranonce bytes
+const MIN_COINBASE_TEXT_LEN: usize = 4;
+
+/// Longest BIP34 height push: a CScriptNum of up to 5 bytes
+const MAX_HEIGHT_PUSH_LEN: usize = 5;
+
+/// `OP_RETURN OP_PUSHBYTES_36 aa21a9ed`, the start of a BIP141 witness commitment output
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Default cap on envelopes parsed from a single transaction
+pub const DEFAULT_MAX_INSCRIPTIONS_PER_TX: usize = 10_000;
+
+/// Default cap on the body size of a single inscription (4 MiB)
+pub const DEFAULT_MAX_INSCRIPTION_SIZE: usize = 4 * 1024 * 1024;
+
 /// Core inscription detection and parsing logic
-pub struct InscriptionParser;
+#[derive(Debug)]
+pub struct InscriptionParser {
+    /// Attempt best-effort recovery of malformed or mislabeled envelopes
+    lenient: bool,
+
+    /// Envelopes parsed per transaction before the rest are skipped
+    max_inscriptions_per_tx: usize,
+
+    /// Body bytes kept per inscription; larger bodies become `Oversized`
+    max_inscription_size: usize,
+
+    /// Resolve `genesis_address` for each inscription
+    genesis_address: bool,
+
+    /// Network used to encode addresses
+    network: Network,
+
+    /// Attach `tx_metadata` to each inscription
+    tx_metadata: bool,
+
+    /// Declared content types whose bodies are read; empty allows all
+    content_types: Vec<String>,
+
+    /// Remove scripts, event handlers, `javascript:` URLs and `<foreignObject>` from SVG bodies
+    sanitize_svg: bool,
+
+    /// Maps content types to inscription variants; `DefaultClassifier` if unset
+    classifier: Option<Box<dyn Classifier>>,
+
+    /// Only determine each inscription's type, leaving bodies undecoded
+    count_only: bool,
+
+    /// Receives the time spent classifying each body, by content type
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Default for InscriptionParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl InscriptionParser {
-    /// Creates a new inscription parser
+    /// Creates a new inscription parser in strict mode
     pub fn new() -> Self {
-        Self
+        Self {
+            lenient: false,
+            max_inscriptions_per_tx: DEFAULT_MAX_INSCRIPTIONS_PER_TX,
+            max_inscription_size: DEFAULT_MAX_INSCRIPTION_SIZE,
+            genesis_address: false,
+            network: Network::Bitcoin,
+            tx_metadata: false,
+            content_types: Vec::new(),
+            sanitize_svg: false,
+            classifier: None,
+            count_only: false,
+            metrics: None,
+        }
+    }
+
+    /// Enables or disables lenient mode
+    ///
+    /// In lenient mode the parser tries to recover inscriptions that
+    /// don't follow the spec exactly, such as compressed bodies whose
+    /// content-encoding tag is missing or envelopes with non-push opcodes.
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Identifies the parser version and options that shape its output
+    pub fn version_tag(&self) -> String {
+        let address = if self.genesis_address { self.network.to_string() } else { "off".to_string() };
+        format!(
+            "v{}-lenient={}-max={}-size={}-address={}-txmeta={}-types={}-svgsafe={}-classifier={}-countonly={}",
+            PARSER_VERSION,
+            self.lenient,
+            self.max_inscriptions_per_tx,
+            self.max_inscription_size,
+            address,
+            self.tx_metadata,
+            self.content_types.join(","),
+            self.sanitize_svg,
+            self.classifier.as_ref().map_or("default", |classifier| classifier.name()),
+            self.count_only
+        )
+    }
+
+    /// Limits how many envelopes are parsed from one transaction
+    ///
+    /// Bounds the work spent on reveal transactions packed with
+    /// thousands of tiny envelopes; inscriptions from a capped
+    /// transaction have `tx_truncated` set.
+    pub fn with_max_inscriptions_per_tx(mut self, max: usize) -> Self {
+        self.max_inscriptions_per_tx = max;
+        self
+    }
+
+    /// Limits the body size of a single inscription
+    ///
+    /// Bodies over the limit are not accumulated or decoded; the
+    /// inscription is reported as `Oversized` with the observed size.
+    pub fn with_max_inscription_size(mut self, max: usize) -> Self {
+        self.max_inscription_size = max;
+        self
+    }
+
+    /// Records the address of the output each inscription is revealed to
+    pub fn with_genesis_address(mut self, enabled: bool) -> Self {
+        self.genesis_address = enabled;
+        self
+    }
+
+    /// Sets the network addresses are encoded for (mainnet by default)
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Records the reveal transaction's version, lock time, input/output
+    /// counts and weight with each inscription
+    pub fn with_tx_metadata(mut self, enabled: bool) -> Self {
+        self.tx_metadata = enabled;
+        self
+    }
+
+    /// Only reads the bodies of envelopes declaring one of `content_types`
+    ///
+    /// Entries are MIME essences or `type/*` wildcards. The declared type
+    /// is checked as soon as the envelope's tags are read, so the body of
+    /// a disallowed envelope is skipped without being reassembled and no
+    /// inscription is produced for it. An empty list allows every type.
+    pub fn with_content_types(mut self, content_types: Vec<String>) -> Self {
+        self.content_types = content_types.iter().map(|mime| mime.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// Strips scripts, event handlers, `javascript:` URLs and `<foreignObject>` from SVG bodies
+    ///
+    /// Only applies to the default classifier.
+    pub fn with_sanitize_svg(mut self, enabled: bool) -> Self {
+        self.sanitize_svg = enabled;
+        self
+    }
+
+    /// Only determines each inscription's type, from the declared or sniffed MIME type
+    ///
+    /// Bodies are not decompressed, classified or scanned for references,
+    /// and inscriptions carry no content: `Unknown` with an empty body, or
+    /// `Empty`/`Oversized`. For tallying types, never for storing.
+    pub fn with_count_only(mut self, enabled: bool) -> Self {
+        self.count_only = enabled;
+        self
+    }
+
+    /// Records how long each body takes to classify, per content type
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Replaces the mapping from content types to inscription variants
+    ///
+    /// The classifier's name goes into `version_tag`, so switching classifiers
+    /// invalidates cached parse results.
+    pub fn with_classifier(mut self, classifier: Box<dyn Classifier>) -> Self {
+        self.classifier = Some(classifier);
+        self
+    }
+
+    /// Parses a transaction, returning only its first inscription
+    ///
+    /// A thin wrapper over `parse_transaction_all` for callers that expect
+    /// at most one inscription per transaction.
+    #[allow(dead_code)]
+    pub fn parse_transaction(&self, tx: &Transaction) -> Option<Inscription> {
+        self.parse_transaction_all(tx).into_iter().next()
     }
 
-    /// Parses a transaction looking for inscriptions
+    /// Parses a transaction collecting every inscription it carries
     ///
-    /// Examines each transaction output for inscription patterns
-    /// and returns the first valid inscription found.
+    /// Batch reveals pack many envelopes into one transaction, spread
+    /// across several input witnesses and outputs. This walks all of
+    /// them and returns each envelope found, in input-then-output order,
+    /// stopping once `max_inscriptions_per_tx` envelopes have been read.
     ///
     /// Parameters:
     /// - tx: The Bitcoin transaction to examine
     ///
     /// Returns:
-    /// - Option<Inscription>: The first inscription found, if any
-    pub fn parse_transaction(&self, tx: &Transaction) -> Option<Inscription> {
-        debug!("Parsing transaction: {}", tx.txid());
-        
-        // First check inputs for coinbase inscriptions
-        for (i, input) in tx.input.iter().enumerate() {
-            debug!("Checking input {} of transaction {}", i, tx.txid());
-            
-            // Check if this is a coinbase input
-            if input.previous_output.is_null() {
-                debug!("Found coinbase input in tx: {}", tx.txid());
-                debug!("Coinbase script: {:?}", input.script_sig);
-                
-                // Log raw script bytes for debugging
-                if let Ok(bytes) = String::from_utf8(input.script_sig.as_bytes().to_vec()) {
-                    debug!("Raw script bytes as UTF-8: {}", bytes);
+    /// - Vec<Inscription>: Every inscription found, possibly empty
+    pub fn parse_transaction_all(&self, tx: &Transaction) -> Vec<Inscription> {
+        let txid = tx.txid();
+        let mut inscriptions = Vec::new();
+        let mut envelopes = 0;
+        let mut truncated = false;
+        debug!("Parsing transaction: {}", txid);
+
+        'scan: {
+            // First check inputs for coinbase text and witness envelopes
+            for (i, input) in tx.input.iter().enumerate() {
+                debug!("Checking input {} of transaction {}", i, txid);
+
+                // Check if this is a coinbase input
+                if input.previous_output.is_null() {
+                    debug!("Found coinbase input in tx: {}", txid);
+                    debug!("Coinbase script: {:?}", input.script_sig);
+
+                    // Log raw script bytes for debugging
+                    if let Ok(bytes) = String::from_utf8(input.script_sig.as_bytes().to_vec()) {
+                        debug!("Raw script bytes as UTF-8: {}", bytes);
+                    }
+
+                    if let Some(text) = self.extract_text_from_script(&input.script_sig) {
+                        debug!("Found text in coinbase: {}", text);
+                        inscriptions.push(Inscription::new(txid, InscriptionType::Text(text)));
+                    } else {
+                        debug!("No text found in coinbase script");
+                    }
+                    continue;
                 }
-                
-                if let Some(text) = self.extract_text_from_script(&input.script_sig) {
-                    debug!("Found text in coinbase: {}", text);
-                    return Some(Inscription {
-                        txid: tx.txid(),
-                        content: InscriptionType::Text(text),
-                    });
-                } else {
-                    debug!("No text found in coinbase script");
+
+                for script in self.witness_scripts(&input.witness) {
+                    for envelope in self.parse_script(script, self.envelope_budget(envelopes)) {
+                        if envelopes as usize >= self.max_inscriptions_per_tx {
+                            truncated = true;
+                            break 'scan;
+                        }
+                        debug!("Found envelope in transaction {} input {}", txid, i);
+                        inscriptions.extend(self.numbered_inscription(txid, envelope, &mut envelopes));
+                    }
+                }
+            }
+
+            // Then check outputs for ordinal inscriptions
+            for (i, output) in tx.output.iter().enumerate() {
+                if is_witness_commitment(&output.script_pubkey) {
+                    continue;
+                }
+                debug!("Checking output {} of transaction {}", i, txid);
+                debug!("Script: {:?}", output.script_pubkey);
+                for envelope in self.parse_script(&output.script_pubkey, self.envelope_budget(envelopes)) {
+                    if envelopes as usize >= self.max_inscriptions_per_tx {
+                        truncated = true;
+                        break 'scan;
+                    }
+                    debug!("Found envelope in transaction {} output {}", txid, i);
+                    inscriptions.extend(self.numbered_inscription(txid, envelope, &mut envelopes));
                 }
             }
         }
 
-        // Then check outputs for ordinal inscriptions
-        for (i, output) in tx.output.iter().enumerate() {
-            debug!("Checking output {} of transaction {}", i, tx.txid());
-            debug!("Script: {:?}", output.script_pubkey);
-            if let Some(content) = self.parse_script(&output.script_pubkey) {
-                debug!("Found inscription in transaction {} output {}", tx.txid(), i);
-                return Some(Inscription {
-                    txid: tx.txid(),
-                    content,
-                });
+        if truncated {
+            warn!(
+                "Transaction {} has more than {} inscriptions, skipping the rest",
+                txid, self.max_inscriptions_per_tx
+            );
+        }
+        for inscription in inscriptions.iter_mut() {
+            inscription.tx_truncated = truncated;
+            if self.genesis_address {
+                inscription.genesis_address = self.genesis_address(tx, inscription.pointer);
+            }
+            if self.tx_metadata {
+                inscription.tx_metadata = Some(TxMetadata::of(tx));
             }
         }
-        None
+        inscriptions
     }
 
-    /// Extracts meaningful text from a script
-    fn extract_text_from_script(&self, script: &Script) -> Option<String> {
-        let mut found_text = None;
-        let mut push_count = 0;
-        
-        for instruction in script.instructions() {
-            if let Ok(Instruction::PushBytes(data)) = instruction {
-                push_count += 1;
-                // The text is in the third push operation (OP_PUSHBYTES_69)
-                if push_count == 3 {
-                    debug!("Found third push data: {:?}", data.as_bytes());
-                    // Convert hex to ASCII
-                    let hex_str = hex::encode(data.as_bytes());
-                    debug!("Hex string: {}", hex_str);
-                    if let Ok(decoded) = hex::decode(&hex_str) {
-                        if let Ok(text) = String::from_utf8(decoded) {
-                            debug!("Decoded text: {}", text);
-                            found_text = Some(text);
-                        }
+    /// Envelopes still worth reading from the next script, after `envelopes` so far
+    ///
+    /// One past the cap, so a transaction over it can be flagged as truncated.
+    fn envelope_budget(&self, envelopes: u32) -> usize {
+        self.max_inscriptions_per_tx.saturating_sub(envelopes as usize).saturating_add(1)
+    }
+
+    /// Witness elements searched for envelopes
+    ///
+    /// Strict mode only looks at the tapscript of a taproot script-path
+    /// spend. Lenient mode also tries every other element that disassembles
+    /// into valid instructions, such as P2WSH witness scripts, to catch
+    /// early and experimental inscriptions revealed outside taproot.
+    ///
+    /// Parameters:
+    /// - witness: The input witness to examine
+    ///
+    /// Returns:
+    /// - Vec<&Script>: Candidate scripts, in witness order
+    fn witness_scripts<'w>(&self, witness: &'w Witness) -> Vec<&'w Script> {
+        if !self.lenient {
+            return tapscript(witness).into_iter().collect();
+        }
+        witness
+            .iter()
+            .map(Script::from_bytes)
+            .filter(|script| !script.is_empty() && script.instructions().all(|instruction| instruction.is_ok()))
+            .collect()
+    }
+
+    /// Builds the inscription for the next envelope in a transaction
+    ///
+    /// Its index is the envelope's position, counted in `envelopes`, so
+    /// envelopes that are filtered out or fail to classify don't shift
+    /// the ids of the ones after them.
+    fn numbered_inscription(&self, txid: bitcoin::Txid, envelope: Envelope, envelopes: &mut u32) -> Option<Inscription> {
+        let index = *envelopes;
+        *envelopes += 1;
+        if envelope.filtered {
+            debug!("Skipping envelope {} in transaction {}, content type not allowed", index, txid);
+            return None;
+        }
+        let mut inscription = self.build_inscription(txid, envelope)?;
+        inscription.index = index;
+        Some(inscription)
+    }
+
+    /// Whether an envelope declaring `content_type` should have its body read
+    fn allows_content_type(&self, content_type: Option<&[u8]>) -> bool {
+        if self.content_types.is_empty() {
+            return true;
+        }
+        let declared = String::from_utf8_lossy(content_type.unwrap_or_default());
+        self.content_types.iter().any(|pattern| mime_matches(pattern, &declared))
+    }
+
+    /// Address of the output holding the inscribed sat
+    ///
+    /// The inscription sits on the first sat of the reveal's outputs, or
+    /// `pointer` sats in when the pointer lands inside them. Outputs whose
+    /// script has no address form (OP_RETURN, bare multisig, ...) yield `None`.
+    ///
+    /// Parameters:
+    /// - tx: The reveal transaction
+    /// - pointer: Sat offset from the envelope's pointer tag, if any
+    ///
+    /// Returns:
+    /// - Option<String>: The encoded address, if any
+    fn genesis_address(&self, tx: &Transaction, pointer: Option<u64>) -> Option<String> {
+        let total: u64 = tx.output.iter().map(|output| output.value).sum();
+        let offset = pointer.filter(|&pointer| pointer < total).unwrap_or(0);
+
+        let mut start = 0;
+        let output = tx.output.iter().find(|output| {
+            let end = start + output.value;
+            let found = offset < end || (total == 0 && start == 0);
+            start = end;
+            found
+        })?;
+        Address::from_script(&output.script_pubkey, self.network)
+            .ok()
+            .map(|address| address.to_string())
+    }
+
+    /// Turns a parsed envelope into an inscription
+    ///
+    /// Decodes the known tags and classifies the body according to
+    /// the declared content type, then collects recursive references.
+    ///
+    /// Parameters:
+    /// - txid: Transaction the envelope was found in
+    /// - envelope: Raw envelope fields and body
+    ///
+    /// Returns:
+    /// - Option<Inscription>: The inscription, if the body classifies
+    fn build_inscription(&self, txid: bitcoin::Txid, envelope: Envelope) -> Option<Inscription> {
+        let content_type_bytes = envelope.field(TAG_CONTENT_TYPE).unwrap_or_default().to_vec();
+        // A missing tag leaves the type unset rather than empty, so `mime_type` can fall back
+        let content_type = envelope
+            .field(TAG_CONTENT_TYPE)
+            .and_then(|value| String::from_utf8(value.to_vec()).ok());
+        let pointer = envelope.field(TAG_POINTER).and_then(decode_le);
+        let delegate = envelope.field(TAG_DELEGATE).and_then(decode_inscription_id);
+        let parents = envelope.fields(TAG_PARENT).filter_map(decode_inscription_id).collect();
+        let metadata = envelope.field(TAG_METADATA).map(|_| {
+            Metadata::decode(envelope.fields(TAG_METADATA).flatten().copied().collect())
+        });
+        let declared_encoding = envelope
+            .field(TAG_CONTENT_ENCODING)
+            .and_then(|value| String::from_utf8(value.to_vec()).ok());
+        let mut oversized = envelope.oversized;
+        let mut body = envelope.body.unwrap_or_default();
+
+        // Only the type is wanted, so the body is neither decoded nor classified
+        if self.count_only {
+            let content = match oversized {
+                Some(size) => InscriptionType::Oversized { size },
+                None if body.is_empty() => InscriptionType::Empty,
+                // Mirrors `classify_inscription`, which drops non-UTF-8 types
+                None if std::str::from_utf8(&content_type_bytes).is_err() => return None,
+                None => InscriptionType::Unknown(Vec::new()),
+            };
+            let sniffed = match declared_encoding {
+                Some(_) => None,
+                None => sniff_mime(&body),
+            };
+            let mut inscription = Inscription::new(txid, content);
+            inscription.content_type = sniffed.map(str::to_string).or(content_type);
+            return Some(inscription);
+        }
+        let mut content_encoding = None;
+        let mut encoding_detected = false;
+        let mut compressed_body = None;
+
+        // Decoding stops at the limit, so an inflated body's full size isn't known
+        let over_limit = self.max_inscription_size.saturating_add(1);
+        if let Some(encoding) = declared_encoding {
+            // Undecodable bodies are kept as inscribed rather than dropped
+            match encoding::decompress(&encoding, &body, self.max_inscription_size) {
+                Ok(Decoded::Body(decoded)) => compressed_body = Some(std::mem::replace(&mut body, decoded)),
+                Ok(Decoded::Oversized) => oversized = oversized.or(Some(over_limit)),
+                Err(e) => debug!("Failed to decode {} body in transaction {}: {}", encoding, txid, e),
+            }
+            content_encoding = Some(encoding);
+        } else if self.lenient && declares_text_or_image(content_type.as_deref()) {
+            // Compressed bodies sometimes omit the encoding tag; recover them
+            // when the declared type can't be satisfied by the raw bytes
+            if let Some(encoding) = encoding::detect_encoding(&body) {
+                match encoding::decompress(encoding, &body, self.max_inscription_size) {
+                    Ok(Decoded::Body(decoded)) => {
+                        debug!("Detected undeclared {} body in transaction {}", encoding, txid);
+                        compressed_body = Some(std::mem::replace(&mut body, decoded));
+                        content_encoding = Some(encoding.to_string());
+                        encoding_detected = true;
+                    }
+                    Ok(Decoded::Oversized) => {
+                        oversized = oversized.or(Some(over_limit));
+                        content_encoding = Some(encoding.to_string());
+                        encoding_detected = true;
                     }
+                    Err(e) => debug!("Body looks like {} but failed to decompress: {}", encoding, e),
                 }
             }
         }
-        
-        found_text
+
+        // JSON is kept as a parsed value, so its id is taken from the bytes here
+        let raw_content_id = content_type
+            .as_deref()
+            .filter(|mime| matches!(mime_essence(mime).as_str(), "application/json" | "text/plain"))
+            .map(|mime| content_digest(mime, &body));
+
+        // Bodyless envelopes are still inscriptions; they just carry no content
+        let mut classify_time = None;
+        let content = if let Some(size) = oversized {
+            warn!(
+                "Inscription in transaction {} has a {} byte body, over the {} byte limit",
+                txid, size, self.max_inscription_size
+            );
+            InscriptionType::Oversized { size }
+        } else if body.is_empty() {
+            InscriptionType::Empty
+        } else {
+            let started = Instant::now();
+            let content = self.classify_inscription(content_type_bytes, body)?;
+            classify_time = Some(started.elapsed());
+            content
+        };
+        let raw_content_id = raw_content_id.filter(|_| matches!(content, InscriptionType::Json(_)));
+
+        let mut inscription = Inscription {
+            txid,
+            index: 0,
+            block_height: 0,
+            block_time: 0,
+            content,
+            content_type,
+            tags: envelope.tags,
+            pointer,
+            parents,
+            metadata,
+            content_encoding,
+            encoding_detected,
+            compressed_body,
+            delegate,
+            tx_truncated: false,
+            genesis_address: None,
+            tx_metadata: None,
+            curse: envelope.pushnum.then_some(Curse::Pushnum),
+            references: Vec::new(),
+            raw_content_id,
+        };
+        if recursion::may_reference(inscription.mime_type()) {
+            inscription.references = recursion::find_references(&inscription.content.bytes());
+        }
+        if let (Some(metrics), Some(elapsed)) = (&self.metrics, classify_time) {
+            metrics.add_parse_time(inscription.mime_type(), elapsed);
+        }
+        Some(inscription)
+    }
+
+    /// Extracts the longest printable text push from a coinbase script
+    ///
+    /// Coinbase scripts start with the height and extranonce and put pool tags
+    /// or messages wherever the miner likes, so every push is considered
+    /// except a leading one short enough to be the BIP34 height.
+    fn extract_text_from_script(&self, script: &Script) -> Option<String> {
+        let mut instructions = script.instructions().peekable();
+        if matches!(instructions.peek(), Some(Ok(Instruction::PushBytes(data))) if data.len() <= MAX_HEIGHT_PUSH_LEN) {
+            instructions.next();
+        }
+        instructions
+            .filter_map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(data)) => std::str::from_utf8(data.as_bytes()).ok(),
+                _ => None,
+            })
+            .map(str::trim)
+            .filter(|text| text.chars().count() >= MIN_COINBASE_TEXT_LEN && text.chars().all(is_printable))
+            .max_by_key(|text| text.len())
+            .map(str::to_string)
     }
 
     /// Parses a Bitcoin script looking for inscription patterns
     ///
     /// Implements the core inscription detection logic:
-    /// - Looks for OP_FALSE/OP_0 OP_IF sequence
+    /// - Looks for OP_FALSE/OP_0 OP_IF sequences anywhere in the script
     /// - Handles both explicit and implicit zero representations
-    /// - Validates complete inscription structure
+    /// - Keeps scanning after each OP_ENDIF for further envelopes, until
+    ///   `limit` have been found
     ///
     /// Parameters:
     /// - script: The Bitcoin script to parse
+    /// - limit: Most envelopes to return; the rest of the script is skipped
     ///
     /// Returns:
-    /// - Option<InscriptionType>: The parsed inscription content, if found
-    fn parse_script(&self, script: &Script) -> Option<InscriptionType> {
+    /// - Vec<Envelope>: The raw fields of every valid envelope found
+    fn parse_script(&self, script: &Script, limit: usize) -> Vec<Envelope> {
+        let mut found = Vec::new();
         let mut instructions = script.instructions().peekable();
-        
-        // Check for OP_FALSE/OP_0 OP_IF sequence
-        match (instructions.next()?, instructions.next()?) {
-            (Ok(first), Ok(Instruction::Op(op2))) => {
-                debug!("Found first instruction: {:?} and second: {:?}", first, op2);
-                
-                // Check if it's either OP_FALSE or OP_0 (PushBytes([]))
-                let is_false = match first {
-                    Instruction::Op(op1) => op1 == OP_FALSE || op1 == OP_0,
-                    Instruction::PushBytes(data) => data.as_bytes().is_empty(),
-                };
-
-                if is_false && op2 == all::OP_IF {
-                    debug!("Found inscription start sequence");
-                    self.parse_inscription_content(&mut instructions)
-                } else {
-                    debug!("Not an inscription sequence");
-                    None
+        let mut previous_was_false = false;
+
+        while found.len() < limit {
+            let instruction = match instructions.next() {
+                Some(Ok(instruction)) => instruction,
+                None => break,
+                Some(Err(e)) => {
+                    debug!("Invalid instruction sequence: {:?}", e);
+                    break;
                 }
+            };
+
+            if previous_was_false && matches!(instruction, Instruction::Op(op) if op == all::OP_IF) {
+                debug!("Found inscription start sequence");
+                if let Some(envelope) = self.parse_inscription_content(&mut instructions) {
+                    found.push(envelope);
+                }
+                previous_was_false = false;
+                continue;
             }
-            other => {
-                debug!("Invalid instruction sequence: {:?}", other);
-                None
-            }
+
+            // Check if it's either OP_FALSE or OP_0 (PushBytes([]))
+            previous_was_false = match instruction {
+                Instruction::Op(op) => op == OP_FALSE || op == OP_0,
+                Instruction::PushBytes(data) => data.as_bytes().is_empty(),
+            };
         }
+
+        found
     }
 
     /// Parses the content portion of an inscription
     ///
     /// Handles the data between OP_IF and OP_ENDIF:
-    /// - Extracts content type and content
-    /// - Validates separators and structure
-    /// - Handles different content encodings
+    /// - Checks the "ord" protocol identifier
+    /// - Reads (tag, value) push pairs until the body tag (empty push)
+    /// - Concatenates every push after the body tag into the body
+    /// - Skips unknown odd tags, rejects unknown even tags per spec
+    /// - Reads pushnum opcodes (OP_1NEGATE, OP_1..OP_16) as the value they
+    ///   push, marking the envelope cursed as ord does
+    /// - Rejects envelopes containing any other opcode, unless lenient
+    ///
+    /// The envelope is always consumed up to its matching OP_ENDIF, past
+    /// nested conditionals, so scanning can resume after it even when it
+    /// is rejected.
     ///
     /// Parameters:
     /// - instructions: Iterator over remaining script instructions
     ///
     /// Returns:
-    /// - Option<InscriptionType>: The parsed content if valid
-    fn parse_inscription_content<'a, I>(&self, instructions: &mut Peekable<I>) -> Option<InscriptionType>
+    /// - Option<Envelope>: The envelope fields if valid
+    fn parse_inscription_content<'a, I>(&self, instructions: &mut Peekable<I>) -> Option<Envelope>
     where
         I: Iterator<Item = Result<Instruction<'a>, bitcoin::blockdata::script::Error>>
     {
-        let mut content_type = Vec::new();
-        let mut content = Vec::new();
-        let mut reading_content_type = true;
+        let mut pushes: Vec<Cow<'a, [u8]>> = Vec::new();
+        let mut pushnum = false;
+        let mut terminated = false;
+        // Conditionals opened inside the envelope, each closed by its own OP_ENDIF
+        let mut depth = 0usize;
+        let mut stray_opcode = None;
 
         while let Some(Ok(instruction)) = instructions.next() {
             match instruction {
-                Instruction::Op(all::OP_ENDIF) => {
+                Instruction::Op(all::OP_ENDIF) if depth == 0 => {
                     debug!("Found OP_ENDIF, ending inscription");
+                    terminated = true;
                     break;
                 }
+                Instruction::Op(all::OP_ENDIF) => depth -= 1,
+                Instruction::Op(op @ (all::OP_IF | all::OP_NOTIF)) => {
+                    depth += 1;
+                    stray_opcode.get_or_insert(op);
+                }
                 Instruction::PushBytes(data) => {
                     debug!("Found PushBytes: {:?}", data.as_bytes());
-                    if reading_content_type {
-                        content_type.extend_from_slice(data.as_bytes());
-                        if let Some(Ok(instruction)) = instructions.peek() {
-                            let is_zero = match instruction {
-                                Instruction::Op(op) => *op == OP_0 || *op == OP_FALSE,
-                                Instruction::PushBytes(data) => data.as_bytes().is_empty(),
-                            };
-                            if is_zero {
-                                debug!("Found OP_0/OP_FALSE, switching to content");
-                                reading_content_type = false;
-                                instructions.next();
-                            }
-                        }
-                    } else {
-                        content.extend_from_slice(data.as_bytes());
-                    }
+                    pushes.push(Cow::Borrowed(data.as_bytes()));
                 }
-                op => {
-                    debug!("Skipping instruction: {:?}", op);
-                    continue;
+                Instruction::Op(op) if pushnum_value(op).is_some() => {
+                    debug!("Found pushnum {:?}", op);
+                    pushnum = true;
+                    pushes.extend(pushnum_value(op).map(|value| Cow::Owned(vec![value])));
+                }
+                Instruction::Op(op) => {
+                    stray_opcode.get_or_insert(op);
+                }
+            }
+        }
+
+        if !terminated {
+            debug!("Envelope is missing OP_ENDIF");
+            return None;
+        }
+        // Only data pushes may appear between OP_IF and OP_ENDIF
+        if let Some(op) = stray_opcode {
+            if !self.lenient {
+                debug!("Rejecting envelope containing {:?}", op);
+                return None;
+            }
+            debug!("Skipping {:?} inside envelope", op);
+        }
+
+        let mut pushes = pushes.iter().map(|push| push.as_ref());
+        if pushes.next() != Some(PROTOCOL_ID) {
+            debug!("Envelope does not start with the ord protocol id");
+            return None;
+        }
+
+        let mut envelope = Envelope { pushnum, ..Envelope::default() };
+        while let Some(tag) = pushes.next() {
+            // An empty push is the body tag; everything after it is content.
+            // Past the size limit only the length is counted, not the bytes
+            if tag.is_empty() {
+                if !self.allows_content_type(envelope.field(TAG_CONTENT_TYPE)) {
+                    envelope.filtered = true;
+                    break;
+                }
+                let mut body = Vec::new();
+                let mut size = 0;
+                for push in pushes.by_ref() {
+                    size += push.len();
+                    if size <= self.max_inscription_size {
+                        body.extend_from_slice(push);
+                    }
+                }
+                if size > self.max_inscription_size {
+                    envelope.oversized = Some(size);
+                    body = Vec::new();
+                }
+                envelope.body = Some(body);
+                break;
+            }
+
+            let value = match pushes.next() {
+                Some(value) => value,
+                None => {
+                    debug!("Tag {:?} has no value", tag);
+                    return None;
+                }
+            };
+
+            match decode_le(tag) {
+                Some(number) if KNOWN_TAGS.contains(&number) => {
+                    envelope.tags.push((number, value.to_vec()));
+                }
+                // Odd tags may be safely ignored by parsers that don't know them
+                _ if tag[0] % 2 == 1 => {
+                    debug!("Skipping unknown odd tag {:?}", tag);
+                    if let Some(number) = decode_le(tag) {
+                        envelope.tags.push((number, value.to_vec()));
+                    }
+                }
+                _ => {
+                    debug!("Rejecting envelope with unknown even tag {:?}", tag);
+                    return None;
                 }
             }
         }
 
-        debug!("Content type: {:?}", String::from_utf8_lossy(&content_type));
-        debug!("Content: {:?}", String::from_utf8_lossy(&content));
+        debug!("Envelope tags: {:?}", envelope.tags);
+        debug!("Content: {:?}", envelope.body.as_deref().map(String::from_utf8_lossy));
 
-        self.classify_inscription(content_type, content)
+        Some(envelope)
     }
 
     /// Classifies inscription content based on MIME type
     ///
     /// Determines the appropriate InscriptionType based on:
-    /// - MIME type parsing
-    /// - Content validation
-    /// - Encoding detection
+    /// - MIME type parsing, falling back to magic-byte sniffing
+    /// - The configured `Classifier` for the resulting type
     ///
     /// Parameters:
     /// - content_type: Raw MIME type bytes
@@ -337,53 +1323,205 @@ impl InscriptionParser {
     /// Returns:
     /// - Option<InscriptionType>: The classified content
     fn classify_inscription(&self, content_type: Vec<u8>, content: Vec<u8>) -> Option<InscriptionType> {
-        let content_type = String::from_utf8(content_type).ok()?;
-        
-        match content_type.as_str() {
-            "text/plain;charset=utf-8" => {
-                String::from_utf8(content)
-                    .ok()
-                    .map(InscriptionType::Text)
-            }
-            mime if mime.starts_with("image/") => {
-                Some(InscriptionType::Image {
-                    mime_type: content_type,
-                    data: content,
-                })
+        let declared = String::from_utf8(content_type).ok()?;
+
+        // Trust the body's signature over a missing or contradicting label
+        let content_type = match sniff_mime(&content) {
+            Some(sniffed) if mime_essence(&declared) != sniffed => {
+                debug!("Declared type {:?} doesn't match body, using sniffed {}", declared, sniffed);
+                sniffed.to_string()
             }
-            _ => Some(InscriptionType::Unknown(content))
+            // Untagged UTF-8 bodies are text, which is how `mime_type` reports them
+            None if declared.is_empty() && std::str::from_utf8(&content).is_ok() => "text/plain".to_string(),
+            _ => declared,
+        };
+        
+        match &self.classifier {
+            Some(classifier) => classifier.classify(&content_type, content),
+            None => DefaultClassifier { sanitize_svg: self.sanitize_svg }.classify(&content_type, content),
         }
     }
 }
 
+/// Strips parameters from a MIME type: "image/svg+xml; charset=utf-8" -> "image/svg+xml"
+pub(super) fn mime_essence(mime: &str) -> String {
+    mime.split(';').next().unwrap_or_default().trim().to_ascii_lowercase()
+}
+
+/// Compares MIME essences, allowing a `type/*` wildcard pattern
+pub(crate) fn mime_matches(pattern: &str, mime: &str) -> bool {
+    let essence = mime_essence(mime);
+    match pattern.strip_suffix("/*") {
+        Some(top_level) => essence.split('/').next() == Some(top_level),
+        None => essence == pattern,
+    }
+}
+
+/// Whether a declared content type is one the classifier decodes
+fn declares_text_or_image(content_type: Option<&str>) -> bool {
+    content_type.map_or(false, |mime| mime.starts_with("text/") || mime.starts_with("image/"))
+}
+
+/// Byte pushed by a pushnum opcode: 0x81 for OP_1NEGATE, 1..=16 for OP_1..OP_16
+fn pushnum_value(op: bitcoin::opcodes::All) -> Option<u8> {
+    let code = op.to_u8();
+    if op == all::OP_PUSHNUM_NEG1 {
+        Some(0x81)
+    } else if (all::OP_PUSHNUM_1.to_u8()..=all::OP_PUSHNUM_16.to_u8()).contains(&code) {
+        Some(code - all::OP_PUSHNUM_1.to_u8() + 1)
+    } else {
+        None
+    }
+}
+
+/// Decodes a little-endian integer of up to 8 bytes
+fn decode_le(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() > 8 {
+        return None;
+    }
+    Some(bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte)))
+}
+
+/// Decodes a serialized inscription id: a 32-byte txid followed by a
+/// little-endian index of up to 4 bytes (trailing zero bytes may be omitted)
+///
+/// Returns the canonical `<txid>i<index>` form.
+fn decode_inscription_id(bytes: &[u8]) -> Option<String> {
+    if bytes.len() < 32 || bytes.len() > 36 {
+        return None;
+    }
+    let txid = bitcoin::Txid::from_slice(&bytes[..32]).ok()?;
+    let index = decode_le(&bytes[32..])?;
+    Some(format!("{}i{}", txid, index))
+}
+
+/// Returns the tapscript of a taproot script-path spend, if any
+///
+/// The script is the second-to-last witness element, or third-to-last
+/// when the witness ends with an annex (an element starting with 0x50).
+/// The last element must be a control block for a tapscript leaf.
+fn tapscript(witness: &Witness) -> Option<&Script> {
+    let mut elements: Vec<&[u8]> = witness.iter().collect();
+    if elements.len() >= 2 && elements.last().and_then(|e| e.first()) == Some(&0x50) {
+        elements.pop();
+    }
+    if elements.len() < 2 || !is_control_block(elements[elements.len() - 1]) {
+        return None;
+    }
+    Some(Script::from_bytes(elements[elements.len() - 2]))
+}
+
+/// Whether `element` is shaped like a control block for a tapscript leaf:
+/// leaf version 0xc0 plus parity, a 32 byte key and whole 32 byte path steps
+fn is_control_block(element: &[u8]) -> bool {
+    element.len() >= 33 && (element.len() - 33) % 32 == 0 && element[0] & 0xfe == 0xc0
+}
+
+/// Whether an output is a segwit witness commitment, which is never an inscription
+fn is_witness_commitment(script: &Script) -> bool {
+    script.as_bytes().starts_with(&WITNESS_COMMITMENT_PREFIX) && script.len() >= 38
+}
+
+/// Whether a character can appear in human-readable coinbase text
+fn is_printable(c: char) -> bool {
+    !c.is_control() && c != char::REPLACEMENT_CHARACTER
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use bitcoin::blockdata::script::Builder;
+    use bitcoin::script::PushBytesBuf;
+    use bitcoin::ScriptBuf;
     use serde_json;
 
-    #[test]
-    fn test_coinbase_text_extraction() {
-        let parser = InscriptionParser::new();
+    fn push(builder: Builder, data: &[u8]) -> Builder {
+        builder.push_slice(PushBytesBuf::try_from(data.to_vec()).unwrap())
+    }
 
-        // Create a transaction with a coinbase input containing the genesis block text
-        let script = Builder::new()
-            .push_slice(b"The Times 03/Jan/2009 Chancellor on brink of second bailout for banks")
-            .into_script();
+    const TEXT_PLAIN: &[u8] = b"text/plain;charset=utf-8";
 
-        let tx = Transaction {
+    /// Builds an `ord` envelope from tag/value pushes and an optional body
+    fn envelope_builder(builder: Builder, fields: &[(u8, &[u8])], body: Option<&[u8]>) -> Builder {
+        let mut builder = push(builder.push_opcode(OP_FALSE).push_opcode(all::OP_IF), b"ord");
+        for (tag, value) in fields {
+            builder = push(push(builder, &[*tag]), value);
+        }
+        if let Some(body) = body {
+            builder = builder.push_opcode(OP_0);
+            for chunk in body.chunks(520) {
+                builder = push(builder, chunk);
+            }
+        }
+        builder.push_opcode(all::OP_ENDIF)
+    }
+
+    fn envelope_script(fields: &[(u8, &[u8])], body: Option<&[u8]>) -> ScriptBuf {
+        envelope_builder(Builder::new(), fields, body).into_script()
+    }
+
+    fn output_tx(script: ScriptBuf) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![bitcoin::TxOut {
+                value: 0,
+                script_pubkey: script,
+            }],
+        }
+    }
+
+    fn coinbase_tx(script_sig: ScriptBuf) -> Transaction {
+        Transaction {
             version: 1,
             lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
             input: vec![bitcoin::TxIn {
                 previous_output: bitcoin::OutPoint::null(),
-                script_sig: script,
+                script_sig,
                 sequence: bitcoin::Sequence::MAX,
                 witness: bitcoin::Witness::default(),
             }],
             output: vec![],
-        };
+        }
+    }
 
-        let inscription = parser.parse_transaction(&tx).unwrap();
+    #[test]
+    fn test_svg_scripts_are_removed_when_sanitizing() {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg"><script>alert(1)</script><circle r="1"/></svg>"#;
+        let tx = output_tx(envelope_script(&[(1, b"image/svg+xml")], Some(svg)));
+
+        let inscription = InscriptionParser::new().parse_transaction(&tx).unwrap();
+        assert!(matches!(&inscription.content, InscriptionType::Svg(text) if text.contains("<script>")));
+        assert_eq!(inscription.mime_type(), "image/svg+xml");
+
+        let inscription = InscriptionParser::new().with_sanitize_svg(true).parse_transaction(&tx).unwrap();
+        match &inscription.content {
+            InscriptionType::Svg(text) => {
+                assert_eq!(text, r#"<svg xmlns="http://www.w3.org/2000/svg"><circle r="1"/></svg>"#)
+            }
+            other => panic!("Expected an SVG, got {:?}", other),
+        }
+
+        // Malformed XML is not classified as an image
+        let tx = output_tx(envelope_script(&[(1, b"image/svg+xml")], Some(b"<svg><script>")));
+        let inscription = InscriptionParser::new().parse_transaction(&tx).unwrap();
+        assert!(matches!(inscription.content, InscriptionType::Unknown(_)));
+    }
+
+    #[test]
+    fn test_coinbase_text_extraction() {
+        let parser = InscriptionParser::new();
+
+        // Create a transaction with a coinbase input containing the genesis block text
+        let script = Builder::new()
+            .push_slice(b"The Times 03/Jan/2009 Chancellor on brink of second bailout for banks")
+            .into_script();
+
+        let inscription = parser.parse_transaction(&coinbase_tx(script)).unwrap();
         if let InscriptionType::Text(text) = inscription.content {
             assert_eq!(text, "The Times 03/Jan/2009 Chancellor on brink of second bailout for banks");
         } else {
@@ -391,6 +1529,79 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_coinbase_text_in_any_push() {
+        let parser = InscriptionParser::new();
+
+        // Height, pool tag, then extranonce: the text is the second push
+        let script = Builder::new()
+            .push_int(840_000)
+            .push_slice(b"/ViaBTC/Mined by satoshi/")
+            .push_slice([0x8f, 0x00, 0xfe, 0x12, 0x34, 0x56, 0x78, 0x9a])
+            .into_script();
+        let inscription = parser.parse_transaction(&coinbase_tx(script)).unwrap();
+        assert!(matches!(inscription.content, InscriptionType::Text(ref t) if t == "/ViaBTC/Mined by satoshi/"));
+
+        // Several printable pushes: the longest wins, short ones are ignored
+        let script = Builder::new()
+            .push_slice(b"abc")
+            .push_slice(b"pool")
+            .push_slice([0xff, 0xfe])
+            .push_slice(b"Chancellor on brink")
+            .into_script();
+        let inscription = parser.parse_transaction(&coinbase_tx(script)).unwrap();
+        assert!(matches!(inscription.content, InscriptionType::Text(ref t) if t == "Chancellor on brink"));
+
+        // Nothing printable: no text at all
+        let script = Builder::new()
+            .push_int(1)
+            .push_slice([0x01, 0x02, 0x03, 0x04, 0x05])
+            .into_script();
+        assert_eq!(parser.extract_text_from_script(&script), None);
+    }
+
+    #[test]
+    fn test_modern_coinbase_has_no_spurious_text() {
+        let parser = InscriptionParser::new();
+        let commitment = ScriptBuf::from_bytes(
+            [WITNESS_COMMITMENT_PREFIX.as_slice(), b"a printable 32-byte commitment!!".as_slice()].concat(),
+        );
+        assert!(is_witness_commitment(&commitment));
+        let coinbase = |script_sig: ScriptBuf| {
+            let mut tx = coinbase_tx(script_sig);
+            tx.input[0].witness = bitcoin::Witness::from_slice(&[[0u8; 32]]);
+            tx.output = vec![
+                bitcoin::TxOut {
+                    value: 312_500_000,
+                    script_pubkey: ScriptBuf::from_bytes([[0x00, 0x14].as_slice(), &[0x75; 20]].concat()),
+                },
+                bitcoin::TxOut { value: 0, script_pubkey: commitment.clone() },
+            ];
+            tx
+        };
+
+        // Height 840000, then extranonce bytes
+        let script = Builder::new()
+            .push_int(840_000)
+            .push_slice([0x8f, 0x00, 0xfe, 0x12, 0x34, 0x56, 0x78, 0x9a])
+            .into_script();
+        assert!(parser.parse_transaction_all(&coinbase(script)).is_empty());
+
+        // A height whose encoding happens to be printable ("abcd") isn't text either
+        let script = Builder::new().push_int(1_684_234_849).push_slice([0xfa, 0xbe, 0x6d, 0x6d]).into_script();
+        assert_eq!(parser.extract_text_from_script(&script), None);
+
+        // The pool tag still is
+        let script = Builder::new()
+            .push_int(840_000)
+            .push_slice(b"/Foundry USA Pool #dropgold/")
+            .push_slice([0x8f, 0x00, 0xfe, 0x12])
+            .into_script();
+        let inscriptions = parser.parse_transaction_all(&coinbase(script));
+        assert_eq!(inscriptions.len(), 1);
+        assert!(matches!(&inscriptions[0].content, InscriptionType::Text(t) if t == "/Foundry USA Pool #dropgold/"));
+    }
+
     #[test]
     fn test_inscription_parsing() {
         let parser = InscriptionParser::new();
@@ -399,23 +1610,15 @@ mod tests {
         let script = Builder::new()
             .push_opcode(OP_FALSE)
             .push_opcode(all::OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1u8])
             .push_slice(b"text/plain;charset=utf-8")
             .push_opcode(OP_0)
             .push_slice(b"Hello, Bitcoin!")
             .push_opcode(all::OP_ENDIF)
             .into_script();
 
-        let tx = Transaction {
-            version: 1,
-            lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
-            input: vec![],
-            output: vec![bitcoin::TxOut {
-                value: 0,
-                script_pubkey: script,
-            }],
-        };
-
-        let inscription = parser.parse_transaction(&tx).unwrap();
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
         match inscription.content {
             InscriptionType::Text(text) => assert_eq!(text, "Hello, Bitcoin!"),
             _ => panic!("Expected text inscription"),
@@ -425,21 +1628,15 @@ mod tests {
         let script = Builder::new()
             .push_opcode(OP_0)
             .push_opcode(all::OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1u8])
             .push_slice(b"text/plain;charset=utf-8")
             .push_opcode(OP_0)
             .push_slice(b"Hello, Bitcoin!")
             .push_opcode(all::OP_ENDIF)
             .into_script();
 
-        let tx = Transaction {
-            version: 1,
-            lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
-            input: vec![],
-            output: vec![bitcoin::TxOut {
-                value: 0,
-                script_pubkey: script,
-            }],
-        };
+        let tx = output_tx(script);
 
         let inscription = parser.parse_transaction(&tx).unwrap();
         
@@ -464,4 +1661,693 @@ mod tests {
             panic!("Expected text inscriptions");
         }
     }
+
+    #[test]
+    fn test_multiple_envelopes_in_tapscript() {
+        let parser = InscriptionParser::new();
+
+        // Two envelopes after a key-spend guard, as batch reveals lay them out
+        let builder = Builder::new()
+            .push_slice([0x02; 32])
+            .push_opcode(all::OP_CHECKSIG);
+        let builder = envelope_builder(builder, &[(1, TEXT_PLAIN)], Some(b"first".as_slice()));
+        let script = envelope_builder(builder, &[(1, TEXT_PLAIN)], Some(b"second".as_slice()))
+            .into_script();
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint {
+                    txid: bitcoin::Txid::all_zeros(),
+                    vout: 0,
+                },
+                script_sig: bitcoin::ScriptBuf::new(),
+                sequence: bitcoin::Sequence::MAX,
+                witness: Witness::from_slice(&[vec![0u8; 64], script.to_bytes(), vec![0xc0; 33]]),
+            }],
+            output: vec![],
+        };
+
+        let inscriptions = parser.parse_transaction_all(&tx);
+        assert_eq!(inscriptions.len(), 2);
+
+        let texts: Vec<_> = inscriptions
+            .iter()
+            .map(|inscription| match &inscription.content {
+                InscriptionType::Text(text) => text.as_str(),
+                _ => panic!("Expected text inscription"),
+            })
+            .collect();
+        assert_eq!(texts, vec!["first", "second"]);
+        assert_eq!(inscriptions[0].inscription_id(), format!("{}i0", tx.txid()));
+        assert_eq!(inscriptions[1].inscription_id(), format!("{}i1", tx.txid()));
+
+        // The single-result wrapper still returns the first envelope
+        match parser.parse_transaction(&tx).unwrap().content {
+            InscriptionType::Text(text) => assert_eq!(text, "first"),
+            _ => panic!("Expected text inscription"),
+        }
+    }
+
+    #[test]
+    fn test_content_type_and_body_tags() {
+        let parser = InscriptionParser::new();
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(b"tagged".as_slice()));
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.content_type.as_deref(), Some("text/plain;charset=utf-8"));
+        assert_eq!(inscription.tags, vec![(TAG_CONTENT_TYPE, TEXT_PLAIN.to_vec())]);
+        match inscription.content {
+            InscriptionType::Text(text) => assert_eq!(text, "tagged"),
+            _ => panic!("Expected text inscription"),
+        }
+    }
+
+    #[test]
+    fn test_missing_content_type_tag_stays_unset() {
+        let parser = InscriptionParser::new();
+        let script = envelope_script(&[], Some(b"untagged".as_slice()));
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.content_type, None);
+        assert_eq!(inscription.mime_type(), "text/plain;charset=utf-8");
+        assert!(matches!(inscription.content, InscriptionType::Text(ref text) if text == "untagged"));
+    }
+
+    #[test]
+    fn test_unknown_tags() {
+        let parser = InscriptionParser::new();
+
+        // Unknown odd tags are skipped, the inscription survives
+        let script = envelope_script(
+            &[(1, TEXT_PLAIN), (99, b"ignored".as_slice())],
+            Some(b"odd".as_slice()),
+        );
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.content_type.as_deref(), Some("text/plain;charset=utf-8"));
+
+        // Unknown even tags invalidate the envelope
+        let script = envelope_script(
+            &[(1, TEXT_PLAIN), (100, b"rejected".as_slice())],
+            Some(b"even".as_slice()),
+        );
+        assert!(parser.parse_transaction(&output_tx(script)).is_none());
+    }
+
+    #[test]
+    fn test_missing_protocol_id() {
+        let parser = InscriptionParser::new();
+        let script = Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(all::OP_IF)
+            .push_slice(b"text/plain;charset=utf-8")
+            .push_opcode(OP_0)
+            .push_slice(b"Hello, Bitcoin!")
+            .push_opcode(all::OP_ENDIF)
+            .into_script();
+
+        assert!(parser.parse_transaction(&output_tx(script)).is_none());
+    }
+
+    #[test]
+    fn test_pointer_tag() {
+        let parser = InscriptionParser::new();
+        let script = envelope_script(
+            &[(1, TEXT_PLAIN), (2, [0x10, 0x27].as_slice())],
+            Some(b"pointed".as_slice()),
+        );
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.pointer, Some(10000));
+
+        // The pointer survives a serialization round trip
+        let json = serde_json::to_string(&inscription).unwrap();
+        let deserialized: Inscription = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.pointer, Some(10000));
+
+        // Pointers wider than 8 bytes are ignored
+        let script = envelope_script(&[(1, TEXT_PLAIN), (2, [1u8; 9].as_slice())], Some(b"wide".as_slice()));
+        assert_eq!(parser.parse_transaction(&output_tx(script)).unwrap().pointer, None);
+    }
+
+    #[test]
+    fn test_cbor_metadata_tag() {
+        let parser = InscriptionParser::new();
+
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(
+            serde_cbor::Value::Text("name".to_string()),
+            serde_cbor::Value::Text("satoshi".to_string()),
+        );
+        let value = serde_cbor::Value::Map(map);
+        let cbor = serde_cbor::to_vec(&value).unwrap();
+
+        // Split the metadata across two tag-5 pushes
+        let (first, second) = cbor.split_at(cbor.len() / 2);
+        let script = envelope_script(&[(1, TEXT_PLAIN), (5, first), (5, second)], Some(b"meta".as_slice()));
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.metadata, Some(Metadata::Cbor(value)));
+
+        let json = serde_json::to_string(&inscription).unwrap();
+        let deserialized: Inscription = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.metadata, inscription.metadata);
+
+        // Invalid CBOR is preserved rather than dropping the inscription
+        let script = envelope_script(&[(1, TEXT_PLAIN), (5, [0xff, 0xff].as_slice())], Some(b"raw".as_slice()));
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.metadata, Some(Metadata::Raw(vec![0xff, 0xff])));
+    }
+
+    #[test]
+    fn test_undeclared_gzip_body_in_lenient_mode() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"Hello, Bitcoin!").unwrap();
+        let compressed = encoder.finish().unwrap();
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(compressed.as_slice()));
+
+        // Strict mode leaves the body alone, so it isn't valid text
+        let strict = InscriptionParser::new();
+        assert!(strict.parse_transaction(&output_tx(script.clone())).is_none());
+
+        let lenient = InscriptionParser::new().with_lenient(true);
+        let inscription = lenient.parse_transaction(&output_tx(script)).unwrap();
+        match &inscription.content {
+            InscriptionType::Text(text) => assert_eq!(text, "Hello, Bitcoin!"),
+            _ => panic!("Expected text inscription"),
+        }
+        assert_eq!(inscription.content_encoding.as_deref(), Some("gzip"));
+        assert!(inscription.encoding_detected);
+    }
+
+    /// Keeps BRC-20 operations as text instead of decoding them as JSON
+    #[derive(Debug)]
+    struct Brc20Classifier;
+
+    impl Classifier for Brc20Classifier {
+        fn classify(&self, content_type: &str, body: Vec<u8>) -> Option<InscriptionType> {
+            if content_type == "application/json; brc-20" {
+                return String::from_utf8(body).ok().map(InscriptionType::Text);
+            }
+            DefaultClassifier::default().classify(content_type, body)
+        }
+
+        fn name(&self) -> &str {
+            "brc20"
+        }
+    }
+
+    #[test]
+    fn test_custom_classifier() {
+        let body = br#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#;
+        let brc20 = envelope_script(&[(1, b"application/json; brc-20".as_slice())], Some(body.as_slice()));
+        let json = envelope_script(&[(1, b"application/json".as_slice())], Some(body.as_slice()));
+
+        let parser = InscriptionParser::new();
+        let default = parser.parse_transaction(&output_tx(brc20.clone())).unwrap();
+        assert!(matches!(default.content, InscriptionType::Json(_)));
+
+        let custom = InscriptionParser::new().with_classifier(Box::new(Brc20Classifier));
+        match custom.parse_transaction(&output_tx(brc20)).unwrap().content {
+            InscriptionType::Text(text) => assert_eq!(text.as_bytes(), body),
+            other => panic!("Expected text, got {:?}", other),
+        }
+        // Other types still get the default handling
+        assert!(matches!(custom.parse_transaction(&output_tx(json)).unwrap().content, InscriptionType::Json(_)));
+        assert_ne!(custom.version_tag(), parser.version_tag());
+    }
+
+    #[test]
+    fn test_html_body_references() {
+        let parser = InscriptionParser::new();
+        let first = format!("{}i0", "1f".repeat(32));
+        let second = format!("{}i3", "2e".repeat(32));
+        let html = format!(
+            r#"<html><img src="/content/{first}"><script src="https://ordinals.com/content/{second}"></script></html>"#
+        );
+        let script = envelope_script(&[(1, b"text/html;charset=utf-8".as_slice())], Some(html.as_bytes()));
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.references, vec![first, second]);
+
+        let json = serde_json::to_string(&inscription).unwrap();
+        let restored: Inscription = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.references, inscription.references);
+
+        // Images aren't scanned for references
+        let script = envelope_script(&[(1, b"image/png".as_slice())], Some(html.as_bytes()));
+        assert!(parser.parse_transaction(&output_tx(script)).unwrap().references.is_empty());
+    }
+
+    #[test]
+    fn test_declared_content_encoding() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let parser = InscriptionParser::new();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"Hello, Bitcoin!").unwrap();
+        let compressed = encoder.finish().unwrap();
+        let script = envelope_script(
+            &[(1, TEXT_PLAIN), (9, b"gzip".as_slice())],
+            Some(compressed.as_slice()),
+        );
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        match &inscription.content {
+            InscriptionType::Text(text) => assert_eq!(text, "Hello, Bitcoin!"),
+            _ => panic!("Expected text inscription"),
+        }
+        assert_eq!(inscription.content_encoding.as_deref(), Some("gzip"));
+        assert!(!inscription.encoding_detected);
+        assert_eq!(inscription.compressed_body, Some(compressed));
+
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+        encoder.write_all(b"Hello, Bitcoin!").unwrap();
+        let compressed = encoder.into_inner();
+        let script = envelope_script(
+            &[(1, TEXT_PLAIN), (9, b"br".as_slice())],
+            Some(compressed.as_slice()),
+        );
+
+        match parser.parse_transaction(&output_tx(script)).unwrap().content {
+            InscriptionType::Text(text) => assert_eq!(text, "Hello, Bitcoin!"),
+            _ => panic!("Expected text inscription"),
+        }
+    }
+
+    #[test]
+    fn test_delegate_tag() {
+        let parser = InscriptionParser::new();
+
+        let mut id = [0xab; 32].to_vec();
+        id.extend_from_slice(&[0x02, 0x00, 0x00, 0x00]);
+        let script = envelope_script(&[(11, id.as_slice())], Some(b"".as_slice()));
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        let txid = bitcoin::Txid::from_slice(&[0xab; 32]).unwrap();
+        assert_eq!(inscription.delegate, Some(format!("{}i2", txid)));
+        assert!(inscription.content.is_empty());
+
+        // A bare 32-byte id means index 0
+        let script = envelope_script(&[(11, [0xab; 32].as_slice())], None);
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.delegate, Some(format!("{}i0", txid)));
+    }
+
+    #[test]
+    fn test_parent_tags_decode_in_order() {
+        let parser = InscriptionParser::new();
+
+        let mut first = [0x11; 32].to_vec();
+        first.push(0x01);
+        let second = [0x22; 32];
+        let script = envelope_script(
+            &[(1, TEXT_PLAIN), (3, first.as_slice()), (3, second.as_slice())],
+            Some(b"child".as_slice()),
+        );
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        let first_txid = bitcoin::Txid::from_slice(&[0x11; 32]).unwrap();
+        let second_txid = bitcoin::Txid::from_slice(&[0x22; 32]).unwrap();
+        assert_eq!(
+            inscription.parents,
+            vec![format!("{}i1", first_txid), format!("{}i0", second_txid)]
+        );
+
+        // Parents survive the round trip through the stored JSON form
+        let json = serde_json::to_string(&inscription).unwrap();
+        let restored: Inscription = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.parents, inscription.parents);
+    }
+
+    #[test]
+    fn test_sniffed_type_overrides_missing_or_wrong_label() {
+        let parser = InscriptionParser::new();
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR".as_slice();
+        let gif = b"GIF89a\x01\x00\x01\x00".as_slice();
+
+        // No content type tag at all
+        let script = envelope_script(&[], Some(png));
+        match parser.parse_transaction(&output_tx(script)).unwrap().content {
+            InscriptionType::Image { mime_type, data } => {
+                assert_eq!(mime_type, "image/png");
+                assert_eq!(data, png);
+            }
+            other => panic!("Expected image inscription, got {:?}", other),
+        }
+
+        // Mislabeled as text
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(gif));
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.content_type.as_deref(), Some("text/plain;charset=utf-8"));
+        match inscription.content {
+            InscriptionType::Image { mime_type, .. } => assert_eq!(mime_type, "image/gif"),
+            other => panic!("Expected image inscription, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_count_only_keeps_type_without_body() {
+        let parser = InscriptionParser::new().with_count_only(true);
+        let gif = b"GIF89a\x01\x00\x01\x00".as_slice();
+
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(gif));
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.mime_type(), "image/gif");
+        assert!(matches!(&inscription.content, InscriptionType::Unknown(body) if body.is_empty()));
+
+        let script = envelope_script(&[(1, b"application/json".as_slice())], Some(b"{\"p\":1}".as_slice()));
+        assert_eq!(parser.parse_transaction(&output_tx(script)).unwrap().mime_type(), "application/json");
+        assert_ne!(parser.version_tag(), InscriptionParser::new().version_tag());
+    }
+
+    #[test]
+    fn test_json_inscriptions() {
+        let parser = InscriptionParser::new();
+        let payload = br#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#.as_slice();
+
+        for content_type in [b"application/json".as_slice(), TEXT_PLAIN, b"text/plain", b"text/plain; charset=utf-8"] {
+            let script = envelope_script(&[(1, content_type)], Some(payload));
+            match parser.parse_transaction(&output_tx(script)).unwrap().content {
+                InscriptionType::Json(value) => {
+                    assert_eq!(value["p"], "brc-20");
+                    assert_eq!(value["op"], "mint");
+                }
+                other => panic!("Expected JSON inscription, got {:?}", other),
+            }
+        }
+
+        // Brace-prefixed text that isn't valid JSON stays text
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(b"{not json".as_slice()));
+        match parser.parse_transaction(&output_tx(script)).unwrap().content {
+            InscriptionType::Text(text) => assert_eq!(text, "{not json"),
+            other => panic!("Expected text inscription, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_content_id_ignores_txid() {
+        let first = bitcoin::Txid::from_slice(&[1u8; 32]).unwrap();
+        let second = bitcoin::Txid::from_slice(&[2u8; 32]).unwrap();
+
+        let a = Inscription::new(first, InscriptionType::Text("same".to_string()));
+        let b = Inscription::new(second, InscriptionType::Text("same".to_string()));
+        assert_eq!(a.content_id(), b.content_id());
+        assert_eq!(a.content_id().len(), 64);
+
+        let mut html = b.clone();
+        html.content_type = Some("text/html;charset=utf-8".to_string());
+        assert_ne!(a.content_id(), html.content_id());
+
+        let other = Inscription::new(first, InscriptionType::Text("different".to_string()));
+        assert_ne!(a.content_id(), other.content_id());
+    }
+
+    #[test]
+    fn test_classification_time_is_recorded_by_type() {
+        let metrics = Arc::new(Metrics::new());
+        let parser = InscriptionParser::new().with_metrics(metrics.clone());
+        let json = envelope_script(&[(1, b"application/json".as_slice())], Some(br#"{"p":"brc-20"}"#.as_slice()));
+        let empty = envelope_script(&[(1, b"text/plain".as_slice())], None);
+        parser.parse_transaction(&output_tx(json)).unwrap();
+        parser.parse_transaction(&output_tx(empty)).unwrap();
+
+        // Bodyless envelopes have nothing to classify
+        let per_type = metrics.get_stats().per_type;
+        assert_eq!(per_type.keys().collect::<Vec<_>>(), vec!["application/json"]);
+        assert_eq!(per_type["application/json"].parsed, 1);
+    }
+
+    #[test]
+    fn test_json_content_id_hashes_the_inscribed_bytes() {
+        let compact = br#"{"p":"brc-20","op":"mint"}"#;
+        let spaced = br#"{ "op": "mint", "p": "brc-20" }"#;
+        let parse = |body: &[u8]| {
+            let script = envelope_script(&[(1, b"application/json".as_slice())], Some(body));
+            InscriptionParser::new().parse_transaction(&output_tx(script)).unwrap()
+        };
+        let (a, b) = (parse(compact), parse(spaced));
+        assert!(matches!(a.content, InscriptionType::Json(_)));
+        assert_eq!(a.content.bytes(), b.content.bytes());
+
+        assert_eq!(a.content_id(), content_digest("application/json", compact));
+        assert_ne!(a.content_id(), b.content_id());
+        let cached: Inscription = serde_json::from_str(&serde_json::to_string(&b).unwrap()).unwrap();
+        assert_eq!(cached.content_id(), b.content_id());
+    }
+
+    #[test]
+    fn test_pointer_without_body() {
+        let parser = InscriptionParser::new();
+
+        // Content type and pointer, but no OP_0 body separator at all
+        let script = envelope_script(&[(1, TEXT_PLAIN), (2, [0x10, 0x27].as_slice())], None);
+
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert!(matches!(inscription.content, InscriptionType::Empty));
+        assert_eq!(inscription.pointer, Some(10000));
+        assert_eq!(inscription.content_type.as_deref(), Some("text/plain;charset=utf-8"));
+        assert_eq!(inscription.mime_type(), "text/plain;charset=utf-8");
+    }
+
+    #[test]
+    fn test_envelopes_per_tx_are_capped() {
+        let parser = InscriptionParser::new().with_max_inscriptions_per_tx(3);
+
+        let mut builder = Builder::new();
+        for i in 0..5u8 {
+            builder = envelope_builder(builder, &[(1, TEXT_PLAIN)], Some([b'a' + i].as_slice()));
+        }
+        let inscriptions = parser.parse_transaction_all(&output_tx(builder.into_script()));
+
+        assert_eq!(inscriptions.len(), 3);
+        assert!(inscriptions.iter().all(|inscription| inscription.tx_truncated));
+        assert_eq!(inscriptions[2].content.bytes().as_ref(), b"c");
+
+        // Scanning stops one envelope past the cap instead of reading the whole script
+        let mut builder = Builder::new();
+        for i in 0..5u8 {
+            builder = envelope_builder(builder, &[(1, TEXT_PLAIN)], Some([b'a' + i].as_slice()));
+        }
+        assert_eq!(parser.parse_script(&builder.into_script(), parser.envelope_budget(0)).len(), 4);
+
+        // At or under the limit nothing is flagged
+        let mut builder = Builder::new();
+        for i in 0..3u8 {
+            builder = envelope_builder(builder, &[(1, TEXT_PLAIN)], Some([b'a' + i].as_slice()));
+        }
+        let inscriptions = parser.parse_transaction_all(&output_tx(builder.into_script()));
+        assert_eq!(inscriptions.len(), 3);
+        assert!(inscriptions.iter().all(|inscription| !inscription.tx_truncated));
+    }
+
+    #[test]
+    fn test_bodies_over_the_size_cap_are_flagged() {
+        let parser = InscriptionParser::new().with_max_inscription_size(1000);
+
+        let body = vec![b'x'; 1500];
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(&body));
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert!(matches!(inscription.content, InscriptionType::Oversized { size: 1500 }));
+        assert!(inscription.content.bytes().is_empty());
+        assert_eq!(inscription.content.kind(), "oversized");
+
+        // Exactly at the cap the body is kept
+        let body = vec![b'x'; 1000];
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(&body));
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.content.bytes().len(), 1000);
+
+        // A small compressed body that inflates past the cap is never fully decoded
+        let compressed = zstd::stream::encode_all(&[b'x'; 1_000_000][..], 19).unwrap();
+        assert!(compressed.len() < 1000);
+        let script = envelope_script(&[(1, TEXT_PLAIN), (9, b"zstd".as_slice())], Some(&compressed));
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert!(matches!(inscription.content, InscriptionType::Oversized { size: 1001 }));
+    }
+
+    #[test]
+    fn test_genesis_address_of_p2tr_reveal() {
+        let parser = InscriptionParser::new().with_genesis_address(true);
+        let envelope = |fields: &[(u8, &[u8])]| {
+            let script = envelope_script(fields, Some(b"hello".as_slice()));
+            Witness::from_slice(&[vec![0u8; 64], script.to_bytes(), vec![0xc0; 33]])
+        };
+        let p2tr = ScriptBuf::from_hex(
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let reveal = |witness: Witness| Transaction {
+            version: 2,
+            lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint { txid: bitcoin::Txid::all_zeros(), vout: 0 },
+                script_sig: ScriptBuf::new(),
+                sequence: bitcoin::Sequence::MAX,
+                witness,
+            }],
+            output: vec![
+                bitcoin::TxOut { value: 546, script_pubkey: p2tr.clone() },
+                // Bare OP_TRUE has no address form
+                bitcoin::TxOut { value: 1000, script_pubkey: ScriptBuf::from_bytes(vec![0x51]) },
+            ],
+        };
+
+        let inscription = parser.parse_transaction(&reveal(envelope(&[(1, TEXT_PLAIN)]))).unwrap();
+        assert_eq!(
+            inscription.genesis_address.as_deref(),
+            Some("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0")
+        );
+
+        // A pointer into the second output lands on a non-standard script
+        let pointed = reveal(envelope(&[(1, TEXT_PLAIN), (2, &600u16.to_le_bytes())]));
+        assert_eq!(parser.parse_transaction(&pointed).unwrap().genesis_address, None);
+
+        // Off by default
+        let inscription = InscriptionParser::new().parse_transaction(&reveal(envelope(&[(1, TEXT_PLAIN)]))).unwrap();
+        assert_eq!(inscription.genesis_address, None);
+    }
+
+    #[test]
+    fn test_tx_metadata_matches_the_reveal() {
+        let parser = InscriptionParser::new().with_tx_metadata(true);
+        let mut tx = output_tx(envelope_script(&[(1, b"text/plain")], Some(b"hi")));
+        tx.version = 2;
+        tx.lock_time = bitcoin::locktime::absolute::LockTime::from_consensus(840_000);
+
+        // 24-byte envelope script in a 33-byte output; 43 bytes without witness data
+        let inscription = parser.parse_transaction(&tx).unwrap();
+        assert_eq!(
+            inscription.tx_metadata,
+            Some(TxMetadata { version: 2, lock_time: 840_000, input_count: 0, output_count: 1, weight: 172 })
+        );
+
+        assert_eq!(InscriptionParser::new().parse_transaction(&tx).unwrap().tx_metadata, None);
+    }
+
+    #[test]
+    fn test_pushnum_in_envelope_is_cursed() {
+        let parser = InscriptionParser::new();
+
+        // OP_1 stands in for the content-type tag push
+        let script = push(
+            push(
+                push(Builder::new().push_opcode(OP_FALSE).push_opcode(all::OP_IF), b"ord")
+                    .push_opcode(all::OP_PUSHNUM_1),
+                TEXT_PLAIN,
+            )
+            .push_opcode(OP_0),
+            b"cursed",
+        )
+        .push_opcode(all::OP_ENDIF)
+        .into_script();
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.curse, Some(Curse::Pushnum));
+        assert_eq!(inscription.content_type.as_deref(), Some("text/plain;charset=utf-8"));
+        assert!(matches!(inscription.content, InscriptionType::Text(ref text) if text == "cursed"));
+
+        // A pushnum in the body is read as the byte it pushes
+        let script = push(Builder::new().push_opcode(OP_FALSE).push_opcode(all::OP_IF), b"ord")
+            .push_opcode(OP_0)
+            .push_opcode(all::OP_PUSHNUM_16)
+            .push_opcode(all::OP_ENDIF)
+            .into_script();
+        let inscription = parser.parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.curse, Some(Curse::Pushnum));
+        assert_eq!(inscription.content.bytes(), vec![16]);
+
+        let clean = envelope_script(&[(1, TEXT_PLAIN)], Some(b"blessed"));
+        assert_eq!(parser.parse_transaction(&output_tx(clean)).unwrap().curse, None);
+    }
+
+    #[test]
+    fn test_nested_if_ends_at_the_matching_endif() {
+        // The inner OP_ENDIF mustn't close the envelope and leave "after" outside it
+        let builder = push(Builder::new().push_opcode(OP_FALSE).push_opcode(all::OP_IF), b"ord");
+        let builder = push(push(builder, &[1]), TEXT_PLAIN).push_opcode(OP_0);
+        let script = push(
+            push(builder, b"before").push_opcode(all::OP_IF).push_opcode(all::OP_ENDIF),
+            b"after",
+        )
+        .push_opcode(all::OP_ENDIF);
+        let script = envelope_builder(script, &[(1, TEXT_PLAIN)], Some(b"next".as_slice())).into_script();
+
+        // The nested conditional is a non-push opcode, so the envelope is rejected,
+        // but the one after it is still found
+        let inscriptions = InscriptionParser::new().parse_transaction_all(&output_tx(script.clone()));
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].content.bytes(), b"next".to_vec());
+
+        let inscriptions = InscriptionParser::new().with_lenient(true).parse_transaction_all(&output_tx(script));
+        assert_eq!(inscriptions.len(), 2);
+        assert_eq!(inscriptions[0].content.bytes(), b"beforeafter".to_vec());
+    }
+
+    #[test]
+    fn test_stray_opcode_rejects_the_envelope() {
+        let builder = push(Builder::new().push_opcode(OP_FALSE).push_opcode(all::OP_IF), b"ord");
+        let builder = push(push(builder, &[1]), TEXT_PLAIN).push_opcode(OP_0);
+        let script = push(push(builder, b"stray").push_opcode(all::OP_DROP), b" opcode")
+            .push_opcode(all::OP_ENDIF)
+            .into_script();
+
+        assert!(InscriptionParser::new().parse_transaction(&output_tx(script.clone())).is_none());
+        let inscription = InscriptionParser::new().with_lenient(true).parse_transaction(&output_tx(script)).unwrap();
+        assert_eq!(inscription.content.bytes(), b"stray opcode".to_vec());
+    }
+
+    #[test]
+    fn test_non_taproot_witness_envelope_only_in_lenient_mode() {
+        // P2WSH-style spend: signature, then the witness script, no control block
+        let script = envelope_script(&[(1, TEXT_PLAIN)], Some(b"pre-taproot"));
+        let tx = Transaction {
+            version: 2,
+            lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint { txid: bitcoin::Txid::all_zeros(), vout: 0 },
+                script_sig: bitcoin::ScriptBuf::new(),
+                sequence: bitcoin::Sequence::MAX,
+                witness: Witness::from_slice(&[vec![0x30; 71], script.to_bytes()]),
+            }],
+            output: vec![],
+        };
+
+        assert!(InscriptionParser::new().parse_transaction_all(&tx).is_empty());
+
+        let inscriptions = InscriptionParser::new().with_lenient(true).parse_transaction_all(&tx);
+        assert_eq!(inscriptions.len(), 1);
+        assert!(matches!(&inscriptions[0].content, InscriptionType::Text(text) if text == "pre-taproot"));
+    }
+
+    #[test]
+    fn test_disallowed_content_type_body_is_never_reassembled() {
+        let parser = InscriptionParser::new().with_content_types(vec!["text/*".to_string()]);
+
+        // A 1 MiB image ahead of an allowed text envelope in the same script
+        let image = vec![0x89; 1024 * 1024];
+        let builder = envelope_builder(Builder::new(), &[(1, b"image/png")], Some(&image));
+        let script = envelope_builder(builder, &[(1, TEXT_PLAIN)], Some(b"kept")).into_script();
+
+        let envelopes = parser.parse_script(&script, usize::MAX);
+        assert_eq!(envelopes.len(), 2);
+        assert!(envelopes[0].filtered);
+        assert!(envelopes[0].body.is_none());
+        assert_eq!(envelopes[1].body.as_deref(), Some(&b"kept"[..]));
+
+        // The skipped envelope still counts towards the next one's id
+        let tx = output_tx(script);
+        let inscriptions = parser.parse_transaction_all(&tx);
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].inscription_id(), format!("{}i1", tx.txid()));
+
+        assert_eq!(InscriptionParser::new().parse_transaction_all(&tx).len(), 2);
+    }
 }
@@ -1,5 +1,25 @@
+mod classify;
+mod encoding;
 mod inscription;
 mod parallel;
+mod recursion;
+mod sniff;
+mod svg;
 
-pub use inscription::{Inscription, InscriptionType};
+pub use classify::{Classifier, DefaultClassifier};
+pub use inscription::{
+    Inscription, InscriptionParser, InscriptionType, TxMetadata, DEFAULT_MAX_INSCRIPTIONS_PER_TX,
+    DEFAULT_MAX_INSCRIPTION_SIZE,
+};
+pub(crate) use inscription::mime_matches;
 pub use parallel::ParallelParser;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ParserError {
+    #[error("Failed to start the parser thread pool: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+}
+
+pub type Result<T> = std::result::Result<T, ParserError>;
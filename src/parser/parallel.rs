@@ -1,67 +1,113 @@
-use super::inscription::{InscriptionParser, InscriptionType};
+use super::inscription::{Inscription, InscriptionParser};
+use super::Result;
 use bitcoin::Block;
 use rayon::prelude::*;
+use rayon::ThreadPool;
 use std::sync::Arc;
-use log::info;
+use crate::cache::ParsedBlockCache;
+use crate::utils::available_cpus;
+use log::{info, warn};
 use num_cpus;
 
 pub struct ParallelParser {
     parser: Arc<InscriptionParser>,
-    batch_size: usize,
+    /// Blocks handed to rayon per chunk, independent of the RPC batch size
+    chunk_size: usize,
     thread_count: usize,
+    /// Built once and reused by every `process_blocks` call
+    pool: Arc<ThreadPool>,
+    /// Parse results of blocks seen before, keyed by block hash
+    cache: Option<Arc<ParsedBlockCache>>,
 }
 
 impl ParallelParser {
-    pub fn new(batch_size: usize) -> Self {
-        // Get the number of physical CPU cores
-        // M1 has 8 cores (4 performance + 4 efficiency)
-        let thread_count = num_cpus::get_physical();
-        info!("Initializing parallel parser with {} threads", thread_count);
-        
-        Self {
+    /// Creates a parser splitting batches into `chunk_size` blocks on
+    /// `thread_count` threads
+    ///
+    /// Without a thread count it uses the physical cores, capped by any
+    /// container CPU quota. Fails if the thread pool can't be started.
+    pub fn new(chunk_size: usize, thread_count: Option<usize>) -> Result<Self> {
+        let detected = num_cpus::get_physical();
+        let thread_count = thread_count.unwrap_or_else(available_cpus).max(1);
+        info!("Initializing parallel parser with {} threads ({} physical cores detected)",
+            thread_count, detected);
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .thread_name(|index| format!("parser-{}", index))
+            .build()?;
+
+        Ok(Self {
             parser: Arc::new(InscriptionParser::new()),
-            batch_size,
+            chunk_size: chunk_size.max(1),
             thread_count,
-        }
+            pool: Arc::new(pool),
+            cache: None,
+        })
     }
 
-    pub fn process_blocks(&self, blocks: Vec<Block>) -> Vec<String> {
-        let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(self.thread_count)
-            .build()
-            .unwrap();
+    /// Replaces the inscription parser, e.g. to enable lenient mode
+    pub fn with_inscription_parser(mut self, parser: InscriptionParser) -> Self {
+        self.parser = Arc::new(parser);
+        self
+    }
+
+    /// Serves re-encountered blocks from `cache` instead of reparsing them
+    pub fn with_parsed_cache(mut self, cache: Arc<ParsedBlockCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
 
+    /// Parses `(height, block)` pairs, tagging each inscription with its block
+    pub fn process_blocks(&self, blocks: Vec<(u64, Block)>) -> Vec<Inscription> {
         info!("Processing {} blocks in parallel using {} threads", blocks.len(), self.thread_count);
-        
-        pool.install(|| {
+
+        self.pool.install(|| {
             blocks
-                .par_chunks(self.batch_size)
+                .par_chunks(self.chunk_size)
                 .flat_map(|chunk| {
                     chunk.par_iter()
-                        .flat_map(|block| self.process_block(block))
+                        .flat_map(|(height, block)| self.process_block(*height, block))
                         .collect::<Vec<_>>()
                 })
                 .collect()
         })
     }
 
-    fn process_block(&self, block: &Block) -> Vec<String> {
+    fn process_block(&self, height: u64, block: &Block) -> Vec<Inscription> {
+        let Some(cache) = &self.cache else {
+            return self.parse_block(height, block);
+        };
+
+        // Cache failures only cost a reparse, so they're logged and skipped
+        let hash = block.block_hash();
+        let version = self.parser.version_tag();
+        match cache.get(&hash, &version) {
+            Ok(Some(mut inscriptions)) => {
+                for inscription in &mut inscriptions {
+                    inscription.block_height = height;
+                }
+                return inscriptions;
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read parsed block {} from cache: {}", hash, e),
+        }
+
+        let inscriptions = self.parse_block(height, block);
+        if let Err(e) = cache.put(&hash, &version, &inscriptions) {
+            warn!("Failed to cache parsed block {}: {}", hash, e);
+        }
+        inscriptions
+    }
+
+    fn parse_block(&self, height: u64, block: &Block) -> Vec<Inscription> {
         block.txdata
             .par_iter()
-            .filter_map(|tx| {
-                if let Some(inscription) = self.parser.parse_transaction(tx) {
-                    if let InscriptionType::Text(text) = inscription.content {
-                        // Only collect text inscriptions that might be interesting
-                        if text.contains("Chancellor") || 
-                           text.contains("bank") || 
-                           text.contains("Times") ||
-                           text.contains("bailout") {
-                            info!("Found relevant inscription text: {}", text);
-                            return Some(text);
-                        }
-                    }
-                }
-                None
+            .flat_map_iter(|tx| self.parser.parse_transaction_all(tx))
+            .map(|mut inscription| {
+                inscription.block_height = height;
+                inscription.block_time = block.header.time;
+                inscription
             })
             .collect()
     }
@@ -70,7 +116,13 @@ impl ParallelParser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::{Classifier, DefaultClassifier};
+    use crate::parser::InscriptionType;
+    use crate::test_utils::dummy_header;
     use bitcoin::{Transaction, locktime::absolute::LockTime};
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
 
     fn create_test_block(num_txs: usize) -> Block {
         // Create a dummy block with the specified number of transactions
@@ -83,18 +135,17 @@ mod tests {
             })
             .collect();
 
-        // For testing purposes only - create a zeroed header since we only care about transaction parsing
-        let header = unsafe { std::mem::zeroed() };
-        Block { header, txdata }
+        // Only the transactions matter here, so any well-formed header will do
+        Block { header: dummy_header(), txdata }
     }
 
     #[test]
     fn test_parallel_processing() {
-        let parser = ParallelParser::new(100);
+        let parser = ParallelParser::new(100, None).unwrap();
         let blocks = vec![
-            create_test_block(10),
-            create_test_block(20),
-            create_test_block(30),
+            (0, create_test_block(10)),
+            (1, create_test_block(20)),
+            (2, create_test_block(30)),
         ];
 
         let inscriptions = parser.process_blocks(blocks);
@@ -102,4 +153,121 @@ mod tests {
         // In this test case, we don't expect any inscriptions since we used dummy transactions
         assert_eq!(inscriptions.len(), 0);
     }
+
+    /// A block with one text inscription, timestamped 1_700_000_000
+    fn create_inscription_block() -> Block {
+        use bitcoin::blockdata::opcodes::all::{OP_ENDIF, OP_IF};
+        use bitcoin::blockdata::script::Builder;
+        use bitcoin::opcodes::{OP_0, OP_FALSE};
+        use bitcoin::TxOut;
+
+        let script = Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice(b"\x01")
+            .push_slice(b"text/plain")
+            .push_opcode(OP_0)
+            .push_slice(b"gm")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let mut block = create_test_block(1);
+        block.header.time = 1_700_000_000;
+        block.txdata[0].output.push(TxOut { value: 0, script_pubkey: script });
+        block
+    }
+
+    #[test]
+    fn test_inscriptions_carry_their_block() {
+        let inscriptions = ParallelParser::new(100, None).unwrap().process_blocks(vec![(812_345, create_inscription_block())]);
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].block_height, 812_345);
+        assert_eq!(inscriptions[0].block_time, 1_700_000_000);
+    }
+
+    #[test]
+    fn test_second_scan_is_served_from_cache() {
+        use crate::cache::CacheDb;
+        use std::time::Duration;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path()).unwrap());
+        let cache = Arc::new(ParsedBlockCache::new(db, Duration::from_secs(3600)));
+        let parser = ParallelParser::new(100, None).unwrap().with_parsed_cache(cache.clone());
+
+        let first = parser.process_blocks(vec![(812_345, create_inscription_block())]);
+        assert_eq!(cache.hits(), 0);
+
+        let second = parser.process_blocks(vec![(812_345, create_inscription_block())]);
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].inscription_id(), first[0].inscription_id());
+        assert_eq!(second[0].content_id(), first[0].content_id());
+        assert_eq!(second[0].block_time, first[0].block_time);
+
+        // A differently configured parser doesn't trust those results
+        let lenient = ParallelParser::new(100, None).unwrap()
+            .with_inscription_parser(InscriptionParser::new().with_lenient(true))
+            .with_parsed_cache(cache.clone());
+        lenient.process_blocks(vec![(812_345, create_inscription_block())]);
+        assert_eq!(cache.hits(), 1);
+    }
+
+    #[test]
+    fn test_thread_count_is_at_least_one() {
+        let parser = ParallelParser::new(100, None).unwrap();
+        assert!(parser.thread_count >= 1);
+        assert_eq!(ParallelParser::new(100, Some(0)).unwrap().thread_count, 1);
+    }
+
+    #[test]
+    fn test_explicit_thread_count_sizes_the_pool() {
+        let parser = ParallelParser::new(2, Some(3)).unwrap();
+        assert_eq!(parser.thread_count, 3);
+        assert_eq!(parser.pool.current_num_threads(), 3);
+
+        // Chunks smaller than the batch still see every block
+        let blocks = (0..5).map(|height| (height, create_inscription_block())).collect();
+        assert_eq!(parser.process_blocks(blocks).len(), 5);
+    }
+
+    /// Records which threads classify bodies
+    #[derive(Debug)]
+    struct ThreadRecorder {
+        threads: Arc<Mutex<HashSet<ThreadId>>>,
+    }
+
+    impl Classifier for ThreadRecorder {
+        fn classify(&self, content_type: &str, body: Vec<u8>) -> Option<InscriptionType> {
+            self.threads.lock().unwrap().insert(thread::current().id());
+            DefaultClassifier::default().classify(content_type, body)
+        }
+
+        fn name(&self) -> &str {
+            "threads"
+        }
+    }
+
+    #[test]
+    fn test_many_small_batches_reuse_the_pool() {
+        let threads = Arc::new(Mutex::new(HashSet::new()));
+        let recorder = ThreadRecorder { threads: threads.clone() };
+        let parser = ParallelParser::new(1, Some(4))
+            .unwrap()
+            .with_inscription_parser(InscriptionParser::new().with_classifier(Box::new(recorder)));
+        let pool_threads = || parser.pool.broadcast(|_| thread::current().id()).into_iter().collect::<HashSet<_>>();
+        let started_with = pool_threads();
+
+        for height in 0..200 {
+            assert_eq!(parser.process_blocks(vec![(height, create_inscription_block())]).len(), 1);
+        }
+
+        // Every batch ran on the threads the pool started with, and it still has them
+        let used = threads.lock().unwrap();
+        assert!(!used.is_empty());
+        assert!(used.is_subset(&started_with), "batches ran outside the parser pool");
+        assert_eq!(pool_threads(), started_with);
+    }
 }
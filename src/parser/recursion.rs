@@ -0,0 +1,65 @@
+// recursion.rs
+//
+// Recursive inscription references: bodies that load other inscriptions
+// through ord's `/content/<inscription_id>` endpoint.
+
+use super::inscription::mime_essence;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// `/content/<txid>i<n>`, absolute (`https://ordinals.com/content/..`) or
+/// relative (`/content/..`, `../content/..`)
+const REFERENCE_PATTERN: &str = r"\bcontent/([0-9a-fA-F]{64}i[0-9]+)\b";
+
+/// Whether bodies of `mime` can embed references: HTML and other text, SVG, JSON
+pub fn may_reference(mime: &str) -> bool {
+    let essence = mime_essence(mime);
+    essence.starts_with("text/")
+        || essence == "image/svg+xml"
+        || essence == "application/json"
+        || essence == "application/javascript"
+}
+
+/// Inscription ids referenced from `body`, lowercased, in order of first appearance
+pub fn find_references(body: &[u8]) -> Vec<String> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| Regex::new(REFERENCE_PATTERN).expect("valid reference pattern"));
+
+    let text = String::from_utf8_lossy(body);
+    let mut references: Vec<String> = Vec::new();
+    for captures in pattern.captures_iter(&text) {
+        let id = captures[1].to_ascii_lowercase();
+        if !references.contains(&id) {
+            references.push(id);
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_references() {
+        let a = "a".repeat(64);
+        let b = "B".repeat(64);
+        let body = format!(
+            r#"<img src="/content/{a}i0"><script src="https://ordinals.com/content/{b}i12"></script>
+            <img src="../content/{a}i0">"#
+        );
+        assert_eq!(find_references(body.as_bytes()), vec![format!("{a}i0"), format!("{}i12", "b".repeat(64))]);
+
+        // Not an id, or not under /content/
+        assert!(find_references(format!("/content/{}i0 /preview/{a}i0 /mycontent/{a}i0", "a".repeat(63)).as_bytes())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_may_reference() {
+        assert!(may_reference("text/html;charset=utf-8"));
+        assert!(may_reference("image/svg+xml"));
+        assert!(may_reference("application/json"));
+        assert!(!may_reference("image/png"));
+    }
+}
@@ -0,0 +1,68 @@
+// sniff.rs
+//
+// Content type detection from magic bytes, used when an inscription's
+// declared MIME type is missing or doesn't match its body.
+
+/// How far into a body to look for an `<svg` root after an XML prolog
+const SVG_SCAN_LIMIT: usize = 1024;
+
+/// Recognizes common inscription formats from their leading bytes
+///
+/// Detects PNG, JPEG, GIF, WEBP, SVG and PDF. Returns `None` for
+/// anything else, including plain text.
+pub fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        return Some("image/jpeg");
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return Some("image/gif");
+    }
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    if data.starts_with(b"%PDF-") {
+        return Some("application/pdf");
+    }
+    if is_svg(data) {
+        return Some("image/svg+xml");
+    }
+    None
+}
+
+fn is_svg(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(SVG_SCAN_LIMIT)];
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        // The scan limit may split a multi-byte character
+        Err(e) => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+
+    text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_image_signatures() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"), Some("image/png"));
+        assert_eq!(sniff_mime(b"GIF89a\x01\x00\x01\x00"), Some("image/gif"));
+        assert_eq!(sniff_mime(b"GIF87a\x01\x00\x01\x00"), Some("image/gif"));
+        assert_eq!(sniff_mime(&[0xff, 0xd8, 0xff, 0xe0]), Some("image/jpeg"));
+        assert_eq!(sniff_mime(b"RIFF\x24\x00\x00\x00WEBPVP8 "), Some("image/webp"));
+    }
+
+    #[test]
+    fn test_sniff_text_formats() {
+        assert_eq!(sniff_mime(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff_mime(b"  <svg xmlns=\"http://www.w3.org/2000/svg\"/>"), Some("image/svg+xml"));
+        assert_eq!(sniff_mime(b"<?xml version=\"1.0\"?>\n<svg/>"), Some("image/svg+xml"));
+        assert_eq!(sniff_mime(b"Hello, Bitcoin!"), None);
+        assert_eq!(sniff_mime(b""), None);
+    }
+}
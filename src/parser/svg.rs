@@ -0,0 +1,185 @@
+// svg.rs
+//
+// Validation and sanitization of SVG bodies. SVG is XML that browsers
+// execute scripts from, so stored files can optionally have everything
+// that runs code removed.
+
+use roxmltree::{Document, Node, ParsingOptions};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Entities XML defines without a DTD
+const PREDEFINED_ENTITIES: [&str; 5] = ["lt", "gt", "amp", "apos", "quot"];
+
+/// Bound on entity name length; the longest HTML one, `CounterClockwiseContourIntegral`, has 31
+const MAX_ENTITY_NAME: usize = 32;
+
+/// Parses `text` as XML whose root element is `<svg>`
+///
+/// DTDs are allowed since many exported SVGs start with a DOCTYPE. `source` is what gets parsed; see
+/// `mask_html_entities`.
+fn parse<'a>(source: &'a str) -> Option<Document<'a>> {
+    let options = ParsingOptions { allow_dtd: true, ..ParsingOptions::default() };
+    let document = Document::parse_with_options(source, options).ok()?;
+    (document.root_element().tag_name().name() == "svg").then_some(document)
+}
+
+/// Replaces HTML entities such as `&nbsp;`, which browsers render but XML rejects, with numeric
+/// references of the same length so node positions still index into `text`
+///
+/// Returns `text` unchanged when it has none.
+fn mask_html_entities(text: &str) -> Cow<'_, str> {
+    let mut masked: Option<String> = None;
+    let mut rest = 0;
+    while let Some(offset) = text[rest..].find('&') {
+        let start = rest + offset;
+        rest = start + 1;
+        // Only the name itself is scanned, so each `&` costs at most `MAX_ENTITY_NAME` bytes
+        let length = text.as_bytes()[rest..]
+            .iter()
+            .take(MAX_ENTITY_NAME + 1)
+            .take_while(|byte| byte.is_ascii_alphanumeric())
+            .count();
+        if length > MAX_ENTITY_NAME || text.as_bytes().get(rest + length) != Some(&b';') {
+            continue;
+        }
+        let name = &text[rest..rest + length];
+        let is_named = name.starts_with(|c: char| c.is_ascii_alphabetic());
+        // `&#32;` is the shortest stand-in; shorter names can't be masked in place
+        if !is_named || PREDEFINED_ENTITIES.contains(&name) || length + 2 < 5 {
+            continue;
+        }
+        let masked = masked.get_or_insert_with(|| text.to_string());
+        masked.replace_range(start..rest + length + 1, &format!("&#{:0>width$};", 32, width = length - 1));
+        rest += length + 1;
+    }
+    masked.map_or(Cow::Borrowed(text), Cow::Owned)
+}
+
+/// Whether `text` is a well-formed SVG document
+pub fn is_svg_document(text: &str) -> bool {
+    parse(&mask_html_entities(text)).is_some()
+}
+
+/// Whether a browser would run `node` or keep it out of the sandbox: scripts, and
+/// `<foreignObject>` since it embeds arbitrary HTML
+fn is_active_element(node: &Node) -> bool {
+    let name = node.tag_name().name();
+    name.eq_ignore_ascii_case("script") || name.eq_ignore_ascii_case("foreignObject")
+}
+
+/// Whether an attribute runs code: `on*` event handlers, and `javascript:` URLs in links or
+/// animation values
+fn is_active_attribute(name: &str, value: &str) -> bool {
+    if name.len() > 2 && name.as_bytes()[..2].eq_ignore_ascii_case(b"on") {
+        return true;
+    }
+    // Browsers ignore whitespace and control characters inside URL schemes
+    let value: String = value.chars().filter(|c| !c.is_ascii_whitespace() && !c.is_control()).collect();
+    value.to_ascii_lowercase().contains("javascript:")
+}
+
+/// Byte range of the attribute starting at `position`, with the whitespace before it
+fn attribute_range(text: &str, position: usize) -> Option<Range<usize>> {
+    let start = text[..position].trim_end().len();
+    let open = position + text[position..].find(['"', '\''])?;
+    let quote = text[open..].chars().next()?;
+    let close = open + 1 + text[open + 1..].find(quote)?;
+    Some(start..close + 1)
+}
+
+/// Removes scripts, `<foreignObject>` elements, event handler attributes and `javascript:` URLs,
+/// or `None` if `text` isn't a well-formed SVG
+pub fn strip_scripts(text: &str) -> Option<String> {
+    let source = mask_html_entities(text);
+    let document = parse(&source)?;
+    let mut removed: Vec<Range<usize>> = Vec::new();
+    for node in document.descendants().filter(Node::is_element) {
+        if is_active_element(&node) {
+            removed.push(node.range());
+            continue;
+        }
+        for attribute in node.attributes() {
+            if is_active_attribute(attribute.name(), attribute.value()) {
+                removed.push(attribute_range(&source, attribute.position())?);
+            }
+        }
+    }
+    removed.sort_by_key(|range| range.start);
+
+    let mut sanitized = String::with_capacity(text.len());
+    let mut copied = 0;
+    for range in removed {
+        // Anything nested in a removed element is already gone
+        if range.start < copied {
+            continue;
+        }
+        sanitized.push_str(&text[copied..range.start]);
+        copied = range.end;
+    }
+    sanitized.push_str(&text[copied..]);
+    Some(sanitized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_scripts() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><script>alert(1)</script><rect width="1"/><script type="text/javascript"><![CDATA[x()]]></script></svg>"#;
+        assert_eq!(
+            strip_scripts(svg).unwrap(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="1"/></svg>"#
+        );
+        assert_eq!(strip_scripts("<svg><rect/></svg>").unwrap(), "<svg><rect/></svg>");
+    }
+
+    #[test]
+    fn test_rejects_malformed_or_non_svg_documents() {
+        assert!(is_svg_document("<?xml version=\"1.0\"?>\n<!DOCTYPE svg><svg/>"));
+        assert!(!is_svg_document("<svg><rect></svg>"));
+        assert!(!is_svg_document("<html><svg/></html>"));
+        assert!(strip_scripts("<svg><script>").is_none());
+    }
+
+    #[test]
+    fn test_strip_scripts_removes_handlers_links_and_foreign_objects() {
+        let svg = concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" onload="x()">"#,
+            r#"<a xlink:href=" java&#9;script:x()"><rect width="1" ONCLICK='y()'/></a>"#,
+            r#"<set attributeName="href" to="javascript:z()"/>"#,
+            r#"<foreignObject><body onload="w()"><p>hi</p></body></foreignObject>"#,
+            r#"<a href="https://example.com"><circle r="2"/></a></svg>"#,
+        );
+        assert_eq!(
+            strip_scripts(svg).unwrap(),
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#,
+                r#"<a><rect width="1"/></a><set attributeName="href"/>"#,
+                r#"<a href="https://example.com"><circle r="2"/></a></svg>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_html_entities_are_accepted_and_kept() {
+        let svg = "<svg><text title=\"a&nbsp;b\">&copy; 2023 &amp; &#169;</text><script>x()</script></svg>";
+        assert!(is_svg_document(svg));
+        assert_eq!(strip_scripts(svg).unwrap(), "<svg><text title=\"a&nbsp;b\">&copy; 2023 &amp; &#169;</text></svg>");
+    }
+
+    #[test]
+    fn test_non_ascii_attribute_names_are_kept() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" aé="1" oné="x()"/>"#;
+        assert_eq!(strip_scripts(svg).unwrap(), r#"<svg xmlns="http://www.w3.org/2000/svg" aé="1"/>"#);
+    }
+
+    #[test]
+    fn test_many_ampersands_without_semicolons_stay_linear() {
+        let text = format!("<svg><text>{}</text></svg>", "&a".repeat(2 * 1024 * 1024));
+        let started = std::time::Instant::now();
+        assert!(matches!(mask_html_entities(&text), Cow::Borrowed(_)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,72 @@
+// progress.rs
+//
+// Terminal progress bar for long scans, advanced once per stored batch.
+// Rate and ETA come from `Metrics`, the same numbers the final summary prints.
+
+use crate::utils::MetricsSnapshot;
+use indicatif::{HumanDuration, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::time::Duration;
+
+const TEMPLATE: &str = "{bar:40.cyan/blue} {pos}/{len} blocks {msg}";
+
+/// Time left to scan `remaining` blocks at `blocks_per_second`
+///
+/// `None` until there's a rate to go by.
+pub fn eta(blocks_per_second: f64, remaining: u64) -> Option<Duration> {
+    if !blocks_per_second.is_finite() || blocks_per_second <= 0.0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(remaining as f64 / blocks_per_second))
+}
+
+/// Progress bar over the scan range `[start, end)`
+pub struct ScanProgress {
+    bar: ProgressBar,
+    start: u64,
+    end: u64,
+}
+
+impl ScanProgress {
+    /// Draws a bar on stdout for the blocks `start..end`
+    pub fn new(start: u64, end: u64) -> Self {
+        let bar = ProgressBar::with_draw_target(Some(end.saturating_sub(start)), ProgressDrawTarget::stdout());
+        if let Ok(style) = ProgressStyle::with_template(TEMPLATE) {
+            bar.set_style(style.progress_chars("=> "));
+        }
+        Self { bar, start, end }
+    }
+
+    /// Moves the bar to `height`, the next block to scan
+    pub fn update(&self, height: u64, stats: &MetricsSnapshot) {
+        self.bar.set_position(height.saturating_sub(self.start));
+        let remaining = self.end.saturating_sub(height);
+        let eta = match eta(stats.blocks_per_second, remaining) {
+            Some(eta) => HumanDuration(eta).to_string(),
+            None => "unknown".to_string(),
+        };
+        self.bar.set_message(format!("{:.1} blocks/s, ETA {}", stats.blocks_per_second, eta));
+    }
+
+    /// Leaves the bar where it stopped
+    pub fn finish(&self) {
+        self.bar.abandon();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eta_from_rate_and_remaining_blocks() {
+        // 500 blocks in 50 seconds, 2000 to go
+        let rate = 500.0 / Duration::from_secs(50).as_secs_f64();
+        assert_eq!(eta(rate, 2000), Some(Duration::from_secs(200)));
+        assert_eq!(eta(rate, 0), Some(Duration::ZERO));
+
+        // Nothing scanned yet, or no time elapsed
+        assert_eq!(eta(0.0, 2000), None);
+        assert_eq!(eta(f64::NAN, 2000), None);
+        assert_eq!(eta(f64::INFINITY, 2000), None);
+    }
+}
@@ -0,0 +1,102 @@
+// prometheus.rs
+//
+// Optional Prometheus endpoint (`--metrics-addr <addr>`): GET /metrics
+// renders the scan counters in the text exposition format. Every scrape
+// reads the atomics in `Metrics` afresh.
+
+use crate::utils::Metrics;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use log::info;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Content type of the text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Renders the current metrics in Prometheus' text format
+pub fn encode(metrics: &Metrics) -> String {
+    let stats = metrics.get_stats();
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP inscription_scanner_blocks_processed_total Blocks parsed since the scanner started");
+    let _ = writeln!(out, "# TYPE inscription_scanner_blocks_processed_total counter");
+    let _ = writeln!(out, "inscription_scanner_blocks_processed_total {}", stats.blocks_processed);
+
+    let _ = writeln!(out, "# HELP inscription_scanner_inscriptions_found_total Inscriptions stored, by type");
+    let _ = writeln!(out, "# TYPE inscription_scanner_inscriptions_found_total counter");
+    for (kind, count) in [
+        ("text", stats.by_type.text),
+        ("image", stats.by_type.image),
+        ("json", stats.by_type.json),
+        ("binary", stats.by_type.binary),
+        ("unknown", stats.by_type.unknown),
+    ] {
+        let _ = writeln!(out, "inscription_scanner_inscriptions_found_total{{type=\"{}\"}} {}", kind, count);
+    }
+
+    let blocks_per_second = if stats.blocks_per_second.is_finite() { stats.blocks_per_second } else { 0.0 };
+    let _ = writeln!(out, "# HELP inscription_scanner_blocks_per_second Average scan rate since the scanner started");
+    let _ = writeln!(out, "# TYPE inscription_scanner_blocks_per_second gauge");
+    let _ = writeln!(out, "inscription_scanner_blocks_per_second {}", blocks_per_second);
+    out
+}
+
+/// Builds the `/metrics` route over shared metrics
+pub fn router(metrics: Arc<Metrics>) -> Router {
+    Router::new().route("/metrics", get(scrape)).with_state(metrics)
+}
+
+/// Serves `/metrics` on an already bound listener until the task is dropped
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    info!("Serving Prometheus metrics on http://{}/metrics", listener.local_addr()?);
+    axum::serve(listener, router(metrics)).await
+}
+
+async fn scrape(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], encode(&metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Inscription, InscriptionType};
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_reads_live_counters() {
+        let metrics = Arc::new(Metrics::new());
+        let app = router(metrics.clone());
+        let scrape = |app: Router| async move {
+            let response = app.oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], CONTENT_TYPE);
+            String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+        };
+
+        let body = scrape(app.clone()).await;
+        assert!(body.contains("inscription_scanner_blocks_processed_total 0\n"));
+
+        // Counted after the router was built, still in the next scrape
+        metrics.increment_blocks(12);
+        let txid = Txid::all_zeros();
+        metrics.increment_by_type(&Inscription::new(txid, InscriptionType::Text("hi".to_string())));
+        metrics.increment_by_type(&Inscription::new(txid, InscriptionType::Json(serde_json::json!({}))));
+        metrics.increment_by_type(&Inscription::new(txid, InscriptionType::Json(serde_json::json!([]))));
+
+        let body = scrape(app).await;
+        assert!(body.contains("inscription_scanner_blocks_processed_total 12\n"));
+        assert!(body.contains("inscription_scanner_inscriptions_found_total{type=\"text\"} 1\n"));
+        assert!(body.contains("inscription_scanner_inscriptions_found_total{type=\"json\"} 2\n"));
+        assert!(body.contains("inscription_scanner_inscriptions_found_total{type=\"image\"} 0\n"));
+        assert!(body.contains("# TYPE inscription_scanner_blocks_per_second gauge\ninscription_scanner_blocks_per_second "));
+    }
+}
@@ -0,0 +1,408 @@
+// reorg.rs
+//
+// Reorg detection while scanning near the tip.
+//
+// Every batch must extend the last block scanned. When its first block's
+// parent isn't the hash recorded for the height below, the chain was
+// reorganized since: walk the recent hashes back to the fork point, purge
+// what was stored from the orphaned blocks and rescan from there.
+//
+// The fork point is found against a `HeaderChain` when the node can serve
+// headers, so only ~80 bytes per new block are fetched to learn the
+// active chain.
+
+use crate::error::AppError;
+use crate::node::{NodeClient, NodeError};
+use crate::storage::{Storage, REORG_WINDOW};
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::{Block, BlockHash};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Anything that can report the active chain's hash at a height
+#[async_trait]
+pub trait BlockHashSource: Send + Sync {
+    async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError>;
+}
+
+#[async_trait]
+impl BlockHashSource for NodeClient {
+    async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError> {
+        self.get_block_hash(height).await
+    }
+}
+
+/// Anything that can serve block headers and the active chain's tip
+#[async_trait]
+pub trait HeaderSource: Send + Sync {
+    async fn header(&self, hash: &BlockHash) -> Result<Header, NodeError>;
+
+    /// Height and hash of the active chain's tip
+    async fn active_tip(&self) -> Result<(u64, BlockHash), NodeError>;
+}
+
+#[async_trait]
+impl HeaderSource for NodeClient {
+    async fn header(&self, hash: &BlockHash) -> Result<Header, NodeError> {
+        self.get_block_header(hash).await
+    }
+
+    async fn active_tip(&self) -> Result<(u64, BlockHash), NodeError> {
+        let tips = self.get_chain_tips().await?;
+        for tip in tips.iter().filter(|tip| tip.status != "active") {
+            debug!("Chain tip {} at {} is {}, {} blocks off the active chain",
+                tip.hash, tip.height, tip.status, tip.branch_length);
+        }
+        tips.into_iter()
+            .find(|tip| tip.status == "active")
+            .map(|tip| (tip.height, tip.hash))
+            .ok_or_else(|| NodeError::Deserialization("getchaintips reported no active tip".to_string()))
+    }
+}
+
+/// Hashes of the active chain's last `REORG_WINDOW` blocks, learned from headers
+#[derive(Debug, Default)]
+pub struct HeaderChain {
+    hashes: BTreeMap<u64, BlockHash>,
+}
+
+impl HeaderChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follows the active tip's headers back until they join the known hashes
+    ///
+    /// Fetches one header per block that is new or was replaced since the
+    /// last sync, and at most `REORG_WINDOW` of them.
+    pub async fn sync<S: HeaderSource + ?Sized>(&mut self, source: &S) -> Result<(), NodeError> {
+        let (mut height, mut hash) = source.active_tip().await?;
+        let mut fetched = Vec::new();
+        let joined = loop {
+            if self.hashes.get(&height) == Some(&hash) {
+                break true;
+            }
+            if fetched.len() == REORG_WINDOW {
+                break false;
+            }
+            let header = source.header(&hash).await?;
+            fetched.push((height, hash));
+            if height == 0 {
+                break false;
+            }
+            height -= 1;
+            hash = header.prev_blockhash;
+        };
+
+        // Everything above the join point was orphaned or is re-added below
+        if joined {
+            self.hashes.retain(|&known, _| known <= height);
+        } else {
+            self.hashes.clear();
+        }
+        self.hashes.extend(fetched);
+        while self.hashes.len() > REORG_WINDOW {
+            self.hashes.pop_first();
+        }
+        Ok(())
+    }
+
+    /// Whether the view goes back at least to `height`
+    pub fn reaches(&self, height: u64) -> bool {
+        self.hashes.keys().next().is_some_and(|&lowest| lowest <= height)
+    }
+}
+
+#[async_trait]
+impl BlockHashSource for HeaderChain {
+    async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError> {
+        self.hashes
+            .get(&height)
+            .copied()
+            .ok_or_else(|| NodeError::BlockNotFound(height))
+    }
+}
+
+/// Whether `block` builds on `parent`
+///
+/// `parent` is the recorded hash of the height below the block, if any;
+/// without it the block is taken as is.
+pub fn extends(parent: Option<BlockHash>, block: &Block) -> bool {
+    parent.map_or(true, |hash| hash == block.header.prev_blockhash)
+}
+
+/// Highest height in `recent` whose hash is still on the active chain
+///
+/// Heights the source no longer has (the new chain is shorter) count as
+/// orphaned.
+pub async fn find_fork_point<S: BlockHashSource + ?Sized>(
+    source: &S,
+    recent: &BTreeMap<u64, BlockHash>,
+) -> Result<Option<u64>, NodeError> {
+    for (&height, &hash) in recent.iter().rev() {
+        match source.block_hash(height).await {
+            Ok(active) if active == hash => return Ok(Some(height)),
+            Ok(_) | Err(NodeError::BlockNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Rolls storage back to the fork point after a reorg
+///
+/// Inscriptions stored from the orphaned blocks are purged and the resume
+/// cursor moved to the fork point. Returns the height to rescan from.
+pub async fn rewind<S: BlockHashSource + ?Sized>(storage: &Storage, source: &S) -> Result<u64, AppError> {
+    let recent = storage.recent_blocks()?;
+    let fork = find_fork_point(source, &recent).await?.ok_or_else(|| {
+        AppError::Reorg(format!(
+            "none of the last {} scanned blocks is on the active chain; rescan with --start-block",
+            recent.len()
+        ))
+    })?;
+    rewind_to(storage, &recent, fork)
+}
+
+/// Purges what `recent` recorded above `fork` and moves the cursor back to it
+fn rewind_to(storage: &Storage, recent: &BTreeMap<u64, BlockHash>, fork: u64) -> Result<u64, AppError> {
+    let orphaned: BTreeSet<u64> = recent.range(fork + 1..).map(|(&height, _)| height).collect();
+    warn!(
+        "Reorg detected: blocks {} to {} were orphaned, rewinding to block {}",
+        fork + 1,
+        orphaned.iter().last().copied().unwrap_or(fork),
+        fork
+    );
+    let purged = storage.purge_heights(&orphaned)?;
+    storage.rewind_recent_blocks(fork)?;
+    // Skipped heights past the fork are rescanned anyway
+    let mut state = storage.load_scan_state()?.unwrap_or_default();
+    state.last_block = fork;
+    state.skipped.retain(|&height| height <= fork);
+    storage.save_scan_state(&state)?;
+    info!("Removed {} inscriptions from orphaned blocks", purged);
+    Ok(fork + 1)
+}
+
+/// `rewind`, finding the fork point from headers when they reach back far enough
+///
+/// The view is synced with the node's active tip first. When the scan is
+/// further behind the tip than the view goes, or the fork is below the
+/// view's lowest header, `source` is asked per height.
+pub async fn rewind_with_headers<H, S>(
+    storage: &Storage,
+    chain: &mut HeaderChain,
+    headers: &H,
+    source: &S,
+) -> Result<u64, AppError>
+where
+    H: HeaderSource + ?Sized,
+    S: BlockHashSource + ?Sized,
+{
+    chain.sync(headers).await?;
+    let recent = storage.recent_blocks()?;
+    let fork = match recent.keys().next_back() {
+        Some(&scanned) if chain.reaches(scanned) => find_fork_point(chain, &recent).await?,
+        _ => None,
+    };
+    match fork {
+        Some(fork) => rewind_to(storage, &recent, fork),
+        None => rewind(storage, source).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ScanState;
+    use crate::parser::{Inscription, InscriptionType};
+    use crate::test_utils::dummy_header;
+    use bitcoin::block::Header;
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    /// Serves the hashes of whichever chain it currently holds
+    struct MockChain {
+        hashes: Mutex<BTreeMap<u64, BlockHash>>,
+    }
+
+    #[async_trait]
+    impl BlockHashSource for MockChain {
+        async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError> {
+            self.hashes
+                .lock()
+                .unwrap()
+                .get(&height)
+                .copied()
+                .ok_or_else(|| NodeError::BlockNotFound(height))
+        }
+    }
+
+    /// Serves the headers of every block it was given, with the last one as the tip
+    struct MockHeaders {
+        headers: Mutex<HashMap<BlockHash, Header>>,
+        tip: Mutex<(u64, BlockHash)>,
+        calls: AtomicUsize,
+    }
+
+    impl MockHeaders {
+        fn new(blocks: &[(u64, Block)]) -> Self {
+            let source = Self {
+                headers: Mutex::new(HashMap::new()),
+                tip: Mutex::new((0, BlockHash::all_zeros())),
+                calls: AtomicUsize::new(0),
+            };
+            source.extend(blocks);
+            source
+        }
+
+        fn extend(&self, blocks: &[(u64, Block)]) {
+            let mut headers = self.headers.lock().unwrap();
+            for (height, block) in blocks {
+                headers.insert(block.block_hash(), block.header);
+                *self.tip.lock().unwrap() = (*height, block.block_hash());
+            }
+        }
+    }
+
+    #[async_trait]
+    impl HeaderSource for MockHeaders {
+        async fn header(&self, hash: &BlockHash) -> Result<Header, NodeError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.headers
+                .lock()
+                .unwrap()
+                .get(hash)
+                .copied()
+                .ok_or_else(|| NodeError::UnknownBlock(hash.to_string()))
+        }
+
+        async fn active_tip(&self) -> Result<(u64, BlockHash), NodeError> {
+            Ok(*self.tip.lock().unwrap())
+        }
+    }
+
+    /// Builds `count` linked blocks on top of `parent`, varied by `nonce`
+    fn chain(parent: BlockHash, start: u64, count: u64, nonce: u32) -> Vec<(u64, Block)> {
+        let mut prev_blockhash = parent;
+        (start..start + count)
+            .map(|height| {
+                let block = Block {
+                    header: Header { prev_blockhash, time: height as u32, nonce, ..dummy_header() },
+                    txdata: vec![],
+                };
+                prev_blockhash = block.block_hash();
+                (height, block)
+            })
+            .collect()
+    }
+
+    fn hashes(blocks: &[(u64, Block)]) -> Vec<(u64, BlockHash)> {
+        blocks.iter().map(|(height, block)| (*height, block.block_hash())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_two_block_reorg_rewinds_to_the_fork() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        // First pass: blocks 0..10, with an inscription stored from block 9
+        let first = chain(BlockHash::all_zeros(), 0, 10, 0);
+        let txid = Txid::from_byte_array([9; 32]);
+        let mut inscription = Inscription::new(txid, InscriptionType::Text("orphaned".to_string()));
+        inscription.block_height = 9;
+        storage.store_inscription(&inscription).await.unwrap();
+        storage.record_blocks(&hashes(&first[9..])).unwrap();
+        storage.record_recent_blocks(&hashes(&first)).unwrap();
+
+        // Second pass: blocks 8 and 9 were replaced and 10 builds on the new 9
+        let second = [&first[..8], &chain(first[7].1.block_hash(), 8, 3, 1)[..]].concat();
+        let source = MockChain { hashes: Mutex::new(hashes(&second).into_iter().collect()) };
+
+        let recent = storage.recent_blocks().unwrap();
+        let parent = recent.get(&9).copied();
+        assert!(!extends(parent, &second[10].1));
+        assert!(extends(Some(first[7].1.block_hash()), &second[8].1));
+        assert!(extends(None, &second[10].1));
+
+        assert_eq!(find_fork_point(&source, &recent).await.unwrap(), Some(7));
+        assert_eq!(rewind(&storage, &source).await.unwrap(), 8);
+
+        // The orphaned inscription is gone and the cursor sits at the fork
+        assert_eq!(storage.entries().unwrap().count(), 0);
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(7)));
+        assert_eq!(storage.recent_blocks().unwrap().keys().last(), Some(&7));
+
+        // A chain sharing nothing with the recorded window can't be rewound
+        storage.record_recent_blocks(&hashes(&first)).unwrap();
+        *source.hashes.lock().unwrap() = hashes(&chain(BlockHash::all_zeros(), 0, 10, 2)).into_iter().collect();
+        assert!(matches!(rewind(&storage, &source).await, Err(AppError::Reorg(_))));
+    }
+
+    #[tokio::test]
+    async fn test_header_chain_follows_a_reorg_from_headers() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        let first = chain(BlockHash::all_zeros(), 0, 10, 0);
+        storage.record_recent_blocks(&hashes(&first)).unwrap();
+        let headers = MockHeaders::new(&first);
+        let mut view = HeaderChain::new();
+        view.sync(&headers).await.unwrap();
+        assert_eq!(view.block_hash(9).await.unwrap(), first[9].1.block_hash());
+        assert!(view.reaches(0));
+
+        // Blocks 8 and 9 are replaced and 10 is added: only their headers are fetched
+        let replacement = chain(first[7].1.block_hash(), 8, 3, 1);
+        headers.extend(&replacement);
+        headers.calls.store(0, Ordering::SeqCst);
+        view.sync(&headers).await.unwrap();
+        assert_eq!(headers.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(view.block_hash(10).await.unwrap(), replacement[2].1.block_hash());
+        assert_eq!(view.block_hash(8).await.unwrap(), replacement[0].1.block_hash());
+        assert_eq!(view.block_hash(7).await.unwrap(), first[7].1.block_hash());
+
+        // The view alone locates the fork; the fallback source is never asked
+        let unused = MockChain { hashes: Mutex::new(BTreeMap::new()) };
+        assert_eq!(rewind_with_headers(&storage, &mut view, &headers, &unused).await.unwrap(), 8);
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(7)));
+    }
+
+    #[tokio::test]
+    async fn test_fork_below_the_header_view_falls_back_to_the_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        // Scanned 0..10, then blocks from 3 on were replaced and the new chain
+        // grew so far that the header view starts at 5, above the fork at 2
+        let first = chain(BlockHash::all_zeros(), 0, 10, 0);
+        storage.record_recent_blocks(&hashes(&first)).unwrap();
+        let replacement = chain(first[2].1.block_hash(), 3, REORG_WINDOW as u64 + 2, 1);
+        let second = [&first[..3], &replacement[..]].concat();
+        let headers = MockHeaders::new(&second);
+        let mut view = HeaderChain::new();
+        view.sync(&headers).await.unwrap();
+        assert!(view.reaches(9) && !view.reaches(4));
+
+        let source = MockChain { hashes: Mutex::new(hashes(&second).into_iter().collect()) };
+        assert_eq!(rewind_with_headers(&storage, &mut view, &headers, &source).await.unwrap(), 3);
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(2)));
+    }
+}
@@ -0,0 +1,179 @@
+// reprocess.rs
+//
+// Re-runs the current classifier over archived raw envelopes for a
+// height range, without touching the Bitcoin node.
+
+use crate::parser::InscriptionParser;
+use crate::storage::{RawArchive, Result, Storage, StorageError};
+use log::info;
+
+/// Re-parses archived transactions for `start..=end` and stores the results
+///
+/// The whole range is validated against the archive before anything is
+/// written, so a partially archived range leaves storage untouched. What
+/// was stored from the range before is purged first, so the results
+/// replace it. Returns the number of inscriptions stored.
+pub async fn reprocess_range(
+    archive: &RawArchive,
+    parser: &InscriptionParser,
+    storage: &Storage,
+    start: u64,
+    end: u64,
+) -> Result<usize> {
+    if end < start {
+        return Err(StorageError::ArchiveError(format!(
+            "invalid range: end {} is before start {}", end, start
+        )));
+    }
+    if let Some(missing) = (start..=end).find(|&height| !archive.contains(height)) {
+        return Err(StorageError::ArchiveError(format!(
+            "height {} is not archived, cannot reprocess {} to {}", missing, start, end
+        )));
+    }
+
+    storage.purge_heights(&(start..=end).collect())?;
+
+    let mut stored = 0;
+    let mut blocks = Vec::new();
+    for height in start..=end {
+        let block = archive.load(height)?;
+        let mut stored_here = false;
+        for tx in &block.txdata {
+            for mut inscription in parser.parse_transaction_all(tx) {
+                inscription.block_height = height;
+                // Heights archived before headers were kept have no block time
+                inscription.block_time = block.header.map_or(0, |header| header.time);
+                if storage.store_inscription(&inscription).await? {
+                    stored += 1;
+                    stored_here = true;
+                }
+            }
+        }
+        // The purge dropped the range's block hashes; put back those the scanner would record
+        if let (true, Some(header)) = (stored_here, block.header) {
+            blocks.push((height, header.block_hash()));
+        }
+    }
+
+    stored += storage.resolve_pending_delegates().await?;
+    storage.record_blocks(&blocks)?;
+
+    info!("Reprocessed blocks {} to {}: {} inscriptions stored", start, end, stored);
+    Ok(stored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::dummy_header;
+    use bitcoin::blockdata::opcodes::all::{OP_ENDIF, OP_IF};
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::opcodes::OP_FALSE;
+    use bitcoin::script::PushBytesBuf;
+    use bitcoin::block::Header;
+    use bitcoin::{Block, Transaction, TxOut};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    // A text envelope whose body is gzipped without declaring tag 9, so only
+    // the lenient classifier recovers it
+    fn gzipped_text_block(height: u64) -> Block {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        write!(encoder, "block {}", height).unwrap();
+        let body = PushBytesBuf::try_from(encoder.finish().unwrap()).unwrap();
+
+        let script = Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice(b"\x01")
+            .push_slice(b"text/plain;charset=utf-8")
+            .push_opcode(OP_FALSE)
+            .push_slice(&body)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        Block {
+            header: Header { time: height as u32, ..dummy_header() },
+            txdata: vec![Transaction {
+                version: 2,
+                lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![TxOut { value: 0, script_pubkey: script }],
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_only_touches_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = RawArchive::new(temp_dir.path().join("raw")).unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        for height in 0..4 {
+            archive.archive_block(height, &gzipped_text_block(height)).unwrap();
+        }
+
+        // The strict classifier can't read the undeclared gzip bodies
+        let strict = InscriptionParser::new();
+        assert_eq!(reprocess_range(&archive, &strict, &storage, 0, 3).await.unwrap(), 0);
+
+        // After switching classifiers, reprocess just heights 1 and 2
+        let lenient = InscriptionParser::new().with_lenient(true);
+        assert_eq!(reprocess_range(&archive, &lenient, &storage, 1, 2).await.unwrap(), 2);
+
+        let bodies: Vec<_> = storage
+            .entries()
+            .unwrap()
+            .map(|entry| String::from_utf8(entry.unwrap().body).unwrap())
+            .collect();
+        assert_eq!(bodies, vec!["block 1", "block 2"]);
+
+        // Results from the earlier classifier are replaced, not kept alongside
+        assert_eq!(reprocess_range(&archive, &lenient, &storage, 1, 2).await.unwrap(), 2);
+        assert_eq!(reprocess_range(&archive, &strict, &storage, 2, 2).await.unwrap(), 0);
+        let bodies: Vec<_> = storage
+            .entries()
+            .unwrap()
+            .map(|entry| String::from_utf8(entry.unwrap().body).unwrap())
+            .collect();
+        assert_eq!(bodies, vec!["block 1"]);
+
+        // Ranges reaching past the archive are rejected up front
+        assert!(reprocess_range(&archive, &lenient, &storage, 2, 5).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_keeps_block_times_and_hashes() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = RawArchive::new(temp_dir.path().join("raw")).unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        let blocks: Vec<_> = (10..13).map(gzipped_text_block).collect();
+        for (height, block) in (10..13).zip(&blocks) {
+            archive.archive_block(height, block).unwrap();
+        }
+        let hashes: Vec<_> = (10..13).zip(blocks.iter().map(Block::block_hash)).collect();
+        storage.record_blocks(&hashes).unwrap();
+
+        let lenient = InscriptionParser::new().with_lenient(true);
+        assert_eq!(reprocess_range(&archive, &lenient, &storage, 10, 12).await.unwrap(), 3);
+
+        assert_eq!(storage.recorded_blocks().unwrap().into_iter().collect::<Vec<_>>(), hashes);
+        let path = temp_dir.path().join("export.json");
+        crate::storage::export(&storage, &path).unwrap();
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let times: Vec<_> = rows.iter().map(|row| (row["block_height"].clone(), row["timestamp"].clone())).collect();
+        assert_eq!(times, (10..13).map(|height| (height.into(), height.into())).collect::<Vec<_>>());
+    }
+}
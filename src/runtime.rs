@@ -0,0 +1,89 @@
+// runtime.rs
+//
+// Sizing of the tokio runtime against the rayon parsing pool.
+//
+// Both pools are CPU-bound at times, so they share one budget of
+// `available_cpus()` threads: tokio gets its configured (or default) share
+// and rayon the rest, instead of each sizing itself to every core.
+
+use crate::config::Config;
+use crate::utils::available_cpus;
+use log::info;
+use tokio::runtime::{Builder, Runtime};
+
+/// Thread counts for the tokio workers and the rayon parsing pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadBudget {
+    pub tokio_workers: usize,
+    pub rayon_threads: usize,
+}
+
+impl ThreadBudget {
+    /// Splits `cpus` between the pools
+    ///
+    /// The node client only waits on RPC (blocking calls run on tokio's
+    /// separate blocking pool), so by default tokio gets a quarter of the
+    /// cores and parsing the rest. Each pool always gets at least one thread,
+    /// and tokio never more than `cpus - 1`, so parsing keeps a core of its own.
+    pub fn plan(cpus: usize, tokio_workers: Option<usize>) -> Self {
+        let cpus = cpus.max(1);
+        let tokio_workers = tokio_workers.unwrap_or(cpus / 4).clamp(1, (cpus - 1).max(1));
+        Self {
+            tokio_workers,
+            rayon_threads: cpus.saturating_sub(tokio_workers).max(1),
+        }
+    }
+
+    /// Plans from the available cores; an explicit `processing.thread_count`
+    /// sets the parser threads regardless of the split
+    pub fn from_config(config: &Config) -> Self {
+        let budget = Self::plan(available_cpus(), config.processing.tokio_worker_threads);
+        match config.processing.thread_count {
+            Some(threads) => Self { rayon_threads: threads.max(1), ..budget },
+            None => budget,
+        }
+    }
+}
+
+/// Builds the multi-threaded runtime with the budget's worker count
+pub fn build_runtime(budget: &ThreadBudget) -> std::io::Result<Runtime> {
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(budget.tokio_workers)
+        .enable_all()
+        .build()?;
+
+    info!(
+        "Runtime: {} tokio worker(s), {} rayon parser thread(s)",
+        runtime.metrics().num_workers(),
+        budget.rayon_threads
+    );
+    Ok(runtime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_never_exceeds_cores() {
+        assert_eq!(ThreadBudget::plan(16, None), ThreadBudget { tokio_workers: 4, rayon_threads: 12 });
+        assert_eq!(ThreadBudget::plan(16, Some(6)), ThreadBudget { tokio_workers: 6, rayon_threads: 10 });
+        assert_eq!(ThreadBudget::plan(2, None), ThreadBudget { tokio_workers: 1, rayon_threads: 1 });
+
+        // Asking for more workers than cores is capped so rayon keeps a core
+        assert_eq!(ThreadBudget::plan(4, Some(32)), ThreadBudget { tokio_workers: 3, rayon_threads: 1 });
+        assert_eq!(ThreadBudget::plan(4, Some(4)), ThreadBudget { tokio_workers: 3, rayon_threads: 1 });
+        assert_eq!(ThreadBudget::plan(1, Some(0)), ThreadBudget { tokio_workers: 1, rayon_threads: 1 });
+    }
+
+    #[test]
+    fn test_runtime_uses_configured_workers() {
+        let mut config = Config::default();
+        config.processing.tokio_worker_threads = Some(3);
+        let budget = ThreadBudget::plan(8, config.processing.tokio_worker_threads);
+
+        let runtime = build_runtime(&budget).unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 3);
+        assert_eq!(runtime.block_on(async { 1 + 1 }), 2);
+    }
+}
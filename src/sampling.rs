@@ -0,0 +1,117 @@
+// sampling.rs
+//
+// Cheap pre-scan estimate of what a full scan would find.
+//
+// A handful of random heights across the requested range are parsed and
+// the inscriptions tallied by content type, giving an estimated type
+// distribution and inscription density without storing anything.
+
+use crate::parser::Inscription;
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::SeedableRng;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Picks up to `count` distinct heights from `start..end`, in ascending order
+///
+/// The same `seed` always yields the same sample, so reports are reproducible.
+pub fn sample_heights(start: u64, end: u64, count: usize, seed: u64) -> Vec<u64> {
+    let span = end.saturating_sub(start) as usize;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut heights: Vec<u64> = index::sample(&mut rng, span, count.min(span))
+        .into_iter()
+        .map(|offset| start + offset as u64)
+        .collect();
+    heights.sort_unstable();
+    heights
+}
+
+/// Content-type distribution over the sampled blocks
+#[derive(Debug, Default)]
+pub struct ContentTypeStats {
+    pub blocks_sampled: u64,
+    pub inscriptions: u64,
+    pub by_type: BTreeMap<String, u64>,
+}
+
+impl ContentTypeStats {
+    /// Tallies the inscriptions found in `blocks` sampled blocks
+    pub fn record(&mut self, blocks: u64, inscriptions: &[Inscription]) {
+        self.blocks_sampled += blocks;
+        self.inscriptions += inscriptions.len() as u64;
+        for inscription in inscriptions {
+            let essence = inscription.mime_type().split(';').next().unwrap_or("").trim().to_string();
+            *self.by_type.entry(essence).or_insert(0) += 1;
+        }
+    }
+
+    /// Average inscriptions per sampled block
+    pub fn density(&self) -> f64 {
+        if self.blocks_sampled == 0 {
+            return 0.0;
+        }
+        self.inscriptions as f64 / self.blocks_sampled as f64
+    }
+}
+
+impl fmt::Display for ContentTypeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Content-type sample:")?;
+        writeln!(f, "  Blocks Sampled: {}", self.blocks_sampled)?;
+        writeln!(f, "  Inscriptions Found: {}", self.inscriptions)?;
+        writeln!(f, "  Inscriptions/Block: {:.4}", self.density())?;
+
+        let mut types: Vec<_> = self.by_type.iter().collect();
+        types.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (content_type, count) in types {
+            let share = *count as f64 * 100.0 / self.inscriptions as f64;
+            writeln!(f, "  {}: {} ({:.1}%)", content_type, count, share)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::InscriptionType;
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    #[test]
+    fn test_sample_heights() {
+        let heights = sample_heights(100, 200, 10, 7);
+        assert_eq!(heights.len(), 10);
+        assert!(heights.windows(2).all(|w| w[0] < w[1]));
+        assert!(heights.iter().all(|h| (100..200).contains(h)));
+        assert_eq!(heights, sample_heights(100, 200, 10, 7));
+
+        // Asking for more heights than the range has returns the whole range
+        assert_eq!(sample_heights(5, 8, 10, 0), vec![5, 6, 7]);
+        assert!(sample_heights(8, 5, 10, 0).is_empty());
+    }
+
+    #[test]
+    fn test_distribution_report() {
+        let txid = Txid::all_zeros();
+        let mut stats = ContentTypeStats::default();
+        stats.record(2, &[
+            Inscription::new(txid, InscriptionType::Text("a".to_string())),
+            Inscription::new(txid, InscriptionType::Text("b".to_string())),
+            Inscription::new(txid, InscriptionType::Image {
+                mime_type: "image/png".to_string(),
+                data: vec![1],
+            }),
+        ]);
+        stats.record(2, &[]);
+
+        assert_eq!(stats.by_type["text/plain"], 2);
+        assert_eq!(stats.by_type["image/png"], 1);
+        assert!((stats.density() - 0.75).abs() < f64::EPSILON);
+
+        let report = stats.to_string();
+        assert!(report.contains("text/plain: 2 (66.7%)"));
+        assert!(report.contains("image/png: 1 (33.3%)"));
+    }
+}
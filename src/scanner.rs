@@ -0,0 +1,960 @@
+// scanner.rs
+//
+// The scan loop: stream a batch of blocks from a bounded fetch task, parse
+// them in chunks as they arrive, route alert matches, store every
+// inscription and only then advance the resume cursor, until the range is
+// done or a shutdown is requested.
+//
+// Blocks come from a `BlockSource`, either the node or generated mock
+// blocks, so the loop can be driven without a running bitcoind.
+
+use crate::alerts::Alerts;
+use crate::cache::CacheDb;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::maintenance::Maintenance;
+use crate::node::{NodeClient, NodeError};
+use crate::parser::ParallelParser;
+use crate::progress::ScanProgress;
+use crate::reorg::{self, BlockHashSource, HeaderChain, HeaderSource};
+use crate::shutdown::Shutdown;
+use crate::storage::{RawArchive, Storage, StorageError};
+use crate::tui::{Dashboard, DashboardEvent};
+use crate::utils::Metrics;
+use async_trait::async_trait;
+use bitcoin::block::{Header, Version};
+use bitcoin::blockdata::opcodes::all::{OP_ENDIF, OP_IF};
+use bitcoin::blockdata::script::Builder;
+use bitcoin::hash_types::TxMerkleNode;
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::{OP_0, OP_FALSE};
+use bitcoin::pow::CompactTarget;
+use bitcoin::script::PushBytesBuf;
+use bitcoin::{Block, BlockHash, Transaction, TxOut};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// Where the scanner gets its blocks from
+#[async_trait]
+pub trait BlockSource: BlockHashSource {
+    /// Blocks for heights `start..end`, in height order
+    async fn blocks(&self, start: u64, end: u64) -> Result<Vec<Block>, NodeError>;
+
+    /// Whether consecutive blocks link to each other, so batches can be
+    /// checked for reorgs
+    fn is_chain(&self) -> bool {
+        true
+    }
+}
+
+#[async_trait]
+impl BlockSource for NodeClient {
+    async fn blocks(&self, start: u64, end: u64) -> Result<Vec<Block>, NodeError> {
+        self.get_blocks_range(start, end).await
+    }
+}
+
+/// Generated blocks with one inscription each, for `--mock`
+pub struct MockSource;
+
+#[async_trait]
+impl BlockHashSource for MockSource {
+    async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError> {
+        Ok(create_mock_inscription_block(height).block_hash())
+    }
+}
+
+#[async_trait]
+impl BlockSource for MockSource {
+    async fn blocks(&self, start: u64, end: u64) -> Result<Vec<Block>, NodeError> {
+        Ok((start..end).map(create_mock_inscription_block).collect())
+    }
+
+    fn is_chain(&self) -> bool {
+        false
+    }
+}
+
+/// 1x1 grayscale PNG used as the body of mock image inscriptions
+const MOCK_PNG: [u8; 67] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00,
+    0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x7e, 0x9b, 0x55, 0x00, 0x00, 0x00,
+    0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x48, 0xaf, 0xa4,
+    0x71, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+/// Content type and body of the mock inscription at `height`
+///
+/// Cycles through text, a PNG image, JSON and an unknown binary type, so
+/// mock scans exercise every storage path.
+fn mock_inscription(height: u64) -> (&'static str, Vec<u8>) {
+    match height % 4 {
+        0 => ("text/plain;charset=utf-8", format!("Hello from block {}!", height).into_bytes()),
+        1 => ("image/png", MOCK_PNG.to_vec()),
+        2 => ("application/json", format!(r#"{{"p":"mock","height":{}}}"#, height).into_bytes()),
+        _ => ("application/octet-stream", [b"\x00mock".as_slice(), &height.to_le_bytes()].concat()),
+    }
+}
+
+/// Creates a mock block containing a test inscription
+/// 
+/// This function generates a valid Bitcoin block structure with a single
+/// transaction containing an inscription. Used for testing the scanner
+/// without requiring a Bitcoin node connection. The inscription's type
+/// depends on `height % 4`; see `mock_inscription`.
+///
+/// Parameters:
+/// - height: Block height, used to generate unique content
+///
+/// Returns:
+/// - Block: A complete Bitcoin block with test inscription
+///
+/// Technical Details:
+/// - Creates valid script following ordinal inscription format
+/// - Uses standard OP_FALSE OP_IF "ord" pattern
+/// - Includes proper content type tag and body
+/// - Sets valid block header fields
+pub fn create_mock_inscription_block(height: u64) -> Block {
+    // Create inscription script following ordinal protocol
+    // Format: OP_FALSE OP_IF "ord" 1 <content-type> OP_0 <content> OP_ENDIF
+    let (mime_type, body) = mock_inscription(height);
+    let mut content_type = PushBytesBuf::new();
+    content_type.extend_from_slice(mime_type.as_bytes()).unwrap();
+
+    let mut content = PushBytesBuf::new();
+    content.extend_from_slice(&body).unwrap();
+
+    // Build complete inscription script
+    let script = Builder::new()
+        .push_opcode(OP_FALSE)  // Standard inscription marker
+        .push_opcode(OP_IF)     // Start conditional
+        .push_slice(b"ord")     // Protocol identifier
+        .push_slice(b"\x01")    // Content type tag
+        .push_slice(&content_type)
+        .push_opcode(OP_0)      // Body tag
+        .push_slice(&content)
+        .push_opcode(OP_ENDIF)  // End conditional
+        .into_script();
+
+    // Create transaction with inscription output; the lock time keeps
+    // txids unique when bodies repeat (every mock image is the same PNG)
+    let tx = Transaction {
+        version: 2,
+        lock_time: bitcoin::locktime::absolute::LockTime::from_consensus(height as u32),
+        input: vec![],
+        output: vec![TxOut {
+            value: 0,            // Inscriptions typically use zero-value outputs
+            script_pubkey: script,
+        }],
+    };
+
+    // Generate deterministic block header
+    let zeros = [0u8; 32];
+    let prev_blockhash = bitcoin::BlockHash::from_slice(&zeros).unwrap();
+    let merkle_root = TxMerkleNode::from_slice(&zeros).unwrap();
+
+    Block {
+        header: Header {
+            version: Version::ONE,
+            prev_blockhash,
+            merkle_root,
+            time: height as u32,  // Use height as timestamp for deterministic testing
+            bits: CompactTarget::from_consensus(0x1d00ffff),
+            nonce: 0,
+        },
+        txdata: vec![tx],
+    }
+}
+
+
+/// Fetches `range` on a background task and streams the blocks back in height order
+///
+/// Blocks are fetched in windows of half of `max_in_flight`, and a window
+/// is only fetched once the channel has room for all of it, so no more than
+/// `max_in_flight` fetched blocks ever wait for the receiver. When a window
+/// fails its blocks are fetched one at a time, so only the heights that
+/// still fail are sent as errors; dropping the receiver stops the fetching.
+pub fn fetch_blocks(
+    source: Arc<dyn BlockSource>,
+    range: Range<u64>,
+    max_in_flight: usize,
+) -> mpsc::Receiver<Result<(u64, Block), (u64, NodeError)>> {
+    let max_in_flight = max_in_flight.max(1);
+    let window = (max_in_flight / 2).max(1) as u64;
+    let (sender, receiver) = mpsc::channel(max_in_flight);
+
+    tokio::spawn(async move {
+        let mut start = range.start;
+        while start < range.end {
+            let end = std::cmp::min(start + window, range.end);
+            let mut permits = Vec::with_capacity((end - start) as usize);
+            for _ in start..end {
+                match sender.reserve().await {
+                    Ok(permit) => permits.push(permit),
+                    Err(_) => return,
+                }
+            }
+            match source.blocks(start, end).await {
+                Ok(blocks) => {
+                    for (permit, fetched) in permits.into_iter().zip((start..end).zip(blocks)) {
+                        permit.send(Ok(fetched));
+                    }
+                }
+                Err(e) if end - start == 1 => {
+                    if let Some(permit) = permits.pop() {
+                        permit.send(Err((start, e)));
+                    }
+                }
+                Err(e) => {
+                    warn!("Fetching blocks {} to {} failed ({}), retrying them one at a time", start, end, e);
+                    for (permit, height) in permits.into_iter().zip(start..end) {
+                        if sender.is_closed() {
+                            return;
+                        }
+                        let fetched = source.blocks(height, height + 1).await.and_then(|blocks| {
+                            blocks.into_iter().next().ok_or_else(|| NodeError::BlockNotFound(height))
+                        });
+                        permit.send(fetched.map(|block| (height, block)).map_err(|e| (height, e)));
+                    }
+                }
+            }
+            start = end;
+        }
+    });
+    receiver
+}
+
+/// A block that couldn't be fetched and was skipped
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockFailure {
+    pub height: u64,
+    pub error: String,
+}
+
+/// Count of skipped blocks followed by the first `shown` of them
+pub fn failure_summary(failures: &[BlockFailure], shown: usize) -> String {
+    let mut out = format!("{} block(s) skipped after fetch errors", failures.len());
+    for failure in failures.iter().take(shown) {
+        let _ = write!(out, "\n  block {}: {}", failure.height, failure.error);
+    }
+    if failures.len() > shown {
+        let _ = write!(out, "\n  ... and {} more", failures.len() - shown);
+    }
+    out
+}
+
+/// Writes `failures` to `path` as a JSON array
+pub fn write_failures(path: &Path, failures: &[BlockFailure]) -> std::io::Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(failures)?)
+}
+
+/// How a batch ended
+enum Batch {
+    /// Every block was stored and the cursor advanced
+    Stored,
+    /// A reorg was rolled back; scanning resumes at this height
+    Rewound(u64),
+    /// The chain changed mid-fetch; the batch is fetched again
+    Refetch,
+    /// The chain ends at this height; the blocks below it were stored
+    EndOfChain(u64),
+}
+
+/// What a batch's parsed chunks stored so far
+#[derive(Default)]
+struct StoredChunk {
+    inscriptions: usize,
+    heights: HashSet<u64>,
+    /// First store failure; the batch fails once its blocks are processed
+    error: Option<StorageError>,
+}
+
+/// Drives the scan loop over a block source
+///
+/// Built with the required parts, then optional ones attached with the
+/// `with_*` methods.
+pub struct Scanner<'a> {
+    source: Arc<dyn BlockSource>,
+    parser: &'a ParallelParser,
+    storage: &'a Storage,
+    metrics: &'a Metrics,
+    config: &'a Config,
+    dry_run: bool,
+    alerts: Option<&'a Alerts>,
+    archive: Option<&'a RawArchive>,
+    cache: Option<&'a CacheDb>,
+    dashboard: Option<&'a Dashboard>,
+    progress: Option<&'a ScanProgress>,
+    maintenance: Option<&'a Maintenance>,
+    shutdown: Option<&'a Shutdown>,
+    fail_fast: bool,
+    failures: Mutex<Vec<BlockFailure>>,
+    headers: Option<Arc<dyn HeaderSource>>,
+    header_chain: tokio::sync::Mutex<HeaderChain>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(
+        source: Arc<dyn BlockSource>,
+        parser: &'a ParallelParser,
+        storage: &'a Storage,
+        metrics: &'a Metrics,
+        config: &'a Config,
+    ) -> Self {
+        Self {
+            source,
+            parser,
+            storage,
+            metrics,
+            config,
+            dry_run: false,
+            alerts: None,
+            archive: None,
+            cache: None,
+            dashboard: None,
+            progress: None,
+            maintenance: None,
+            shutdown: None,
+            fail_fast: false,
+            failures: Mutex::new(Vec::new()),
+            headers: None,
+            header_chain: tokio::sync::Mutex::new(HeaderChain::new()),
+        }
+    }
+
+    /// Only logs alert matches instead of emitting them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_alerts(mut self, alerts: &'a Alerts) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Keeps the raw envelopes of every fetched block
+    pub fn with_archive(mut self, archive: Option<&'a RawArchive>) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Mirrors the resume cursor into the cache and flushes it every batch
+    pub fn with_cache(mut self, cache: Option<&'a CacheDb>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_dashboard(mut self, dashboard: Option<&'a Dashboard>) -> Self {
+        self.dashboard = dashboard;
+        self
+    }
+
+    /// Advances a progress bar after every stored batch
+    pub fn with_progress(mut self, progress: Option<&'a ScanProgress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Runs scheduled upkeep between batches, once the cursor is saved
+    pub fn with_maintenance(mut self, maintenance: Option<&'a Maintenance>) -> Self {
+        self.maintenance = maintenance;
+        self
+    }
+
+    /// Fails the scan on the first block that can't be fetched instead of skipping it
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Locates reorg fork points from block headers instead of a hash lookup per height
+    pub fn with_headers(mut self, headers: Option<Arc<dyn HeaderSource>>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Blocks skipped so far because they couldn't be fetched, in height order
+    pub fn failures(&self) -> Vec<BlockFailure> {
+        self.failures.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Stops between batches once a shutdown is requested
+    pub fn with_shutdown(mut self, shutdown: &'a Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Scans `range` in batches of `processing.batch_size` blocks
+    ///
+    /// The resume cursor only advances once a whole batch is stored, so an
+    /// interrupted or failed batch is re-processed rather than skipped.
+    /// Blocks that can't be fetched are skipped and recorded in
+    /// `failures()`, or fail the scan with `with_fail_fast`; a height the
+    /// chain doesn't have yet ends the scan instead. Returns the height the
+    /// scan stopped at: `range.end` unless a shutdown was requested or the
+    /// chain ended first.
+    pub async fn run(&self, range: Range<u64>) -> Result<u64, AppError> {
+        self.scan(range, false).await
+    }
+
+    /// Fetches the heights an earlier scan skipped again, as a rescan of each
+    ///
+    /// Heights that are stored this time are dropped from the saved state;
+    /// the ones that fail again stay there for the next resume.
+    pub async fn retry_skipped(&self) -> Result<(), AppError> {
+        let mut state = match self.storage.load_scan_state()? {
+            Some(state) if !state.skipped.is_empty() => state,
+            _ => return Ok(()),
+        };
+        info!("Retrying {} block(s) skipped by an earlier scan", state.skipped.len());
+        let mut still_skipped = Vec::new();
+        for &height in &state.skipped {
+            let failed_before = self.failures().len();
+            self.scan(height..height + 1, true).await?;
+            if self.failures().len() > failed_before {
+                still_skipped.push(height);
+            }
+        }
+        state.skipped = still_skipped;
+        self.storage.save_scan_state(&state)?;
+        Ok(())
+    }
+
+    /// Replaces what's stored for the already scanned blocks in `range`
+    ///
+    /// Each batch is fetched in full, then what was stored from its heights
+    /// is purged and replaced. Heights that can't be fetched keep what they
+    /// had. The resume cursor and the reorg window are left alone, so a
+    /// rescan can run behind the main scan, and running it twice leaves the
+    /// same contents as running it once.
+    pub async fn rescan(&self, range: Range<u64>) -> Result<u64, AppError> {
+        self.scan(range, true).await
+    }
+
+    async fn scan(&self, range: Range<u64>, rescan: bool) -> Result<u64, AppError> {
+        let mut current_block = range.start;
+        while current_block < range.end {
+            // Only stop between batches so the cursor always matches stored data
+            if self.shutdown.is_some_and(Shutdown::is_requested) {
+                info!("Shutdown requested, stopping before block {}", current_block);
+                break;
+            }
+
+            let end_block = std::cmp::min(current_block + self.config.processing.batch_size as u64, range.end);
+            info!("Processing blocks {} to {}", current_block, end_block);
+            let batch_started = Instant::now();
+
+            match self.scan_batch(current_block, end_block, rescan).await? {
+                Batch::Stored => {}
+                Batch::Rewound(height) => {
+                    current_block = height;
+                    continue;
+                }
+                Batch::Refetch => continue,
+                Batch::EndOfChain(height) => {
+                    info!("Reached the end of the chain at block {}", height);
+                    current_block = height;
+                    break;
+                }
+            }
+
+            self.metrics.add_processing_time(batch_started.elapsed());
+            if let Some(progress) = self.progress {
+                progress.update(end_block, &self.metrics.get_stats());
+            }
+            info!("Completed blocks {} to {}", current_block, end_block);
+            current_block = end_block;
+        }
+        Ok(current_block)
+    }
+
+    /// Rolls storage back to the fork point after a reorg, returning the height to rescan from
+    async fn rewind(&self) -> Result<u64, AppError> {
+        match &self.headers {
+            Some(headers) => {
+                let mut chain = self.header_chain.lock().await;
+                reorg::rewind_with_headers(self.storage, &mut chain, headers.as_ref(), self.source.as_ref()).await
+            }
+            None => reorg::rewind(self.storage, self.source.as_ref()).await,
+        }
+    }
+
+    /// Streams, parses and stores the blocks `start..end`, then advances the cursor
+    ///
+    /// Blocks are parsed in chunks of `processing.chunk_size` as they arrive
+    /// while later ones are still being fetched, so at most
+    /// `processing.max_in_flight` fetched blocks wait to be parsed. A
+    /// `rescan` batch leaves the cursor and the recent block hashes as they are.
+    async fn scan_batch(&self, start: u64, end: u64, rescan: bool) -> Result<Batch, AppError> {
+        let mut blocks = fetch_blocks(self.source.clone(), start..end, self.config.processing.max_in_flight);
+
+        // Each batch must extend the previous one; otherwise the chain
+        // was reorganized (or changed while this batch was fetched)
+        let mut parent = match start.checked_sub(1) {
+            Some(below) if self.source.is_chain() && !rescan => self.storage.recent_blocks()?.get(&below).copied(),
+            _ => None,
+        };
+        let mut hashes: Vec<(u64, BlockHash)> = Vec::with_capacity((end - start) as usize);
+        let mut pending = Vec::new();
+        let mut stored = StoredChunk::default();
+        // Only kept once the batch is stored, so a refetched batch isn't counted twice
+        let mut failures = Vec::new();
+
+        // Set when the chain turns out to end inside the batch
+        let mut chain_end = None;
+
+        while let Some(fetched) = blocks.recv().await {
+            let (height, block) = match fetched {
+                Ok(fetched) => fetched,
+                // The range ran past the tip (or the tip moved back); blocks
+                // arrive in height order, so everything below is in hand
+                Err((height, NodeError::BlockNotFound(_))) => {
+                    chain_end = Some(height);
+                    break;
+                }
+                Err((height, e)) if self.fail_fast => {
+                    // Nothing from this batch is committed, so the cursor stays put
+                    error!("Failed to fetch block {}: {}", height, e);
+                    self.send(DashboardEvent::Error(format!("block {}: {}", height, e)));
+                    return Err(e.into());
+                }
+                Err((height, e)) => {
+                    warn!("Skipping block {}: {}", height, e);
+                    self.send(DashboardEvent::Error(format!("block {}: {}", height, e)));
+                    failures.push(BlockFailure { height, error: e.to_string() });
+                    // The next block can't be checked against one we don't have
+                    parent = None;
+                    continue;
+                }
+            };
+
+            if self.source.is_chain() && !reorg::extends(parent, &block) {
+                if height == start {
+                    return Ok(Batch::Rewound(self.rewind().await?));
+                }
+                warn!("Block {} doesn't extend block {}, the chain changed during the fetch; refetching",
+                    height, height - 1);
+                // What was already stored from this batch may be on the replaced branch
+                self.storage.purge_heights(&(start..height).collect())?;
+                return Ok(Batch::Refetch);
+            }
+            let hash = block.block_hash();
+            parent = Some(hash);
+            hashes.push((height, hash));
+
+            if let Some(archive) = self.archive {
+                if let Err(e) = archive.archive_block(height, &block) {
+                    error!("Failed to archive block {}: {}", height, e);
+                }
+            }
+
+            pending.push((height, block));
+            // A rescan stores nothing until the whole batch is in hand
+            if !rescan && pending.len() >= self.config.processing.chunk_size {
+                self.parse_and_store(std::mem::take(&mut pending), &mut stored).await;
+            }
+        }
+        if rescan {
+            self.storage.purge_heights(&pending.iter().map(|(height, _)| *height).collect())?;
+        }
+        if !pending.is_empty() {
+            self.parse_and_store(pending, &mut stored).await;
+        }
+        if chain_end == Some(start) {
+            return Ok(Batch::EndOfChain(start));
+        }
+        let end = chain_end.unwrap_or(end);
+
+        let received = (hashes.len() + failures.len()) as u64;
+        if received != end - start {
+            return Err(NodeError::ConnectionError(format!(
+                "block fetch stopped after {} of {} blocks", received, end - start
+            ))
+            .into());
+        }
+        info!("Found {} inscriptions in blocks {} to {}", stored.inscriptions, start, end);
+
+        if let Some(e) = stored.error {
+            error!("Batch {} to {} was not fully stored, stopping without advancing the resume cursor",
+                start, end);
+            return Err(e.into());
+        }
+        // Delegates are looked up together, once every target in the batch is stored
+        self.storage.resolve_pending_delegates().await?;
+        // Mock blocks don't chain, so only real ones are kept for reorg checks
+        if self.source.is_chain() && !rescan {
+            self.storage.record_recent_blocks(&hashes)?;
+        }
+        // Remember which block each stored height came from, for diff-chain
+        hashes.retain(|(height, _)| stored.heights.contains(height));
+        self.storage.record_blocks(&hashes)?;
+        // The cache is flushed first, so the cursor never points past data the cache lost
+        if let Some(cache) = self.cache {
+            if !rescan {
+                cache.put(b"last_block", &(end - 1))?;
+            }
+            cache.flush()?;
+        }
+        if !rescan {
+            // Skipped heights stay in the state so a resumed scan retries them
+            let mut state = self.storage.load_scan_state()?.unwrap_or_default();
+            state.last_block = end - 1;
+            state.skipped.extend(failures.iter().map(|failure| failure.height));
+            state.skipped.sort_unstable();
+            state.skipped.dedup();
+            self.storage.save_scan_state(&state)?;
+        }
+        if !failures.is_empty() {
+            self.failures.lock().unwrap_or_else(|e| e.into_inner()).append(&mut failures);
+        }
+        // Upkeep only slows the scan when it fails, so a failure doesn't stop it
+        if let Some(maintenance) = self.maintenance {
+            if let Err(e) = maintenance.run_if_due(Instant::now(), self.metrics).await {
+                warn!("Maintenance failed: {}", e);
+            }
+        }
+        self.send(DashboardEvent::Height(end));
+        Ok(match chain_end {
+            Some(height) => Batch::EndOfChain(height),
+            None => Batch::Stored,
+        })
+    }
+
+    /// Parses a chunk of blocks, routes alert matches and stores every inscription
+    ///
+    /// A failed store doesn't stop the rest of the chunk; the first error is
+    /// kept in `stored` so the batch can refuse to advance the cursor.
+    async fn parse_and_store(&self, blocks: Vec<(u64, Block)>, stored: &mut StoredChunk) {
+        let count = blocks.len() as u64;
+        let inscriptions = self.parser.process_blocks(blocks);
+        self.metrics.increment_blocks(count);
+        self.metrics.increment_inscriptions(inscriptions.len() as u64);
+        stored.inscriptions += inscriptions.len();
+
+        for inscription in inscriptions {
+            stored.heights.insert(inscription.block_height);
+            self.send(DashboardEvent::Inscription {
+                kind: inscription.content.kind().to_string(),
+                summary: format!("{} {}", inscription.txid, inscription.mime_type()),
+            });
+            if let Some(alerts) = self.alerts {
+                if self.dry_run {
+                    let matched = alerts.evaluate(&inscription);
+                    if !matched.is_empty() {
+                        info!("[dry-run] {} would match alerts: {}", inscription.inscription_id(), matched.join(", "));
+                    }
+                } else {
+                    match alerts.emit(&inscription) {
+                        Ok(0) => {}
+                        Ok(matched) => info!("Inscription {} matched {} alert(s)", inscription.txid, matched),
+                        Err(e) => error!("Failed to emit alerts for {}: {}", inscription.txid, e),
+                    }
+                }
+            }
+            let store_started = Instant::now();
+            match self.storage.store_inscription(&inscription).await {
+                Ok(true) => self.metrics.increment_by_type(&inscription),
+                Ok(false) => {}
+                Err(e) => {
+                    error!("Failed to store inscription {}: {}", inscription.txid, e);
+                    stored.error.get_or_insert(e);
+                }
+            }
+            self.metrics.add_store_time(inscription.mime_type(), store_started.elapsed());
+        }
+    }
+
+    fn send(&self, event: DashboardEvent) {
+        if let Some(dashboard) = self.dashboard {
+            dashboard.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ScanState;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Mock blocks, counting how many have been fetched
+    struct CountingSource {
+        fetched: AtomicU64,
+    }
+
+    #[async_trait]
+    impl BlockHashSource for CountingSource {
+        async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError> {
+            MockSource.block_hash(height).await
+        }
+    }
+
+    #[async_trait]
+    impl BlockSource for CountingSource {
+        async fn blocks(&self, start: u64, end: u64) -> Result<Vec<Block>, NodeError> {
+            let blocks = MockSource.blocks(start, end).await?;
+            self.fetched.fetch_add(blocks.len() as u64, Ordering::SeqCst);
+            Ok(blocks)
+        }
+    }
+
+    /// Mock blocks, except that one height can't be fetched
+    struct FlakySource {
+        bad_height: u64,
+    }
+
+    #[async_trait]
+    impl BlockHashSource for FlakySource {
+        async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError> {
+            MockSource.block_hash(height).await
+        }
+    }
+
+    #[async_trait]
+    impl BlockSource for FlakySource {
+        async fn blocks(&self, start: u64, end: u64) -> Result<Vec<Block>, NodeError> {
+            if (start..end).contains(&self.bad_height) {
+                return Err(NodeError::NotConnected("connection reset".to_string()));
+            }
+            MockSource.blocks(start, end).await
+        }
+
+        fn is_chain(&self) -> bool {
+            false
+        }
+    }
+
+    /// Mock blocks up to, but not including, `tip`
+    struct ShortSource {
+        tip: u64,
+    }
+
+    #[async_trait]
+    impl BlockHashSource for ShortSource {
+        async fn block_hash(&self, height: u64) -> Result<BlockHash, NodeError> {
+            MockSource.block_hash(height).await
+        }
+    }
+
+    #[async_trait]
+    impl BlockSource for ShortSource {
+        async fn blocks(&self, start: u64, end: u64) -> Result<Vec<Block>, NodeError> {
+            if end > self.tip {
+                return Err(NodeError::BlockNotFound(self.tip.max(start)));
+            }
+            MockSource.blocks(start, end).await
+        }
+
+        fn is_chain(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_stops_at_the_end_of_the_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+        let parser = ParallelParser::new(10, None).unwrap();
+        let metrics = Metrics::new();
+        let mut config = Config::default();
+        config.processing.batch_size = 4;
+
+        // Even with fail-fast, running out of blocks isn't an error
+        let scanner = Scanner::new(Arc::new(ShortSource { tip: 6 }), &parser, &storage, &metrics, &config)
+            .with_fail_fast(true);
+        assert_eq!(scanner.run(0..10).await.unwrap(), 6);
+        assert!(scanner.failures().is_empty());
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(5)));
+        // Block 3's binary inscription needs binary storage
+        assert_eq!(storage.entries().unwrap().count(), 5);
+
+        // A batch starting at the tip stores nothing
+        assert_eq!(scanner.run(6..10).await.unwrap(), 6);
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(5)));
+    }
+
+    #[tokio::test]
+    async fn test_unfetchable_block_is_skipped_and_recorded() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+        let parser = ParallelParser::new(10, None).unwrap();
+        let metrics = Metrics::new();
+        let mut config = Config::default();
+        config.processing.batch_size = 4;
+        let source = Arc::new(FlakySource { bad_height: 3 });
+
+        let scanner = Scanner::new(source.clone(), &parser, &storage, &metrics, &config);
+        assert_eq!(scanner.run(0..8).await.unwrap(), 8);
+        let failures = scanner.failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].height, 3);
+        assert!(failures[0].error.contains("connection reset"));
+        // Block 7's binary inscription needs binary storage
+        assert_eq!(storage.entries().unwrap().count(), 6);
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState { last_block: 7, skipped: vec![3] }));
+        assert!(failure_summary(&failures, 5).starts_with("1 block(s) skipped"));
+
+        // A resume retries it, keeping it in the state until it can be fetched
+        scanner.retry_skipped().await.unwrap();
+        assert_eq!(storage.load_scan_state().unwrap().unwrap().skipped, vec![3]);
+        Scanner::new(Arc::new(MockSource), &parser, &storage, &metrics, &config).retry_skipped().await.unwrap();
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(7)));
+
+        // With fail-fast the same block stops the scan before its batch is stored
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+        let scanner = Scanner::new(source, &parser, &storage, &metrics, &config).with_fail_fast(true);
+        assert!(matches!(scanner.run(0..8).await, Err(AppError::Node(_))));
+        assert!(scanner.failures().is_empty());
+        assert_eq!(storage.load_scan_state().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_rescan_is_idempotent_and_keeps_the_cursor() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+        let parser = ParallelParser::new(10, None).unwrap();
+        let metrics = Metrics::new();
+        let mut config = Config::default();
+        config.processing.batch_size = 2;
+        let contents = |storage: &Storage| {
+            let mut entries: Vec<(String, String, Vec<u8>)> = storage
+                .entries()
+                .unwrap()
+                .map(|entry| entry.unwrap())
+                .map(|entry| (entry.txid, entry.content_type, entry.body))
+                .collect();
+            entries.sort();
+            entries
+        };
+
+        let scanner = Scanner::new(Arc::new(MockSource), &parser, &storage, &metrics, &config);
+        scanner.run(0..6).await.unwrap();
+        let scanned = contents(&storage);
+        // Block 3's binary inscription needs binary storage
+        assert_eq!(scanned.len(), 5);
+
+        assert_eq!(scanner.rescan(2..5).await.unwrap(), 5);
+        let once = contents(&storage);
+        assert_eq!(scanner.rescan(2..5).await.unwrap(), 5);
+        assert_eq!(contents(&storage), once);
+        assert_eq!(once, scanned);
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(5)));
+
+        // A height that can't be fetched again keeps what was stored from it
+        let flaky = Scanner::new(Arc::new(FlakySource { bad_height: 4 }), &parser, &storage, &metrics, &config);
+        flaky.rescan(2..6).await.unwrap();
+        assert_eq!(contents(&storage), scanned);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stays_within_max_in_flight() {
+        let source = Arc::new(CountingSource { fetched: AtomicU64::new(0) });
+        let mut blocks = fetch_blocks(source.clone(), 0..40, 4);
+
+        // A slow consumer: the fetch task has to wait for it
+        let mut received = 0;
+        while let Some(fetched) = blocks.recv().await {
+            let (height, _) = fetched.unwrap();
+            assert_eq!(height, received);
+            received += 1;
+            let ahead = source.fetched.load(Ordering::SeqCst) - received;
+            assert!(ahead <= 4, "{} blocks fetched ahead of the consumer", ahead);
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        assert_eq!(received, 40);
+        assert_eq!(source.fetched.load(Ordering::SeqCst), 40);
+    }
+
+    #[tokio::test]
+    async fn test_run_stores_every_mock_inscription() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+        let parser = ParallelParser::new(10, None).unwrap();
+        let metrics = Metrics::new();
+        let mut config = Config::default();
+        config.processing.batch_size = 2;
+
+        let scanner = Scanner::new(Arc::new(MockSource), &parser, &storage, &metrics, &config);
+        assert_eq!(scanner.run(100..105).await.unwrap(), 105);
+
+        let mut stored: Vec<(String, Vec<u8>)> = storage
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .map(|entry| (entry.content_type, entry.body))
+            .collect();
+        stored.sort();
+        // Block 103's binary inscription needs binary storage
+        let text = "text/plain;charset=utf-8".to_string();
+        assert_eq!(stored, vec![
+            ("application/json".to_string(), br#"{"height":102,"p":"mock"}"#.to_vec()),
+            ("image/png".to_string(), MOCK_PNG.to_vec()),
+            (text.clone(), b"Hello from block 100!".to_vec()),
+            (text, b"Hello from block 104!".to_vec()),
+        ]);
+
+        assert_eq!(storage.load_scan_state().unwrap(), Some(ScanState::at(104)));
+        let stats = metrics.get_stats();
+        assert_eq!((stats.blocks_processed, stats.inscriptions_found), (5, 5));
+        // Only what was actually written counts by type; the binary body wasn't
+        let by_type = &stats.by_type;
+        assert_eq!((by_type.text, by_type.image, by_type.json, by_type.binary), (2, 1, 1, 0));
+
+        // A requested shutdown stops before the next batch
+        let shutdown = Shutdown::new();
+        shutdown.request();
+        let scanner = Scanner::new(Arc::new(MockSource), &parser, &storage, &metrics, &config)
+            .with_shutdown(&shutdown);
+        assert_eq!(scanner.run(105..110).await.unwrap(), 105);
+        let txid = create_mock_inscription_block(105).txdata[0].txid();
+        assert!(storage.get_by_txid(txid).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_mock_blocks_cycle_through_types() {
+        let parser = ParallelParser::new(10, None).unwrap();
+        let blocks = (20..28).map(|height| (height, create_mock_inscription_block(height))).collect();
+        let mut inscriptions = parser.process_blocks(blocks);
+        inscriptions.sort_by_key(|inscription| inscription.block_height);
+
+        let kinds: Vec<&str> = inscriptions.iter().map(|inscription| inscription.content.kind()).collect();
+        assert_eq!(kinds, ["text", "image", "json", "unknown", "text", "image", "json", "unknown"]);
+        assert_eq!(inscriptions[1].mime_type(), "image/png");
+        assert_eq!(inscriptions[3].mime_type(), "application/octet-stream");
+
+        // Deterministic, and every block gets its own transaction
+        assert_eq!(create_mock_inscription_block(21), create_mock_inscription_block(21));
+        assert_ne!(inscriptions[1].txid, inscriptions[5].txid);
+    }
+}
@@ -0,0 +1,254 @@
+// server.rs
+//
+// Optional HTTP API (`--serve <addr>`) for querying the scanner while it
+// runs. It reads the same `Storage` and `Metrics` the scan loop writes.
+//
+//   GET /inscription/:txid              first inscription stored for a txid
+//   GET /stats                          current metrics snapshot
+//   GET /inscriptions?offset=&limit=    stored inscriptions, paginated
+//   GET /duplicates/:hash               ids of the inscriptions whose body has this blake3 hash
+//   GET /children/:id                   ids of the inscriptions that name this one as their parent
+//   GET /content/:content_id            ids of the inscriptions carrying this content
+//   GET /refcount/:content_id           number of stored inscriptions carrying this content
+
+use crate::parser::Inscription;
+use crate::storage::{ExportRow, Storage};
+use crate::utils::{Metrics, MetricsSnapshot};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use bitcoin::Txid;
+use log::info;
+use serde::Deserialize;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Page size when `limit` isn't given
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page a single request can ask for
+const MAX_PAGE_SIZE: usize = 1000;
+
+type ApiResult<T> = std::result::Result<Json<T>, (StatusCode, String)>;
+
+#[derive(Clone)]
+struct ApiState {
+    storage: Arc<Storage>,
+    metrics: Arc<Metrics>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    DEFAULT_PAGE_SIZE
+}
+
+/// Builds the API routes over shared storage and metrics
+pub fn router(storage: Arc<Storage>, metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/inscription/:txid", get(inscription))
+        .route("/stats", get(stats))
+        .route("/inscriptions", get(inscriptions))
+        .route("/duplicates/:hash", get(duplicates))
+        .route("/children/:id", get(children))
+        .route("/content/:content_id", get(content))
+        .route("/refcount/:content_id", get(refcount))
+        .with_state(ApiState { storage, metrics })
+}
+
+/// Serves the API on an already bound listener until the task is dropped
+///
+/// Binding is left to the caller so a taken port fails before scanning starts.
+pub async fn serve(listener: TcpListener, storage: Arc<Storage>, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    info!("Serving the HTTP API on {}", listener.local_addr()?);
+    axum::serve(listener, router(storage, metrics)).await
+}
+
+/// Runs a storage lookup on the blocking pool, so file and database reads
+/// never stall the async workers serving other requests
+async fn blocking<T: Send + 'static>(
+    storage: &Arc<Storage>,
+    lookup: impl FnOnce(&Storage) -> crate::storage::Result<T> + Send + 'static,
+) -> std::result::Result<T, (StatusCode, String)> {
+    let storage = storage.clone();
+    match tokio::task::spawn_blocking(move || lookup(&storage)).await {
+        Ok(result) => result.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+async fn inscription(State(state): State<ApiState>, Path(txid): Path<String>) -> ApiResult<Inscription> {
+    let txid = Txid::from_str(&txid).map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid txid: {}", e)))?;
+    match blocking(&state.storage, move |storage| storage.get_by_txid(txid)).await? {
+        Some(inscription) => Ok(Json(inscription)),
+        None => Err((StatusCode::NOT_FOUND, format!("no inscription stored for {}", txid))),
+    }
+}
+
+async fn stats(State(state): State<ApiState>) -> Json<MetricsSnapshot> {
+    Json(state.metrics.get_stats())
+}
+
+async fn inscriptions(State(state): State<ApiState>, Query(page): Query<Page>) -> ApiResult<Vec<ExportRow>> {
+    let limit = page.limit.min(MAX_PAGE_SIZE);
+    blocking(&state.storage, move |storage| storage.page(page.offset, limit)).await.map(Json)
+}
+
+async fn duplicates(State(state): State<ApiState>, Path(hash): Path<String>) -> ApiResult<Vec<String>> {
+    let hash = blake3::Hash::from_hex(&hash).map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid hash: {}", e)))?;
+    blocking(&state.storage, move |storage| storage.find_duplicates(&hash)).await.map(Json)
+}
+
+async fn children(State(state): State<ApiState>, Path(id): Path<String>) -> ApiResult<Vec<String>> {
+    blocking(&state.storage, move |storage| storage.children_of(&id)).await.map(Json)
+}
+
+async fn content(State(state): State<ApiState>, Path(content_id): Path<String>) -> ApiResult<Vec<String>> {
+    blocking(&state.storage, move |storage| storage.inscriptions_with_content(&content_id)).await.map(Json)
+}
+
+async fn refcount(State(state): State<ApiState>, Path(content_id): Path<String>) -> ApiResult<usize> {
+    blocking(&state.storage, move |storage| storage.content_refcount(&content_id)).await.map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::InscriptionType;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    fn temp_storage(temp_dir: &TempDir) -> Arc<Storage> {
+        Arc::new(
+            Storage::new(temp_dir.path().join("images"), temp_dir.path().join("inscriptions.log")).unwrap(),
+        )
+    }
+
+    async fn get_json(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_stats_returns_the_metrics_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let metrics = Arc::new(Metrics::new());
+        metrics.increment_blocks(10);
+        metrics.increment_inscriptions(4);
+        metrics.add_store_time("text/plain", Duration::from_millis(2));
+
+        let (status, stats) = get_json(router(temp_storage(&temp_dir), metrics), "/stats").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stats["blocks_processed"], 10);
+        assert_eq!(stats["inscriptions_found"], 4);
+        assert_eq!(stats["inscriptions_per_block"], 0.4);
+        assert!(stats["total_time"].is_f64());
+        assert_eq!(stats["per_type"]["text/plain"]["stored"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_inscriptions_are_paginated() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+        for n in 1..=3u8 {
+            let txid = Txid::from_str(&format!("{:02x}", n).repeat(32)).unwrap();
+            let inscription = Inscription::new(txid, InscriptionType::Text(format!("note {}", n)));
+            storage.store_inscription(&inscription).await.unwrap();
+        }
+        let app = router(storage, Arc::new(Metrics::new()));
+
+        let (status, page) = get_json(app.clone(), "/inscriptions?offset=1&limit=5").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page.as_array().unwrap().len(), 2);
+
+        let (status, found) = get_json(app.clone(), &format!("/inscription/{}", "01".repeat(32))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found["content"]["Text"], "note 1");
+
+        let (status, _) = get_json(app.clone(), &format!("/inscription/{}", "09".repeat(32))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get_json(app, "/inscription/not-a-txid").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_are_listed_by_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(crate::cache::CacheDb::new(temp_dir.path().join("cache")).unwrap());
+        let storage = Storage::new(temp_dir.path().join("images"), temp_dir.path().join("inscriptions.log"))
+            .unwrap()
+            .with_content_hashes(crate::cache::ContentHashIndex::new(db));
+        let mut copies = Vec::new();
+        for n in 1..=2u8 {
+            let txid = Txid::from_str(&format!("{:02x}", n).repeat(32)).unwrap();
+            let inscription = Inscription::new(txid, InscriptionType::Text("copy".to_string()));
+            storage.store_inscription(&inscription).await.unwrap();
+            copies.push(inscription.inscription_id());
+        }
+        let app = router(Arc::new(storage), Arc::new(Metrics::new()));
+
+        let hash = blake3::hash(b"copy").to_hex();
+        let (status, ids) = get_json(app.clone(), &format!("/duplicates/{}", hash)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids, serde_json::json!(copies));
+        let (status, _) = get_json(app, "/duplicates/not-a-hash").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_children_are_listed_by_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+        let parent = format!("{}i0", "aa".repeat(32));
+        let txid = Txid::from_str(&"01".repeat(32)).unwrap();
+        let mut child = Inscription::new(txid, InscriptionType::Text("child".to_string()));
+        child.parents = vec![parent.clone()];
+        storage.store_inscription(&child).await.unwrap();
+        let app = router(storage, Arc::new(Metrics::new()));
+
+        let (status, ids) = get_json(app.clone(), &format!("/children/{}", parent)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids, serde_json::json!([child.inscription_id()]));
+        let (_, ids) = get_json(app, &format!("/children/{}", child.inscription_id())).await;
+        assert_eq!(ids, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_inscriptions_and_refcount_are_listed_by_content_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+        let mut content_id = String::new();
+        let mut ids = Vec::new();
+        for n in 1..=2u8 {
+            let txid = Txid::from_str(&format!("{:02x}", n).repeat(32)).unwrap();
+            let inscription = Inscription::new(txid, InscriptionType::Text("shared".to_string()));
+            storage.store_inscription(&inscription).await.unwrap();
+            content_id = inscription.content_id();
+            ids.push(inscription.inscription_id());
+        }
+        let app = router(storage, Arc::new(Metrics::new()));
+
+        let (status, found) = get_json(app.clone(), &format!("/content/{}", content_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found, serde_json::json!(ids));
+
+        let (status, count) = get_json(app.clone(), &format!("/refcount/{}", content_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(count, 2);
+        let (_, count) = get_json(app, &format!("/refcount/{}", "00".repeat(32))).await;
+        assert_eq!(count, 0);
+    }
+}
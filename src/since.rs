@@ -0,0 +1,96 @@
+// since.rs
+//
+// Maps a calendar date to a block height for `--since`, by binary-searching
+// block header times instead of downloading blocks.
+
+use crate::error::AppError;
+use crate::node::{NodeClient, NodeError};
+use chrono::{DateTime, NaiveDate};
+use std::future::Future;
+
+/// Parses an RFC 3339 timestamp or a `YYYY-MM-DD` date (midnight UTC) to Unix seconds
+pub fn parse_timestamp(value: &str) -> Result<u32, String> {
+    let seconds = match DateTime::parse_from_rfc3339(value) {
+        Ok(datetime) => datetime.timestamp(),
+        Err(_) => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| format!("{:?} is neither an RFC 3339 timestamp nor a YYYY-MM-DD date", value))?
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc()
+            .timestamp(),
+    };
+    u32::try_from(seconds).map_err(|_| format!("{} is outside the range of block timestamps", value))
+}
+
+/// First height in `0..=tip` whose block time is at least `timestamp`
+///
+/// Returns `None` when even the tip is older. Timestamps before genesis
+/// give height 0. Block times only roughly increase, so right at the
+/// boundary the result can be a few blocks off.
+pub async fn first_block_at<F, Fut, E>(tip: u64, timestamp: u32, mut time_at: F) -> Result<Option<u64>, E>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<u32, E>>,
+{
+    if time_at(tip).await? < timestamp {
+        return Ok(None);
+    }
+    // The block at `high` is always at or after the timestamp
+    let (mut low, mut high) = (0, tip);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if time_at(mid).await? >= timestamp {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(Some(low))
+}
+
+/// Height of the node's first block at or after `timestamp`, fetching only headers
+pub async fn block_since(client: &NodeClient, timestamp: u32) -> Result<u64, AppError> {
+    let tip = client.get_block_count().await?;
+    let found = first_block_at(tip, timestamp, |height| async move {
+        let hash = client.get_block_hash(height).await?;
+        Ok::<_, NodeError>(client.get_block_header(&hash).await?.time)
+    })
+    .await?;
+    found.ok_or_else(|| {
+        AppError::Usage(format!("No block at or after timestamp {}: the tip (block {}) is older", timestamp, tip))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS_TIME: u32 = 1_231_006_505;
+
+    /// Ten-minute blocks from genesis
+    async fn synthetic(height: u64) -> Result<u32, NodeError> {
+        Ok(GENESIS_TIME + height as u32 * 600)
+    }
+
+    #[tokio::test]
+    async fn test_first_block_at() {
+        let tip = 800_000;
+        let search = |timestamp| first_block_at(tip, timestamp, synthetic);
+
+        assert_eq!(search(GENESIS_TIME + 600 * 1234).await.unwrap(), Some(1234));
+        // Between two blocks: the later one
+        assert_eq!(search(GENESIS_TIME + 600 * 1234 + 1).await.unwrap(), Some(1235));
+        assert_eq!(search(GENESIS_TIME - 86_400).await.unwrap(), Some(0));
+        assert_eq!(search(GENESIS_TIME + 600 * tip as u32).await.unwrap(), Some(tip));
+        assert_eq!(search(GENESIS_TIME + 600 * tip as u32 + 1).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("2023-01-01"), Ok(1_672_531_200));
+        assert_eq!(parse_timestamp("2023-01-01T02:00:00+02:00"), Ok(1_672_531_200));
+        assert_eq!(parse_timestamp("2023-01-01T00:00:30Z"), Ok(1_672_531_230));
+        assert!(parse_timestamp("01/01/2023").is_err());
+        assert!(parse_timestamp("1960-01-01").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use super::{Result, StorageError};
+use bitcoin::block::Header;
+use bitcoin::{Block, Transaction};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+/// Byte pattern opening every ord envelope: OP_0 OP_IF OP_PUSHBYTES_3 "ord"
+const ENVELOPE_MARKER: &[u8] = &[0x00, 0x63, 0x03, b'o', b'r', b'd'];
+/// Prefix of the line holding the block header, ahead of the transactions
+const HEADER_PREFIX: &str = "header ";
+
+/// What the archive kept of a block
+pub struct ArchivedBlock {
+    /// `None` for heights archived before headers were kept
+    pub header: Option<Header>,
+    pub txdata: Vec<Transaction>,
+}
+
+/// Archive of raw envelope-bearing transactions, one file per block height
+///
+/// Keeping the raw transactions lets a changed classifier be re-run over
+/// a height range without fetching anything from the node again.
+pub struct RawArchive {
+    dir: PathBuf,
+}
+
+impl RawArchive {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Archives every transaction in the block that contains an envelope marker
+    ///
+    /// A file is written even when no transaction matches, so the archive
+    /// records which heights were covered.
+    pub fn archive_block(&self, height: u64, block: &Block) -> Result<()> {
+        let tmp_path = self.path(height).with_extension("hex.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+        writeln!(writer, "{}{}", HEADER_PREFIX, bitcoin::consensus::encode::serialize_hex(&block.header))?;
+        for tx in block.txdata.iter().filter(|tx| has_envelope_marker(tx)) {
+            writeln!(writer, "{}", bitcoin::consensus::encode::serialize_hex(tx))?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(tmp_path, self.path(height))?;
+        Ok(())
+    }
+
+    pub fn contains(&self, height: u64) -> bool {
+        self.path(height).exists()
+    }
+
+    /// Loads the archived header and transactions for a height
+    pub fn load(&self, height: u64) -> Result<ArchivedBlock> {
+        if !self.contains(height) {
+            return Err(StorageError::ArchiveError(format!("height {} is not archived", height)));
+        }
+
+        let mut block = ArchivedBlock { header: None, txdata: Vec::new() };
+        for line in BufReader::new(File::open(self.path(height))?).lines() {
+            let line = line?;
+            match line.trim().strip_prefix(HEADER_PREFIX) {
+                Some(header) => block.header = Some(decode(header)?),
+                None => block.txdata.push(decode(line.trim())?),
+            }
+        }
+        Ok(block)
+    }
+
+    fn path(&self, height: u64) -> PathBuf {
+        self.dir.join(format!("{}.hex", height))
+    }
+}
+
+fn decode<T: bitcoin::consensus::Decodable>(hex: &str) -> Result<T> {
+    let bytes = hex::decode(hex).map_err(|e| StorageError::ArchiveError(e.to_string()))?;
+    bitcoin::consensus::encode::deserialize(&bytes).map_err(|e| StorageError::ArchiveError(e.to_string()))
+}
+
+/// Cheap byte-level check for an envelope in any witness element or output script
+fn has_envelope_marker(tx: &Transaction) -> bool {
+    let contains = |bytes: &[u8]| bytes.windows(ENVELOPE_MARKER.len()).any(|w| w == ENVELOPE_MARKER);
+
+    tx.input.iter().any(|input| input.witness.iter().any(contains))
+        || tx.output.iter().any(|output| contains(output.script_pubkey.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::dummy_header;
+    use bitcoin::{ScriptBuf, TxOut};
+    use tempfile::TempDir;
+
+    fn tx_with_script(script: Vec<u8>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut { value: 0, script_pubkey: ScriptBuf::from(script) }],
+        }
+    }
+
+    #[test]
+    fn test_archives_only_envelope_transactions() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = RawArchive::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut envelope = ENVELOPE_MARKER.to_vec();
+        envelope.push(0x68);
+        let block = Block {
+            header: dummy_header(),
+            txdata: vec![tx_with_script(vec![0x51]), tx_with_script(envelope)],
+        };
+
+        archive.archive_block(7, &block).unwrap();
+        assert!(archive.contains(7));
+        assert!(!archive.contains(8));
+
+        let archived = archive.load(7).unwrap();
+        assert_eq!(archived.header, Some(block.header));
+        assert_eq!(archived.txdata.len(), 1);
+        assert_eq!(archived.txdata[0].txid(), block.txdata[1].txid());
+
+        // Heights archived before headers were kept still load
+        fs::write(archive.path(9), format!("{}\n", bitcoin::consensus::encode::serialize_hex(&block.txdata[1]))).unwrap();
+        let archived = archive.load(9).unwrap();
+        assert_eq!(archived.header, None);
+        assert_eq!(archived.txdata.len(), 1);
+
+        assert!(matches!(archive.load(8), Err(StorageError::ArchiveError(_))));
+    }
+}
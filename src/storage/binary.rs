@@ -0,0 +1,119 @@
+use super::{write_atomically, Provenance, Result};
+use crate::parser::TxMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Sidecar written next to every stored body, `<id>.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BinarySidecar {
+    pub id: String,
+    pub txid: String,
+    pub content_type: String,
+    pub size: usize,
+    pub block_height: u64,
+    pub block_time: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_metadata: Option<TxMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<String>,
+    /// Inscription ids the body loads through `/content/<id>` (recursion)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+}
+
+/// Bodies that are neither text nor images (audio, video, models, HTML, ...)
+///
+/// Each is written as `<id>.bin` as inscribed, with its MIME type and
+/// provenance in a `<id>.json` sidecar.
+pub struct BinaryStorage {
+    base_dir: PathBuf,
+}
+
+impl BinaryStorage {
+    pub fn new(base_dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&base_dir)?;
+        Ok(Self { base_dir })
+    }
+
+    /// Writes the body and its sidecar unless `id` is already stored
+    ///
+    /// Returns whether anything was written.
+    pub fn store(&self, id: &str, txid: &str, content_type: &str, data: &[u8], provenance: &Provenance) -> Result<bool> {
+        let sidecar_path = self.base_dir.join(format!("{}.json", id));
+        if sidecar_path.exists() {
+            return Ok(false);
+        }
+
+        write_atomically(&self.path(id), |file| Ok(file.write_all(data)?))?;
+        // Written last, so a body without a sidecar is never taken as stored
+        let sidecar = BinarySidecar {
+            id: id.to_string(),
+            txid: txid.to_string(),
+            content_type: content_type.to_string(),
+            size: data.len(),
+            block_height: provenance.block_height,
+            block_time: provenance.block_time,
+            genesis_address: provenance.genesis_address.clone(),
+            tx_metadata: provenance.tx_metadata.clone(),
+            parents: provenance.parents.clone(),
+            references: provenance.references.clone(),
+        };
+        write_atomically(&sidecar_path, |file| Ok(serde_json::to_writer(file, &sidecar)?))?;
+        Ok(true)
+    }
+
+    /// Deletes every body found at one of `heights`, returning their sidecars
+    pub fn remove_heights(&self, heights: &BTreeSet<u64>) -> Result<Vec<BinarySidecar>> {
+        let mut removed = Vec::new();
+        for sidecar in self.sidecars()? {
+            let sidecar = sidecar?;
+            if heights.contains(&sidecar.block_height) {
+                // Sidecar first, so an interrupted purge never leaves one without its body
+                fs::remove_file(self.base_dir.join(format!("{}.json", sidecar.id)))?;
+                fs::remove_file(self.path(&sidecar.id))?;
+                removed.push(sidecar);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// The sidecar and body stored for `id`, if any
+    pub fn get(&self, id: &str) -> Result<Option<(BinarySidecar, Vec<u8>)>> {
+        let sidecar_path = self.base_dir.join(format!("{}.json", id));
+        if !sidecar_path.exists() {
+            return Ok(None);
+        }
+        let sidecar: BinarySidecar = serde_json::from_slice(&fs::read(&sidecar_path)?)?;
+        Ok(Some((sidecar, self.body(id)?)))
+    }
+
+    /// The body file of `id`
+    pub fn path(&self, id: &str) -> PathBuf {
+        self.base_dir.join(format!("{}.bin", id))
+    }
+
+    pub fn body(&self, id: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.path(id))?)
+    }
+
+    /// Every stored sidecar, ordered by inscription id
+    ///
+    /// Each sidecar is read as the iterator reaches it; bodies aren't read at all.
+    pub fn sidecars(&self) -> Result<impl Iterator<Item = Result<BinarySidecar>>> {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(&self.base_dir) {
+            Ok(dir) => dir
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        paths.sort();
+        Ok(paths.into_iter().map(|path| Ok(serde_json::from_slice(&fs::read(path)?)?)))
+    }
+}
@@ -0,0 +1,157 @@
+use super::{Result, StorageError};
+use bitcoin::BlockHash;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Append-only log of the block hash each stored height came from
+///
+/// Each line is `<height> <block hash>`. Only heights that stored
+/// inscriptions are logged; a later line for the same height (a rescan
+/// after a reorg) supersedes earlier ones.
+pub struct BlockLog {
+    path: PathBuf,
+}
+
+impl BlockLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn record(&self, blocks: &[(u64, BlockHash)]) -> Result<()> {
+        if blocks.is_empty() {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        for (height, hash) in blocks {
+            writeln!(file, "{} {}", height, hash)?;
+        }
+        Ok(())
+    }
+
+    /// Latest recorded hash per height
+    pub fn hashes(&self) -> Result<BTreeMap<u64, BlockHash>> {
+        let mut hashes = BTreeMap::new();
+        if !self.path.exists() {
+            return Ok(hashes);
+        }
+
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            if let Some((height, hash)) = line.split_once(' ') {
+                if let Ok(height) = height.parse() {
+                    let hash = BlockHash::from_str(hash)
+                        .map_err(|e| StorageError::BlockLogError(format!("{}: {}", line, e)))?;
+                    hashes.insert(height, hash);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Drops every entry for `heights`, rewriting the log in place
+    pub fn remove(&self, heights: &BTreeSet<u64>) -> Result<()> {
+        let kept: Vec<(u64, BlockHash)> = self
+            .hashes()?
+            .into_iter()
+            .filter(|(height, _)| !heights.contains(height))
+            .collect();
+
+        let tmp_path = self.path.with_extension("txt.tmp");
+        let _ = fs::remove_file(&tmp_path);
+        BlockLog::new(tmp_path.clone()).record(&kept)?;
+        if kept.is_empty() {
+            File::create(&tmp_path)?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Number of most recent block hashes kept for reorg detection
+pub const REORG_WINDOW: usize = 144;
+
+/// Hashes of the last `REORG_WINDOW` scanned blocks, stored or not
+///
+/// Unlike `BlockLog` this covers every processed height, so each batch can
+/// check that its first block extends the last one scanned. It's rewritten
+/// on every update, in the same `<height> <block hash>` format.
+pub struct RecentBlocks {
+    log: BlockLog,
+}
+
+impl RecentBlocks {
+    pub fn new(path: PathBuf) -> Self {
+        Self { log: BlockLog::new(path) }
+    }
+
+    pub fn hashes(&self) -> Result<BTreeMap<u64, BlockHash>> {
+        self.log.hashes()
+    }
+
+    /// Adds `blocks`, dropping everything but the highest `REORG_WINDOW` heights
+    pub fn record(&self, blocks: &[(u64, BlockHash)]) -> Result<()> {
+        let mut hashes = self.hashes()?;
+        hashes.extend(blocks.iter().copied());
+        let skip = hashes.len().saturating_sub(REORG_WINDOW);
+        self.replace(hashes.into_iter().skip(skip).collect())
+    }
+
+    /// Forgets every height above `height`
+    pub fn truncate(&self, height: u64) -> Result<()> {
+        let kept = self.hashes()?.into_iter().filter(|(h, _)| *h <= height).collect();
+        self.replace(kept)
+    }
+
+    fn replace(&self, blocks: Vec<(u64, BlockHash)>) -> Result<()> {
+        let tmp_path = self.log.path.with_extension("txt.tmp");
+        File::create(&tmp_path)?;
+        BlockLog::new(tmp_path.clone()).record(&blocks)?;
+        fs::rename(&tmp_path, &self.log.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_later_entries_supersede_earlier_ones() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = BlockLog::new(temp_dir.path().join("blocks.txt"));
+        let a = BlockHash::all_zeros();
+        let b = BlockHash::from_byte_array([1; 32]);
+
+        assert!(log.hashes().unwrap().is_empty());
+        log.record(&[(100, a), (101, a)]).unwrap();
+        log.record(&[(101, b)]).unwrap();
+        assert_eq!(log.hashes().unwrap(), BTreeMap::from([(100, a), (101, b)]));
+
+        log.remove(&BTreeSet::from([101])).unwrap();
+        assert_eq!(log.hashes().unwrap(), BTreeMap::from([(100, a)]));
+    }
+
+    #[test]
+    fn test_recent_blocks_keep_a_bounded_window() {
+        let temp_dir = TempDir::new().unwrap();
+        let recent = RecentBlocks::new(temp_dir.path().join("recent_blocks.txt"));
+        let hash = |height: u64| BlockHash::from_byte_array([height as u8; 32]);
+
+        let blocks: Vec<_> = (0..REORG_WINDOW as u64 + 10).map(|height| (height, hash(height))).collect();
+        recent.record(&blocks).unwrap();
+        let hashes = recent.hashes().unwrap();
+        assert_eq!(hashes.len(), REORG_WINDOW);
+        assert_eq!(hashes.keys().next(), Some(&10));
+
+        recent.truncate(100).unwrap();
+        assert_eq!(recent.hashes().unwrap().keys().last(), Some(&100));
+    }
+}
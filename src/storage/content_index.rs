@@ -0,0 +1,66 @@
+use super::{write_atomically, Result};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// Append-only index from content id to the inscriptions carrying that content
+///
+/// Each line is `<content_id> <inscription id>`, so the same content inscribed
+/// on several transactions (or chains), or twice in one batch reveal, can be
+/// grouped after the fact.
+pub struct ContentIndex {
+    path: PathBuf,
+}
+
+impl ContentIndex {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn record(&self, content_id: &str, inscription_id: &str) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{} {}", content_id, inscription_id)?;
+        Ok(())
+    }
+
+    /// Every inscription id recorded for a content id, in insertion order
+    pub fn inscription_ids(&self, content_id: &str) -> Result<Vec<String>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut ids = Vec::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            if let Some((id, inscription_id)) = line.split_once(' ') {
+                if id == content_id {
+                    ids.push(inscription_id.to_string());
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Drops every line for an inscription made by one of `txids`, for purged blocks
+    pub fn forget_txids(&self, txids: &[String]) -> Result<()> {
+        if txids.is_empty() || !self.path.exists() {
+            return Ok(());
+        }
+        let txids: HashSet<&str> = txids.iter().map(String::as_str).collect();
+        let lines = BufReader::new(File::open(&self.path)?).lines();
+        write_atomically(&self.path, |file| {
+            for line in lines {
+                let line = line?;
+                let txid = line.split_once(' ').and_then(|(_, id)| id.split('i').next());
+                if !txid.is_some_and(|txid| txids.contains(txid)) {
+                    writeln!(file, "{}", line)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
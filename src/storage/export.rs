@@ -0,0 +1,223 @@
+use super::{Result, Storage, StorageError, StorageInner};
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+/// One exported inscription; images are referenced by path, not inlined
+#[derive(Debug, Serialize)]
+pub struct ExportRow {
+    pub txid: String,
+    /// "text", "json", "image" or "binary"
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub content_type: String,
+    pub block_height: u64,
+    pub size: usize,
+    /// Timestamp of the source block
+    pub timestamp: u32,
+    /// Stored image or binary file, empty for text and JSON
+    pub path: Option<String>,
+}
+
+/// Output formats for `export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// Picks the format from a `.csv` or `.json` extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Streams one row per stored inscription: text entries first, then images,
+/// content-linked inscriptions and binary bodies
+pub(super) fn rows(storage: &StorageInner) -> Result<impl Iterator<Item = Result<ExportRow>> + '_> {
+    let texts: Box<dyn Iterator<Item = Result<ExportRow>>> = match &storage.sqlite {
+        Some(sqlite) => Box::new(sqlite.entries().map(|entry| {
+            entry.map(|entry| ExportRow {
+                txid: entry.txid,
+                kind: text_kind(&entry.content_type),
+                content_type: entry.content_type,
+                block_height: entry.block_height,
+                size: entry.body.len(),
+                timestamp: entry.block_time,
+                path: None,
+            })
+        })),
+        None => Box::new(storage.text_storage.read_entries()?.map(|entry| {
+            entry.map(|entry| ExportRow {
+                kind: text_kind(&entry.content_type()),
+                content_type: entry.content_type(),
+                txid: entry.txid,
+                block_height: entry.block_height,
+                size: entry.content.len(),
+                timestamp: entry.block_time,
+                path: None,
+            })
+        })),
+    };
+
+    let images = storage.image_storage.iter_index()?.map(move |entry| {
+        let entry = entry?;
+        Ok(ExportRow {
+            path: Some(storage.image_storage.path(&entry.file).display().to_string()),
+            txid: entry.txid,
+            kind: "image",
+            content_type: entry.mime_type,
+            block_height: entry.block_height,
+            size: entry.size,
+            timestamp: entry.block_time,
+        })
+    });
+
+    let linked: Box<dyn Iterator<Item = Result<ExportRow>>> = match &storage.linked {
+        Some(linked) => Box::new(linked.iter_entries()?.map(move |entry| {
+            let entry = entry?;
+            let kind = if entry.content_type.starts_with("image/") {
+                "image"
+            } else {
+                text_kind(&entry.content_type)
+            };
+            Ok(ExportRow {
+                path: Some(linked.body_path(&entry.content_id).display().to_string()),
+                size: std::fs::metadata(linked.body_path(&entry.content_id))?.len() as usize,
+                txid: entry.txid,
+                kind,
+                content_type: entry.content_type,
+                block_height: entry.block_height,
+                timestamp: entry.block_time,
+            })
+        })),
+        None => Box::new(std::iter::empty()),
+    };
+
+    let binaries: Box<dyn Iterator<Item = Result<ExportRow>>> = match &storage.binary {
+        Some(binary) => Box::new(binary.sidecars()?.map(move |sidecar| {
+            let sidecar = sidecar?;
+            Ok(ExportRow {
+                path: Some(binary.path(&sidecar.id).display().to_string()),
+                txid: sidecar.txid,
+                kind: "binary",
+                content_type: sidecar.content_type,
+                block_height: sidecar.block_height,
+                size: sidecar.size,
+                timestamp: sidecar.block_time,
+            })
+        })),
+        None => Box::new(std::iter::empty()),
+    };
+
+    Ok(texts.chain(images).chain(linked).chain(binaries))
+}
+
+fn text_kind(content_type: &str) -> &'static str {
+    if content_type.starts_with("application/json") {
+        "json"
+    } else {
+        "text"
+    }
+}
+
+/// Writes every stored inscription as CSV with a header row
+///
+/// Returns the number of rows written, excluding the header.
+pub fn export_csv<W: Write>(storage: &Storage, writer: W) -> Result<u64> {
+    let mut csv = csv::Writer::from_writer(writer);
+    let mut count = 0;
+    for row in rows(storage)? {
+        csv.serialize(row?)?;
+        count += 1;
+    }
+    csv.flush()?;
+    Ok(count)
+}
+
+/// Writes every stored inscription as a JSON array, one object per row
+///
+/// Returns the number of rows written.
+pub fn export_json<W: Write>(storage: &Storage, mut writer: W) -> Result<u64> {
+    writer.write_all(b"[")?;
+    let mut count = 0;
+    for row in rows(storage)? {
+        if count > 0 {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(b"\n")?;
+        serde_json::to_writer(&mut writer, &row?)?;
+        count += 1;
+    }
+    writer.write_all(b"\n]\n")?;
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Exports to `path` in the format named by its extension
+pub fn export(storage: &Storage, path: &Path) -> Result<u64> {
+    let format = ExportFormat::from_path(path).ok_or_else(|| {
+        StorageError::ExportError(format!("{}: expected a .csv or .json file", path.display()))
+    })?;
+
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match format {
+        ExportFormat::Csv => export_csv(storage, file),
+        ExportFormat::Json => export_json(storage, file),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Inscription, InscriptionType};
+    use bitcoin::Txid;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_json_export_references_images_by_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        let txid = Txid::from_str("1111111111111111111111111111111111111111111111111111111111111111").unwrap();
+        let mut text = Inscription::new(txid, InscriptionType::Text("hello".to_string()));
+        text.block_height = 800_000;
+        let mut image = Inscription::new(txid, InscriptionType::Image {
+            mime_type: "image/png".to_string(),
+            data: b"\x89PNG\r\n\x1a\n".to_vec(),
+        });
+        image.index = 1;
+        storage.store_inscription(&text).await.unwrap();
+        storage.store_inscription(&image).await.unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(export_json(&storage, &mut out).unwrap(), 2);
+        let rows: Vec<serde_json::Value> = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(rows[0]["type"], "text");
+        assert_eq!(rows[0]["block_height"], 800_000);
+        assert_eq!(rows[0]["size"], 5);
+        assert!(rows[0]["path"].is_null());
+
+        assert_eq!(rows[1]["type"], "image");
+        let path = rows[1]["path"].as_str().unwrap();
+        assert!(Path::new(path).exists());
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(ExportFormat::from_path(Path::new("out.CSV")), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::from_path(Path::new("out.json")), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::from_path(Path::new("out.xlsx")), None);
+    }
+}
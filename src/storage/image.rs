@@ -1,42 +1,513 @@
-use super::Result;
+use super::thumbnail::{preview_data_uri, thumbnail_png, PREVIEW_SIZE, THUMBNAIL_SIZE};
+use super::{drop_torn_tail, write_atomically, Provenance, Result};
+use crate::parser::TxMetadata;
 use bitcoin::Txid;
-use std::path::PathBuf;
-use std::fs::{self, File};
-use std::io::Write;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::sync::Mutex;
 use blake3::Hash;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use image::ImageFormat;
+use log::warn;
+
+/// Index of stored images, one JSON entry per line
+const INDEX_FILE: &str = "index.jsonl";
+
+/// SVGs are stored as plain `.svg` (or gzipped `.svgz`) files that can be served directly
+const SVG_MIME_TYPE: &str = "image/svg+xml";
+
+/// Extensions of stored image files; every other file in the directory is ignored
+const IMAGE_EXTENSIONS: &[&str] = &["bin", "bin.gz", "svg", "svgz"];
+
+/// Deepest shard layout `with_shard_depth` accepts; lookups probe every depth up to it
+pub const MAX_SHARD_DEPTH: usize = 4;
+
+/// Formats that are already compressed, so gzipping them only costs CPU
+const PRECOMPRESSED_TYPES: &[&str] = &["image/jpeg", "image/webp", "image/gif", "image/avif"];
+
+/// Metadata recorded in the index for every stored image
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageIndexEntry {
+    /// Inscription id; absent for entries written before ids were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub txid: String,
+    pub file: String,
+    pub mime_type: String,
+    pub size: usize,
+    /// Source block; zero for entries written before heights were recorded
+    #[serde(default)]
+    pub block_height: u64,
+    #[serde(default)]
+    pub block_time: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_metadata: Option<TxMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<String>,
+    /// Inscription ids the body loads through `/content/<id>` (recursion)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+    /// Small `data:image/png;base64,...` preview, when enabled and decodable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+impl ImageIndexEntry {
+    /// Inscription id, assuming the first inscription for legacy entries
+    pub fn id(&self) -> String {
+        self.id.clone().unwrap_or_else(|| format!("{}i0", self.txid))
+    }
+}
+
+/// How image bytes compare to the MIME type they were inscribed with
+#[derive(Debug, PartialEq, Eq)]
+enum Sniffed {
+    /// The bytes match, or the declared type isn't one the image crate recognizes (e.g. SVG)
+    Matches,
+    /// The bytes are a different known format
+    Mislabeled(&'static str),
+    /// The bytes aren't any known image format
+    Unrecognized,
+}
+
+/// Leading part of an image's file name: the txid for a transaction's first
+/// inscription, named as before indexes were part of it, else the inscription id
+fn file_key(id: &str, txid: Txid) -> String {
+    let txid = txid.to_string();
+    match id.strip_prefix(txid.as_str()) {
+        Some("i0") | Some("") => txid,
+        _ => id.to_string(),
+    }
+}
+
+fn sniff(mime_type: &str, data: &[u8]) -> Sniffed {
+    let declared = match ImageFormat::from_mime_type(mime_type) {
+        Some(format) => format,
+        None => return Sniffed::Matches,
+    };
+    match image::guess_format(data) {
+        Ok(actual) if actual == declared => Sniffed::Matches,
+        Ok(actual) => Sniffed::Mislabeled(actual.to_mime_type()),
+        Err(_) => Sniffed::Unrecognized,
+    }
+}
 
 pub struct ImageStorage {
     base_dir: PathBuf,
+    index_thumbnails: bool,
+    /// Gzip files of compressible formats (`.bin.gz`)
+    compress: bool,
+    /// Skip mislabeled images instead of storing them under their sniffed type
+    strict: bool,
+    /// Write a `<txid>-<hash>.thumb.png` next to each decodable image
+    thumbnails: bool,
+    /// Levels of `ab/cd/` directories, named by leading txid characters, files are nested under
+    shard_depth: usize,
+    /// Inscription ids in the index, read on first use
+    indexed: Mutex<Option<HashSet<String>>>,
 }
 
 impl ImageStorage {
     pub fn new(base_dir: PathBuf) -> Result<Self> {
         fs::create_dir_all(&base_dir)?;
-        Ok(Self { base_dir })
+        drop_torn_tail(&base_dir.join(INDEX_FILE))?;
+        Ok(Self::detached(base_dir))
     }
 
-    pub fn store(&self, txid: Txid, mime_type: &str, data: &[u8]) -> Result<()> {
-        let hash = blake3::hash(data);
-        let filename = format!("{}-{}.bin", txid, hash);
-        let path = self.base_dir.join(filename);
-        
-        let mut file = File::create(path)?;
-        file.write_all(mime_type.as_bytes())?;
-        file.write_all(b"\n")?;
-        file.write_all(data)?;
+    /// Points at `base_dir` without creating it, for read-only or dry-run use
+    pub fn detached(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            index_thumbnails: false, compress: false, strict: false, thumbnails: false,
+            shard_depth: 0,
+            indexed: Mutex::new(None),
+        }
+    }
+
+    /// Gzips newly stored files unless the format is already compressed
+    ///
+    /// Reads handle both plain and gzipped files, so this can be toggled
+    /// on an existing image directory.
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.compress = enabled;
+        self
+    }
+
+    /// Rejects images whose bytes don't match their declared type
+    ///
+    /// By default a mislabeled image is stored under the type sniffed from
+    /// its bytes; bytes that aren't a known image format are never stored.
+    pub fn with_strict_images(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Writes a PNG thumbnail of at most 256px next to each stored image
+    ///
+    /// SVG and formats the image crate can't decode get no thumbnail.
+    pub fn with_thumbnails(mut self, enabled: bool) -> Self {
+        self.thumbnails = enabled;
+        self
+    }
+
+    /// Nests new files under `depth` directory levels named by pairs of
+    /// leading txid characters, e.g. `ab/cd/<txid>-<hash>.bin` for depth 2
+    ///
+    /// 0 keeps every file directly in the image directory. Files already
+    /// stored under another depth (up to `MAX_SHARD_DEPTH`) stay where they
+    /// are and are still found, so changing the depth never stores an image twice.
+    pub fn with_shard_depth(mut self, depth: usize) -> Self {
+        self.shard_depth = depth;
+        self
+    }
+
+    /// Embeds a small preview in each index entry
+    pub fn with_index_thumbnails(mut self, enabled: bool) -> Self {
+        self.index_thumbnails = enabled;
+        self
+    }
+
+    /// Writes the image unless it's already stored or fails validation
+    ///
+    /// Files are keyed by inscription and content hash, so re-processing a
+    /// block finds the existing file and skips it, while the same image
+    /// inscribed twice in one transaction is kept twice. A file left without
+    /// an index entry by an interrupted run is indexed instead of skipped.
+    /// Returns whether an index entry was added.
+    #[cfg(test)]
+    pub fn store(&self, id: &str, txid: Txid, mime_type: &str, data: &[u8], provenance: &Provenance) -> Result<bool> {
+        self.store_hashed(id, txid, mime_type, data, blake3::hash(data), provenance)
+    }
+
+    /// `store` for a caller that already has the blake3 hash of `data`
+    pub fn store_hashed(
+        &self,
+        id: &str,
+        txid: Txid,
+        mime_type: &str,
+        data: &[u8],
+        hash: Hash,
+        provenance: &Provenance,
+    ) -> Result<bool> {
+        let mime_type = match self.validate(txid, mime_type, data) {
+            Some(mime_type) => mime_type,
+            None => return Ok(false),
+        };
+
+        let key = file_key(id, txid);
+        let filename = match self.find_file(&key, hash) {
+            Some(_) if self.is_indexed(id)? => return Ok(false),
+            // Written by a run that stopped before indexing it
+            Some(path) => self.relative_name(&path)?,
+            None => self.write_file(&key, mime_type, data, hash)?,
+        };
+
+        if self.thumbnails && mime_type != SVG_MIME_TYPE && !self.thumbnail_path(&filename).exists() {
+            if let Some(thumbnail) = thumbnail_png(data, THUMBNAIL_SIZE) {
+                write_atomically(&self.thumbnail_path(&filename), |file| Ok(file.write_all(&thumbnail)?))?;
+            }
+        }
+
+        self.append_index(ImageIndexEntry {
+            id: Some(id.to_string()),
+            txid: txid.to_string(),
+            file: filename,
+            mime_type: mime_type.to_string(),
+            size: data.len(),
+            block_height: provenance.block_height,
+            block_time: provenance.block_time,
+            genesis_address: provenance.genesis_address.clone(),
+            tx_metadata: provenance.tx_metadata.clone(),
+            parents: provenance.parents.clone(),
+            references: provenance.references.clone(),
+            preview: if self.index_thumbnails {
+                preview_data_uri(data, PREVIEW_SIZE)
+            } else {
+                None
+            },
+        })?;
         
+        Ok(true)
+    }
+
+    /// Writes a new image file, returning its name relative to the image directory
+    fn write_file(&self, key: &str, mime_type: &str, data: &[u8], hash: Hash) -> Result<String> {
+        let compress = self.compress && !PRECOMPRESSED_TYPES.contains(&mime_type);
+        let extension = match (mime_type == SVG_MIME_TYPE, compress) {
+            (true, true) => "svgz",
+            (true, false) => "svg",
+            (false, true) => "bin.gz",
+            (false, false) => "bin",
+        };
+        let filename = self.file_name(key, hash, extension);
+        if let Some(dir) = self.base_dir.join(&filename).parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        write_atomically(&self.base_dir.join(&filename), |file| {
+            if compress {
+                let mut encoder = GzEncoder::new(file, Compression::default());
+                Self::write_contents(&mut encoder, mime_type, data)?;
+                encoder.finish()?;
+                Ok(())
+            } else {
+                Self::write_contents(file, mime_type, data)
+            }
+        })?;
+        Ok(filename)
+    }
+
+    /// Name of a file under the image directory as the index records it
+    fn relative_name(&self, path: &Path) -> Result<String> {
+        path.strip_prefix(&self.base_dir)
+            .ok()
+            .and_then(|path| path.to_str())
+            .map(|path| path.replace(std::path::MAIN_SEPARATOR, "/"))
+            .ok_or_else(|| super::StorageError::ImageError(format!("Invalid image path {}", path.display())))
+    }
+
+    /// Whether the index has an entry for inscription `id`
+    fn is_indexed(&self, id: &str) -> Result<bool> {
+        let mut indexed = self.indexed.lock().unwrap();
+        if indexed.is_none() {
+            let ids = self.iter_index()?.map(|entry| entry.map(|entry| entry.id())).collect::<Result<_>>()?;
+            *indexed = Some(ids);
+        }
+        Ok(indexed.as_ref().is_some_and(|ids| ids.contains(id)))
+    }
+
+    /// The MIME type to store an image under, or `None` to skip it
+    ///
+    /// Mislabeled images get their sniffed type, unless strict mode is on.
+    pub fn validate<'a>(&self, txid: Txid, mime_type: &'a str, data: &[u8]) -> Option<&'a str> {
+        match sniff(mime_type, data) {
+            Sniffed::Matches => Some(mime_type),
+            Sniffed::Mislabeled(actual) if !self.strict => {
+                warn!("Image in {} is labeled {} but contains {}, relabeling", txid, mime_type, actual);
+                Some(actual)
+            }
+            Sniffed::Mislabeled(actual) => {
+                warn!("Image in {} is labeled {} but contains {}, skipping", txid, mime_type, actual);
+                None
+            }
+            Sniffed::Unrecognized => {
+                warn!("Image in {} is labeled {} but isn't a recognized image, skipping", txid, mime_type);
+                None
+            }
+        }
+    }
+
+    /// File layout: the mime type, a newline, then the raw image bytes
+    ///
+    /// SVGs have no header; their extension identifies them.
+    fn write_contents(out: &mut impl Write, mime_type: &str, data: &[u8]) -> Result<()> {
+        if mime_type != SVG_MIME_TYPE {
+            out.write_all(mime_type.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+        out.write_all(data)?;
         Ok(())
     }
 
+    /// Path of an image file relative to the image directory, under its shard directories
+    fn file_name(&self, key: &str, hash: Hash, extension: &str) -> String {
+        Self::file_name_at(self.shard_depth, key, hash, extension)
+    }
+
+    fn file_name_at(depth: usize, key: &str, hash: Hash, extension: &str) -> String {
+        let mut parts: Vec<&str> = (0..depth.min(key.len() / 2))
+            .map(|level| &key[level * 2..level * 2 + 2])
+            .collect();
+        let name = format!("{}-{}.{}", key, hash, extension);
+        parts.push(&name);
+        parts.join("/")
+    }
+
+    /// `<key>-<hash>.thumb.png` for a stored `<key>-<hash>.<ext>`
+    fn thumbnail_path(&self, file: &str) -> PathBuf {
+        let stem = file.split('.').next().unwrap_or(file);
+        self.base_dir.join(format!("{}.thumb.png", stem))
+    }
+
+    /// Path of the stored file for an image, plain or gzipped, under any shard depth
+    ///
+    /// The current depth is tried first; files stored before it changed are
+    /// still found.
+    fn find_file(&self, key: &str, hash: Hash) -> Option<PathBuf> {
+        std::iter::once(self.shard_depth)
+            .chain((0..=MAX_SHARD_DEPTH).filter(|&depth| depth != self.shard_depth))
+            .flat_map(|depth| IMAGE_EXTENSIONS.iter().map(move |ext| Self::file_name_at(depth, key, hash, ext)))
+            .map(|file| self.base_dir.join(file))
+            .find(|path| path.exists())
+    }
+
+    /// Appends an entry in a single write, synced before the id counts as indexed
+    ///
+    /// A crash can still tear the line; `new` drops it on the next start.
+    fn append_index(&self, entry: ImageIndexEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.base_dir.join(INDEX_FILE))?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+        if let Some(ids) = self.indexed.lock().unwrap().as_mut() {
+            ids.insert(entry.id());
+        }
+        Ok(())
+    }
+
+    /// Full path of a stored file named in the index
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.base_dir.join(file)
+    }
+
+    /// Reads every entry of the image index
+    pub fn index(&self) -> Result<Vec<ImageIndexEntry>> {
+        self.iter_index()?.collect()
+    }
+
+    /// Entries of the image index, each read as the iterator reaches it
+    pub fn iter_index(&self) -> Result<impl Iterator<Item = Result<ImageIndexEntry>>> {
+        let file = match File::open(self.base_dir.join(INDEX_FILE)) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(file
+            .into_iter()
+            .flat_map(|file| BufReader::new(file).lines())
+            .map(|line| Ok(serde_json::from_str(&line?)?)))
+    }
+
+    /// Deletes images from `heights` and their index entries
+    ///
+    /// Returns the removed index entries.
+    pub fn remove_heights(&self, heights: &BTreeSet<u64>) -> Result<Vec<ImageIndexEntry>> {
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .index()?
+            .into_iter()
+            .partition(|entry| heights.contains(&entry.block_height));
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        write_atomically(&self.base_dir.join(INDEX_FILE), |file| {
+            let mut writer = BufWriter::new(file);
+            for entry in &kept {
+                serde_json::to_writer(&mut writer, entry)?;
+                writeln!(writer)?;
+            }
+            writer.flush()?;
+            Ok(())
+        })?;
+        if let Some(ids) = self.indexed.lock().unwrap().as_mut() {
+            for entry in &removed {
+                ids.remove(&entry.id());
+            }
+        }
+
+        for entry in &removed {
+            for path in [self.base_dir.join(&entry.file), self.thumbnail_path(&entry.file)] {
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// The image with content `hash` stored for the first inscription of `txid`
+    #[cfg(test)]
     pub fn get(&self, txid: Txid, hash: Hash) -> Result<Option<(String, Vec<u8>)>> {
-        let filename = format!("{}-{}.bin", txid, hash);
-        let path = self.base_dir.join(filename);
-        
-        if !path.exists() {
-            return Ok(None);
+        match self.find_file(&txid.to_string(), hash) {
+            Some(path) => Self::read_file(&path).map(Some),
+            None => Ok(None),
         }
+    }
+
+    /// Finds the first image stored for a txid, looking its file up in the index
+    pub fn get_by_txid(&self, txid: Txid) -> Result<Option<(ImageIndexEntry, Vec<u8>)>> {
+        let txid = txid.to_string();
+        let found = self
+            .iter_index()?
+            .find(|entry| entry.as_ref().map_or(true, |entry| entry.txid == txid))
+            .transpose()?;
+        match found {
+            Some(entry) => {
+                let data = self.body(&entry)?;
+                Ok(Some((entry, data)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the image an index entry points at
+    pub fn body(&self, entry: &ImageIndexEntry) -> Result<Vec<u8>> {
+        let (_, data) = Self::read_file(&self.base_dir.join(&entry.file))?;
+        Ok(data)
+    }
+
+    /// Lists every stored image as (txid, mime type, data), sorted by filename
+    ///
+    /// Only the file names are gathered up front; each image is read as the
+    /// iterator reaches it.
+    pub fn entries(&self) -> Result<impl Iterator<Item = Result<(String, String, Vec<u8>)>>> {
+        let mut paths = Vec::new();
+        Self::collect_files(&self.base_dir, &mut paths)?;
+        paths.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        Ok(paths.into_iter().map(|path| {
+            let txid = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.split(['-', 'i']).next())
+                .ok_or_else(|| super::StorageError::ImageError("Invalid filename".to_string()))?
+                .to_string();
+            let (mime_type, data) = Self::read_file(&path)?;
+            Ok((txid, mime_type, data))
+        }))
+    }
+
+    /// Image files in `dir` and its shard directories
+    fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            // Not `path.is_dir()`, which follows symlinks out of the image directory
+            if entry.file_type()?.is_dir() {
+                Self::collect_files(&path, paths)?;
+                continue;
+            }
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+            if IMAGE_EXTENSIONS.iter().any(|ext| name.ends_with(&format!(".{}", ext))) {
+                paths.push(path);
+            }
+        }
+        Ok(())
+    }
 
-        let content = fs::read(&path)?;
+    fn read_file(path: &Path) -> Result<(String, Vec<u8>)> {
+        let mut content = fs::read(path)?;
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        if extension == "gz" || extension == "svgz" {
+            let mut decoded = Vec::new();
+            GzDecoder::new(content.as_slice()).read_to_end(&mut decoded)?;
+            content = decoded;
+        }
+        if extension == "svg" || extension == "svgz" {
+            return Ok((SVG_MIME_TYPE.to_string(), content));
+        }
         let mut parts = content.splitn(2, |&b| b == b'\n');
         
         let mime_type = parts
@@ -49,7 +520,7 @@ impl ImageStorage {
             .ok_or_else(|| super::StorageError::ImageError("Invalid data".to_string()))?
             .to_vec();
 
-        Ok(Some((mime_type, data)))
+        Ok((mime_type, data))
     }
 }
 
@@ -66,14 +537,216 @@ mod tests {
 
         let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
         let mime_type = "image/png";
-        let data = vec![1, 2, 3, 4];
+        let data = crate::storage::thumbnail::tests::sample_png(4, 4);
         
-        storage.store(txid, mime_type, &data).unwrap();
+        storage.store(&format!("{}i0", txid), txid, mime_type, &data, &Provenance::default()).unwrap();
         
         let hash = blake3::hash(&data);
         let (stored_mime_type, stored_data) = storage.get(txid, hash).unwrap().unwrap();
         
         assert_eq!(stored_mime_type, mime_type);
         assert_eq!(stored_data, data);
+        assert_eq!(storage.index().unwrap()[0].id, Some(format!("{}i0", txid)));
+    }
+
+    #[test]
+    fn test_same_image_at_two_indexes_is_kept_twice() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let txid = Txid::from_str(&"ab".repeat(32)).unwrap();
+        let data = crate::storage::thumbnail::tests::sample_png(4, 4);
+
+        for index in [0, 1] {
+            let id = format!("{}i{}", txid, index);
+            assert!(storage.store(&id, txid, "image/png", &data, &Provenance::default()).unwrap());
+            assert!(!storage.store(&id, txid, "image/png", &data, &Provenance::default()).unwrap());
+        }
+
+        let hash = blake3::hash(&data);
+        assert!(temp_dir.path().join(format!("{}-{}.bin", txid, hash)).exists());
+        assert!(temp_dir.path().join(format!("{}i1-{}.bin", txid, hash)).exists());
+        let ids: Vec<String> = storage.index().unwrap().iter().map(ImageIndexEntry::id).collect();
+        assert_eq!(ids, vec![format!("{}i0", txid), format!("{}i1", txid)]);
+        let txids: Vec<String> = storage.entries().unwrap().map(|entry| entry.unwrap().0).collect();
+        assert_eq!(txids, vec![txid.to_string(), txid.to_string()]);
+    }
+
+    #[test]
+    fn test_unindexed_file_is_indexed_on_the_next_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap().with_shard_depth(1);
+        let txid = Txid::from_str(&"cd".repeat(32)).unwrap();
+        let id = format!("{}i0", txid);
+        let data = crate::storage::thumbnail::tests::sample_png(4, 4);
+        assert!(storage.store(&id, txid, "image/png", &data, &Provenance::default()).unwrap());
+
+        // A crash after the file was written but before it was indexed
+        fs::remove_file(temp_dir.path().join(INDEX_FILE)).unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap().with_shard_depth(1);
+        assert!(storage.store(&id, txid, "image/png", &data, &Provenance::default()).unwrap());
+        assert!(!storage.store(&id, txid, "image/png", &data, &Provenance::default()).unwrap());
+
+        let index = storage.index().unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[0].file, format!("cd/{}-{}.bin", txid, blake3::hash(&data)));
+        assert_eq!(storage.body(&index[0]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_torn_index_line_is_dropped_on_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let txid = Txid::from_str(&"ce".repeat(32)).unwrap();
+        let data = crate::storage::thumbnail::tests::sample_png(4, 4);
+        assert!(storage.store(&format!("{}i0", txid), txid, "image/png", &data, &Provenance::default()).unwrap());
+
+        // A crash mid-append leaves half an entry behind
+        let mut index = OpenOptions::new().append(true).open(temp_dir.path().join(INDEX_FILE)).unwrap();
+        index.write_all(br#"{"id":"torn","txid":"#).unwrap();
+        drop(index);
+
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        assert!(!storage.store(&format!("{}i0", txid), txid, "image/png", &data, &Provenance::default()).unwrap());
+        assert!(storage.store(&format!("{}i1", txid), txid, "image/png", &data, &Provenance::default()).unwrap());
+        assert_eq!(storage.index().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_sharded_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap().with_shard_depth(2);
+
+        let txid = Txid::from_str("abcdef0000000000000000000000000000000000000000000000000000000000").unwrap();
+        let data = crate::storage::thumbnail::tests::sample_png(4, 4);
+        let hash = blake3::hash(&data);
+        assert!(storage.store(&format!("{}i0", txid), txid, "image/png", &data, &Provenance::default()).unwrap());
+
+        let nested = temp_dir.path().join("ab").join("cd").join(format!("{}-{}.bin", txid, hash));
+        assert!(nested.exists());
+        assert_eq!(storage.path(&storage.index().unwrap()[0].file), nested);
+
+        let (mime_type, stored) = storage.get(txid, hash).unwrap().unwrap();
+        assert_eq!(mime_type, "image/png");
+        assert_eq!(stored, data);
+        assert_eq!(storage.entries().unwrap().count(), 1);
+        assert!(!storage.store(&format!("{}i0", txid), txid, "image/png", &data, &Provenance::default()).unwrap());
+
+        // Switching layouts still finds the image stored under the old one
+        for depth in [0, 1, 3] {
+            let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap().with_shard_depth(depth);
+            assert_eq!(storage.get(txid, hash).unwrap().unwrap().1, data);
+            assert!(!storage.store(&format!("{}i0", txid), txid, "image/png", &data, &Provenance::default()).unwrap());
+        }
+        assert_eq!(storage.index().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_compressed_svg_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf())
+            .unwrap()
+            .with_compression(true);
+
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+        let svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\">{}</svg>",
+            "<rect width=\"1\" height=\"1\"/>".repeat(200)
+        )
+        .into_bytes();
+        assert!(storage.store(&format!("{}i0", txid), txid, "image/svg+xml", &svg, &Provenance::default()).unwrap());
+        assert!(!storage.store(&format!("{}i0", txid), txid, "image/svg+xml", &svg, &Provenance::default()).unwrap());
+
+        let hash = blake3::hash(&svg);
+        let path = temp_dir.path().join(format!("{}-{}.svgz", txid, hash));
+        assert!(fs::metadata(&path).unwrap().len() < svg.len() as u64);
+
+        let (mime_type, data) = storage.get(txid, hash).unwrap().unwrap();
+        assert_eq!(mime_type, "image/svg+xml");
+        assert_eq!(data, svg);
+        assert_eq!(storage.get_by_txid(txid).unwrap().unwrap().1, svg);
+        assert_eq!(storage.entries().unwrap().next().unwrap().unwrap().2, svg);
+
+        // Already-compressed formats are stored as-is
+        let jpeg = vec![0xff, 0xd8, 0xff, 0xe0];
+        storage.store(&format!("{}i0", txid), txid, "image/jpeg", &jpeg, &Provenance::default()).unwrap();
+        let plain = temp_dir.path().join(format!("{}-{}.bin", txid, blake3::hash(&jpeg)));
+        assert!(plain.exists());
+    }
+
+    #[test]
+    fn test_images_are_checked_against_their_mime_type() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000003").unwrap();
+        let png = crate::storage::thumbnail::tests::sample_png(8, 8);
+
+        assert_eq!(sniff("image/png", &png), Sniffed::Matches);
+        assert_eq!(sniff("image/svg+xml", b"<svg/>"), Sniffed::Matches);
+
+        // Random bytes labeled as PNG are never written
+        let noise: Vec<u8> = (0..64u8).map(|i| i.wrapping_mul(37) ^ 0x5a).collect();
+        assert_eq!(sniff("image/png", &noise), Sniffed::Unrecognized);
+        assert!(!storage.store(&format!("{}i0", txid), txid, "image/png", &noise, &Provenance::default()).unwrap());
+        assert!(storage.get(txid, blake3::hash(&noise)).unwrap().is_none());
+
+        // A PNG labeled as JPEG is stored under its real type
+        assert!(storage.store(&format!("{}i0", txid), txid, "image/jpeg", &png, &Provenance::default()).unwrap());
+        let (mime_type, data) = storage.get(txid, blake3::hash(&png)).unwrap().unwrap();
+        assert_eq!(mime_type, "image/png");
+        assert_eq!(data, png);
+        assert_eq!(storage.index().unwrap()[0].mime_type, "image/png");
+
+        // ...or rejected outright in strict mode
+        let strict_dir = TempDir::new().unwrap();
+        let strict = ImageStorage::new(strict_dir.path().to_path_buf())
+            .unwrap()
+            .with_strict_images(true);
+        assert!(!strict.store(&format!("{}i0", txid), txid, "image/jpeg", &png, &Provenance::default()).unwrap());
+        assert!(strict.store(&format!("{}i0", txid), txid, "image/png", &png, &Provenance::default()).unwrap());
+        assert_eq!(strict.index().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_thumbnail_written_next_to_image() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf())
+            .unwrap()
+            .with_thumbnails(true);
+
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000004").unwrap();
+        let png = crate::storage::thumbnail::tests::sample_png(600, 300);
+        storage.store(&format!("{}i0", txid), txid, "image/png", &png, &Provenance::default()).unwrap();
+
+        let path = temp_dir.path().join(format!("{}-{}.thumb.png", txid, blake3::hash(&png)));
+        let thumbnail = image::open(&path).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+        // SVG is stored without one, and thumbnails aren't listed as images
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>".to_vec();
+        storage.store(&format!("{}i1", txid), txid, "image/svg+xml", &svg, &Provenance::default()).unwrap();
+        assert!(!temp_dir.path().join(format!("{}i1-{}.thumb.png", txid, blake3::hash(&svg))).exists());
+        assert_eq!(fs::read(temp_dir.path().join(format!("{}i1-{}.svg", txid, blake3::hash(&svg)))).unwrap(), svg);
+        assert_eq!(storage.entries().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_index_preview() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ImageStorage::new(temp_dir.path().to_path_buf())
+            .unwrap()
+            .with_index_thumbnails(true);
+
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+        let png = crate::storage::thumbnail::tests::sample_png(100, 100);
+        storage.store(&format!("{}i0", txid), txid, "image/png", &png, &Provenance::default()).unwrap();
+
+        let index = storage.index().unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[0].size, png.len());
+
+        let preview = index[0].preview.as_deref().unwrap();
+        let encoded = preview.strip_prefix("data:image/png;base64,").unwrap();
+        let thumbnail = image::load_from_memory(&base64::decode(encoded).unwrap()).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (32, 32));
     }
 }
@@ -0,0 +1,215 @@
+use super::{write_atomically, Result};
+use crate::parser::TxMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Record of one inscription whose body lives in the content store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkEntry {
+    pub id: String,
+    pub txid: String,
+    /// Content id (see `Inscription::content_id`) naming the shared body
+    pub content_id: String,
+    pub content_type: String,
+    pub block_height: u64,
+    pub block_time: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_metadata: Option<TxMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<String>,
+    /// Inscription ids the body loads through `/content/<id>` (recursion)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+}
+
+/// In-memory view of the link log
+#[derive(Default)]
+struct Links {
+    ids: HashSet<String>,
+    refcounts: HashMap<String, usize>,
+}
+
+/// Content-addressed bodies shared by every inscription that carries them
+///
+/// Each distinct body is written once to `content/<xx>/<content id>` and
+/// every inscription, text or image, is an entry in `links.jsonl` pointing
+/// at it. Collections reusing the same traits then cost one body each.
+pub struct LinkedStorage {
+    content_dir: PathBuf,
+    links_file: PathBuf,
+    links: Mutex<Links>,
+}
+
+impl LinkedStorage {
+    /// Opens the content store and link log under `data_dir`
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let content_dir = data_dir.join("content");
+        fs::create_dir_all(&content_dir)?;
+
+        let mut storage = Self {
+            content_dir,
+            links_file: data_dir.join("links.jsonl"),
+            links: Mutex::new(Links::default()),
+        };
+        let mut links = Links::default();
+        for entry in storage.entries()? {
+            *links.refcounts.entry(entry.content_id).or_default() += 1;
+            links.ids.insert(entry.id);
+        }
+        storage.links = Mutex::new(links);
+        Ok(storage)
+    }
+
+    /// Links `entry` to its body, writing the body only if no other
+    /// inscription carries it yet
+    ///
+    /// Returns whether the entry was recorded; an id already linked is skipped.
+    pub fn store(&self, entry: &LinkEntry, body: &[u8]) -> Result<bool> {
+        let mut links = self.links.lock().unwrap_or_else(|e| e.into_inner());
+        if links.ids.contains(&entry.id) {
+            return Ok(false);
+        }
+
+        let path = self.body_path(&entry.content_id);
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_atomically(&path, |file| Ok(file.write_all(body)?))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.links_file)?;
+        serde_json::to_writer(&mut file, entry)?;
+        writeln!(file)?;
+
+        links.ids.insert(entry.id.clone());
+        *links.refcounts.entry(entry.content_id.clone()).or_default() += 1;
+        Ok(true)
+    }
+
+    /// Number of inscriptions linked to a body
+    #[cfg(test)]
+    pub fn refcount(&self, content_id: &str) -> usize {
+        let links = self.links.lock().unwrap_or_else(|e| e.into_inner());
+        links.refcounts.get(content_id).copied().unwrap_or(0)
+    }
+
+    /// Where the body for a content id is kept
+    pub fn body_path(&self, content_id: &str) -> PathBuf {
+        let shard = content_id.get(..2).unwrap_or(content_id);
+        self.content_dir.join(shard).join(content_id)
+    }
+
+    pub fn body(&self, content_id: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.body_path(content_id))?)
+    }
+
+    /// Every link entry, in insertion order
+    pub fn entries(&self) -> Result<Vec<LinkEntry>> {
+        self.iter_entries()?.collect()
+    }
+
+    /// Link entries in insertion order, each read as the iterator reaches it
+    pub fn iter_entries(&self) -> Result<impl Iterator<Item = Result<LinkEntry>>> {
+        let file = match File::open(&self.links_file) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(file
+            .into_iter()
+            .flat_map(|file| BufReader::new(file).lines())
+            .map(|line| Ok(serde_json::from_str(&line?)?)))
+    }
+
+    /// Unlinks every inscription from `heights`, deleting bodies nothing links to anymore
+    ///
+    /// Returns the removed entries.
+    pub fn remove_heights(&self, heights: &BTreeSet<u64>) -> Result<Vec<LinkEntry>> {
+        let mut links = self.links.lock().unwrap_or_else(|e| e.into_inner());
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .entries()?
+            .into_iter()
+            .partition(|entry| heights.contains(&entry.block_height));
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        write_atomically(&self.links_file, |file| {
+            let mut writer = BufWriter::new(file);
+            for entry in &kept {
+                serde_json::to_writer(&mut writer, entry)?;
+                writeln!(writer)?;
+            }
+            writer.flush()?;
+            Ok(())
+        })?;
+
+        for entry in &removed {
+            links.ids.remove(&entry.id);
+            let count = links.refcounts.entry(entry.content_id.clone()).or_default();
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                links.refcounts.remove(&entry.content_id);
+                match fs::remove_file(self.body_path(&entry.content_id)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(id: &str, content_id: &str, block_height: u64) -> LinkEntry {
+        LinkEntry {
+            id: id.to_string(),
+            txid: id.trim_end_matches("i0").to_string(),
+            content_id: content_id.to_string(),
+            content_type: "text/plain;charset=utf-8".to_string(),
+            block_height,
+            block_time: 0,
+            genesis_address: None,
+            tx_metadata: None,
+            parents: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_bodies_are_deleted_with_their_last_link() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LinkedStorage::open(temp_dir.path()).unwrap();
+
+        assert!(storage.store(&entry("ai0", "abcd", 1), b"shared").unwrap());
+        assert!(storage.store(&entry("bi0", "abcd", 2), b"shared").unwrap());
+        assert!(!storage.store(&entry("bi0", "abcd", 2), b"shared").unwrap());
+        assert_eq!(storage.refcount("abcd"), 2);
+
+        // Counts are rebuilt from the link log on reopen
+        let storage = LinkedStorage::open(temp_dir.path()).unwrap();
+        assert_eq!(storage.refcount("abcd"), 2);
+
+        storage.remove_heights(&BTreeSet::from([1])).unwrap();
+        assert_eq!(storage.refcount("abcd"), 1);
+        assert_eq!(storage.body("abcd").unwrap(), b"shared");
+
+        storage.remove_heights(&BTreeSet::from([2])).unwrap();
+        assert_eq!(storage.refcount("abcd"), 0);
+        assert!(!storage.body_path("abcd").exists());
+    }
+}
@@ -0,0 +1,114 @@
+use super::{Result, StorageError};
+use log::warn;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const LOCK_FILE: &str = ".scanner.lock";
+
+/// Exclusive lock on a storage directory, held for the lifetime of a scan
+///
+/// The lock file records the owner's PID. It is removed when the lock is
+/// dropped, so a clean exit always releases it.
+#[derive(Debug)]
+pub struct ScanLock {
+    path: PathBuf,
+}
+
+impl ScanLock {
+    /// Acquires the lock in `dir`, refusing if another live instance holds it
+    ///
+    /// With `force`, an existing lock is taken over regardless of its owner.
+    pub fn acquire(dir: &Path, force: bool) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(LOCK_FILE);
+
+        match Self::create(&path) {
+            Err(StorageError::IoError(e)) if e.kind() == ErrorKind::AlreadyExists => {}
+            other => return other,
+        }
+
+        let owner = fs::read_to_string(&path)
+            .ok()
+            .and_then(|pid| pid.trim().parse::<u32>().ok());
+
+        match owner {
+            Some(pid) if !force && process_alive(pid) => {
+                return Err(StorageError::LockError(format!(
+                    "{} is held by running process {} (use --force to override)",
+                    path.display(),
+                    pid
+                )));
+            }
+            Some(pid) => warn!("Taking over lock {} from process {}", path.display(), pid),
+            None => warn!("Replacing unreadable lock file {}", path.display()),
+        }
+
+        fs::remove_file(&path)?;
+        Self::create(&path)
+    }
+
+    fn create(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+
+        Ok(Self { path: path.to_path_buf() })
+    }
+}
+
+impl Drop for ScanLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to release lock {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+// Without a cheap liveness check, assume the owner is alive and rely on --force
+#[cfg(not(target_os = "linux"))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_instance_fails_while_locked() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let lock = ScanLock::acquire(temp_dir.path(), false).unwrap();
+        assert!(temp_dir.path().join(LOCK_FILE).exists());
+
+        // Our own PID is alive, so a second acquire must be refused
+        match ScanLock::acquire(temp_dir.path(), false) {
+            Err(StorageError::LockError(_)) => {}
+            other => panic!("Expected lock error, got {:?}", other),
+        }
+
+        drop(lock);
+        assert!(!temp_dir.path().join(LOCK_FILE).exists());
+        let _lock = ScanLock::acquire(temp_dir.path(), false).unwrap();
+    }
+
+    #[test]
+    fn test_force_overrides_stale_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(LOCK_FILE), "not-a-pid").unwrap();
+
+        let _lock = ScanLock::acquire(temp_dir.path(), true).unwrap();
+        let pid = fs::read_to_string(temp_dir.path().join(LOCK_FILE)).unwrap();
+        assert_eq!(pid, std::process::id().to_string());
+    }
+}
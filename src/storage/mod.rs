@@ -1,8 +1,43 @@
+mod archive;
+mod binary;
+mod chain;
+mod content_index;
+mod export;
 mod image;
+mod linked;
+mod lock;
+mod ord;
+mod parent_index;
+mod sqlite;
+mod state;
+mod stream;
 mod text;
+mod thumbnail;
 
-use crate::parser::Inscription;
-use std::path::PathBuf;
+pub use archive::RawArchive;
+pub use binary::BinaryStorage;
+pub use chain::REORG_WINDOW;
+pub use export::{export, ExportRow};
+pub use image::MAX_SHARD_DEPTH;
+pub use linked::LinkedStorage;
+pub use lock::ScanLock;
+pub use ord::export_ord;
+pub use sqlite::SqliteStorage;
+pub use state::ScanState;
+pub use stream::StreamSink;
+
+use crate::cache::{ContentHashIndex, Deduplicator};
+use crate::parser::{Inscription, InscriptionType, TxMetadata};
+use bitcoin::{BlockHash, Txid};
+use log::{debug, info, warn};
+use lru::LruCache;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 use serde_json;
 
@@ -22,36 +57,1197 @@ pub enum StorageError {
 
     #[error("Hash error: {0}")]
     HashError(#[from] bitcoin::hashes::Error),
+
+    #[error("Lock error: {0}")]
+    LockError(String),
+
+    #[error("Archive error: {0}")]
+    ArchiveError(String),
+
+    #[error("Cache error: {0}")]
+    CacheError(#[from] crate::cache::CacheError),
+
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[error("CSV error: {0}")]
+    CsvError(#[from] csv::Error),
+
+    #[error("Export error: {0}")]
+    ExportError(String),
+
+    #[error("Block log error: {0}")]
+    BlockLogError(String),
 }
 
 pub type Result<T> = std::result::Result<T, StorageError>;
 
+/// Where an inscription was found, recorded with every stored entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Provenance {
+    pub block_height: u64,
+    pub block_time: u32,
+    /// Address of the reveal output, when the parser recorded it
+    pub genesis_address: Option<String>,
+    /// Shape of the reveal transaction, when the parser recorded it
+    pub tx_metadata: Option<TxMetadata>,
+    /// Ids of the parent inscriptions (tag 3)
+    pub parents: Vec<String>,
+    /// Ids of the inscriptions the body loads recursively
+    pub references: Vec<String>,
+}
+
+impl Provenance {
+    pub fn of(inscription: &Inscription) -> Self {
+        Self {
+            block_height: inscription.block_height,
+            block_time: inscription.block_time,
+            genesis_address: inscription.genesis_address.clone(),
+            tx_metadata: inscription.tx_metadata.clone(),
+            parents: inscription.parents.clone(),
+            references: inscription.references.clone(),
+        }
+    }
+
+    /// Copies the recorded fields back onto an inscription read from storage
+    fn apply(self, inscription: &mut Inscription) {
+        inscription.block_height = self.block_height;
+        inscription.block_time = self.block_time;
+        inscription.genesis_address = self.genesis_address;
+        inscription.tx_metadata = self.tx_metadata;
+        inscription.parents = self.parents;
+        inscription.references = self.references;
+    }
+}
+
+/// A stored inscription as read back from any backend
+#[derive(Debug, Clone)]
+pub struct StoredEntry {
+    pub txid: String,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl StoredEntry {
+    /// What storage holds for `inscription` once it's stored
+    fn of(inscription: &Inscription) -> Self {
+        Self {
+            txid: inscription.txid.to_string(),
+            content_type: inscription.mime_type().to_string(),
+            body: inscription.content.bytes().into_owned(),
+        }
+    }
+
+    /// Converts the stored body back into inscription content
+    pub fn into_content(self) -> InscriptionType {
+        if self.content_type == "image/svg+xml" {
+            return match String::from_utf8(self.body) {
+                Ok(svg) => InscriptionType::Svg(svg),
+                Err(e) => InscriptionType::Unknown(e.into_bytes()),
+            };
+        }
+        if self.content_type.starts_with("image/") {
+            return InscriptionType::Image {
+                mime_type: self.content_type,
+                data: self.body,
+            };
+        }
+        if self.content_type.starts_with("application/json") {
+            if let Ok(value) = serde_json::from_slice(&self.body) {
+                return InscriptionType::Json(value);
+            }
+        }
+        match String::from_utf8(self.body) {
+            Ok(text) => InscriptionType::Text(text),
+            Err(e) => InscriptionType::Unknown(e.into_bytes()),
+        }
+    }
+}
+
+/// Delegating inscriptions whose target wasn't stored yet, one JSON per line
+const PENDING_DELEGATES: &str = "unresolved_delegates.jsonl";
+
+/// What `resolve_pending_delegates` needs to know without reading storage
+#[derive(Default)]
+struct PendingDelegates {
+    /// Targets of every delegate in the pending file
+    targets: HashSet<String>,
+    /// Targets of delegates deferred since the last pass, looked up in storage once
+    deferred: HashSet<String>,
+    /// Pending targets stored since the last pass
+    stored: HashMap<String, StoredEntry>,
+}
+
+impl PendingDelegates {
+    /// Reads the targets of the delegates a previous run left pending
+    ///
+    /// They count as newly deferred, so the first pass looks them up once in
+    /// case the run stopped after storing a target but before resolving.
+    fn load(path: &Path) -> Result<Self> {
+        let mut pending = Self::default();
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let inscription: Inscription = serde_json::from_str(&line?)?;
+                pending.targets.extend(inscription.delegate);
+            }
+        }
+        pending.deferred = pending.targets.clone();
+        Ok(pending)
+    }
+}
+
+/// Creates `path` through a hidden temp file in the same directory, renamed
+/// into place only once `write` succeeds and the file is synced to disk
+///
+/// A crash or write error never leaves a truncated file at `path`, and the
+/// directory is synced after the rename so the new name survives power loss.
+fn write_atomically(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let tmp_path = path.with_file_name(format!(".{}.tmp", name));
+    let written = File::create(&tmp_path).map_err(StorageError::from).and_then(|mut file| {
+        write(&mut file)?;
+        file.flush()?;
+        file.sync_all()?;
+        Ok(())
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    fs::rename(&tmp_path, path)?;
+    sync_dir(path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")))
+}
+
+/// Truncates an append-only JSONL file after its last complete line
+///
+/// A crash while an entry was being appended can leave half of it at the
+/// end; it's dropped with a warning so the file stays readable.
+fn drop_torn_tail(path: &Path) -> Result<()> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let len = file.metadata()?.len();
+
+    // Scan back from the end for the last newline
+    let mut end = len;
+    let mut chunk = [0u8; 8192];
+    while end > 0 {
+        let start = end.saturating_sub(chunk.len() as u64);
+        let buf = &mut chunk[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(buf)?;
+        if let Some(newline) = buf.iter().rposition(|&byte| byte == b'\n') {
+            end = start + newline as u64 + 1;
+            break;
+        }
+        end = start;
+    }
+
+    if end < len {
+        warn!("Dropping a torn {} byte entry at the end of {}", len - end, path.display());
+        file.set_len(end)?;
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Flushes a directory's entries, e.g. a rename into it
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Directories can't be opened as files here; renames are left to the OS
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Stores inscriptions in the configured backends
+///
+/// The backends sit behind an `Arc`, so stores run on tokio's blocking pool
+/// without holding up the runtime. Reads go through `StorageInner`.
 pub struct Storage {
+    inner: Arc<StorageInner>,
+}
+
+/// The backends and bookkeeping a `Storage` shares with its blocking tasks
+pub struct StorageInner {
     image_storage: image::ImageStorage,
     text_storage: text::TextStorage,
+    content_index: content_index::ContentIndex,
+    /// Children of each parent inscription, for `children_of`
+    parent_index: parent_index::ParentIndex,
+    /// Block hash of every height that stored inscriptions, for `diff-chain`
+    block_log: chain::BlockLog,
+    recent_blocks: chain::RecentBlocks,
+    data_dir: PathBuf,
+    /// Log what would be stored instead of writing anything
+    dry_run: bool,
+    /// Fast skip for inscriptions stored by an earlier run
+    dedup: Option<Deduplicator>,
+    /// Replaces the JSONL text log when the sqlite backend is selected
+    sqlite: Option<SqliteStorage>,
+    /// Stores every body once, content-addressed, instead of per inscription
+    linked: Option<LinkedStorage>,
+    /// Keeps bodies with a declared type that isn't text or an image
+    binary: Option<BinaryStorage>,
+    /// Publishes every stored inscription for downstream consumers
+    stream: Option<StreamSink>,
+    /// Inscription ids per body hash, for `find_duplicates`
+    content_hashes: Option<ContentHashIndex>,
+    /// Recently stored text, JSON and SVG inscriptions by id, served without reading storage
+    recent: Option<Mutex<LruCache<String, Inscription>>>,
+    /// Targets of deferred delegates, and those stored since the last resolve
+    delegates: Mutex<PendingDelegates>,
 }
 
 impl Storage {
     pub fn new(image_dir: PathBuf, text_log: PathBuf) -> Result<Self> {
-        Ok(Self {
+        // Scanner bookkeeping (cursor, lock) lives next to the text log
+        let data_dir = text_log
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let delegates = PendingDelegates::load(&data_dir.join(PENDING_DELEGATES))?;
+        let inner = StorageInner {
             image_storage: image::ImageStorage::new(image_dir)?,
             text_storage: text::TextStorage::new(text_log)?,
+            content_index: content_index::ContentIndex::new(data_dir.join("content_ids.txt")),
+            parent_index: parent_index::ParentIndex::new(data_dir.join("parents.txt")),
+            block_log: chain::BlockLog::new(data_dir.join("blocks.txt")),
+            recent_blocks: chain::RecentBlocks::new(data_dir.join("recent_blocks.txt")),
+            data_dir,
+            dry_run: false,
+            dedup: None,
+            sqlite: None,
+            linked: None,
+            binary: None,
+            stream: None,
+            content_hashes: None,
+            recent: None,
+            delegates: Mutex::new(delegates),
+        };
+        Ok(Self { inner: Arc::new(inner) })
+    }
+
+    /// Storage that never touches the disk: stores are logged and the
+    /// scan cursor is not saved, so a dry run leaves no files behind
+    pub fn dry_run(image_dir: PathBuf, text_log: PathBuf) -> Self {
+        let data_dir = text_log
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let inner = StorageInner {
+            image_storage: image::ImageStorage::detached(image_dir),
+            text_storage: text::TextStorage::detached(text_log),
+            content_index: content_index::ContentIndex::new(data_dir.join("content_ids.txt")),
+            parent_index: parent_index::ParentIndex::new(data_dir.join("parents.txt")),
+            block_log: chain::BlockLog::new(data_dir.join("blocks.txt")),
+            recent_blocks: chain::RecentBlocks::new(data_dir.join("recent_blocks.txt")),
+            data_dir,
+            dry_run: true,
+            dedup: None,
+            sqlite: None,
+            linked: None,
+            binary: None,
+            stream: None,
+            content_hashes: None,
+            recent: None,
+            delegates: Mutex::new(PendingDelegates::default()),
+        };
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// Skips inscriptions the deduplicator has already seen stored
+    pub fn with_dedup(self, dedup: Deduplicator) -> Self {
+        self.configure(|mut inner| {
+            inner.dedup = Some(dedup);
+            inner
+        })
+    }
+
+    /// Gzips stored image files of compressible formats
+    pub fn with_compress_images(self, enabled: bool) -> Self {
+        self.configure(|mut inner| {
+            inner.image_storage = inner.image_storage.with_compression(enabled);
+            inner
+        })
+    }
+
+    /// Skips mislabeled images instead of relabeling them with their sniffed type
+    pub fn with_strict_images(self, strict: bool) -> Self {
+        self.configure(|mut inner| {
+            inner.image_storage = inner.image_storage.with_strict_images(strict);
+            inner
+        })
+    }
+
+    /// Rotates the text log once it reaches `max_log_bytes`; 0 disables rotation
+    pub fn with_max_log_bytes(self, max_log_bytes: u64) -> Self {
+        self.configure(|mut inner| {
+            inner.text_storage = inner.text_storage.with_max_log_bytes(max_log_bytes);
+            inner
+        })
+    }
+
+    /// Stores text and JSON inscriptions in SQLite instead of the text log
+    pub fn with_sqlite(self, sqlite: SqliteStorage) -> Self {
+        self.configure(|mut inner| {
+            inner.sqlite = Some(sqlite);
+            inner
+        })
+    }
+
+    /// Stores text, JSON and image bodies once per distinct content,
+    /// linking every inscription that carries them
+    pub fn with_linked(self, linked: LinkedStorage) -> Self {
+        self.configure(|mut inner| {
+            inner.linked = Some(linked);
+            inner
         })
     }
 
-    #[allow(dead_code)]
-pub async fn store_inscription(&self, inscription: &Inscription) -> Result<()> {
-    match &inscription.content {
+    /// Stores bodies with a declared type other than text or images
+    /// (audio, video, HTML, ...) instead of dropping them
+    pub fn with_binary(self, binary: BinaryStorage) -> Self {
+        self.configure(|mut inner| {
+            inner.binary = Some(binary);
+            inner
+        })
+    }
+
+    /// Indexes every inscription by the blake3 hash of its body
+    pub fn with_content_hashes(self, index: ContentHashIndex) -> Self {
+        self.configure(|mut inner| {
+            inner.content_hashes = Some(index);
+            inner
+        })
+    }
+
+    /// Keeps the last `capacity` stored inscriptions in memory for `get_by_txid`; 0 disables
+    pub fn with_recent_cache(self, capacity: usize) -> Self {
+        self.configure(|mut inner| {
+            inner.recent = NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity)));
+            inner
+        })
+    }
+
+    /// Also publishes every stored inscription to a message stream
+    pub fn with_stream(self, stream: StreamSink) -> Self {
+        self.configure(|mut inner| {
+            inner.stream = Some(stream);
+            inner
+        })
+    }
+
+    /// Nests image files under `depth` levels of txid-prefix directories; 0 keeps them flat
+    pub fn with_image_shard_depth(self, depth: usize) -> Self {
+        self.configure(|mut inner| {
+            inner.image_storage = inner.image_storage.with_shard_depth(depth);
+            inner
+        })
+    }
+
+    /// Embeds small base64 previews in the image index
+    pub fn with_index_thumbnails(self, enabled: bool) -> Self {
+        self.configure(|mut inner| {
+            inner.image_storage = inner.image_storage.with_index_thumbnails(enabled);
+            inner
+        })
+    }
+
+    /// Writes a 256px PNG thumbnail next to each decodable image
+    pub fn with_thumbnails(self, enabled: bool) -> Self {
+        self.configure(|mut inner| {
+            inner.image_storage = inner.image_storage.with_thumbnails(enabled);
+            inner
+        })
+    }
+
+    /// Applies a builder setting, before the backends are first shared
+    fn configure(self, apply: impl FnOnce(StorageInner) -> StorageInner) -> Self {
+        let inner = Arc::try_unwrap(self.inner)
+            .unwrap_or_else(|_| panic!("storage is configured before it's shared"));
+        Self { inner: Arc::new(apply(inner)) }
+    }
+
+    /// Runs `work` against the backends on tokio's blocking pool
+    async fn blocking<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&StorageInner) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let inner = Arc::clone(&self.inner);
+        match tokio::task::spawn_blocking(move || work(&inner)).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(StorageError::IoError(std::io::Error::new(std::io::ErrorKind::Interrupted, e))),
+        }
+    }
+
+    /// Stores an inscription unless it's a duplicate, unstorable or a deferred delegate
+    ///
+    /// Returns whether a new record was written; in dry-run mode, whether one would be.
+    pub async fn store_inscription(&self, inscription: &Inscription) -> Result<bool> {
+        if self.dry_run {
+            info!("[dry-run] {} {} {} bytes", inscription.inscription_id(), inscription.content.kind(),
+                inscription.content.bytes().len());
+            return Ok(true);
+        }
+
+        // All file and cache I/O happens on the blocking pool
+        let owned = inscription.clone();
+        let stored = self.blocking(move |inner| inner.store_blocking(&owned)).await?;
+
+        if stored {
+            self.remember(inscription);
+            self.note_pending_target(inscription);
+        }
+        if let (true, Some(stream)) = (stored, &self.stream) {
+            stream.publish(inscription).await;
+        }
+        Ok(stored)
+    }
+
+    /// Stores every deferred delegate whose target is now stored, keeping the rest pending
+    ///
+    /// Targets are matched by inscription id. Storage is only searched for the
+    /// targets of delegates deferred since the last pass; a delegate still
+    /// waiting after that is resolved when its target is stored, never by
+    /// searching again. Returns how many delegates were stored.
+    pub async fn resolve_pending_delegates(&self) -> Result<usize> {
+        if self.dry_run {
+            return Ok(0);
+        }
+
+        let (deferred, found) = {
+            let mut delegates = self.delegates.lock().unwrap_or_else(|e| e.into_inner());
+            (std::mem::take(&mut delegates.deferred), std::mem::take(&mut delegates.stored))
+        };
+        if deferred.is_empty() && found.is_empty() {
+            return Ok(0);
+        }
+
+        let resolved = self.blocking(move |inner| inner.resolve_blocking(deferred, found)).await?;
+
+        for inscription in &resolved {
+            self.remember(inscription);
+            // A delegate can itself be the target of one still waiting
+            self.note_pending_target(inscription);
+            if let Some(stream) = &self.stream {
+                stream.publish(inscription).await;
+            }
+        }
+        if !resolved.is_empty() {
+            info!("Resolved {} pending delegates", resolved.len());
+        }
+        Ok(resolved.len())
+    }
+}
+
+impl Deref for Storage {
+    type Target = StorageInner;
+
+    fn deref(&self) -> &StorageInner {
+        &self.inner
+    }
+}
+
+impl StorageInner {
+    /// Directory holding the text log and scanner bookkeeping files
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Moves the text log's entries to a new segment, e.g. on a schedule
+    ///
+    /// Returns whether there were any to move.
+    pub fn rotate_text_log(&self) -> Result<bool> {
+        if self.dry_run {
+            return Ok(false);
+        }
+        self.text_storage.rotate_if_nonempty()
+    }
+
+    pub fn load_scan_state(&self) -> Result<Option<ScanState>> {
+        ScanState::load(&self.data_dir.join("scan_state.json"))
+    }
+
+    /// Saves the resume cursor once buffered text entries are on disk,
+    /// so the cursor never gets ahead of the log
+    pub fn save_scan_state(&self, state: &ScanState) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        self.text_storage.flush()?;
+        state.save(&self.data_dir.join("scan_state.json"))
+    }
+
+/// The blocking part of `Storage::store_inscription`: dedup, then the backends
+fn store_blocking(&self, inscription: &Inscription) -> Result<bool> {
+    // Keyed by inscription id rather than txid so batch reveals keep every inscription
+    let key = inscription.inscription_id();
+    if let Some(dedup) = &self.dedup {
+        if dedup.is_duplicate(key.as_bytes())? {
+            debug!("Skipping duplicate inscription {}", key);
+            return Ok(false);
+        }
+    }
+
+    // Delegates carry no body of their own; they render their target's content
+    let stored = match &inscription.delegate {
+        Some(_) if inscription.content.is_empty() => {
+            self.defer_delegate(inscription)?;
+            false
+        }
+        _ => self.store_content(inscription)?,
+    };
+
+    // Rejected or deferred inscriptions stay unmarked, so a rescan handles them again
+    if let (true, Some(dedup)) = (stored, &self.dedup) {
+        dedup.mark_stored(key.as_bytes(), inscription.block_height)?;
+    }
+    Ok(stored)
+}
+
+/// Keeps the body of a stored inscription that a pending delegate is waiting for
+fn note_pending_target(&self, inscription: &Inscription) {
+    let mut delegates = self.delegates.lock().unwrap_or_else(|e| e.into_inner());
+    let id = inscription.inscription_id();
+    if delegates.targets.contains(&id) {
+        delegates.stored.insert(id, StoredEntry::of(inscription));
+    }
+}
+
+/// Caches a stored inscription by id for `get_by_txid` and delegate lookups
+///
+/// Images are left out, so the cache holds small bodies only. The cached
+/// copy keeps just the fields storage persists: a hit returns what a read
+/// from disk would.
+fn remember(&self, inscription: &Inscription) {
+    let Some(recent) = &self.recent else {
+        return;
+    };
+    if !matches!(inscription.content, InscriptionType::Text(_) | InscriptionType::Json(_) | InscriptionType::Svg(_)) {
+        return;
+    }
+    let mut stored = Inscription::new(inscription.txid, inscription.content.clone());
+    stored.index = inscription.index;
+    stored.content_type = Some(inscription.mime_type().to_string());
+    Provenance::of(inscription).apply(&mut stored);
+    recent.lock().unwrap_or_else(|e| e.into_inner()).put(inscription.inscription_id(), stored);
+}
+
+/// Returns whether a new record was written
+fn store_content(&self, inscription: &Inscription) -> Result<bool> {
+    // Keyed by inscription id, so re-processing a block never duplicates records
+    let id = inscription.inscription_id();
+    // Hashed once: image files are named by the same hash
+    let hash = inscription.content_hash();
+    if let Some(linked) = &self.linked {
+        let stored = self.store_linked(linked, inscription, &id)?;
+        if let (true, Some(index)) = (stored, &self.content_hashes) {
+            index.record(&hash, &id)?;
+        }
+        return Ok(stored);
+    }
+
+    let stored = match &inscription.content {
+        crate::parser::InscriptionType::Image { mime_type, data } => {
+            self.image_storage.store_hashed(&id, inscription.txid, mime_type, data, hash, &Provenance::of(inscription))?
+        }
+        crate::parser::InscriptionType::Svg(svg) => self.image_storage.store_hashed(
+            &id,
+            inscription.txid,
+            inscription.mime_type(),
+            svg.as_bytes(),
+            hash,
+            &Provenance::of(inscription),
+        )?,
+        crate::parser::InscriptionType::Text(text) => self.store_text_entry(inscription, &id, text)?,
+        crate::parser::InscriptionType::Json(value) => {
+            self.store_text_entry(inscription, &id, &value.to_string())?
+        }
+        crate::parser::InscriptionType::Unknown(data) if inscription.content_type.is_some() => match &self.binary {
+            Some(binary) => binary.store(
+                &id,
+                &inscription.txid.to_string(),
+                inscription.mime_type(),
+                data,
+                &Provenance::of(inscription),
+            )?,
+            None => false,
+        },
+        crate::parser::InscriptionType::Unknown(_)
+        | crate::parser::InscriptionType::Empty
+        | crate::parser::InscriptionType::Oversized { .. } => false,
+    };
+
+    if stored {
+        self.content_index.record(&inscription.content_id(), &id)?;
+        self.parent_index.record(&id, &inscription.parents)?;
+        if let Some(index) = &self.content_hashes {
+            index.record(&hash, &id)?;
+        }
+    } else {
+        debug!("Inscription {} already stored or not storable, skipping", id);
+    }
+    Ok(stored)
+}
+
+/// Links an inscription to its content-addressed body
+fn store_linked(&self, linked: &LinkedStorage, inscription: &Inscription, id: &str) -> Result<bool> {
+    let content_type = match &inscription.content {
         crate::parser::InscriptionType::Image { mime_type, data } => {
-            self.image_storage.store(inscription.txid, mime_type, data)
+            match self.image_storage.validate(inscription.txid, mime_type, data) {
+                Some(mime_type) => mime_type,
+                None => return Ok(false),
+            }
+        }
+        crate::parser::InscriptionType::Text(_)
+        | crate::parser::InscriptionType::Json(_)
+        | crate::parser::InscriptionType::Svg(_) => inscription.mime_type(),
+        crate::parser::InscriptionType::Unknown(_) if inscription.content_type.is_some() => inscription.mime_type(),
+        crate::parser::InscriptionType::Unknown(_)
+        | crate::parser::InscriptionType::Empty
+        | crate::parser::InscriptionType::Oversized { .. } => {
+            debug!("Inscription {} is not storable, skipping", id);
+            return Ok(false);
+        }
+    };
+
+    let entry = linked::LinkEntry {
+        id: id.to_string(),
+        txid: inscription.txid.to_string(),
+        content_id: inscription.content_id(),
+        content_type: content_type.to_string(),
+        block_height: inscription.block_height,
+        block_time: inscription.block_time,
+        genesis_address: inscription.genesis_address.clone(),
+        tx_metadata: inscription.tx_metadata.clone(),
+        parents: inscription.parents.clone(),
+        references: inscription.references.clone(),
+    };
+    let stored = linked.store(&entry, &inscription.content.bytes())?;
+    if stored {
+        self.content_index.record(&entry.content_id, id)?;
+        self.parent_index.record(id, &inscription.parents)?;
+    } else {
+        debug!("Inscription {} already stored, skipping", id);
+    }
+    Ok(stored)
+}
+
+/// Number of stored inscriptions carrying the content with this id
+///
+/// With content linking this is the number of inscriptions sharing the one
+/// stored body; otherwise it counts the copies stored per inscription.
+pub fn content_refcount(&self, content_id: &str) -> Result<usize> {
+    match &self.linked {
+        Some(linked) => Ok(linked.refcount(content_id)),
+        None => Ok(self.content_index.inscription_ids(content_id)?.len()),
+    }
+}
+
+/// Writes a text or JSON body to whichever backend is configured
+fn store_text_entry(&self, inscription: &Inscription, id: &str, text: &str) -> Result<bool> {
+    match &self.sqlite {
+        Some(sqlite) => sqlite.store(
+            id,
+            inscription.txid,
+            inscription.mime_type(),
+            text.as_bytes(),
+            &Provenance::of(inscription),
+        ),
+        None => {
+            // The log has no index of its own; an unsaved bloom filter may have missed this id
+            if let Some(dedup) = &self.dedup {
+                if dedup.is_marked(id.as_bytes())? {
+                    return Ok(false);
+                }
+            }
+            self.text_storage.store(id, inscription.txid, inscription.mime_type(), text, &Provenance::of(inscription))
+        }
+    }
+}
+
+/// Queues a delegate for `resolve_pending_delegates`, which looks every
+/// newly deferred target up in one pass instead of searching storage per delegate
+fn defer_delegate(&self, inscription: &Inscription) -> Result<()> {
+    debug!("Deferring delegate {} until its target is looked up", inscription.inscription_id());
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(self.data_dir.join(PENDING_DELEGATES))?;
+    serde_json::to_writer(&mut file, inscription)?;
+    writeln!(file)?;
+    if let Some(target) = &inscription.delegate {
+        let mut delegates = self.delegates.lock().unwrap_or_else(|e| e.into_inner());
+        delegates.targets.insert(target.clone());
+        delegates.deferred.insert(target.clone());
+    }
+    Ok(())
+}
+
+/// The blocking part of `Storage::resolve_pending_delegates`
+///
+/// Looks up the `deferred` targets not already `found`, stores the delegates
+/// whose target turned up and rewrites the pending file with the rest.
+fn resolve_blocking(
+    &self,
+    deferred: HashSet<String>,
+    mut found: HashMap<String, StoredEntry>,
+) -> Result<Vec<Inscription>> {
+    let path = self.data_dir.join(PENDING_DELEGATES);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    // A rescan can defer the same delegate again
+    let mut seen = HashSet::new();
+    let pending: Vec<Inscription> = BufReader::new(fs::File::open(&path)?)
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .filter(|inscription: &Result<Inscription>| {
+            inscription.as_ref().map_or(true, |inscription| seen.insert(inscription.inscription_id()))
+        })
+        .collect::<Result<_>>()?;
+    // Targets the cache DB has no stored marker for can't be in storage
+    let mut lookup = HashSet::new();
+    for target in deferred.into_iter().filter(|target| !found.contains_key(target)) {
+        if self.dedup.as_ref().map_or(Ok(true), |dedup| dedup.is_marked(target.as_bytes()))? {
+            lookup.insert(target);
+        }
+    }
+    if !lookup.is_empty() {
+        found.extend(self.entries_by_id(&lookup)?);
+    }
+
+    let (mut resolved, mut waiting) = (Vec::new(), Vec::new());
+    for mut inscription in pending {
+        let Some(entry) = inscription.delegate.as_ref().and_then(|delegate| found.get(delegate)) else {
+            waiting.push(inscription);
+            continue;
+        };
+        inscription.content = entry.clone().into_content();
+        if self.store_content(&inscription)? {
+            if let Some(dedup) = &self.dedup {
+                dedup.mark_stored(inscription.inscription_id().as_bytes(), inscription.block_height)?;
+            }
+            resolved.push(inscription);
+        }
+    }
+
+    self.delegates.lock().unwrap_or_else(|e| e.into_inner()).targets =
+        waiting.iter().filter_map(|inscription| inscription.delegate.clone()).collect();
+    // Replaced only once the resolved delegates are stored
+    if waiting.is_empty() {
+        fs::remove_file(&path)?;
+    } else {
+        write_atomically(&path, |file| {
+            for inscription in &waiting {
+                serde_json::to_writer(&mut *file, inscription)?;
+                writeln!(file)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(resolved)
+}
+
+/// The stored entry of each of `ids` that exists, reading each backend once
+fn entries_by_id(&self, ids: &HashSet<String>) -> Result<HashMap<String, StoredEntry>> {
+    let mut found = HashMap::new();
+    if let Some(recent) = &self.recent {
+        let mut recent = recent.lock().unwrap_or_else(|e| e.into_inner());
+        for id in ids {
+            if let Some(cached) = recent.get(id) {
+                found.insert(id.clone(), StoredEntry::of(cached));
+            }
+        }
+    }
+    if found.len() == ids.len() {
+        return Ok(found);
+    }
+
+    match &self.sqlite {
+        Some(sqlite) => {
+            for id in ids {
+                if found.contains_key(id) {
+                    continue;
+                }
+                if let Some(row) = sqlite.get(id)? {
+                    found.insert(id.clone(), StoredEntry { txid: row.txid, content_type: row.content_type, body: row.body });
+                }
+            }
         }
-        crate::parser::InscriptionType::Text(text) => {
-            self.text_storage.store(inscription.txid, text)
+        None => {
+            for entry in self.text_storage.read_entries()? {
+                let entry = entry?;
+                let id = entry.id();
+                if ids.contains(&id) {
+                    found.entry(id).or_insert(StoredEntry {
+                        content_type: entry.content_type(),
+                        txid: entry.txid,
+                        body: entry.content.into_bytes(),
+                    });
+                }
+            }
+        }
+    }
+    for entry in self.image_storage.index()? {
+        let id = entry.id();
+        if ids.contains(&id) && !found.contains_key(&id) {
+            let body = self.image_storage.body(&entry)?;
+            found.insert(id, StoredEntry { txid: entry.txid, content_type: entry.mime_type, body });
+        }
+    }
+    if let Some(linked) = &self.linked {
+        for entry in linked.entries()? {
+            if ids.contains(&entry.id) && !found.contains_key(&entry.id) {
+                let body = linked.body(&entry.content_id)?;
+                found.insert(entry.id, StoredEntry { txid: entry.txid, content_type: entry.content_type, body });
+            }
+        }
+    }
+    if let Some(binary) = &self.binary {
+        for id in ids {
+            if found.contains_key(id) {
+                continue;
+            }
+            if let Some((sidecar, body)) = binary.get(id)? {
+                found.insert(id.clone(), StoredEntry { txid: sidecar.txid, content_type: sidecar.content_type, body });
+            }
         }
-        crate::parser::InscriptionType::Unknown(_) => Ok(()),
     }
+    Ok(found)
 }
 
+/// Looks up a stored inscription by txid, checking text storage before images
+///
+/// Returns the first inscription stored for the transaction; envelope
+/// fields that aren't persisted (tags, metadata, ...) are left empty.
+pub fn get_by_txid(&self, txid: Txid) -> Result<Option<Inscription>> {
+    // Only the first inscription is served from memory: a later one of a batch
+    // reveal being cached doesn't mean it's what storage would return
+    if let Some(recent) = &self.recent {
+        let mut recent = recent.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(inscription) = recent.get(&format!("{}i0", txid)) {
+            return Ok(Some(inscription.clone()));
+        }
+    }
+
+    if let Some(linked) = &self.linked {
+        let txid_str = txid.to_string();
+        let found = linked
+            .iter_entries()?
+            .find(|entry| entry.as_ref().map_or(true, |entry| entry.txid == txid_str))
+            .transpose()?;
+        if let Some(entry) = found {
+            let body = linked.body(&entry.content_id)?;
+            let stored = StoredEntry { txid: entry.txid, content_type: entry.content_type.clone(), body };
+            let mut inscription = Inscription::new(txid, stored.into_content());
+            inscription.index = entry.id.rsplit('i').next().and_then(|n| n.parse().ok()).unwrap_or_default();
+            inscription.content_type = Some(entry.content_type);
+            Provenance {
+                block_height: entry.block_height,
+                block_time: entry.block_time,
+                genesis_address: entry.genesis_address,
+                tx_metadata: entry.tx_metadata,
+                parents: entry.parents,
+                references: entry.references,
+            }
+            .apply(&mut inscription);
+            return Ok(Some(inscription));
+        }
+    }
+
+    let text = match &self.sqlite {
+        Some(sqlite) => sqlite.by_txid(&txid.to_string())?.into_iter().next().map(|row| {
+            let provenance = Provenance {
+                block_height: row.block_height,
+                block_time: row.block_time,
+                genesis_address: row.genesis_address,
+                tx_metadata: row.tx_metadata,
+                parents: row.parents,
+                references: row.references,
+            };
+            (row.id, row.content_type, row.body, provenance)
+        }),
+        None => self.text_storage.find(txid)?.map(|entry| {
+            let id = entry.id();
+            let content_type = entry.content_type();
+            let provenance = Provenance {
+                block_height: entry.block_height,
+                block_time: entry.block_time,
+                genesis_address: entry.genesis_address,
+                tx_metadata: entry.tx_metadata,
+                parents: entry.parents,
+                references: entry.references,
+            };
+            (id, content_type, entry.content.into_bytes(), provenance)
+        }),
+    };
+
+    if let Some((id, content_type, body, provenance)) = text {
+        let stored = StoredEntry { txid: txid.to_string(), content_type: content_type.clone(), body };
+        let mut inscription = Inscription::new(txid, stored.into_content());
+        inscription.index = id.rsplit('i').next().and_then(|n| n.parse().ok()).unwrap_or_default();
+        inscription.content_type = Some(content_type);
+        provenance.apply(&mut inscription);
+        return Ok(Some(inscription));
+    }
+
+    Ok(self.image_storage.get_by_txid(txid)?.map(|(entry, data)| {
+        let mut inscription = Inscription::new(txid, InscriptionType::Image {
+            mime_type: entry.mime_type.clone(),
+            data,
+        });
+        inscription.index = entry
+            .id
+            .as_deref()
+            .and_then(|id| id.rsplit('i').next())
+            .and_then(|n| n.parse().ok())
+            .unwrap_or_default();
+        inscription.content_type = Some(entry.mime_type);
+        Provenance {
+            block_height: entry.block_height,
+            block_time: entry.block_time,
+            genesis_address: entry.genesis_address,
+            tx_metadata: entry.tx_metadata,
+            parents: entry.parents,
+            references: entry.references,
+        }
+        .apply(&mut inscription);
+        inscription
+    }))
+}
+
+/// Ids of every inscription whose body hashes to `content_hash`
+///
+/// See `Inscription::content_hash`. Empty unless the content hash index
+/// (the cache) is enabled.
+pub fn find_duplicates(&self, content_hash: &blake3::Hash) -> Result<Vec<String>> {
+    match &self.content_hashes {
+        Some(index) => Ok(index.ids(content_hash)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Ids of the stored inscriptions carrying the content with this id, in the order stored
+///
+/// See `Inscription::content_id`.
+pub fn inscriptions_with_content(&self, content_id: &str) -> Result<Vec<String>> {
+    self.content_index.inscription_ids(content_id)
+}
+
+/// Ids of the stored inscriptions that name `parent_id` as a parent (tag 3)
+pub fn children_of(&self, parent_id: &str) -> Result<Vec<String>> {
+    self.parent_index.children(parent_id)
+}
+
+/// Records the hash of each block that stored inscriptions
+pub fn record_blocks(&self, blocks: &[(u64, BlockHash)]) -> Result<()> {
+    if self.dry_run {
+        return Ok(());
+    }
+    self.block_log.record(blocks)
+}
+
+/// Hash of the block each stored height was scanned from
+pub fn recorded_blocks(&self) -> Result<BTreeMap<u64, BlockHash>> {
+    self.block_log.hashes()
+}
+
+/// Records the hash of every block in a completed batch, for reorg detection
+pub fn record_recent_blocks(&self, blocks: &[(u64, BlockHash)]) -> Result<()> {
+    if self.dry_run {
+        return Ok(());
+    }
+    self.recent_blocks.record(blocks)
+}
+
+/// Hashes of the most recently scanned blocks
+pub fn recent_blocks(&self) -> Result<BTreeMap<u64, BlockHash>> {
+    self.recent_blocks.hashes()
+}
+
+/// Forgets the recent hashes above `height`, after rewinding to it
+pub fn rewind_recent_blocks(&self, height: u64) -> Result<()> {
+    if self.dry_run {
+        return Ok(());
+    }
+    self.recent_blocks.truncate(height)
+}
+
+/// Txids of the inscriptions stored from each of `heights`
+pub fn txids_at_heights(&self, heights: &BTreeSet<u64>) -> Result<BTreeMap<u64, Vec<String>>> {
+    let mut txids: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    match &self.sqlite {
+        Some(sqlite) => {
+            for &height in heights {
+                let found = sqlite.txids_at_height(height)?;
+                if !found.is_empty() {
+                    txids.insert(height, found);
+                }
+            }
+        }
+        None => {
+            for entry in self.text_storage.read_entries()? {
+                let entry = entry?;
+                if heights.contains(&entry.block_height) {
+                    txids.entry(entry.block_height).or_default().push(entry.txid);
+                }
+            }
+        }
+    }
+    for entry in self.image_storage.index()? {
+        if heights.contains(&entry.block_height) {
+            txids.entry(entry.block_height).or_default().push(entry.txid);
+        }
+    }
+    if let Some(linked) = &self.linked {
+        for entry in linked.entries()? {
+            if heights.contains(&entry.block_height) {
+                txids.entry(entry.block_height).or_default().push(entry.txid);
+            }
+        }
+    }
+    Ok(txids)
+}
+
+/// Deletes every inscription stored from `heights`
+///
+/// The deduplicator forgets the removed txids, so rescanning the heights
+/// stores whatever the active chain has there. Returns the number removed.
+/// With the deduplicator, heights it holds no markers for are known to be
+/// empty and nothing is rewritten.
+pub fn purge_heights(&self, heights: &BTreeSet<u64>) -> Result<usize> {
+    if let Some(dedup) = &self.dedup {
+        if !dedup.any_at_heights(heights.iter().copied())? {
+            debug!("Nothing stored from {} blocks, skipping the purge", heights.len());
+            return Ok(0);
+        }
+    }
+    let mut txids = Vec::new();
+    match &self.sqlite {
+        Some(sqlite) => {
+            for &height in heights {
+                txids.extend(sqlite.delete_at_height(height)?);
+            }
+        }
+        None => {
+            let removed = self.text_storage.retain(|entry| !heights.contains(&entry.block_height))?;
+            txids.extend(removed.into_iter().map(|entry| entry.txid));
+        }
+    }
+    txids.extend(self.image_storage.remove_heights(heights)?.into_iter().map(|entry| entry.txid));
+    if let Some(linked) = &self.linked {
+        txids.extend(linked.remove_heights(heights)?.into_iter().map(|entry| entry.txid));
+    }
+    if let Some(binary) = &self.binary {
+        txids.extend(binary.remove_heights(heights)?.into_iter().map(|sidecar| sidecar.txid));
+    }
+
+    // By height too: inscriptions that were handled but not storable left no txid behind
+    if let Some(dedup) = &self.dedup {
+        for txid in &txids {
+            dedup.forget_txid(txid)?;
+        }
+        dedup.forget_heights(heights.iter().copied())?;
+    }
+    if let Some(index) = &self.content_hashes {
+        for txid in &txids {
+            index.forget_txid(txid)?;
+        }
+    }
+    self.content_index.forget_txids(&txids)?;
+    self.parent_index.forget_txids(&txids)?;
+    if let Some(recent) = &self.recent {
+        let mut recent = recent.lock().unwrap_or_else(|e| e.into_inner());
+        let purged: Vec<String> = recent
+            .iter()
+            .filter(|(_, cached)| heights.contains(&cached.block_height))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in purged {
+            recent.pop(&id);
+        }
+    }
+    self.block_log.remove(heights)?;
+
+    info!("Purged {} inscriptions from {} blocks", txids.len(), heights.len());
+    Ok(txids.len())
+}
+
+/// Iterates every stored inscription: text entries first, then images,
+/// content-linked inscriptions and binary bodies
+pub fn entries(&self) -> Result<impl Iterator<Item = Result<StoredEntry>> + '_> {
+    let texts: Box<dyn Iterator<Item = Result<StoredEntry>>> = match &self.sqlite {
+        Some(sqlite) => Box::new(sqlite.entries().map(|entry| {
+            entry.map(|entry| StoredEntry {
+                txid: entry.txid,
+                content_type: entry.content_type,
+                body: entry.body,
+            })
+        })),
+        None => Box::new(self.text_storage.read_entries()?.map(|entry| {
+            entry.map(|entry| StoredEntry {
+                content_type: entry.content_type(),
+                txid: entry.txid,
+                body: entry.content.into_bytes(),
+            })
+        })),
+    };
+
+    let images = self.image_storage.entries()?.map(|entry| {
+        entry.map(|(txid, mime_type, data)| StoredEntry {
+            txid,
+            content_type: mime_type,
+            body: data,
+        })
+    });
+
+    let linked: Box<dyn Iterator<Item = Result<StoredEntry>>> = match &self.linked {
+        Some(linked) => Box::new(linked.iter_entries()?.map(move |entry| {
+            let entry = entry?;
+            Ok(StoredEntry {
+                body: linked.body(&entry.content_id)?,
+                txid: entry.txid,
+                content_type: entry.content_type,
+            })
+        })),
+        None => Box::new(std::iter::empty()),
+    };
+
+    // Bodies are read one at a time, as the iterator reaches them
+    let binaries: Box<dyn Iterator<Item = Result<StoredEntry>>> = match &self.binary {
+        Some(binary) => Box::new(binary.sidecars()?.map(move |sidecar| {
+            let sidecar = sidecar?;
+            Ok(StoredEntry {
+                body: binary.body(&sidecar.id)?,
+                txid: sidecar.txid,
+                content_type: sidecar.content_type,
+            })
+        })),
+        None => Box::new(std::iter::empty()),
+    };
+
+    Ok(texts.chain(images).chain(linked).chain(binaries))
+}
+
+/// Stored inscriptions in export order, skipping `offset` and returning at most `limit`
+///
+/// Rows are read lazily and only up to the end of the page.
+pub fn page(&self, offset: usize, limit: usize) -> Result<Vec<ExportRow>> {
+    export::rows(self)?.skip(offset).take(limit).collect()
+}
+
+#[allow(dead_code)]
 pub async fn store_text(&self, text: String) -> Result<()> {
     // Generate a unique identifier using timestamp and text hash
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -71,6 +1267,609 @@ pub async fn store_text(&self, text: String) -> Result<()> {
     let pseudo_txid = bitcoin::Txid::from_slice(&hash_bytes)
         .map_err(|e| StorageError::HashError(e))?;
     
-    self.text_storage.store(pseudo_txid, &text)
+    self.text_storage.store(
+        &format!("{}i0", pseudo_txid),
+        pseudo_txid,
+        text::LEGACY_CONTENT_TYPE,
+        &text,
+        &Provenance::default(),
+    )?;
+    Ok(())
+}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_failed_write_leaves_no_partial_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("image.bin");
+
+        let result = write_atomically(&path, |file| {
+            file.write_all(b"image/png\n\x89PNG")?;
+            Err(StorageError::ImageError("disk full".to_string()))
+        });
+        assert!(matches!(result, Err(StorageError::ImageError(_))));
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        write_atomically(&path, |file| Ok(file.write_all(b"image/png\n\x89PNG")?)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"image/png\n\x89PNG");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    fn temp_storage(temp_dir: &TempDir) -> Storage {
+        Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_binary_bodies_are_persisted_with_their_type() {
+        let temp_dir = TempDir::new().unwrap();
+        let binary_dir = temp_dir.path().join("binary");
+        let storage = temp_storage(&temp_dir).with_binary(BinaryStorage::new(binary_dir.clone()).unwrap());
+
+        let glb = b"glTF\x02\x00\x00\x00\x0c\x00\x00\x00".to_vec();
+        let html = b"<html><body>hi</body></html>".to_vec();
+        for (n, (content_type, body)) in [("model/gltf-binary", &glb), ("text/html;charset=utf-8", &html)].into_iter().enumerate() {
+            let txid = Txid::from_str(&format!("{:02x}", n + 1).repeat(32)).unwrap();
+            let mut inscription = Inscription::new(txid, InscriptionType::Unknown(body.clone()));
+            inscription.content_type = Some(content_type.to_string());
+            inscription.block_height = 800_000;
+            storage.store_inscription(&inscription).await.unwrap();
+            // Stored once, even when the block is processed again
+            storage.store_inscription(&inscription).await.unwrap();
+        }
+
+        let glb_id = format!("{}i0", "01".repeat(32));
+        assert_eq!(fs::read(binary_dir.join(format!("{}.bin", glb_id))).unwrap(), glb);
+        let sidecar: binary::BinarySidecar =
+            serde_json::from_slice(&fs::read(binary_dir.join(format!("{}.json", glb_id))).unwrap()).unwrap();
+        assert_eq!(sidecar.content_type, "model/gltf-binary");
+        assert_eq!(sidecar.size, glb.len());
+        assert_eq!(sidecar.block_height, 800_000);
+
+        let entries: Vec<StoredEntry> = storage.entries().unwrap().map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].content_type, "text/html;charset=utf-8");
+        assert_eq!(entries[1].body, html);
+
+        // Pages list them from the sidecars, by path and size
+        let page = storage.page(0, 10).unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!((page[0].kind, page[0].size), ("binary", glb.len()));
+        assert_eq!(page[0].path.as_deref(), Some(binary_dir.join(format!("{}.bin", glb_id)).to_str().unwrap()));
+
+        // Without a declared type there's nothing to tell what the bytes are
+        let untyped = Txid::from_str(&"03".repeat(32)).unwrap();
+        storage.store_inscription(&Inscription::new(untyped, InscriptionType::Unknown(vec![0xff]))).await.unwrap();
+        assert_eq!(storage.entries().unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_children_of_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+        let parent = format!("{}i0", "aa".repeat(32));
+        let other_parent = format!("{}i3", "bb".repeat(32));
+
+        let child = Txid::from_str(&"01".repeat(32)).unwrap();
+        let mut inscription = Inscription::new(child, InscriptionType::Text("child".to_string()));
+        inscription.parents = vec![parent.clone(), other_parent.clone()];
+        inscription.block_height = 7;
+        storage.store_inscription(&inscription).await.unwrap();
+        storage.store_inscription(&inscription).await.unwrap();
+
+        let orphan = Txid::from_str(&"02".repeat(32)).unwrap();
+        storage.store_inscription(&Inscription::new(orphan, InscriptionType::Text("no parent".to_string()))).await.unwrap();
+
+        assert_eq!(storage.children_of(&parent).unwrap(), vec![format!("{}i0", child)]);
+        assert_eq!(storage.children_of(&other_parent).unwrap(), vec![format!("{}i0", child)]);
+        assert!(storage.children_of(&format!("{}i0", child)).unwrap().is_empty());
+
+        // The stored record keeps them too
+        let found = storage.get_by_txid(child).unwrap().unwrap();
+        assert_eq!(found.parents, vec![parent.clone(), other_parent]);
+
+        // Purging the child's block drops its links
+        storage.purge_heights(&BTreeSet::from([7])).unwrap();
+        assert!(storage.children_of(&parent).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delegate_resolution() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+
+        let target = Txid::from_str("1111111111111111111111111111111111111111111111111111111111111111").unwrap();
+        let delegating = Txid::from_str("2222222222222222222222222222222222222222222222222222222222222222").unwrap();
+        let delegate_id = format!("{}i0", delegating);
+
+        // The delegate names the second inscription of the target transaction
+        let mut inscription = Inscription::new(delegating, InscriptionType::Unknown(Vec::new()));
+        inscription.delegate = Some(format!("{}i1", target));
+
+        // Target not stored yet: the delegate stays pending, even when deferred twice
+        storage.store_inscription(&inscription).await.unwrap();
+        storage.store_inscription(&inscription).await.unwrap();
+        assert_eq!(storage.resolve_pending_delegates().await.unwrap(), 0);
+        assert!(storage.entries_by_id(&HashSet::from([delegate_id.clone()])).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(temp_dir.path().join(PENDING_DELEGATES)).unwrap().lines().count(), 1);
+
+        // Once the target exists, a later pass copies its content
+        storage.store_inscription(&Inscription::new(target, InscriptionType::Text("first".to_string()))).await.unwrap();
+        let mut second = Inscription::new(target, InscriptionType::Text("shared".to_string()));
+        second.index = 1;
+        storage.store_inscription(&second).await.unwrap();
+        assert_eq!(storage.resolve_pending_delegates().await.unwrap(), 1);
+
+        let found = storage.entries_by_id(&HashSet::from([delegate_id.clone()])).unwrap();
+        assert_eq!(found[&delegate_id].body, b"shared");
+        assert!(!temp_dir.path().join(PENDING_DELEGATES).exists());
+    }
+
+    #[tokio::test]
+    async fn test_waiting_delegates_dont_search_storage_again() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+        let target = Txid::from_str(&"7b".repeat(32)).unwrap();
+        let mut delegate = Inscription::new(Txid::from_str(&"7c".repeat(32)).unwrap(), InscriptionType::Empty);
+        delegate.delegate = Some(format!("{}i0", target));
+        storage.store_inscription(&delegate).await.unwrap();
+        assert_eq!(storage.resolve_pending_delegates().await.unwrap(), 0);
+
+        // Storage can't be searched any more; later passes must not try
+        storage.text_storage.flush().unwrap();
+        fs::write(temp_dir.path().join("inscriptions.log"), "not json\n").unwrap();
+        let unrelated = Inscription::new(Txid::from_str(&"7d".repeat(32)).unwrap(), InscriptionType::Text("other".to_string()));
+        storage.store_inscription(&unrelated).await.unwrap();
+        assert_eq!(storage.resolve_pending_delegates().await.unwrap(), 0);
+
+        // The target being stored is enough to resolve it
+        storage.store_inscription(&Inscription::new(target, InscriptionType::Text("late".to_string()))).await.unwrap();
+        assert_eq!(storage.resolve_pending_delegates().await.unwrap(), 1);
+        assert!(!temp_dir.path().join(PENDING_DELEGATES).exists());
+    }
+
+    #[tokio::test]
+    async fn test_content_id_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+
+        let first = Txid::from_str("1111111111111111111111111111111111111111111111111111111111111111").unwrap();
+        let second = Txid::from_str("2222222222222222222222222222222222222222222222222222222222222222").unwrap();
+
+        let a = Inscription::new(first, InscriptionType::Text("same".to_string()));
+        let b = Inscription::new(second, InscriptionType::Text("same".to_string()));
+        // A batch reveal inscribing the same content twice keeps both
+        let mut c = Inscription::new(second, InscriptionType::Text("same".to_string()));
+        c.index = 1;
+        for inscription in [&a, &b, &c] {
+            storage.store_inscription(inscription).await.unwrap();
+        }
+
+        assert_eq!(
+            storage.inscriptions_with_content(&a.content_id()).unwrap(),
+            vec![a.inscription_id(), b.inscription_id(), c.inscription_id()]
+        );
+
+        storage.content_index.forget_txids(&[second.to_string()]).unwrap();
+        assert_eq!(storage.inscriptions_with_content(&a.content_id()).unwrap(), vec![a.inscription_id()]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_stores_dont_starve_other_tasks() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = std::sync::Arc::new(temp_storage(&temp_dir));
+
+        // Ticks while the stores run; it only finishes if the runtime stays free
+        let heartbeat = tokio::spawn(async {
+            for _ in 0..20 {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        });
+        let stores: Vec<_> = (0..200u32)
+            .map(|i| {
+                let storage = storage.clone();
+                tokio::spawn(async move {
+                    let txid = Txid::from_str(&format!("{:064x}", i)).unwrap();
+                    let body = format!("{} {}", i, "x".repeat(64 * 1024));
+                    storage.store_inscription(&Inscription::new(txid, InscriptionType::Text(body))).await
+                })
+            })
+            .collect();
+
+        tokio::time::timeout(std::time::Duration::from_secs(10), heartbeat).await.unwrap().unwrap();
+        for store in stores {
+            store.await.unwrap().unwrap();
+        }
+        assert_eq!(storage.entries().unwrap().count(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_identical_bodies_share_a_content_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = std::sync::Arc::new(crate::cache::CacheDb::new(temp_dir.path().join("cache")).unwrap());
+        let storage = temp_storage(&temp_dir).with_content_hashes(ContentHashIndex::new(db));
+
+        let first = Txid::from_str(&"44".repeat(32)).unwrap();
+        let second = Txid::from_str(&"55".repeat(32)).unwrap();
+        let a = Inscription::new(first, InscriptionType::Text("<b>copy</b>".to_string()));
+        let mut b = Inscription::new(second, InscriptionType::Text("<b>copy</b>".to_string()));
+        // The declared type changes the content id but not the body hash
+        b.content_type = Some("text/html".to_string());
+        let mut other = Inscription::new(second, InscriptionType::Text("original".to_string()));
+        other.index = 1;
+        for inscription in [&a, &b, &other] {
+            storage.store_inscription(inscription).await.unwrap();
+        }
+
+        assert_ne!(a.content_id(), b.content_id());
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert_eq!(
+            storage.find_duplicates(&a.content_hash()).unwrap(),
+            vec![a.inscription_id(), b.inscription_id()]
+        );
+        assert_eq!(storage.find_duplicates(&other.content_hash()).unwrap(), vec![other.inscription_id()]);
+
+        // Purging a block forgets its hashes along with its inscriptions
+        storage.purge_heights(&BTreeSet::from([0])).unwrap();
+        assert!(storage.find_duplicates(&a.content_hash()).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_storing_twice_keeps_one_record() {
+        use crate::cache::{BloomCache, CacheDb};
+        use std::sync::Arc;
+
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path().join("cache")).unwrap());
+        let with_dedup = |storage: Storage| {
+            // A fresh bloom filter, as after a crash before it was saved
+            storage.with_dedup(Deduplicator::new(Arc::new(BloomCache::new(1000, 0.01)), db.clone()))
+        };
+        let storage = with_dedup(temp_storage(&temp_dir));
+
+        let txid = Txid::from_str("3333333333333333333333333333333333333333333333333333333333333333").unwrap();
+        let text = Inscription::new(txid, InscriptionType::Text("once".to_string()));
+        let mut image = Inscription::new(txid, InscriptionType::Image {
+            mime_type: "image/png".to_string(),
+            data: b"\x89PNG\r\n\x1a\n".to_vec(),
+        });
+        image.index = 1;
+
+        for _ in 0..2 {
+            storage.store_inscription(&text).await.unwrap();
+            storage.store_inscription(&image).await.unwrap();
+        }
+        drop(storage);
+
+        // A restarted scanner re-processing the same block also skips them
+        let restarted = with_dedup(temp_storage(&temp_dir));
+        restarted.store_inscription(&text).await.unwrap();
+        restarted.store_inscription(&image).await.unwrap();
+
+        assert_eq!(restarted.entries().unwrap().count(), 2);
+        assert_eq!(restarted.inscriptions_with_content(&text.content_id()).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_writes_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::dry_run(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        );
+
+        let txid = Txid::from_str("4444444444444444444444444444444444444444444444444444444444444444").unwrap();
+        storage.store_inscription(&Inscription::new(
+            txid,
+            InscriptionType::Text("not written".to_string()),
+        )).await.unwrap();
+        storage.store_inscription(&Inscription::new(
+            txid,
+            InscriptionType::Image { mime_type: "image/png".to_string(), data: b"\x89PNG\r\n\x1a\n".to_vec() },
+        )).await.unwrap();
+        storage.save_scan_state(&ScanState::at(10)).unwrap();
+
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_by_txid() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+
+        let text_txid = Txid::from_str("8888888888888888888888888888888888888888888888888888888888888888").unwrap();
+        let image_txid = Txid::from_str("9999999999999999999999999999999999999999999999999999999999999999").unwrap();
+        let mut text = Inscription::new(text_txid, InscriptionType::Text("findable".to_string()));
+        text.index = 2;
+        text.block_height = 800_000;
+        let mut image = Inscription::new(image_txid, InscriptionType::Image {
+            mime_type: "image/webp".to_string(),
+            data: b"RIFF\x24\x00\x00\x00WEBPVP8 ".to_vec(),
+        });
+        image.block_height = 800_001;
+        storage.store_inscription(&text).await.unwrap();
+        storage.store_inscription(&image).await.unwrap();
+
+        let found = storage.get_by_txid(text_txid).unwrap().unwrap();
+        assert_eq!(found.inscription_id(), text.inscription_id());
+        assert_eq!(found.block_height, 800_000);
+        assert!(matches!(found.content, InscriptionType::Text(ref t) if t == "findable"));
+
+        let found = storage.get_by_txid(image_txid).unwrap().unwrap();
+        assert_eq!(found.block_height, 800_001);
+        match found.content {
+            InscriptionType::Image { mime_type, data } => {
+                assert_eq!(mime_type, "image/webp");
+                assert_eq!(data, b"RIFF\x24\x00\x00\x00WEBPVP8 ");
+            }
+            other => panic!("Expected image inscription, got {:?}", other),
+        }
+
+        let missing = Txid::from_str("1212121212121212121212121212121212121212121212121212121212121212").unwrap();
+        assert!(storage.get_by_txid(missing).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recent_inscriptions_are_served_from_memory() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir).with_recent_cache(2);
+
+        let txids: Vec<Txid> = ["1a", "2b", "3c"]
+            .iter()
+            .map(|byte| Txid::from_str(&byte.repeat(32)).unwrap())
+            .collect();
+        for (n, txid) in txids.iter().enumerate() {
+            let inscription = Inscription::new(*txid, InscriptionType::Text(format!("recent {}", n)));
+            storage.store_inscription(&inscription).await.unwrap();
+        }
+
+        // With the log emptied, only the cache can answer
+        storage.text_storage.flush().unwrap();
+        fs::write(temp_dir.path().join("inscriptions.log"), "").unwrap();
+
+        let found = storage.get_by_txid(txids[2]).unwrap().unwrap();
+        assert!(matches!(found.content, InscriptionType::Text(ref t) if t == "recent 2"));
+        assert!(storage.get_by_txid(txids[1]).unwrap().is_some());
+        // Evicted once the third inscription was stored
+        assert!(storage.get_by_txid(txids[0]).unwrap().is_none());
+
+        // Delegates are resolved from the cache too
+        let mut delegate = Inscription::new(Txid::from_str(&"4d".repeat(32)).unwrap(), InscriptionType::Empty);
+        delegate.delegate = Some(format!("{}i0", txids[2]));
+        storage.store_inscription(&delegate).await.unwrap();
+        assert_eq!(storage.resolve_pending_delegates().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_evicted_first_inscription_is_read_from_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir).with_recent_cache(2);
+
+        // i0 is evicted by the later stores while i1 stays cached
+        let batch = Txid::from_str(&"6f".repeat(32)).unwrap();
+        for index in 0..2 {
+            let mut inscription = Inscription::new(batch, InscriptionType::Text(format!("batch {}", index)));
+            inscription.index = index;
+            storage.store_inscription(&inscription).await.unwrap();
+        }
+        let other = Txid::from_str(&"7a".repeat(32)).unwrap();
+        storage.store_inscription(&Inscription::new(other, InscriptionType::Text("other".to_string()))).await.unwrap();
+        storage.text_storage.flush().unwrap();
+
+        let found = storage.get_by_txid(batch).unwrap().unwrap();
+        assert_eq!(found.index, 0);
+        assert!(matches!(found.content, InscriptionType::Text(ref t) if t == "batch 0"));
+    }
+
+    #[tokio::test]
+    async fn test_recent_cache_matches_what_storage_returns() {
+        let temp_dir = TempDir::new().unwrap();
+        let cached = temp_storage(&temp_dir).with_recent_cache(8);
+        let uncached = TempDir::new().unwrap();
+        let uncached = temp_storage(&uncached);
+
+        let txid = Txid::from_str(&"5e".repeat(32)).unwrap();
+        let mut text = Inscription::new(txid, InscriptionType::Text("tagged".to_string()));
+        text.content_type = Some("text/plain;charset=utf-8".to_string());
+        text.block_height = 800_000;
+        text.tags = vec![(255, b"unknown".to_vec())];
+        text.pointer = Some(1);
+        text.content_encoding = Some("br".to_string());
+        let image = Inscription::new(Txid::from_str(&"6f".repeat(32)).unwrap(), InscriptionType::Image {
+            mime_type: "image/png".to_string(),
+            data: b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec(),
+        });
+        for storage in [&cached, &uncached] {
+            storage.store_inscription(&text).await.unwrap();
+            storage.store_inscription(&image).await.unwrap();
+        }
+
+        // Fields storage doesn't keep are dropped from the cached copy as well
+        let from_cache = serde_json::to_value(cached.get_by_txid(txid).unwrap().unwrap()).unwrap();
+        let from_disk = serde_json::to_value(uncached.get_by_txid(txid).unwrap().unwrap()).unwrap();
+        assert_eq!(from_cache, from_disk);
+        // Images aren't cached at all
+        assert!(cached.recent.as_ref().unwrap().lock().unwrap().peek(&image.inscription_id()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stored_entries_record_their_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = temp_storage(&temp_dir);
+
+        let txid = Txid::from_str("7777777777777777777777777777777777777777777777777777777777777777").unwrap();
+        let mut text = Inscription::new(txid, InscriptionType::Text("dated".to_string()));
+        text.block_height = 812_345;
+        text.block_time = 1_697_000_000;
+        text.genesis_address = Some("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0".to_string());
+        text.tx_metadata = Some(TxMetadata { version: 2, lock_time: 0, input_count: 1, output_count: 2, weight: 616 });
+        let mut image = Inscription::new(txid, InscriptionType::Image {
+            mime_type: "image/png".to_string(),
+            data: b"\x89PNG\r\n\x1a\n".to_vec(),
+        });
+        image.index = 1;
+        image.block_height = 812_345;
+        image.block_time = 1_697_000_000;
+
+        storage.store_inscription(&text).await.unwrap();
+        storage.store_inscription(&image).await.unwrap();
+
+        let entry = storage.text_storage.read_entries().unwrap().next().unwrap().unwrap();
+        assert_eq!((entry.block_height, entry.block_time), (812_345, 1_697_000_000));
+        let index = storage.image_storage.index().unwrap();
+        assert_eq!((index[0].block_height, index[0].block_time), (812_345, 1_697_000_000));
+        assert_eq!(index[0].genesis_address, None);
+
+        let found = storage.get_by_txid(txid).unwrap().unwrap();
+        assert_eq!(found.genesis_address, text.genesis_address);
+        assert_eq!(found.tx_metadata, text.tx_metadata);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_backend_replaces_text_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let sqlite = SqliteStorage::open(&temp_dir.path().join("inscriptions.db")).unwrap();
+        let storage = temp_storage(&temp_dir).with_sqlite(sqlite);
+
+        let txid = Txid::from_str("6666666666666666666666666666666666666666666666666666666666666666").unwrap();
+        let inscription = Inscription::new(txid, InscriptionType::Json(serde_json::json!({"p": "brc-20"})));
+        storage.store_inscription(&inscription).await.unwrap();
+        storage.store_inscription(&inscription).await.unwrap();
+
+        let entries: Vec<_> = storage.entries().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].content_type, "application/json");
+        assert_eq!(entries[0].body, br#"{"p":"brc-20"}"#);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("inscriptions.log")).unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn test_references_are_kept_by_every_text_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let referenced = format!("{}i0", "cc".repeat(32));
+        let txid = Txid::from_str(&"07".repeat(32)).unwrap();
+        let mut inscription = Inscription::new(txid, InscriptionType::Text(format!("/content/{}", referenced)));
+        inscription.references = vec![referenced.clone()];
+
+        let log = temp_storage(&temp_dir);
+        log.store_inscription(&inscription).await.unwrap();
+        assert_eq!(log.get_by_txid(txid).unwrap().unwrap().references, vec![referenced.clone()]);
+
+        let sqlite = SqliteStorage::open(&temp_dir.path().join("inscriptions.db")).unwrap();
+        let db = temp_storage(&temp_dir).with_sqlite(sqlite);
+        db.store_inscription(&inscription).await.unwrap();
+        assert_eq!(db.get_by_txid(txid).unwrap().unwrap().references, vec![referenced]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_is_stored_once() {
+        use crate::cache::{BloomCache, CacheDb};
+        use std::sync::Arc;
+
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path().join("cache")).unwrap());
+        let storage = temp_storage(&temp_dir)
+            .with_dedup(Deduplicator::new(Arc::new(BloomCache::new(1000, 0.01)), db.clone()));
+
+        let txid = Txid::from_str("5555555555555555555555555555555555555555555555555555555555555555").unwrap();
+        let inscription = Inscription::new(txid, InscriptionType::Text("rescanned".to_string()));
+        storage.store_inscription(&inscription).await.unwrap();
+        storage.store_inscription(&inscription).await.unwrap();
+
+        assert_eq!(storage.entries().unwrap().count(), 1);
+        assert!(db.get::<bool>(format!("stored:{}", inscription.inscription_id()).as_bytes()).unwrap().is_some());
+
+        // A rejected image isn't marked, so it's considered again on a rescan
+        let strict = temp_storage(&temp_dir)
+            .with_strict_images(true)
+            .with_dedup(Deduplicator::new(Arc::new(BloomCache::new(1000, 0.01)), db.clone()));
+        let mislabeled = Inscription::new(Txid::from_str(&"56".repeat(32)).unwrap(), InscriptionType::Image {
+            mime_type: "image/jpeg".to_string(),
+            data: b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec(),
+        });
+        strict.store_inscription(&mislabeled).await.unwrap();
+        assert!(db.get::<bool>(format!("stored:{}", mislabeled.inscription_id()).as_bytes()).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_purging_empty_heights_rewrites_nothing() {
+        use crate::cache::{BloomCache, CacheDb};
+        use std::sync::Arc;
+
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(CacheDb::new(temp_dir.path().join("cache")).unwrap());
+        let storage = temp_storage(&temp_dir)
+            .with_dedup(Deduplicator::new(Arc::new(BloomCache::new(1000, 0.01)), db));
+        let mut inscription = Inscription::new(Txid::from_str(&"57".repeat(32)).unwrap(), InscriptionType::Text("kept".to_string()));
+        inscription.block_height = 5;
+        storage.store_inscription(&inscription).await.unwrap();
+        storage.text_storage.flush().unwrap();
+
+        // The log would have to be rewritten to purge anything
+        let log = temp_dir.path().join("inscriptions.log");
+        let modified = fs::metadata(&log).unwrap().modified().unwrap();
+        assert_eq!(storage.purge_heights(&BTreeSet::from([6, 7])).unwrap(), 0);
+        assert_eq!(fs::metadata(&log).unwrap().modified().unwrap(), modified);
+        assert_eq!(storage.entries().unwrap().count(), 1);
+
+        assert_eq!(storage.purge_heights(&BTreeSet::from([5])).unwrap(), 1);
+        assert_eq!(storage.entries().unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_linked_content_is_stored_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let linked = LinkedStorage::open(temp_dir.path()).unwrap();
+        let storage = temp_storage(&temp_dir).with_linked(linked);
+
+        let trait_png = b"\x89PNG\r\n\x1a\nshared trait".to_vec();
+        let mut inscriptions = Vec::new();
+        for digit in ["a", "b", "c"] {
+            let txid = Txid::from_str(&digit.repeat(64)).unwrap();
+            inscriptions.push(Inscription::new(txid, InscriptionType::Image {
+                mime_type: "image/png".to_string(),
+                data: trait_png.clone(),
+            }));
+        }
+        for inscription in &inscriptions {
+            storage.store_inscription(inscription).await.unwrap();
+        }
+
+        let content_id = inscriptions[0].content_id();
+        assert_eq!(storage.content_refcount(&content_id).unwrap(), 3);
+
+        let bodies: Vec<_> = walk_files(&temp_dir.path().join("content"));
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(fs::read(&bodies[0]).unwrap(), trait_png);
+
+        // Every inscription still reads back with the shared body
+        let entries: Vec<_> = storage.entries().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|entry| entry.body == trait_png));
+        let found = storage.get_by_txid(inscriptions[2].txid).unwrap().unwrap();
+        assert_eq!(found.content.bytes().as_ref(), trait_png.as_slice());
+    }
+
+    fn walk_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(walk_files(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files
+    }
 }
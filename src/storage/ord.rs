@@ -0,0 +1,86 @@
+use super::{Result, Storage};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Sidecar written next to each exported body as `<number>.meta.json`
+#[derive(Debug, Serialize)]
+struct OrdMeta<'a> {
+    number: u64,
+    txid: &'a str,
+    content_type: &'a str,
+}
+
+/// Exports stored inscriptions in the layout ord tooling expects
+///
+/// Each inscription is written to `<dir>/<number>` with no extension, and
+/// its content type to `<dir>/<number>.meta.json`. Numbers are assigned in
+/// storage order starting at 0. Returns the number of inscriptions written.
+pub fn export_ord(storage: &Storage, dir: &Path) -> Result<u64> {
+    fs::create_dir_all(dir)?;
+
+    let mut number = 0;
+    for entry in storage.entries()? {
+        let entry = entry?;
+
+        fs::write(dir.join(number.to_string()), &entry.body)?;
+
+        let meta = OrdMeta {
+            number,
+            txid: &entry.txid,
+            content_type: &entry.content_type,
+        };
+        fs::write(
+            dir.join(format!("{}.meta.json", number)),
+            serde_json::to_vec_pretty(&meta)?,
+        )?;
+
+        number += 1;
+    }
+
+    Ok(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Inscription, InscriptionType};
+    use bitcoin::Txid;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_export_ord_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+        storage.store_inscription(&Inscription::new(
+            txid,
+            InscriptionType::Text("first".to_string()),
+        )).await.unwrap();
+        storage.store_inscription(&Inscription::new(
+            txid,
+            InscriptionType::Image {
+                mime_type: "image/png".to_string(),
+                data: b"\x89PNG\r\n\x1a\n".to_vec(),
+            },
+        )).await.unwrap();
+
+        let out = temp_dir.path().join("ord");
+        assert_eq!(export_ord(&storage, &out).unwrap(), 2);
+
+        assert_eq!(fs::read(out.join("0")).unwrap(), b"first");
+        assert_eq!(fs::read(out.join("1")).unwrap(), b"\x89PNG\r\n\x1a\n");
+
+        let meta: serde_json::Value =
+            serde_json::from_slice(&fs::read(out.join("1.meta.json")).unwrap()).unwrap();
+        assert_eq!(meta["content_type"], "image/png");
+        assert_eq!(meta["number"], 1);
+        assert!(out.join("0.meta.json").exists());
+    }
+}
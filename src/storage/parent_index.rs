@@ -0,0 +1,77 @@
+use super::{write_atomically, Result};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// Append-only index from parent inscription id to its children
+///
+/// Each line is `<parent id> <child id>`, written when a child that names
+/// the parent under tag 3 is stored, so collections can be walked without
+/// reading every stored record.
+pub struct ParentIndex {
+    path: PathBuf,
+}
+
+impl ParentIndex {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn record(&self, child_id: &str, parents: &[String]) -> Result<()> {
+        if parents.is_empty() {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        for parent in parents {
+            writeln!(file, "{} {}", parent, child_id)?;
+        }
+        Ok(())
+    }
+
+    /// Ids of every child recorded for `parent_id`, in insertion order
+    ///
+    /// A child stored again after its block was purged and rescanned is
+    /// listed once.
+    pub fn children(&self, parent_id: &str) -> Result<Vec<String>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut seen = HashSet::new();
+        let mut children = Vec::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            if let Some((parent, child)) = line.split_once(' ') {
+                if parent == parent_id && seen.insert(child.to_string()) {
+                    children.push(child.to_string());
+                }
+            }
+        }
+        Ok(children)
+    }
+
+    /// Drops the links of every child inscribed by one of `txids`
+    ///
+    /// Called for purged blocks, so a reorg leaves no child that's no longer stored.
+    pub fn forget_txids(&self, txids: &[String]) -> Result<()> {
+        if txids.is_empty() || !self.path.exists() {
+            return Ok(());
+        }
+        let txids: HashSet<&str> = txids.iter().map(String::as_str).collect();
+        let lines = BufReader::new(File::open(&self.path)?).lines();
+        write_atomically(&self.path, |file| {
+            for line in lines {
+                let line = line?;
+                let child_txid = line.split_once(' ').and_then(|(_, child)| child.split('i').next());
+                if !child_txid.is_some_and(|txid| txids.contains(txid)) {
+                    writeln!(file, "{}", line)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
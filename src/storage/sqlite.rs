@@ -0,0 +1,302 @@
+use super::{Provenance, Result};
+use crate::parser::TxMetadata;
+use bitcoin::Txid;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS inscriptions (
+        id TEXT PRIMARY KEY,
+        txid TEXT NOT NULL,
+        content_type TEXT NOT NULL,
+        body BLOB NOT NULL,
+        block_height INTEGER NOT NULL,
+        block_time INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        genesis_address TEXT,
+        tx_metadata TEXT,
+        parents TEXT,
+        -- REFERENCES is an SQL keyword
+        references_json TEXT
+    );
+    CREATE INDEX IF NOT EXISTS inscriptions_txid ON inscriptions (txid);
+";
+
+/// Rows `entries` reads per query
+const ENTRIES_CHUNK: usize = 256;
+
+const COLUMNS: &str =
+    "id, txid, content_type, body, block_height, block_time, timestamp, genesis_address, tx_metadata, parents, \
+     references_json";
+
+/// One stored inscription row
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqliteEntry {
+    pub id: String,
+    pub txid: String,
+    pub content_type: String,
+    pub body: Vec<u8>,
+    pub block_height: u64,
+    pub block_time: u32,
+    pub timestamp: u64,
+    pub genesis_address: Option<String>,
+    /// Reveal transaction metadata, stored as JSON
+    pub tx_metadata: Option<TxMetadata>,
+    /// Parent inscription ids, stored as a JSON array
+    pub parents: Vec<String>,
+    /// Recursively referenced inscription ids, stored as a JSON array
+    pub references: Vec<String>,
+}
+
+impl SqliteEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            txid: row.get(1)?,
+            content_type: row.get(2)?,
+            body: row.get(3)?,
+            block_height: row.get(4)?,
+            block_time: row.get(5)?,
+            timestamp: row.get(6)?,
+            genesis_address: row.get(7)?,
+            tx_metadata: row
+                .get::<_, Option<String>>(8)?
+                .and_then(|json| serde_json::from_str(&json).ok()),
+            parents: row
+                .get::<_, Option<String>>(9)?
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            references: row
+                .get::<_, Option<String>>(10)?
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Text and JSON inscriptions kept in a SQLite database instead of the JSONL log
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        // Databases created by older versions lack the later columns
+        for column in ["genesis_address", "tx_metadata", "parents", "references_json"] {
+            let exists: bool = conn.query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('inscriptions') WHERE name = ?1",
+                params![column],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                conn.execute_batch(&format!("ALTER TABLE inscriptions ADD COLUMN {} TEXT", column))?;
+            }
+        }
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Inserts a row unless `id` is already stored
+    ///
+    /// Returns whether the row was written.
+    pub fn store(
+        &self,
+        id: &str,
+        txid: Txid,
+        content_type: &str,
+        body: &[u8],
+        provenance: &Provenance,
+    ) -> Result<bool> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let tx_metadata = provenance.tx_metadata.as_ref().map(serde_json::to_string).transpose()?;
+        let parents = (!provenance.parents.is_empty())
+            .then(|| serde_json::to_string(&provenance.parents))
+            .transpose()?;
+        let references = (!provenance.references.is_empty())
+            .then(|| serde_json::to_string(&provenance.references))
+            .transpose()?;
+
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let inserted = conn.execute(
+            &format!("INSERT OR IGNORE INTO inscriptions ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)", COLUMNS),
+            params![
+                id,
+                txid.to_string(),
+                content_type,
+                body,
+                provenance.block_height,
+                provenance.block_time,
+                timestamp,
+                provenance.genesis_address,
+                tx_metadata,
+                parents,
+                references,
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<SqliteEntry>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = conn
+            .query_row(
+                &format!("SELECT {} FROM inscriptions WHERE id = ?1", COLUMNS),
+                params![id],
+                SqliteEntry::from_row,
+            )
+            .optional()?;
+        Ok(entry)
+    }
+
+    /// Every row for a txid, in inscription order
+    pub fn by_txid(&self, txid: &str) -> Result<Vec<SqliteEntry>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM inscriptions WHERE txid = ?1 ORDER BY rowid",
+            COLUMNS
+        ))?;
+        let entries = stmt
+            .query_map(params![txid], SqliteEntry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Txids of every row from `height`
+    pub fn txids_at_height(&self, height: u64) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        Self::select_txids(&conn, height)
+    }
+
+    /// Deletes every row from `height`, returning the deleted rows' txids
+    pub fn delete_at_height(&self, height: u64) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let txids = Self::select_txids(&conn, height)?;
+        conn.execute("DELETE FROM inscriptions WHERE block_height = ?1", params![height])?;
+        Ok(txids)
+    }
+
+    fn select_txids(conn: &Connection, height: u64) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare_cached("SELECT txid FROM inscriptions WHERE block_height = ?1")?;
+        let txids = stmt
+            .query_map(params![height], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(txids)
+    }
+
+    /// Every row, in insertion order
+    ///
+    /// Rows are read `ENTRIES_CHUNK` at a time, each query resuming after
+    /// the last rowid seen, so the table is never loaded whole and the
+    /// connection is free for stores between chunks.
+    pub fn entries(&self) -> impl Iterator<Item = Result<SqliteEntry>> + '_ {
+        let mut chunk = std::collections::VecDeque::new();
+        let mut after = 0;
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if chunk.is_empty() && !done {
+                match self.entries_after(after) {
+                    Ok(rows) => {
+                        done = rows.len() < ENTRIES_CHUNK;
+                        after = rows.last().map_or(after, |(rowid, _)| *rowid);
+                        chunk.extend(rows.into_iter().map(|(_, entry)| entry));
+                    }
+                    Err(e) => {
+                        done = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            chunk.pop_front().map(Ok)
+        })
+    }
+
+    /// Up to `ENTRIES_CHUNK` rows after `rowid`, each with its rowid
+    fn entries_after(&self, rowid: i64) -> Result<Vec<(i64, SqliteEntry)>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {}, rowid FROM inscriptions WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+            COLUMNS
+        ))?;
+        let rows = stmt
+            .query_map(params![rowid, ENTRIES_CHUNK as i64], |row| {
+                // rowid follows the 11 entry columns
+                Ok((row.get(11)?, SqliteEntry::from_row(row)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    fn at(block_height: u64, block_time: u32) -> Provenance {
+        Provenance { block_height, block_time, ..Default::default() }
+    }
+
+    #[test]
+    fn test_insert_and_lookup_by_txid() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("inscriptions.db");
+        let storage = SqliteStorage::open(&path).unwrap();
+
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+        let other = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+
+        assert!(storage.store(&format!("{}i0", txid), txid, "text/plain", b"first", &at(800_000, 1_690_000_000)).unwrap());
+        assert!(storage.store(&format!("{}i1", txid), txid, "application/json", b"{}", &at(800_000, 1_690_000_000)).unwrap());
+        assert!(storage.store(&format!("{}i0", other), other, "text/plain", b"other", &at(800_001, 1_690_000_600)).unwrap());
+
+        // Storing the same id again is a no-op
+        assert!(!storage.store(&format!("{}i0", txid), txid, "text/plain", b"first", &at(800_000, 1_690_000_000)).unwrap());
+
+        let rows = storage.by_txid(&txid.to_string()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].body, b"first");
+        assert_eq!(rows[0].block_height, 800_000);
+        assert_eq!(rows[0].block_time, 1_690_000_000);
+        assert_eq!(rows[1].content_type, "application/json");
+
+        assert!(storage.by_txid("missing").unwrap().is_empty());
+
+        // Rows survive reopening the database
+        drop(storage);
+        let reopened = SqliteStorage::open(&path).unwrap();
+        assert_eq!(reopened.entries().count(), 3);
+        assert_eq!(reopened.get(&format!("{}i0", other)).unwrap().unwrap().body, b"other");
+    }
+
+    #[test]
+    fn test_entries_are_read_in_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = SqliteStorage::open(&temp_dir.path().join("inscriptions.db")).unwrap();
+        let txid = Txid::from_str(&"ab".repeat(32)).unwrap();
+        let count = ENTRIES_CHUNK * 2 + 1;
+        for i in 0..count {
+            storage.store(&format!("{}i{}", txid, i), txid, "text/plain", b"row", &at(i as u64, 0)).unwrap();
+        }
+
+        let heights: Vec<u64> = storage.entries().map(|entry| entry.unwrap().block_height).collect();
+        assert_eq!(heights, (0..count as u64).collect::<Vec<_>>());
+
+        // Stores between chunks don't deadlock on the connection
+        let mut entries = storage.entries();
+        entries.next().unwrap().unwrap();
+        storage.store(&format!("{}i{}", txid, count), txid, "text/plain", b"row", &at(0, 0)).unwrap();
+        assert_eq!(entries.count(), count);
+        assert_eq!(storage.txids_at_height(3).unwrap(), vec![txid.to_string()]);
+    }
+}
@@ -0,0 +1,73 @@
+use super::Result;
+use serde::{Serialize, Deserialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+/// Scan cursor persisted after every completed batch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanState {
+    /// Highest block height whose inscriptions are fully stored
+    pub last_block: u64,
+    /// Heights at or below `last_block` that couldn't be fetched, retried on resume
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<u64>,
+}
+
+impl ScanState {
+    /// State with nothing skipped
+    #[cfg(test)]
+    pub fn at(last_block: u64) -> Self {
+        Self { last_block, skipped: Vec::new() }
+    }
+
+    /// Loads the saved state, returning `None` if nothing has been saved yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read(path)?;
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    /// Saves the state by writing a temp file and renaming it into place,
+    /// so a crash never leaves a half-written cursor behind
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+
+        let mut file = File::create(&tmp_path)?;
+        serde_json::to_writer(&mut file, self)?;
+        file.flush()?;
+        file.sync_all()?;
+
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan_state_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("scan_state.json");
+
+        assert!(ScanState::load(&path).unwrap().is_none());
+
+        let state = ScanState { last_block: 780_123, skipped: vec![780_001] };
+        state.save(&path).unwrap();
+        assert_eq!(ScanState::load(&path).unwrap(), Some(state));
+
+        // Saving again overwrites the previous cursor
+        ScanState::at(780_124).save(&path).unwrap();
+        assert_eq!(ScanState::load(&path).unwrap(), Some(ScanState::at(780_124)));
+
+        // Cursors saved before skipped heights were recorded still load
+        fs::write(&path, r#"{"last_block":5}"#).unwrap();
+        assert_eq!(ScanState::load(&path).unwrap(), Some(ScanState::at(5)));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::parser::{Inscription, InscriptionType};
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Message published for each stored inscription
+#[derive(Debug, Serialize)]
+struct StreamMessage<'a> {
+    id: String,
+    txid: String,
+    block_height: u64,
+    block_time: u32,
+    content_type: &'a str,
+    kind: &'static str,
+    size: usize,
+    /// The body of text, JSON and SVG inscriptions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    /// The body of everything else, base64-encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_base64: Option<String>,
+}
+
+impl<'a> StreamMessage<'a> {
+    fn of(inscription: &'a Inscription) -> Self {
+        let body = inscription.content.bytes();
+        let (text, body_base64) = match &inscription.content {
+            InscriptionType::Text(_) | InscriptionType::Json(_) | InscriptionType::Svg(_) => {
+                (Some(String::from_utf8_lossy(&body).into_owned()), None)
+            }
+            InscriptionType::Empty | InscriptionType::Oversized { .. } => (None, None),
+            _ => (None, Some(base64::encode(&body))),
+        };
+        Self {
+            id: inscription.inscription_id(),
+            txid: inscription.txid.to_string(),
+            block_height: inscription.block_height,
+            block_time: inscription.block_time,
+            content_type: inscription.mime_type(),
+            kind: inscription.content.kind(),
+            size: body.len(),
+            text,
+            body_base64,
+        }
+    }
+}
+
+/// A message broker connection that can publish to a subject
+#[async_trait]
+pub trait Publisher: Send + Sync {
+    async fn publish(&self, subject: &str, payload: Vec<u8>) -> std::result::Result<(), String>;
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl Publisher for async_nats::Client {
+    async fn publish(&self, subject: &str, payload: Vec<u8>) -> std::result::Result<(), String> {
+        async_nats::Client::publish(self, subject.to_string(), payload.into())
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Publishes every stored inscription as a JSON message for downstream consumers
+///
+/// Best effort: an unreachable broker or a failed publish is logged and
+/// the scan carries on, so the stream can miss messages the files have.
+pub struct StreamSink {
+    publisher: Option<Box<dyn Publisher>>,
+    subject: String,
+    published: AtomicU64,
+    /// Set while publishing fails, so an outage is warned about once
+    failing: AtomicBool,
+}
+
+impl StreamSink {
+    #[cfg(test)]
+    pub fn new(publisher: Box<dyn Publisher>, subject: &str) -> Self {
+        Self::with_publisher(Some(publisher), subject)
+    }
+
+    fn with_publisher(publisher: Option<Box<dyn Publisher>>, subject: &str) -> Self {
+        Self {
+            publisher,
+            subject: subject.to_string(),
+            published: AtomicU64::new(0),
+            failing: AtomicBool::new(false),
+        }
+    }
+
+    /// Connects to the NATS server at `url`, or returns a sink that
+    /// publishes nothing if it is unreachable
+    #[cfg(feature = "nats")]
+    pub async fn connect_nats(url: &str, subject: &str) -> Self {
+        match async_nats::connect(url).await {
+            Ok(client) => {
+                info!("Publishing inscriptions to {} on {}", subject, url);
+                Self::with_publisher(Some(Box::new(client)), subject)
+            }
+            Err(e) => {
+                warn!("NATS server {} unreachable, inscriptions won't be published: {}", url, e);
+                Self::with_publisher(None, subject)
+            }
+        }
+    }
+
+    /// Without the nats feature there's no client; config validation
+    /// rejects `storage.stream` before this is reached
+    #[cfg(not(feature = "nats"))]
+    pub async fn connect_nats(url: &str, subject: &str) -> Self {
+        warn!("Built without the nats feature, inscriptions won't be published to {}", url);
+        Self::with_publisher(None, subject)
+    }
+
+    pub async fn publish(&self, inscription: &Inscription) {
+        let Some(publisher) = &self.publisher else {
+            return;
+        };
+        let payload = match serde_json::to_vec(&StreamMessage::of(inscription)) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to encode {} for the stream: {}", inscription.inscription_id(), e);
+                return;
+            }
+        };
+
+        match publisher.publish(&self.subject, payload).await {
+            Ok(()) => {
+                self.published.fetch_add(1, Ordering::Relaxed);
+                if self.failing.swap(false, Ordering::Relaxed) {
+                    info!("Publishing to {} recovered", self.subject);
+                }
+            }
+            Err(e) => {
+                if !self.failing.swap(true, Ordering::Relaxed) {
+                    warn!("Publishing to {} failed, continuing without it: {}", self.subject, e);
+                }
+            }
+        }
+    }
+
+    /// Number of messages published so far
+    #[cfg(test)]
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::Txid;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    /// Records every message instead of sending it
+    #[derive(Clone, Default)]
+    struct CapturingPublisher {
+        messages: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+        fail: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Publisher for CapturingPublisher {
+        async fn publish(&self, subject: &str, payload: Vec<u8>) -> std::result::Result<(), String> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err("connection reset".to_string());
+            }
+            let message = serde_json::from_slice(&payload).map_err(|e| e.to_string())?;
+            self.messages.lock().unwrap().push((subject.to_string(), message));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inscriptions_are_published_as_json() {
+        let publisher = CapturingPublisher::default();
+        let sink = StreamSink::new(Box::new(publisher.clone()), "inscriptions");
+        let txid = Txid::from_str(&"ab".repeat(32)).unwrap();
+
+        let mut text = Inscription::new(txid, InscriptionType::Text("gm".to_string()));
+        text.block_height = 800_000;
+        sink.publish(&text).await;
+        let image = Inscription::new(
+            txid,
+            InscriptionType::Image { mime_type: "image/png".to_string(), data: vec![0x89, b'P', b'N', b'G'] },
+        );
+        sink.publish(&image).await;
+
+        publisher.fail.store(true, Ordering::SeqCst);
+        sink.publish(&text).await;
+        assert_eq!(sink.published(), 2);
+
+        let messages = publisher.messages.lock().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].0, "inscriptions");
+        assert_eq!(messages[0].1["id"], text.inscription_id());
+        assert_eq!(messages[0].1["block_height"], 800_000);
+        assert_eq!(messages[0].1["text"], "gm");
+        assert_eq!(messages[1].1["content_type"], "image/png");
+        assert_eq!(messages[1].1["body_base64"], base64::encode([0x89, b'P', b'N', b'G']));
+    }
+
+    #[cfg(feature = "nats")]
+    #[tokio::test]
+    async fn test_unreachable_server_is_a_no_op() {
+        let sink = StreamSink::connect_nats("nats://127.0.0.1:1", "inscriptions").await;
+        let txid = Txid::from_str(&"ab".repeat(32)).unwrap();
+        sink.publish(&Inscription::new(txid, InscriptionType::Text("gm".to_string()))).await;
+        assert_eq!(sink.published(), 0);
+    }
+}
@@ -1,19 +1,90 @@
-use super::Result;
+use super::{drop_torn_tail, write_atomically, Provenance, Result};
+use crate::parser::TxMetadata;
 use bitcoin::Txid;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufWriter, Write, BufRead, BufReader};
+use std::time::{Duration, Instant};
+use log::{info, warn};
 use serde::{Serialize, Deserialize};
 
+/// Buffered entries are written out once this many bytes are pending
+const FLUSH_BYTES: usize = 64 * 1024;
+
+/// ...or once the oldest unflushed entry is this old
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Type of entries written before content types were recorded
+pub const LEGACY_CONTENT_TYPE: &str = "text/plain;charset=utf-8";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TextEntry {
+    /// Inscription id; entries written before ids existed lack it
+    #[serde(default)]
+    pub id: Option<String>,
     pub txid: String,
+    /// Declared MIME type; see `content_type()` for entries that lack it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
     pub content: String,
+    /// Wall-clock time the entry was written
     pub timestamp: u64,
+    /// Source block; zero for entries written before heights were recorded
+    #[serde(default)]
+    pub block_height: u64,
+    #[serde(default)]
+    pub block_time: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_metadata: Option<TxMetadata>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<String>,
+    /// Inscription ids the body loads through `/content/<id>` (recursion)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+}
+
+/// Append handle to the log, kept open between stores
+struct LogWriter {
+    writer: BufWriter<File>,
+    last_flush: Instant,
+    /// Size of the active log, including buffered entries
+    bytes: u64,
+}
+
+impl LogWriter {
+    fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        let bytes = file.metadata()?.len();
+        Ok(Self { writer: BufWriter::with_capacity(FLUSH_BYTES, file), last_flush: Instant::now(), bytes })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
 }
 
+/// Append-only JSONL log of text inscriptions
+///
+/// Entries go through one long-lived buffered writer and reach the file in
+/// batches: when `FLUSH_BYTES` are pending, `FLUSH_INTERVAL` after the last
+/// flush, on `flush`, before every read and on drop.
+///
+/// With a size limit, a full log is renamed to `<log>.1`, `<log>.2`, ...
+/// and a fresh one started; reads cover every segment, oldest first.
+///
+/// The log keeps no index of its ids: `Storage` skips ids already marked
+/// stored in the cache DB before appending.
 pub struct TextStorage {
     log_file: PathBuf,
+    /// Absent for detached storage, which never writes
+    writer: Option<Mutex<LogWriter>>,
+    /// Rotate the active log once it reaches this size; 0 never rotates
+    max_log_bytes: u64,
 }
 
 impl TextStorage {
@@ -22,38 +93,178 @@ impl TextStorage {
             fs::create_dir_all(parent)?;
         }
         
-        if !log_file.exists() {
-            File::create(&log_file)?;
+        drop_torn_tail(&log_file)?;
+        let writer = LogWriter::open(&log_file)?;
+        Ok(Self {
+            log_file,
+            writer: Some(Mutex::new(writer)),
+            max_log_bytes: 0,
+        })
+    }
+
+    /// Points at `log_file` without creating or reading it, for dry-run use
+    pub fn detached(log_file: PathBuf) -> Self {
+        Self { log_file, writer: None, max_log_bytes: 0 }
+    }
+
+    /// Rotates the active log once it reaches `max_log_bytes`; 0 disables rotation
+    pub fn with_max_log_bytes(mut self, max_log_bytes: u64) -> Self {
+        self.max_log_bytes = max_log_bytes;
+        self
+    }
+
+    /// Writes every buffered entry to the log file
+    pub fn flush(&self) -> Result<()> {
+        match &self.writer {
+            Some(writer) => writer.lock().unwrap_or_else(|e| e.into_inner()).flush(),
+            None => Ok(()),
         }
-        
-        Ok(Self { log_file })
     }
 
-    pub fn store(&self, txid: Txid, content: &str) -> Result<()> {
+    /// Appends an entry for `id`
+    ///
+    /// Returns whether the entry was written; detached storage never writes.
+    pub fn store(&self, id: &str, txid: Txid, content_type: &str, content: &str, provenance: &Provenance) -> Result<bool> {
         let entry = TextEntry {
+            id: Some(id.to_string()),
             txid: txid.to_string(),
+            content_type: Some(content_type.to_string()),
             content: content.to_string(),
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            block_height: provenance.block_height,
+            block_time: provenance.block_time,
+            genesis_address: provenance.genesis_address.clone(),
+            tx_metadata: provenance.tx_metadata.clone(),
+            parents: provenance.parents.clone(),
+            references: provenance.references.clone(),
         };
 
-        let file = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&self.log_file)?;
-            
-        let mut writer = BufWriter::new(file);
-        serde_json::to_writer(&mut writer, &entry)?;
-        writeln!(writer)?;
+        let writer = match &self.writer {
+            Some(writer) => writer,
+            None => return Ok(false),
+        };
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        writer.writer.write_all(&line)?;
+        writer.bytes += line.len() as u64;
+        if self.max_log_bytes > 0 && writer.bytes >= self.max_log_bytes {
+            self.rotate(&mut writer)?;
+        } else if writer.last_flush.elapsed() >= FLUSH_INTERVAL {
+            writer.flush()?;
+        }
+        Ok(true)
+    }
+
+    /// Starts a new segment if the active log holds any entries
+    ///
+    /// Returns whether it rotated.
+    pub fn rotate_if_nonempty(&self) -> Result<bool> {
+        let Some(writer) = &self.writer else {
+            return Ok(false);
+        };
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        if writer.bytes == 0 {
+            return Ok(false);
+        }
+        self.rotate(&mut writer)?;
+        Ok(true)
+    }
+
+    /// Renames the full active log to the next segment and starts a fresh one
+    fn rotate(&self, writer: &mut LogWriter) -> Result<()> {
         writer.flush()?;
-        
+        let segment = self.segment_path(self.segments().len() + 1);
+        fs::rename(&self.log_file, &segment)?;
+        *writer = LogWriter::open(&self.log_file)?;
+        info!("Rotated {} to {}", self.log_file.display(), segment.display());
         Ok(())
     }
 
+    /// `<log>.<n>`, the n-th rotated segment
+    fn segment_path(&self, n: usize) -> PathBuf {
+        let mut path = self.log_file.clone().into_os_string();
+        path.push(format!(".{}", n));
+        PathBuf::from(path)
+    }
+
+    /// Rotated segments, oldest first
+    fn segments(&self) -> Vec<PathBuf> {
+        (1..).map(|n| self.segment_path(n)).take_while(|path| path.exists()).collect()
+    }
+
+    /// Every log file, oldest first: the rotated segments, then the active log
+    fn log_files(&self) -> Vec<PathBuf> {
+        let mut files = self.segments();
+        files.push(self.log_file.clone());
+        files
+    }
+
+    /// Rewrites the log keeping only entries for which `keep` returns true
+    ///
+    /// Each segment is rewritten in place, so rotation boundaries stay put.
+    /// Returns the removed entries.
+    pub fn retain(&self, keep: impl Fn(&TextEntry) -> bool) -> Result<Vec<TextEntry>> {
+        // Held throughout, so no store lands in a file that's being replaced
+        let mut log = self.writer.as_ref().map(|writer| writer.lock().unwrap_or_else(|e| e.into_inner()));
+        if let Some(log) = log.as_mut() {
+            log.flush()?;
+        }
+
+        let mut removed = Vec::new();
+        for path in self.log_files() {
+            let entries = Self::read_file(&path)?;
+            write_atomically(&path, |file| {
+                let mut writer = BufWriter::new(file);
+                for entry in entries {
+                    let entry = entry?;
+                    if keep(&entry) {
+                        serde_json::to_writer(&mut writer, &entry)?;
+                        writeln!(writer)?;
+                    } else {
+                        removed.push(entry);
+                    }
+                }
+                writer.flush()?;
+                Ok(())
+            })?;
+        }
+
+        // The old handle still points at the replaced file
+        if let Some(log) = log.as_mut() {
+            **log = LogWriter::open(&self.log_file)?;
+        }
+        Ok(removed)
+    }
+
+    /// First entry logged for a txid
+    pub fn find(&self, txid: Txid) -> Result<Option<TextEntry>> {
+        let txid = txid.to_string();
+        for entry in self.read_entries()? {
+            let entry = entry?;
+            if entry.txid == txid {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every entry in the log, across rotated segments, oldest first
     pub fn read_entries(&self) -> Result<impl Iterator<Item = Result<TextEntry>>> {
-        let file = File::open(&self.log_file)?;
+        self.flush()?;
+        let files = self
+            .log_files()
+            .into_iter()
+            .map(|path| Self::read_file(&path))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(files.into_iter().flatten())
+    }
+
+    fn read_file(path: &Path) -> Result<impl Iterator<Item = Result<TextEntry>>> {
+        let file = File::open(path)?;
         let reader = BufReader::new(file);
         
         Ok(reader.lines().map(|line| {
@@ -66,6 +277,26 @@ impl TextStorage {
     }
 }
 
+impl Drop for TextStorage {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Failed to flush {}: {}", self.log_file.display(), e);
+        }
+    }
+}
+
+impl TextEntry {
+    /// Inscription id, assuming the first inscription for legacy entries
+    pub fn id(&self) -> String {
+        self.id.clone().unwrap_or_else(|| format!("{}i0", self.txid))
+    }
+
+    /// MIME type, assuming plain text for legacy entries
+    pub fn content_type(&self) -> String {
+        self.content_type.clone().unwrap_or_else(|| LEGACY_CONTENT_TYPE.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,7 +311,7 @@ mod tests {
         let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
         let content = "Hello, Bitcoin!";
         
-        storage.store(txid, content).unwrap();
+        assert!(storage.store(&format!("{}i0", txid), txid, "text/plain", content, &Provenance { block_height: 800_000, block_time: 1_690_000_000, ..Default::default() }).unwrap());
         
         let entries: Vec<_> = storage.read_entries().unwrap()
             .collect::<std::result::Result<Vec<_>, _>>()
@@ -88,5 +319,88 @@ mod tests {
         assert_eq!(entries.len(), 1);
         assert_eq!(entries[0].content, content);
         assert_eq!(entries[0].txid, txid.to_string());
+        assert_eq!(entries[0].block_height, 800_000);
+        assert_eq!(entries[0].block_time, 1_690_000_000);
+        assert_eq!(entries[0].content_type(), "text/plain");
+
+        let legacy: TextEntry = serde_json::from_str(r#"{"txid":"t","content":"c","timestamp":0}"#).unwrap();
+        assert_eq!(legacy.content_type(), LEGACY_CONTENT_TYPE);
+    }
+
+    #[test]
+    fn test_torn_last_line_is_dropped_on_reopen() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+
+        let storage = TextStorage::new(temp_file.path().to_path_buf()).unwrap();
+        assert!(storage.store(&format!("{}i0", txid), txid, "text/plain", "whole", &Provenance::default()).unwrap());
+        drop(storage);
+
+        // A crash mid-append leaves half an entry behind
+        let mut file = OpenOptions::new().append(true).open(temp_file.path()).unwrap();
+        file.write_all(br#"{"id":"torn","txid":"#).unwrap();
+        drop(file);
+
+        let reopened = TextStorage::new(temp_file.path().to_path_buf()).unwrap();
+        assert!(reopened.store(&format!("{}i1", txid), txid, "text/plain", "after", &Provenance::default()).unwrap());
+        let entries: Vec<_> = reopened.read_entries().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.content.as_str()).collect::<Vec<_>>(), ["whole", "after"]);
+    }
+
+    #[test]
+    fn test_buffered_writes_are_not_lost() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+        let ids: Vec<String> = (0..1000).map(|i| format!("{}i{}", txid, i)).collect();
+
+        let storage = TextStorage::new(temp_file.path().to_path_buf()).unwrap();
+        for (i, id) in ids.iter().enumerate() {
+            assert!(storage.store(id, txid, "text/plain", &format!("entry {}", i), &Provenance::default()).unwrap());
+        }
+
+        // Reads see everything still buffered
+        let entries: Vec<_> = storage.read_entries().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(entries.iter().map(TextEntry::id).collect::<Vec<_>>(), ids);
+
+        // Writes after a rewrite go to the new file, and dropping flushes them
+        storage.retain(|entry| entry.content != "entry 0").unwrap();
+        assert!(storage.store(&format!("{}i1000", txid), txid, "text/plain", "last", &Provenance::default()).unwrap());
+        drop(storage);
+
+        let reopened = TextStorage::new(temp_file.path().to_path_buf()).unwrap();
+        let entries: Vec<_> = reopened.read_entries().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 1000);
+        assert_eq!(entries[0].content, "entry 1");
+        assert_eq!(entries[999].content, "last");
+    }
+
+    #[test]
+    fn test_rotated_segments_are_still_read() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let log = temp_dir.path().join("inscriptions.log");
+        let txid = Txid::from_str("0000000000000000000000000000000000000000000000000000000000000003").unwrap();
+        let content = "x".repeat(300);
+
+        let storage = TextStorage::new(log.clone()).unwrap().with_max_log_bytes(4096);
+        // About 500 bytes per entry, so the eighth or ninth one fills the first segment
+        for i in 0..12 {
+            assert!(storage.store(&format!("{}i{}", txid, i), txid, "text/plain", &content, &Provenance::default()).unwrap());
+        }
+        let segment = temp_dir.path().join("inscriptions.log.1");
+        assert!(segment.exists());
+        assert!(!temp_dir.path().join("inscriptions.log.2").exists());
+        assert!(fs::metadata(&segment).unwrap().len() >= 4096);
+
+        let ids = |storage: &TextStorage| -> Vec<String> {
+            storage.read_entries().unwrap().map(|entry| entry.unwrap().id()).collect()
+        };
+        let expected: Vec<String> = (0..12).map(|i| format!("{}i{}", txid, i)).collect();
+        assert_eq!(ids(&storage), expected);
+
+        // Rewrites and restarts see the rotated entries too
+        storage.retain(|entry| entry.id() != expected[0]).unwrap();
+        drop(storage);
+        let reopened = TextStorage::new(log).unwrap();
+        assert_eq!(ids(&reopened), expected[1..]);
     }
 }
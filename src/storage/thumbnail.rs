@@ -0,0 +1,55 @@
+use image::ImageOutputFormat;
+use std::io::Cursor;
+
+/// Edge length of the previews embedded in the image index
+pub const PREVIEW_SIZE: u32 = 32;
+
+/// Edge length of the thumbnail files written next to stored images
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Renders a PNG thumbnail fitting within `max_size` x `max_size`
+///
+/// Images that already fit are re-encoded at their own size rather than
+/// upscaled. Returns `None` for data the image crate can't decode (e.g. SVG).
+pub fn thumbnail_png(data: &[u8], max_size: u32) -> Option<Vec<u8>> {
+    let mut image = image::load_from_memory(data).ok()?;
+    if image.width() > max_size || image.height() > max_size {
+        image = image.thumbnail(max_size, max_size);
+    }
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageOutputFormat::Png).ok()?;
+    Some(png.into_inner())
+}
+
+/// Thumbnail as a `data:` URI that a viewer can render inline
+pub fn preview_data_uri(data: &[u8], max_size: u32) -> Option<String> {
+    thumbnail_png(data, max_size).map(|png| format!("data:image/png;base64,{}", base64::encode(png)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    /// Encodes a solid-colour PNG for tests
+    pub(crate) fn sample_png(width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([200, 40, 40])));
+        let mut png = Cursor::new(Vec::new());
+        image.write_to(&mut png, ImageOutputFormat::Png).unwrap();
+        png.into_inner()
+    }
+
+    #[test]
+    fn test_thumbnail_fits_bounds() {
+        let thumbnail = thumbnail_png(&sample_png(128, 64), PREVIEW_SIZE).unwrap();
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (32, 16));
+
+        assert!(thumbnail_png(b"<svg/>", PREVIEW_SIZE).is_none());
+
+        // Small images are never upscaled
+        let small = thumbnail_png(&sample_png(8, 8), PREVIEW_SIZE).unwrap();
+        let decoded = image::load_from_memory(&small).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (8, 8));
+    }
+}
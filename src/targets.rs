@@ -0,0 +1,309 @@
+// targets.rs
+//
+// Parses an explicit list of transactions or blocks, e.g. ids exported by
+// another indexer or raw blocks piped in, instead of scanning a height range.
+
+use crate::error::AppError;
+use crate::node::{Confirmation, NodeClient, NodeError};
+use crate::parser::{InscriptionParser, ParallelParser};
+use crate::storage::Storage;
+use crate::utils::run_blocking;
+use async_trait::async_trait;
+use bitcoin::{Block, BlockHash, Transaction, Txid};
+use log::{info, warn};
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Anything that can fetch a transaction or a block by its id
+#[async_trait]
+pub trait TargetSource: Send + Sync {
+    /// The transaction and, once confirmed, the block it was confirmed in
+    async fn transaction(&self, txid: &Txid) -> Result<(Transaction, Option<Confirmation>), NodeError>;
+    async fn block(&self, hash: &BlockHash) -> Result<Block, NodeError>;
+}
+
+#[async_trait]
+impl TargetSource for NodeClient {
+    async fn transaction(&self, txid: &Txid) -> Result<(Transaction, Option<Confirmation>), NodeError> {
+        self.get_raw_transaction(txid).await
+    }
+
+    async fn block(&self, hash: &BlockHash) -> Result<Block, NodeError> {
+        self.get_block(hash).await
+    }
+}
+
+/// Reads newline-delimited ids, ignoring blank lines and `#` comments
+pub fn read_ids<T>(path: &Path) -> Result<Vec<T>, AppError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            line.parse().map_err(|e| {
+                AppError::Usage(format!("{}:{}: invalid id {:?}: {}", path.display(), number + 1, line, e))
+            })
+        })
+        .collect()
+}
+
+/// Fetches and parses each transaction, storing whatever inscriptions it carries
+///
+/// Transactions without inscriptions, and ones the node can't return, are
+/// skipped. Inscriptions take the height and time of the confirming block;
+/// unconfirmed ones are stored at height 0. Returns the number of new records.
+pub async fn scan_txids<S: TargetSource + ?Sized>(
+    source: &S,
+    parser: &InscriptionParser,
+    storage: &Storage,
+    txids: &[Txid],
+) -> Result<usize, AppError> {
+    let mut stored = 0;
+    for txid in txids {
+        let (tx, confirmation) = match source.transaction(txid).await {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("Skipping transaction {}: {}", txid, e);
+                continue;
+            }
+        };
+        if confirmation.is_none() {
+            warn!("Transaction {} is unconfirmed, storing its inscriptions at height 0", txid);
+        }
+        for mut inscription in parser.parse_transaction_all(&tx) {
+            if let Some(confirmation) = confirmation {
+                inscription.block_height = confirmation.height;
+                inscription.block_time = confirmation.time;
+            }
+            if storage.store_inscription(&inscription).await? {
+                stored += 1;
+            }
+        }
+    }
+    stored += storage.resolve_pending_delegates().await?;
+    info!("Parsed {} transactions: {} inscriptions stored", txids.len(), stored);
+    Ok(stored)
+}
+
+/// Fetches and parses each block, storing its inscriptions
+///
+/// The height is read from the coinbase (BIP34), so blocks before
+/// BIP34 activation are stored at height 0. Returns the number of new records.
+pub async fn scan_block_hashes<S: TargetSource + ?Sized>(
+    source: &S,
+    parser: &ParallelParser,
+    storage: &Storage,
+    hashes: &[BlockHash],
+) -> Result<usize, AppError> {
+    let mut stored = 0;
+    for hash in hashes {
+        let block = match source.block(hash).await {
+            Ok(block) => block,
+            Err(e) => {
+                warn!("Skipping block {}: {}", hash, e);
+                continue;
+            }
+        };
+        let height = block.bip34_block_height().unwrap_or(0);
+        for inscription in parser.process_blocks(vec![(height, block)]) {
+            if storage.store_inscription(&inscription).await? {
+                stored += 1;
+            }
+        }
+    }
+    stored += storage.resolve_pending_delegates().await?;
+    info!("Parsed {} blocks: {} inscriptions stored", hashes.len(), stored);
+    Ok(stored)
+}
+
+/// Parses newline-delimited hex-encoded blocks from `reader`, storing their inscriptions
+///
+/// Needs no node. A line may start with the block's height and a space;
+/// otherwise the height is read from the coinbase (BIP34), and blocks
+/// without one are skipped since they'd be stored at the wrong height.
+/// Lines that aren't UTF-8 or hex, or don't decode to a block, are logged
+/// and skipped. `reader` is read through `run_blocking`, in place on the
+/// current worker. Returns the number of new records.
+pub async fn scan_raw_blocks<R: BufRead>(
+    mut reader: R,
+    parser: &ParallelParser,
+    storage: &Storage,
+) -> Result<usize, AppError> {
+    let (mut blocks, mut stored) = (0, 0);
+    let mut bytes = Vec::new();
+    for number in 1.. {
+        bytes.clear();
+        if run_blocking(|| reader.read_until(b'\n', &mut bytes))? == 0 {
+            break;
+        }
+        let line = match std::str::from_utf8(&bytes) {
+            Ok(line) => line.trim(),
+            Err(e) => {
+                warn!("Skipping line {}: not UTF-8: {}", number, e);
+                continue;
+            }
+        };
+        if line.is_empty() {
+            continue;
+        }
+        let (height, hex) = match line.split_once(char::is_whitespace) {
+            Some((height, hex)) => match height.parse::<u64>() {
+                Ok(height) => (Some(height), hex.trim_start()),
+                Err(e) => {
+                    warn!("Skipping line {}: invalid height {:?}: {}", number, height, e);
+                    continue;
+                }
+            },
+            None => (None, line),
+        };
+        let block: Block = match hex::decode(hex)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| bitcoin::consensus::encode::deserialize(&bytes).map_err(|e| e.to_string()))
+        {
+            Ok(block) => block,
+            Err(e) => {
+                warn!("Skipping line {}: not a hex-encoded block: {}", number, e);
+                continue;
+            }
+        };
+        let height = match height.map_or_else(|| block.bip34_block_height().ok(), Some) {
+            Some(height) => height,
+            None => {
+                warn!(
+                    "Skipping block {} on line {}: no BIP34 height in its coinbase; prefix the line with its height",
+                    block.block_hash(),
+                    number
+                );
+                continue;
+            }
+        };
+        blocks += 1;
+        for inscription in parser.process_blocks(vec![(height, block)]) {
+            if storage.store_inscription(&inscription).await? {
+                stored += 1;
+            }
+        }
+    }
+    stored += storage.resolve_pending_delegates().await?;
+    info!("Parsed {} blocks: {} inscriptions stored", blocks, stored);
+    Ok(stored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::create_mock_inscription_block;
+    use bitcoin::hashes::Hash;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    /// Serves the transactions it was built with, all confirmed in one block
+    struct MockTargets {
+        transactions: HashMap<Txid, Transaction>,
+        confirmation: Option<Confirmation>,
+    }
+
+    #[async_trait]
+    impl TargetSource for MockTargets {
+        async fn transaction(&self, txid: &Txid) -> Result<(Transaction, Option<Confirmation>), NodeError> {
+            self.transactions
+                .get(txid)
+                .map(|tx| (tx.clone(), self.confirmation))
+                .ok_or_else(|| NodeError::Deserialization(format!("No such transaction {}", txid)))
+        }
+
+        async fn block(&self, hash: &BlockHash) -> Result<Block, NodeError> {
+            Err(NodeError::UnknownBlock(hash.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_txid_file_stores_only_inscribed_transactions() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = std::sync::Arc::new(crate::cache::CacheDb::new(temp_dir.path().join("cache")).unwrap());
+        let bloom = std::sync::Arc::new(crate::cache::BloomCache::new(1000, 0.01));
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap()
+        .with_dedup(crate::cache::Deduplicator::new(bloom, db));
+
+        let inscribed = create_mock_inscription_block(4).txdata.remove(0);
+        let mut plain = inscribed.clone();
+        plain.output[0].script_pubkey = bitcoin::ScriptBuf::new();
+        let source = MockTargets {
+            transactions: [inscribed.clone(), plain.clone()].into_iter().map(|tx| (tx.txid(), tx)).collect(),
+            confirmation: Some(Confirmation { height: 812_345, time: 1_697_000_000 }),
+        };
+
+        let path = temp_dir.path().join("txids.txt");
+        fs::write(&path, format!("{}\n\n# no inscription\n{}\n", inscribed.txid(), plain.txid())).unwrap();
+        let txids: Vec<Txid> = read_ids(&path).unwrap();
+        assert_eq!(txids, vec![inscribed.txid(), plain.txid()]);
+
+        let stored = scan_txids(&source, &InscriptionParser::new(), &storage, &txids).await.unwrap();
+        assert_eq!(stored, 1);
+        let entries: Vec<_> = storage.entries().unwrap().map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].txid, inscribed.txid().to_string());
+
+        // Already stored, so a second pass writes nothing new
+        let stored = scan_txids(&source, &InscriptionParser::new(), &storage, &txids).await.unwrap();
+        assert_eq!(stored, 0);
+
+        // Flushes the log
+        drop(storage);
+        let log = fs::read_to_string(temp_dir.path().join("inscriptions.log")).unwrap();
+        assert!(log.contains(r#""block_height":812345"#) && log.contains(r#""block_time":1697000000"#), "{}", log);
+
+        fs::write(&path, "not-a-txid\n").unwrap();
+        assert!(matches!(read_ids::<Txid>(&path), Err(AppError::Usage(_))));
+    }
+
+    #[tokio::test]
+    async fn test_raw_blocks_from_a_pipe() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().join("images"),
+            temp_dir.path().join("inscriptions.log"),
+        )
+        .unwrap();
+
+        // A taproot reveal: the envelope sits in the script-path witness
+        let mut block = create_mock_inscription_block(800_000);
+        let envelope = std::mem::take(&mut block.txdata[0].output[0].script_pubkey);
+        block.txdata[0].input.push(bitcoin::TxIn {
+            // Spends a commit output; a null outpoint would make this the coinbase
+            previous_output: bitcoin::OutPoint { txid: Txid::all_zeros(), vout: 0 },
+            witness: bitcoin::Witness::from_slice(&[vec![0u8; 64], envelope.to_bytes(), vec![0xc0; 33]]),
+            ..Default::default()
+        });
+        // The mock block has no coinbase, so its height comes from the line prefix
+        let hex = bitcoin::consensus::encode::serialize_hex(&block);
+        let mut input = format!("not hex\n00\n\n{}\nx {}\n", hex, hex).into_bytes();
+        input.extend_from_slice(b"\xff\xfe\n");
+        input.extend_from_slice(format!("800000 {}\n", hex).as_bytes());
+        let parser = ParallelParser::new(1, Some(1)).unwrap();
+
+        // The malformed and height-less lines are skipped, the block's inscription is stored
+        let stored = scan_raw_blocks(Cursor::new(input), &parser, &storage).await.unwrap();
+        assert_eq!(stored, 1);
+        let entries: Vec<_> = storage.entries().unwrap().map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].txid, block.txdata[0].txid().to_string());
+        assert_eq!(entries[0].body, b"Hello from block 800000!");
+
+        drop(storage);
+        let log = fs::read_to_string(temp_dir.path().join("inscriptions.log")).unwrap();
+        assert!(log.contains(r#""block_height":800000"#), "{}", log);
+    }
+}
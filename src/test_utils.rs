@@ -0,0 +1,71 @@
+// test_utils.rs
+//
+// Fixtures shared by unit tests across modules.
+
+use bitcoin::block::{Header, Version};
+use bitcoin::hash_types::TxMerkleNode;
+use bitcoin::hashes::Hash;
+use bitcoin::pow::CompactTarget;
+use bitcoin::BlockHash;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// A well-formed header with zero hashes, for blocks whose header doesn't matter
+pub fn dummy_header() -> Header {
+    Header {
+        version: Version::ONE,
+        prev_blockhash: BlockHash::all_zeros(),
+        merkle_root: TxMerkleNode::all_zeros(),
+        time: 0,
+        bits: CompactTarget::from_consensus(0x1d00ffff),
+        nonce: 0,
+    }
+}
+
+/// Reads request lines up to the blank line, without line endings
+pub fn read_lines(reader: &mut BufReader<TcpStream>) -> Vec<String> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line == "\r\n" {
+            return lines;
+        }
+        lines.push(line.trim_end().to_string());
+    }
+}
+
+/// Plays the node for one JSON-RPC request, returning its headers and body
+pub fn answer_rpc(reader: &mut BufReader<TcpStream>, result: serde_json::Value) -> (Vec<String>, serde_json::Value) {
+    let headers = read_lines(reader);
+    let length: usize = headers
+        .iter()
+        .find_map(|header| header.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let response = serde_json::json!({ "result": result, "error": null, "id": request["id"] }).to_string();
+    write!(
+        reader.get_mut(),
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        response.len(),
+        response
+    )
+    .unwrap();
+    (headers, request)
+}
+
+/// Answers one JSON-RPC request with `result` on a background thread, returning the node's URL
+pub fn serve_rpc_once(result: serde_json::Value) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        answer_rpc(&mut BufReader::new(stream), result);
+    });
+    url
+}
@@ -0,0 +1,279 @@
+use crate::shutdown::Shutdown;
+use crate::utils::{Metrics, MetricsSnapshot};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Stdout};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How many recent inscriptions the dashboard keeps on screen
+const RECENT_CAPACITY: usize = 20;
+
+/// How often the dashboard redraws
+const TICK: Duration = Duration::from_millis(250);
+
+/// Updates pushed from the scan loop to the dashboard
+#[derive(Debug, Clone)]
+pub enum DashboardEvent {
+    /// Highest block height completed so far
+    Height(u64),
+    /// An inscription was found; `kind` is its category, `summary` a short description
+    Inscription { kind: String, summary: String },
+    /// A recoverable error occurred during the scan
+    Error(String),
+}
+
+/// Everything the dashboard renders, kept separate from drawing so it can be tested
+#[derive(Debug, Default)]
+pub struct DashboardState {
+    pub start_height: u64,
+    pub target_height: u64,
+    pub current_height: u64,
+    pub blocks_processed: u64,
+    pub blocks_per_second: f64,
+    pub inscriptions_found: u64,
+    pub by_type: BTreeMap<String, u64>,
+    pub recent: VecDeque<String>,
+    pub errors: u64,
+    pub last_error: Option<String>,
+}
+
+impl DashboardState {
+    pub fn new(start_height: u64, target_height: u64) -> Self {
+        Self {
+            start_height,
+            target_height,
+            current_height: start_height,
+            ..Default::default()
+        }
+    }
+
+    /// Refreshes the counters that come from the shared metrics
+    pub fn update_from_metrics(&mut self, snapshot: &MetricsSnapshot) {
+        self.blocks_processed = snapshot.blocks_processed;
+        self.blocks_per_second = snapshot.blocks_per_second;
+        self.inscriptions_found = snapshot.inscriptions_found;
+    }
+
+    pub fn apply(&mut self, event: DashboardEvent) {
+        match event {
+            DashboardEvent::Height(height) => self.current_height = height,
+            DashboardEvent::Inscription { kind, summary } => {
+                *self.by_type.entry(kind.clone()).or_insert(0) += 1;
+                if self.recent.len() == RECENT_CAPACITY {
+                    self.recent.pop_back();
+                }
+                self.recent.push_front(format!("[{}] {}", kind, summary));
+            }
+            DashboardEvent::Error(message) => {
+                self.errors += 1;
+                self.last_error = Some(message);
+            }
+        }
+    }
+
+    /// Fraction of the requested range completed, between 0 and 1
+    pub fn progress(&self) -> f64 {
+        let total = self.target_height.saturating_sub(self.start_height);
+        if total == 0 {
+            return 1.0;
+        }
+        let done = self.current_height.saturating_sub(self.start_height);
+        (done as f64 / total as f64).min(1.0)
+    }
+}
+
+/// Handle to a running dashboard; dropping it without `finish` leaves it running
+pub struct Dashboard {
+    events: Sender<DashboardEvent>,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<io::Result<()>>,
+}
+
+impl Dashboard {
+    /// Takes over the terminal and starts redrawing from `metrics`; `q` or Ctrl-C requests `shutdown`
+    pub fn start(
+        metrics: Arc<Metrics>,
+        shutdown: Shutdown,
+        start_height: u64,
+        target_height: u64,
+    ) -> io::Result<Self> {
+        let (events, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut terminal = setup_terminal()?;
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let state = DashboardState::new(start_height, target_height);
+            let result = run(&mut terminal, state, &metrics, &receiver, &shutdown, &thread_stop);
+            restore_terminal(&mut terminal)?;
+            result
+        });
+
+        Ok(Self { events, stop, handle })
+    }
+
+    pub fn send(&self, event: DashboardEvent) {
+        // The render thread only goes away on shutdown, so a failed send is harmless
+        let _ = self.events.send(event);
+    }
+
+    /// Stops redrawing and hands the terminal back
+    pub fn finish(self) -> io::Result<()> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "dashboard thread panicked")))
+    }
+}
+
+fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(stdout))
+}
+
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()
+}
+
+fn run(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    mut state: DashboardState,
+    metrics: &Metrics,
+    receiver: &Receiver<DashboardEvent>,
+    shutdown: &Shutdown,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    while !stop.load(Ordering::SeqCst) {
+        for event in receiver.try_iter() {
+            state.apply(event);
+        }
+        state.update_from_metrics(&metrics.get_stats());
+        terminal.draw(|frame| render(frame, &state, shutdown.is_requested()))?;
+
+        // Raw mode swallows Ctrl-C, so treat it (and `q`) like the signal: the scan finishes its
+        // batch, saves its cursor and then stops us through `finish`
+        if event::poll(TICK)? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+                if ctrl_c || key.code == KeyCode::Char('q') {
+                    shutdown.request();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn render(frame: &mut Frame, state: &DashboardState, stopping: bool) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(6),
+            Constraint::Min(5),
+            Constraint::Length(3),
+        ])
+        .split(frame.size());
+
+    let progress = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Block {} of {}",
+            state.current_height, state.target_height
+        )))
+        .ratio(state.progress());
+    frame.render_widget(progress, rows[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+
+    let throughput = Paragraph::new(format!(
+        "Blocks processed: {}\nBlocks/second: {:.2}\nInscriptions found: {}",
+        state.blocks_processed, state.blocks_per_second, state.inscriptions_found
+    ))
+    .block(Block::default().borders(Borders::ALL).title("Throughput"));
+    frame.render_widget(throughput, columns[0]);
+
+    let by_type = state
+        .by_type
+        .iter()
+        .map(|(kind, count)| format!("{}: {}", kind, count))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let types = Paragraph::new(by_type)
+        .block(Block::default().borders(Borders::ALL).title("By type"));
+    frame.render_widget(types, columns[1]);
+
+    let recent: Vec<ListItem> = state
+        .recent
+        .iter()
+        .map(|line| ListItem::new(line.clone()))
+        .collect();
+    let recent = List::new(recent)
+        .block(Block::default().borders(Borders::ALL).title("Recent inscriptions"));
+    frame.render_widget(recent, rows[2]);
+
+    let errors = Paragraph::new(format!(
+        "{} errors{}",
+        state.errors,
+        state.last_error.as_deref().map(|e| format!(" - last: {}", e)).unwrap_or_default()
+    ))
+    .block(Block::default().borders(Borders::ALL).title(if stopping {
+        "Errors (stopping after this batch)"
+    } else {
+        "Errors (q to quit)"
+    }));
+    frame.render_widget(errors, rows[3]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_updates_from_metrics() {
+        let metrics = Metrics::new();
+        metrics.increment_blocks(5);
+        metrics.increment_inscriptions(3);
+
+        let mut state = DashboardState::new(100, 110);
+        state.update_from_metrics(&metrics.get_stats());
+        assert_eq!(state.blocks_processed, 5);
+        assert_eq!(state.inscriptions_found, 3);
+
+        state.apply(DashboardEvent::Height(105));
+        assert!((state.progress() - 0.5).abs() < f64::EPSILON);
+
+        state.apply(DashboardEvent::Inscription { kind: "text".to_string(), summary: "hello".to_string() });
+        state.apply(DashboardEvent::Inscription { kind: "text".to_string(), summary: "world".to_string() });
+        state.apply(DashboardEvent::Error("boom".to_string()));
+
+        assert_eq!(state.by_type.get("text"), Some(&2));
+        assert_eq!(state.recent.front().map(String::as_str), Some("[text] world"));
+        assert_eq!(state.errors, 1);
+    }
+
+    #[test]
+    fn test_recent_is_bounded() {
+        let mut state = DashboardState::new(0, 1);
+        for i in 0..(RECENT_CAPACITY + 5) {
+            state.apply(DashboardEvent::Inscription { kind: "text".to_string(), summary: i.to_string() });
+        }
+        assert_eq!(state.recent.len(), RECENT_CAPACITY);
+    }
+}
@@ -0,0 +1,15 @@
+/// Runs blocking I/O in place from async code
+///
+/// `f` always blocks the calling task. On a multi-threaded runtime the
+/// worker first hands its queued tasks to another thread, so other tasks
+/// keep running. A current-thread runtime (or a plain thread) runs `f`
+/// inline, so keep it to short reads and writes that borrow from the
+/// caller; storage writes go through `spawn_blocking` instead.
+pub fn run_blocking<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
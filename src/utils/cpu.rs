@@ -0,0 +1,67 @@
+use std::fs;
+
+/// Number of CPUs the scanner should plan for
+///
+/// Starts from the physical core count, caps it at the container's cgroup
+/// CPU quota when one is set, and never returns less than 1 so thread
+/// pools can always be built.
+pub fn available_cpus() -> usize {
+    clamp_cpus(num_cpus::get_physical(), cgroup_cpu_quota())
+}
+
+/// Combines a detected core count with an optional quota, with a floor of 1
+pub fn clamp_cpus(detected: usize, quota: Option<usize>) -> usize {
+    let cpus = match quota {
+        Some(quota) => detected.min(quota),
+        None => detected,
+    };
+    cpus.max(1)
+}
+
+/// Reads the CPU quota from cgroup v2 or v1, rounded up to whole CPUs
+fn cgroup_cpu_quota() -> Option<usize> {
+    // cgroup v2: "<quota> <period>" or "max <period>"
+    if let Ok(content) = fs::read_to_string("/sys/fs/cgroup/cpu.max") {
+        let mut parts = content.split_whitespace();
+        let quota = parts.next()?;
+        let period = parts.next()?;
+        return quota_to_cpus(quota, period);
+    }
+
+    // cgroup v1: separate files, quota of -1 means unlimited
+    let quota = fs::read_to_string("/sys/fs/cgroup/cpu/cpu.cfs_quota_us").ok()?;
+    let period = fs::read_to_string("/sys/fs/cgroup/cpu/cpu.cfs_period_us").ok()?;
+    quota_to_cpus(quota.trim(), period.trim())
+}
+
+fn quota_to_cpus(quota: &str, period: &str) -> Option<usize> {
+    let quota: i64 = quota.parse().ok()?;
+    let period: i64 = period.parse().ok()?;
+    if quota <= 0 || period <= 0 {
+        return None;
+    }
+    Some(((quota + period - 1) / period) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp_never_returns_zero() {
+        assert_eq!(clamp_cpus(0, None), 1);
+        assert_eq!(clamp_cpus(0, Some(4)), 1);
+        assert_eq!(clamp_cpus(8, Some(0)), 1);
+        assert_eq!(clamp_cpus(8, None), 8);
+        assert_eq!(clamp_cpus(8, Some(2)), 2);
+        assert!(available_cpus() >= 1);
+    }
+
+    #[test]
+    fn test_quota_parsing() {
+        assert_eq!(quota_to_cpus("200000", "100000"), Some(2));
+        assert_eq!(quota_to_cpus("150000", "100000"), Some(2));
+        assert_eq!(quota_to_cpus("max", "100000"), None);
+        assert_eq!(quota_to_cpus("-1", "100000"), None);
+    }
+}
@@ -1,10 +1,31 @@
+use crate::parser::{Inscription, InscriptionType};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
+/// Accumulated timings for one content type, in microseconds
+#[derive(Debug, Default)]
+struct TypeTimers {
+    parsed: AtomicU64,
+    parse_time: AtomicU64,
+    stored: AtomicU64,
+    store_time: AtomicU64,
+}
+
+#[derive(Debug)]
 pub struct Metrics {
     blocks_processed: AtomicU64,
     inscriptions_found: AtomicU64,
     processing_time: AtomicU64,
+    maintenance_runs: AtomicU64,
+    text_stored: AtomicU64,
+    image_stored: AtomicU64,
+    json_stored: AtomicU64,
+    binary_stored: AtomicU64,
+    unknown_stored: AtomicU64,
+    per_type: RwLock<HashMap<String, Arc<TypeTimers>>>,
     start_time: Instant,
 }
 
@@ -14,6 +35,13 @@ impl Metrics {
             blocks_processed: AtomicU64::new(0),
             inscriptions_found: AtomicU64::new(0),
             processing_time: AtomicU64::new(0),
+            maintenance_runs: AtomicU64::new(0),
+            text_stored: AtomicU64::new(0),
+            image_stored: AtomicU64::new(0),
+            json_stored: AtomicU64::new(0),
+            binary_stored: AtomicU64::new(0),
+            unknown_stored: AtomicU64::new(0),
+            per_type: RwLock::new(HashMap::new()),
             start_time: Instant::now(),
         }
     }
@@ -30,6 +58,53 @@ impl Metrics {
         self.processing_time.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
     }
 
+    pub fn increment_maintenance_runs(&self) {
+        self.maintenance_runs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts one stored inscription towards its category
+    ///
+    /// Other bodies with a declared type are binary; untyped, empty and
+    /// oversized ones have nothing readable, so they count as unknown.
+    pub fn increment_by_type(&self, inscription: &Inscription) {
+        let counter = match &inscription.content {
+            InscriptionType::Text(_) => &self.text_stored,
+            InscriptionType::Image { .. } | InscriptionType::Svg(_) => &self.image_stored,
+            InscriptionType::Json(_) => &self.json_stored,
+            InscriptionType::Unknown(_) if inscription.content_type.is_some() => &self.binary_stored,
+            InscriptionType::Unknown(_) | InscriptionType::Empty | InscriptionType::Oversized { .. } => {
+                &self.unknown_stored
+            }
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the time spent parsing one inscription of `content_type`
+    pub fn add_parse_time(&self, content_type: &str, duration: Duration) {
+        let timers = self.timers(content_type);
+        timers.parsed.fetch_add(1, Ordering::Relaxed);
+        timers.parse_time.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Records the time spent storing one inscription of `content_type`
+    pub fn add_store_time(&self, content_type: &str, duration: Duration) {
+        let timers = self.timers(content_type);
+        timers.stored.fetch_add(1, Ordering::Relaxed);
+        timers.store_time.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    // Takes only a read lock once a type has been seen, so the hot path
+    // is a map lookup plus atomic adds
+    fn timers(&self, content_type: &str) -> Arc<TypeTimers> {
+        if let Some(timers) = self.per_type.read().ok().and_then(|map| map.get(content_type).cloned()) {
+            return timers;
+        }
+        match self.per_type.write() {
+            Ok(mut map) => map.entry(content_type.to_string()).or_default().clone(),
+            Err(poisoned) => poisoned.into_inner().entry(content_type.to_string()).or_default().clone(),
+        }
+    }
+
     pub fn get_stats(&self) -> MetricsSnapshot {
         let blocks = self.blocks_processed.load(Ordering::Relaxed);
         let inscriptions = self.inscriptions_found.load(Ordering::Relaxed);
@@ -37,6 +112,22 @@ impl Metrics {
             self.processing_time.load(Ordering::Relaxed)
         );
         let total_time = self.start_time.elapsed();
+        let per_type = self
+            .per_type
+            .read()
+            .map(|map| {
+                map.iter()
+                    .map(|(content_type, timers)| {
+                        (content_type.clone(), TypeTiming {
+                            parsed: timers.parsed.load(Ordering::Relaxed),
+                            parse_time: Duration::from_micros(timers.parse_time.load(Ordering::Relaxed)),
+                            stored: timers.stored.load(Ordering::Relaxed),
+                            store_time: Duration::from_micros(timers.store_time.load(Ordering::Relaxed)),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
 
         MetricsSnapshot {
             blocks_processed: blocks,
@@ -49,18 +140,58 @@ impl Metrics {
             } else {
                 0.0
             },
+            maintenance_runs: self.maintenance_runs.load(Ordering::Relaxed),
+            by_type: TypeCounts {
+                text: self.text_stored.load(Ordering::Relaxed),
+                image: self.image_stored.load(Ordering::Relaxed),
+                json: self.json_stored.load(Ordering::Relaxed),
+                binary: self.binary_stored.load(Ordering::Relaxed),
+                unknown: self.unknown_stored.load(Ordering::Relaxed),
+            },
+            per_type,
         }
     }
 }
 
-#[derive(Debug)]
+/// Durations are serialized as fractional seconds
+fn as_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Time spent on one content type
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TypeTiming {
+    pub parsed: u64,
+    #[serde(serialize_with = "as_secs")]
+    pub parse_time: Duration,
+    pub stored: u64,
+    #[serde(serialize_with = "as_secs")]
+    pub store_time: Duration,
+}
+
+/// Stored inscriptions per category
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TypeCounts {
+    pub text: u64,
+    pub image: u64,
+    pub json: u64,
+    pub binary: u64,
+    pub unknown: u64,
+}
+
+#[derive(Debug, Serialize)]
 pub struct MetricsSnapshot {
     pub blocks_processed: u64,
     pub inscriptions_found: u64,
+    #[serde(serialize_with = "as_secs")]
     pub processing_time: Duration,
+    #[serde(serialize_with = "as_secs")]
     pub total_time: Duration,
     pub blocks_per_second: f64,
     pub inscriptions_per_block: f64,
+    pub maintenance_runs: u64,
+    pub by_type: TypeCounts,
+    pub per_type: BTreeMap<String, TypeTiming>,
 }
 
 impl std::fmt::Display for MetricsSnapshot {
@@ -72,6 +203,86 @@ impl std::fmt::Display for MetricsSnapshot {
         writeln!(f, "  Total Time: {:.2?}", self.total_time)?;
         writeln!(f, "  Blocks/Second: {:.2}", self.blocks_per_second)?;
         writeln!(f, "  Inscriptions/Block: {:.4}", self.inscriptions_per_block)?;
+        writeln!(f, "  By Type: text {}, image {}, json {}, binary {}, unknown {}",
+            self.by_type.text, self.by_type.image, self.by_type.json, self.by_type.binary, self.by_type.unknown)?;
+        if self.maintenance_runs > 0 {
+            writeln!(f, "  Maintenance Runs: {}", self.maintenance_runs)?;
+        }
+        for (content_type, timing) in &self.per_type {
+            writeln!(f, "  {}: parsed {} in {:.2?}, stored {} in {:.2?}",
+                content_type, timing.parsed, timing.parse_time, timing.stored, timing.store_time)?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    #[test]
+    fn test_per_type_timers_accumulate() {
+        let metrics = Metrics::new();
+
+        metrics.add_parse_time("text/plain", Duration::from_micros(10));
+        metrics.add_parse_time("image/png", Duration::from_micros(100));
+        metrics.add_parse_time("text/plain", Duration::from_micros(20));
+        metrics.add_store_time("image/png", Duration::from_micros(500));
+        metrics.add_store_time("image/png", Duration::from_micros(300));
+
+        let stats = metrics.get_stats();
+        let text = &stats.per_type["text/plain"];
+        assert_eq!(text.parsed, 2);
+        assert_eq!(text.parse_time, Duration::from_micros(30));
+        assert_eq!(text.stored, 0);
+
+        let png = &stats.per_type["image/png"];
+        assert_eq!(png.parsed, 1);
+        assert_eq!(png.stored, 2);
+        assert_eq!(png.store_time, Duration::from_micros(800));
+
+        assert!(stats.to_string().contains("image/png"));
+    }
+
+    #[test]
+    fn test_counts_by_type() {
+        let metrics = Metrics::new();
+        let txid = bitcoin::Txid::all_zeros();
+        let mut html = Inscription::new(txid, InscriptionType::Unknown(b"<p>".to_vec()));
+        html.content_type = Some("text/html".to_string());
+        metrics.increment_by_type(&html);
+        for content in [
+            InscriptionType::Text("a".to_string()),
+            InscriptionType::Text("b".to_string()),
+            InscriptionType::Image { mime_type: "image/png".to_string(), data: vec![1] },
+            InscriptionType::Json(serde_json::json!({"p": "brc-20"})),
+            InscriptionType::Unknown(vec![0]),
+            InscriptionType::Empty,
+            InscriptionType::Oversized { size: 1 << 20 },
+        ] {
+            metrics.increment_by_type(&Inscription::new(txid, content));
+        }
+
+        let stats = metrics.get_stats();
+        assert_eq!(stats.by_type, TypeCounts { text: 2, image: 1, json: 1, binary: 1, unknown: 3 });
+        assert!(stats.to_string().contains("By Type: text 2, image 1, json 1, binary 1, unknown 3"));
+    }
+
+    #[test]
+    fn test_increment_and_snapshot() {
+        let metrics = Metrics::new();
+        metrics.increment_blocks(10);
+        metrics.increment_blocks(5);
+        metrics.increment_inscriptions(3);
+        metrics.add_processing_time(Duration::from_millis(40));
+        metrics.add_processing_time(Duration::from_millis(60));
+
+        let stats = metrics.get_stats();
+        assert_eq!(stats.blocks_processed, 15);
+        assert_eq!(stats.inscriptions_found, 3);
+        assert_eq!(stats.processing_time, Duration::from_millis(100));
+        assert!((stats.inscriptions_per_block - 0.2).abs() < f64::EPSILON);
+        assert!(stats.to_string().contains("Blocks Processed: 15"));
+    }
+}
@@ -1 +1,7 @@
+mod blocking;
+mod cpu;
 mod metrics;
+
+pub use blocking::run_blocking;
+pub use cpu::available_cpus;
+pub use metrics::{Metrics, MetricsSnapshot};